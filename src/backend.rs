@@ -1 +1,86 @@
 pub mod mips;
+pub mod interpreter;
+pub mod debugger;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Write;
+
+use crate::frontend::intermediate_gen::IntermediateInstr;
+use crate::frontend::semantics::SymbolTable;
+use mips::CodegenOptions;
+
+
+/**
+ * A target architecture which a program's intermediate code can be compiled into, chosen on the command line
+ * with a `-` followed by its name, such as `-mips`. A new target implements this and is added to
+ * `get_backends`, which is how the compiler and `--list-targets` find it.
+ */
+pub trait Backend {
+    /**
+     * Returns the name of the target, as given after the `-` of its target flag.
+     */
+    fn get_name(&self) -> &'static str;
+
+    /**
+     * Returns the extension added to the name of the file the code is written to, without the dot.
+     */
+    fn get_extension(&self) -> &'static str;
+
+    /**
+     * Returns a short description of the target for `--list-targets` and `--help`.
+     */
+    fn get_description(&self) -> &'static str;
+
+    /**
+     * Generates the code for the program and writes it to `writer`, returning the number of target
+     * instructions generated for each function, by name.
+     */
+    fn generate(&self, intermediate_code:Vec<IntermediateInstr>, symbol_table:&SymbolTable, writer:&mut dyn Write)
+        -> Result<HashMap<String, usize>, Box<dyn Error>>;
+}
+
+
+/**
+ * Returns every backend compiled into the compiler, each generating code with the given options.
+ */
+pub fn get_backends<'a>(options:&CodegenOptions<'a>) -> Vec<Box<dyn Backend + 'a>> {
+    vec![Box::new(mips::MipsBackend {options: *options})]
+}
+
+
+/**
+ * Returns the backend with the given name, generating code with the given options, or `None` if no backend
+ * compiled into the compiler has that name.
+ */
+pub fn find_backend<'a>(name:&str, options:&CodegenOptions<'a>) -> Option<Box<dyn Backend + 'a>> {
+    get_backends(options).into_iter().find(|backend| backend.get_name() == name)
+}
+
+
+/**
+ * What int arithmetic does when its result does not fit in an int, chosen with `--overflow`. `Wrap` gives the
+ * result wrapped around, so `2147483647 + 1` is `-2147483648`, while `Trap` prints
+ * `integer overflow in function <name>` and exits with code 1. Both backends follow the same mode, so the
+ * interpreter gives the same output as the MIPS it is compared against.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OverflowMode {
+    #[default]
+    Wrap,
+    Trap
+}
+
+impl OverflowMode {
+    /**
+     * Takes the value given to `--overflow` and returns the mode it names, or an error if it is not `trap` or
+     * `wrap`.
+     */
+    pub fn from_name(name:&str) -> Result<OverflowMode, String> {
+        match name {
+            "wrap" => Ok(OverflowMode::Wrap),
+            "trap" => Ok(OverflowMode::Trap),
+            other => Err(format!("{} is not a valid overflow mode, expected trap or wrap", other))
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use serde_json::json;
+
+use crate::backend::interpreter::{Interpreter, Value};
+use crate::diagnostics::MessageFormat;
+use crate::frontend::intermediate_gen::{FunctionVariables, IntermediateInstr};
+
+
+/**
+ * Represents a command typed at the prompt of the debugger started with `-run --debug`. Each has a long and
+ * a one letter name, such as `step` or `s`.
+ */
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Step,
+    Continue,
+    Break(String),
+    Stack,
+    Print(String),
+    Quit
+}
+
+impl Command {
+    /**
+     * Takes a line typed at the prompt and returns the command it gives, or a message saying what is wrong
+     * with it.
+     */
+    fn parse(line:&str) -> Result<Command, String> {
+        let words:Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["step" | "s"] => Ok(Command::Step),
+            ["continue" | "c"] => Ok(Command::Continue),
+            ["break" | "b", location] => Ok(Command::Break(location.to_owned())),
+            ["stack"] => Ok(Command::Stack),
+            ["print" | "p", variable] => Ok(Command::Print(variable.to_owned())),
+            ["quit" | "q"] => Ok(Command::Quit),
+            ["break" | "b"] => Err(String::from("break must be given a function or label")),
+            ["print" | "p"] => Err(String::from("print must be given a variable name or id")),
+            _ => Err(format!("unknown command {}, expected step, continue, break, stack, print or quit", line.trim()))
+        }
+    }
+}
+
+
+/**
+ * Runs a program in the interpreter one instruction at a time, stopping at breakpoints to show the evaluation
+ * stack and the variables of the function being run. Commands are read from the same input as the program,
+ * so the lines after a command which lets the program read input are given to the program. Everything the
+ * debugger prints goes to its own writer, kept apart from the output of the program, and is either text or,
+ * with `--message-format json`, one JSON object per line for an editor to read.
+ */
+pub struct Debugger<'a, 'b> {
+    interpreter: Interpreter<'a>,
+    instructions: &'a [IntermediateInstr],
+    variables: &'b [FunctionVariables],
+    breakpoints: HashSet<usize>,
+    format: MessageFormat
+}
+
+impl<'a, 'b> Debugger<'a, 'b> {
+    /**
+     * Creates a debugger for the given interpreter, which has not been started, and the intermediate code it
+     * runs. Variables can be printed by name if they are in `variables`, and by the id of their slot anyway.
+     */
+    pub fn new(interpreter:Interpreter<'a>, instructions:&'a [IntermediateInstr], variables:&'b [FunctionVariables], format:MessageFormat) -> Debugger<'a, 'b> {
+        Debugger {interpreter, instructions, variables, breakpoints: HashSet::new(), format}
+    }
+
+
+    /**
+     * Starts the program stopped before its first instruction and carries out commands read from `input`
+     * until it finishes, the debugger is told to quit, or the input runs out. Returns the exit code of the
+     * program, which is what it had reached if it did not finish.
+     */
+    pub fn run(&mut self, input:&mut dyn BufRead, output:&mut dyn Write, debug_output:&mut dyn Write) -> Result<i32, Box<dyn Error>> {
+        self.interpreter.start()?;
+        self.report_stop(debug_output, "start")?;
+        while !self.interpreter.is_finished() {
+            if self.format == MessageFormat::Human {
+                write!(debug_output, "(debug) ")?;
+                debug_output.flush()?;
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            let command = match Command::parse(&line) {
+                Ok(command) => command,
+                Err(message) => {
+                    self.report(debug_output, json!({"event": "error", "message": message}), format!("error: {}", message))?;
+                    continue;
+                }
+            };
+
+            match command {
+                Command::Step => {
+                    self.interpreter.step_instruction(input, output)?;
+                    self.report_stop(debug_output, "step")?;
+                },
+
+                // the first instruction is always run, so continuing from a breakpoint does not stop at it again
+                Command::Continue => {
+                    while self.interpreter.step_instruction(input, output)? && !self.breakpoints.contains(&self.interpreter.get_pc()) {}
+                    self.report_stop(debug_output, "breakpoint")?;
+                },
+
+                Command::Break(location) => self.add_breakpoint(&location, debug_output)?,
+                Command::Stack => self.report_stack(debug_output)?,
+                Command::Print(variable) => self.report_variable(&variable, debug_output)?,
+                Command::Quit => break
+            }
+        }
+
+        output.flush()?;
+        Ok(self.interpreter.get_exit_code())
+    }
+
+
+    /**
+     * Writes a line to the debug output, as the given JSON object with `--message-format json` and as the
+     * given text otherwise.
+     */
+    fn report(&self, debug_output:&mut dyn Write, object:serde_json::Value, text:String) -> Result<(), Box<dyn Error>> {
+        match self.format {
+            MessageFormat::Human => writeln!(debug_output, "{}", text)?,
+            MessageFormat::Json => writeln!(debug_output, "{}", object)?
+        }
+
+        Ok(())
+    }
+
+
+    /**
+     * Reports where the program has stopped and why, being `start`, `step` or `breakpoint`, or that it has
+     * finished and its exit code.
+     */
+    fn report_stop(&self, debug_output:&mut dyn Write, reason:&str) -> Result<(), Box<dyn Error>> {
+        let pc = self.interpreter.get_pc();
+        let (Some(instr), Some(function)) = (self.instructions.get(pc), self.interpreter.get_current_function()) else {
+            let code = self.interpreter.get_exit_code();
+            return self.report(debug_output, json!({"event": "exited", "code": code}), format!("program exited with code {}", code));
+        };
+
+        let instr = instr.to_string().trim().to_owned();
+        let object = json!({"event": "stopped", "reason": reason, "pc": pc, "function": function, "instruction": instr});
+        self.report(debug_output, object, format!("stopped at {} in {}: {}", pc, function, instr))
+    }
+
+
+    /**
+     * Adds a breakpoint on the first instruction of the body of a function, or on a label, looking for a
+     * function of the given name first.
+     */
+    fn add_breakpoint(&mut self, location:&str, debug_output:&mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let Some(pc) = self.interpreter.find_function_body(location).or_else(|| self.interpreter.find_label(location)) else {
+            let message = format!("there is no function or label named {}", location);
+            return self.report(debug_output, json!({"event": "error", "message": message}), format!("error: {}", message));
+        };
+
+        self.breakpoints.insert(pc);
+        self.report(debug_output, json!({"event": "breakpoint", "location": location, "pc": pc}), format!("breakpoint on {} at {}", location, pc))
+    }
+
+
+    /**
+     * Reports every value on the evaluation stack with its type, from the bottom of the stack to the top.
+     */
+    fn report_stack(&self, debug_output:&mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let stack = self.interpreter.get_stack();
+        let values:Vec<serde_json::Value> = stack.iter().map(|value| json!({"type": value.get_type().to_string(), "value": value.to_string()})).collect();
+        let text = match stack.is_empty() {
+            true => String::from("stack: empty"),
+            false => format!("stack: {}", stack.iter().map(describe_value).collect::<Vec<_>>().join(", "))
+        };
+
+        self.report(debug_output, json!({"event": "stack", "values": values}), text)
+    }
+
+
+    /**
+     * Reports the value of a variable of the function being run, given either the id of the slot it is kept
+     * in or its name. A name declared more than once in the function gives the latest declaration which has
+     * been given a value.
+     */
+    fn report_variable(&self, variable:&str, debug_output:&mut dyn Write) -> Result<(), Box<dyn Error>> {
+        let function = self.interpreter.get_current_function().unwrap_or_default();
+        let slots:Vec<usize> = match variable.parse::<usize>() {
+            Ok(id) => vec![id],
+            Err(_) => self.variables.iter()
+                .filter(|variables| variables.function == function)
+                .flat_map(|variables| variables.variables.iter().rev())
+                .filter(|frame_variable| frame_variable.identifier == variable)
+                .map(|frame_variable| frame_variable.slot)
+                .collect()
+        };
+
+        let Some((id, value)) = slots.iter().find_map(|id| self.interpreter.get_variable(*id).map(|value| (*id, value))) else {
+            let message = format!("{} has no value in {}", variable, function);
+            return self.report(debug_output, json!({"event": "error", "message": message}), format!("error: {}", message));
+        };
+
+        let object = json!({"event": "variable", "name": variable, "id": id, "type": value.get_type().to_string(), "value": value.to_string()});
+        self.report(debug_output, object, format!("{} (slot {}) = {}", variable, id, describe_value(value)))
+    }
+}
+
+
+/**
+ * Returns the type of a value followed by the value, such as `int 5`.
+ */
+fn describe_value(value:&Value) -> String {
+    format!("{} {}", value.get_type(), value)
+}
@@ -0,0 +1,63 @@
+# float for MIPS, used to print floats to a given number of decimal places
+
+# Prints the float whose bits are in $a1 truncated to the number of decimal places in $a0, which is at most 6.
+# The sign is printed first, then the whole part as an int, then the fractional part scaled up to an int and
+# padded with leading zeros, so 3.14159 to 2 places is printed as 3.14. The whole part must fit in an int.
+__print_float:
+    move $t0, $a0 # number of decimal places
+    li $t1, 1 # becomes 10 to the power of the number of places
+    move $t2, $t0
+
+__print_float_scale:
+    beqz $t2, __print_float_sign
+    mul $t1, $t1, 10
+    subi $t2, $t2, 1
+    j __print_float_scale
+
+# the sign is the top bit of the float, which is cleared after printing a '-' to leave the float positive
+__print_float_sign:
+    bgez $a1, __print_float_whole
+    li $v0, 11
+    li $a0, 0x2d
+    syscall # print '-'
+    sll $a1, $a1, 1
+    srl $a1, $a1, 1
+
+# the float is only kept in a register within this block
+__print_float_whole:
+    mtc1 $a1, $f12
+    trunc.w.s $f4, $f12
+    mfc1 $a0, $f4
+    li $v0, 1
+    syscall # print the whole part
+    beqz $t0, __print_float_end
+    cvt.s.w $f4, $f4
+    sub.s $f12, $f12, $f4 # leave only the fractional part
+    mtc1 $t1, $f4
+    cvt.s.w $f4, $f4
+    mul.s $f12, $f12, $f4
+    trunc.w.s $f4, $f12
+    mfc1 $t2, $f4 # the fractional part scaled up to an int
+
+    li $v0, 11
+    li $a0, 0x2e
+    syscall # print '.'
+
+# print a zero for each leading place the fractional part is too small to fill, but never the last place,
+# which is always printed with the rest of the digits
+__print_float_pad:
+    div $t1, $t1, 10
+    beq $t1, 1, __print_float_digits
+    bge $t2, $t1, __print_float_digits
+
+    li $a0, 0x30
+    syscall # print '0'
+    j __print_float_pad
+
+__print_float_digits:
+    move $a0, $t2
+    li $v0, 1
+    syscall
+
+__print_float_end:
+    jr $ra
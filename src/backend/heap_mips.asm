@@ -0,0 +1,26 @@
+# heap for MIPS, used by the alloc intrinsic to give out memory which is never freed
+
+# Takes a number of bytes in $a0 and puts the address of a new block of at least that many bytes into $a0.
+# Each block is taken from the end of the heap with sbrk, and its size is rounded up to a multiple of 8 so
+# that the block after it is aligned to 8 bytes.
+__alloc:
+    addi $a0, $a0, 7 # round the size up to a multiple of 8
+    li $t0, -8
+    and $a0, $a0, $t0
+
+    li $v0, 9 # sbrk
+    syscall
+
+    andi $t0, $v0, 7
+    beq $t0, $zero, __alloc_return # if the block is already aligned, return it
+
+    # otherwise the heap ended on a word, so take the word after the block and move the block up into it
+    move $t1, $v0
+    li $a0, 4
+    li $v0, 9
+    syscall
+    addi $v0, $t1, 4
+
+__alloc_return:
+    move $a0, $v0
+    jr $ra
@@ -0,0 +1,985 @@
+use std::io::{self, BufRead, Write};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::backend::OverflowMode;
+use crate::errors::RuntimeError;
+use crate::frontend::intermediate_gen::{IntermediateInstr, Argument};
+use crate::frontend::semantics::{SymbolTable, SymbolTableRow};
+use crate::frontend::ast::{PrintFormat, Type};
+
+
+/**
+ * The address of the first byte of the heap, which is where the heap starts in MARS so that addresses given
+ * out by `alloc` match those in the simulator.
+ */
+const HEAP_BASE:i64 = 0x10040000;
+
+
+/**
+ * Represents a value on the interpreter's evaluation stack or stored in a variable.
+ */
+#[derive(PartialEq, Debug, Clone)]
+pub enum Value {
+    Byte(u8),
+    Integer(i32),
+    Long(i64),
+    Char(char),
+    Boolean(bool),
+    Float(f32),
+    Double(f64),
+    String(String)
+}
+
+impl Value {
+    /**
+     * Returns true if the value is zero or false, which is when `JumpZero` takes the jump.
+     */
+    fn is_zero(&self) -> bool {
+        match self {
+            Value::Byte(value) => *value == 0,
+            Value::Integer(value) => *value == 0,
+            Value::Long(value) => *value == 0,
+            Value::Char(value) => *value == '\0',
+            Value::Boolean(value) => !value,
+            Value::Float(value) => *value == 0.0,
+            Value::Double(value) => *value == 0.0,
+            Value::String(value) => value.is_empty()
+        }
+    }
+
+
+    /**
+     * Returns the type of the value, as it is written in a program.
+     */
+    pub fn get_type(&self) -> Type {
+        match self {
+            Value::Byte(_) => Type::Byte,
+            Value::Integer(_) => Type::Integer,
+            Value::Long(_) => Type::Long,
+            Value::Char(_) => Type::Char,
+            Value::Boolean(_) => Type::Boolean,
+            Value::Float(_) => Type::Float,
+            Value::Double(_) => Type::Double,
+            Value::String(_) => Type::String
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /**
+     * Formats the value as it would be written as a literal, with chars and strings in quotes.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Byte(value) => write!(f, "{}", value),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Long(value) => write!(f, "{}", value),
+            Value::Char(value) => write!(f, "{:?}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Double(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "\"{}\"", value)
+        }
+    }
+}
+
+
+/**
+ * Represents the state of a single function call: the variables stored in it, the arguments it was called
+ * with, the height of the stack when it was called, and where to carry on from when it returns.
+ */
+struct Frame {
+    function: String,
+    variables: HashMap<usize, Value>,
+    arguments: Vec<Value>,
+    stack_base: usize,
+    return_address: usize
+}
+
+
+/**
+ * Executes intermediate code directly, without generating any target code. Used to get the expected
+ * behaviour of a program to compare the target code against, and to run programs from the command line.
+ */
+pub struct Interpreter<'a> {
+    instructions: &'a [IntermediateInstr],
+    functions: HashMap<String, usize>,
+    labels: HashMap<String, usize>,
+    parameters: HashMap<String, Vec<Type>>,
+    statics: HashMap<String, Value>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    heap: Vec<u8>,
+    pc: usize,
+    steps: u64,
+    exit_code: i32,
+    max_steps: Option<u64>,
+    max_call_depth: Option<usize>,
+    max_heap: Option<usize>,
+    overflow: OverflowMode
+}
+
+impl<'a> Interpreter<'a> {
+    /**
+     * Creates an interpreter for the given intermediate code, finding the location of every function and label
+     * and the parameter types of each function from the symbol table. Static variables are given their
+     * initial values up front, as they would be in the data section.
+     */
+    pub fn new(instructions:&'a [IntermediateInstr], symbol_table:&SymbolTable) -> Interpreter<'a> {
+        let mut functions = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut statics = HashMap::new();
+        for (index, instr) in instructions.iter().enumerate() {
+            match instr {
+                IntermediateInstr::FuncStart(name) => { functions.insert(name.to_owned(), index); },
+                IntermediateInstr::Label(label) => { labels.insert(label.to_owned(), index); },
+                IntermediateInstr::DeclareStatic(_, label, value) => { statics.insert(label.to_owned(), get_argument_value(value)); },
+                _ => {}
+            }
+        }
+
+        let mut parameters = HashMap::new();
+        for row in &symbol_table.rows {
+            if let SymbolTableRow::Function {identifier, parameters: param_types, ..} = row {
+                parameters.insert(identifier.to_owned(), param_types.clone());
+            }
+        }
+
+        Interpreter {
+            instructions,
+            functions,
+            labels,
+            parameters,
+            statics,
+            stack: vec![],
+            frames: vec![],
+            heap: vec![],
+            pc: instructions.len(),
+            steps: 0,
+            exit_code: 0,
+            max_steps: None,
+            max_call_depth: None,
+            max_heap: None,
+            overflow: OverflowMode::Wrap
+        }
+    }
+
+
+    /**
+     * Limits the number of instructions the program may execute, so that a program which never ends is
+     * stopped with a `BudgetExceeded` error rather than running forever.
+     */
+    pub fn with_max_steps(mut self, max_steps:u64) -> Interpreter<'a> {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+
+    /**
+     * Limits the number of calls which may be in progress at once, counting `main`, so that a program which
+     * recurses without end is stopped with a `BudgetExceeded` error.
+     */
+    pub fn with_max_call_depth(mut self, max_call_depth:usize) -> Interpreter<'a> {
+        self.max_call_depth = Some(max_call_depth);
+        self
+    }
+
+
+    /**
+     * Limits the number of bytes the program may allocate on the heap with `alloc`, after each block is
+     * rounded up to a multiple of 8, going over which stops it with a `BudgetExceeded` error.
+     */
+    pub fn with_max_heap(mut self, max_heap:usize) -> Interpreter<'a> {
+        self.max_heap = Some(max_heap);
+        self
+    }
+
+
+    /**
+     * Sets what int arithmetic does when its result does not fit in an int, which is to wrap by default. In
+     * trap mode the program prints `integer overflow in function <name>` and exits with code 1, as the MIPS
+     * generated with `--overflow trap` does.
+     */
+    pub fn with_overflow(mut self, overflow:OverflowMode) -> Interpreter<'a> {
+        self.overflow = overflow;
+        self
+    }
+
+
+    /**
+     * Runs the program from the start of `main` until `main` ends or a `Trap` stops it, reading input from
+     * `input` and writing output to `output`. Returns the exit code of the program, which is 1 if it was
+     * stopped by a trap.
+     */
+    pub fn run(&mut self, input:&mut dyn BufRead, output:&mut dyn Write) -> Result<i32, RuntimeError> {
+        self.start()?;
+        let result = self.run_from(self.pc, input, output);
+        output.flush().map_err(|err| RuntimeError::OutputFailed(err.to_string()))?;
+        result.map(|_| self.exit_code)
+    }
+
+
+    /**
+     * Sets the program up to run from the start of `main` one instruction at a time with `step_instruction`,
+     * without executing anything.
+     */
+    pub fn start(&mut self) -> Result<(), RuntimeError> {
+        self.pc = self.get_function_start("main")?;
+        self.steps = 0;
+        self.frames.push(Frame {
+            function: "main".to_owned(),
+            variables: HashMap::new(),
+            arguments: vec![],
+            stack_base: 0,
+            return_address: self.instructions.len()
+        });
+
+        Ok(())
+    }
+
+
+    /**
+     * Executes the next instruction of a program set up with `start`, counting it against the limit on steps
+     * if there is one. Returns false once the program has finished, after which nothing more is executed.
+     */
+    pub fn step_instruction(&mut self, input:&mut dyn BufRead, output:&mut dyn Write) -> Result<bool, RuntimeError> {
+        if self.is_finished() {
+            return Ok(false);
+        }
+
+        self.steps += 1;
+        if let Some(max_steps) = self.max_steps.filter(|max_steps| self.steps > *max_steps) {
+            return Err(RuntimeError::BudgetExceeded(format!("{} steps", max_steps)));
+        }
+
+        self.pc = self.step(self.pc, input, output)?.unwrap_or(self.instructions.len());
+        Ok(!self.is_finished())
+    }
+
+
+    /**
+     * Returns the index of the instruction `step_instruction` executes next.
+     */
+    pub fn get_pc(&self) -> usize {
+        self.pc
+    }
+
+
+    pub fn is_finished(&self) -> bool {
+        self.pc >= self.instructions.len()
+    }
+
+
+    pub fn get_exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+
+    /**
+     * Returns the evaluation stack, with the value on top of it last.
+     */
+    pub fn get_stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+
+    /**
+     * Returns the name of the function being run, or `None` if the program has finished.
+     */
+    pub fn get_current_function(&self) -> Option<&str> {
+        self.frames.last().map(|frame| frame.function.as_str())
+    }
+
+
+    /**
+     * Returns the value stored in the given slot of the frame of the function being run, or `None` if nothing
+     * has been stored there yet.
+     */
+    pub fn get_variable(&self, id:usize) -> Option<&Value> {
+        self.frames.last().and_then(|frame| frame.variables.get(&id))
+    }
+
+
+    /**
+     * Returns the index of the first instruction of the body of the given function, which is where a call to
+     * it carries on from, or `None` if there is no such function.
+     */
+    pub fn find_function_body(&self, name:&str) -> Option<usize> {
+        self.functions.get(name).map(|start| start + 1)
+    }
+
+
+    /**
+     * Returns the index of the given label, or `None` if there is no such label.
+     */
+    pub fn find_label(&self, label:&str) -> Option<usize> {
+        self.labels.get(label).copied()
+    }
+
+
+    /**
+     * Calls the given function with the given arguments, with no input and with any output thrown away, and
+     * returns the value it returns. Returns `None` if the function is void or stops the program with a trap.
+     * Used to work out calls to pure functions at compile time, so the same interpreter can be used for many
+     * calls.
+     */
+    pub fn evaluate_call(&mut self, function:&str, arguments:&[Argument]) -> Result<Option<Value>, RuntimeError> {
+        let pc = self.get_function_start(function)?;
+        self.exit_code = 0;
+        self.stack.clear();
+        self.frames.clear();
+        self.frames.push(Frame {
+            function: function.to_owned(),
+            variables: HashMap::new(),
+            arguments: arguments.iter().map(get_argument_value).collect(),
+            stack_base: 0,
+            return_address: self.instructions.len()
+        });
+
+        self.run_from(pc, &mut io::empty(), &mut io::sink())?;
+        match self.exit_code {
+            0 => Ok(self.stack.pop()),
+            _ => Ok(None)
+        }
+    }
+
+
+    /**
+     * Executes instructions from `pc` until the outermost call returns or a trap stops the program, counting
+     * the steps taken against the limit if there is one.
+     */
+    fn run_from(&mut self, pc:usize, input:&mut dyn BufRead, output:&mut dyn Write) -> Result<(), RuntimeError> {
+        self.pc = pc;
+        self.steps = 0;
+        while self.step_instruction(input, output)? {}
+
+        Ok(())
+    }
+
+
+    /**
+     * Executes the instruction at `pc` and returns the index of the next instruction to execute, or `None`
+     * if the program has finished.
+     */
+    fn step(&mut self, pc:usize, input:&mut dyn BufRead, output:&mut dyn Write) -> Result<Option<usize>, RuntimeError> {
+        match &self.instructions[pc] {
+            IntermediateInstr::Push(_, argument) => self.stack.push(get_argument_value(argument)),
+
+            IntermediateInstr::Load(_, id) => {
+                let value = self.get_frame()?.variables.get(id).cloned().ok_or(RuntimeError::UninitialisedVariable(*id))?;
+                self.stack.push(value);
+            },
+
+            IntermediateInstr::Store(_, id) => {
+                let value = self.pop()?;
+                self.get_frame_mut()?.variables.insert(*id, value);
+            },
+
+            IntermediateInstr::LoadStatic(_, label) => {
+                let value = self.statics.get(label).cloned().ok_or_else(|| RuntimeError::UnknownStatic(label.to_owned()))?;
+                self.stack.push(value);
+            },
+
+            IntermediateInstr::StoreStatic(_, label) => {
+                let value = self.pop()?;
+                match self.statics.get_mut(label) {
+                    Some(stored) => *stored = value,
+                    None => return Err(RuntimeError::UnknownStatic(label.to_owned()))
+                }
+            },
+
+            IntermediateInstr::DeclareStatic(..) => {},
+
+            IntermediateInstr::LoadParam(_, index) => {
+                let frame = self.get_frame()?;
+                let value = frame.arguments.get(*index).cloned()
+                                 .ok_or_else(|| RuntimeError::InvalidParameter(frame.function.to_owned(), *index))?;
+                self.stack.push(value);
+            },
+
+            IntermediateInstr::Pop(_) => { self.pop()?; },
+
+            IntermediateInstr::Add
+              | IntermediateInstr::Sub
+              | IntermediateInstr::Mult
+              | IntermediateInstr::Div
+              | IntermediateInstr::BitwiseAnd
+              | IntermediateInstr::BitwiseOr
+              | IntermediateInstr::BitwiseXor
+              | IntermediateInstr::LeftShiftLogical
+              | IntermediateInstr::RightShiftArithmetic
+              | IntermediateInstr::RightShiftLogical
+              | IntermediateInstr::LogicAnd
+              | IntermediateInstr::LogicOr
+              | IntermediateInstr::LogicXor => {
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
+                let function = self.get_frame()?.function.to_owned();
+                if self.overflow == OverflowMode::Trap && overflows(&self.instructions[pc], &lhs, &rhs) {
+                    return self.trap_overflow(&function, output);
+                }
+
+                self.stack.push(apply_binary_operator(&self.instructions[pc], lhs, rhs, &function)?);
+            },
+
+            IntermediateInstr::GreaterThan
+              | IntermediateInstr::LessThan
+              | IntermediateInstr::GreaterEqual
+              | IntermediateInstr::LessEqual
+              | IntermediateInstr::Equal
+              | IntermediateInstr::NotEqual => {
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
+                self.stack.push(Value::Boolean(apply_comparison(&self.instructions[pc], &lhs, &rhs)?));
+            },
+
+            IntermediateInstr::Min | IntermediateInstr::Max | IntermediateInstr::Pow => {
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
+                self.stack.push(apply_intrinsic(&self.instructions[pc], lhs, rhs)?);
+            },
+
+            IntermediateInstr::NumNeg | IntermediateInstr::Complement | IntermediateInstr::LogicNeg | IntermediateInstr::Abs
+              | IntermediateInstr::Round | IntermediateInstr::Floor | IntermediateInstr::Ceil => {
+                let operand = self.pop()?;
+                if self.overflow == OverflowMode::Trap && matches!((&self.instructions[pc], &operand), (IntermediateInstr::NumNeg, Value::Integer(i32::MIN))) {
+                    let function = self.get_frame()?.function.to_owned();
+                    return self.trap_overflow(&function, output);
+                }
+
+                self.stack.push(apply_unary_operator(&self.instructions[pc], operand)?);
+            },
+
+            // strings keep the escape sequences of their literals, so each one counts as the char it stands for
+            IntermediateInstr::StrLen => {
+                let string = match self.pop()? {
+                    Value::String(string) => unescape_string(&string),
+                    other => return Err(RuntimeError::TypeMismatch(format!("cannot take the length of {:?}", other)))
+                };
+
+                self.stack.push(Value::Integer(string.chars().count() as i32));
+            },
+
+            IntermediateInstr::StrIndex => {
+                let (index, string) = match (self.pop()?, self.pop()?) {
+                    (Value::Integer(index), Value::String(string)) => (index, unescape_string(&string)),
+                    (index, string) => return Err(RuntimeError::TypeMismatch(format!("cannot index {:?} with {:?}", string, index)))
+                };
+
+                let character = usize::try_from(index).ok().and_then(|index| string.chars().nth(index));
+                self.stack.push(Value::Char(character.ok_or(RuntimeError::IndexOutOfRange(index, string.chars().count()))?));
+            },
+
+            // the string and index are left on the stack for the `StrIndex` which follows
+            IntermediateInstr::CheckIndex(function) => {
+                let (index, length) = match &self.stack[..] {
+                    [.., Value::String(string), Value::Integer(index)] => (*index, unescape_string(string).chars().count()),
+                    _ => return Err(RuntimeError::TypeMismatch("cannot check the index of a non-string value".to_owned()))
+                };
+
+                if usize::try_from(index).map_or(true, |index| index >= length) {
+                    writeln!(output, "Index {} is out of range for a string of length {} in function {}", index, length, function)
+                        .map_err(|err| RuntimeError::OutputFailed(err.to_string()))?;
+                    self.exit_code = 1;
+                    return Ok(None);
+                }
+            },
+
+            // blocks are never freed, so each one is taken from the end of the heap after rounding it up to a
+            // multiple of 8 bytes, which keeps the start of every block aligned
+            IntermediateInstr::Alloc => {
+                let bytes = match self.pop()? {
+                    Value::Integer(bytes) if bytes >= 0 => bytes as usize,
+                    Value::Integer(bytes) => return Err(RuntimeError::InvalidAllocation(bytes)),
+                    other => return Err(RuntimeError::TypeMismatch(format!("cannot allocate {:?} bytes", other)))
+                };
+
+                let size = self.heap.len() + bytes.next_multiple_of(8);
+                if let Some(max_heap) = self.max_heap.filter(|max_heap| size > *max_heap) {
+                    return Err(RuntimeError::BudgetExceeded(format!("{} bytes of heap", max_heap)));
+                }
+
+                let address = HEAP_BASE + self.heap.len() as i64;
+                self.heap.resize(size, 0);
+                self.stack.push(Value::Long(address));
+            },
+
+            IntermediateInstr::Cast(_, into) => {
+                let value = self.pop()?;
+                self.stack.push(cast_value(value, into)?);
+            },
+
+            // hex and floats are printed in the same way as the MIPS library, with hex given a 0x prefix and no leading
+            // zeros
+            IntermediateInstr::Out(_, format) => {
+                let text = match (self.pop()?, format) {
+                    (Value::String(string), PrintFormat::Plain) => unescape_string(&string),
+                    (Value::Char(character), PrintFormat::Plain) => character.to_string(),
+                    (Value::Byte(byte), PrintFormat::Plain) => byte.to_string(),
+                    (Value::Integer(int), PrintFormat::Plain) => int.to_string(),
+                    (Value::Byte(byte), PrintFormat::Hex) => format!("{:#x}", byte),
+                    (Value::Integer(int), PrintFormat::Hex) => format!("{:#x}", int),
+                    (Value::Float(float), PrintFormat::Precision(places)) => format_float(float, *places),
+                    (other, format) => return Err(RuntimeError::TypeMismatch(format!("cannot print {:?} as {:?}", other, format)))
+                };
+
+                write!(output, "{}", text).map_err(|err| RuntimeError::OutputFailed(err.to_string()))?
+            },
+
+            // the program is stopped straight away, as the function has no value to give its caller
+            IntermediateInstr::Trap(message) => {
+                write!(output, "{}", unescape_string(message)).map_err(|err| RuntimeError::OutputFailed(err.to_string()))?;
+                self.exit_code = 1;
+                return Ok(None);
+            },
+
+            // a single byte is read, giving '\0' once the input has run out
+            IntermediateInstr::In(Type::Char, _) => {
+                let byte = input.fill_buf().map_err(|err| RuntimeError::InputFailed(err.to_string()))?.first().copied();
+                if byte.is_some() {
+                    input.consume(1);
+                }
+
+                self.stack.push(Value::Char(byte.map_or('\0', char::from)));
+            },
+
+            IntermediateInstr::In(_, length) => {
+                let mut line = String::new();
+                input.read_line(&mut line).map_err(|err| RuntimeError::InputFailed(err.to_string()))?;
+                let line:String = line.trim_end_matches(['\n', '\r']).chars().take(*length).collect();
+                self.stack.push(Value::String(line));
+            },
+
+            IntermediateInstr::Jump(label) => return Ok(Some(self.get_label(label)?)),
+            IntermediateInstr::JumpZero(label) => {
+                if self.pop()?.is_zero() {
+                    return Ok(Some(self.get_label(label)?));
+                }
+            },
+
+            IntermediateInstr::TableSwitch(low, labels, default) => {
+                let value = match self.pop()? {
+                    Value::Integer(value) => value as i64,
+                    Value::Char(value) => value as i64,
+                    other => return Err(RuntimeError::TypeMismatch(format!("cannot switch on {:?}", other)))
+                };
+
+                let label = usize::try_from(value - *low as i64).ok().and_then(|index| labels.get(index)).unwrap_or(default);
+                return Ok(Some(self.get_label(label)?));
+            },
+
+            IntermediateInstr::Call(name, _, argument_count) => {
+                let argument_count = *argument_count;
+                let expected = self.parameters.get(name).map(|types| types.len()).ok_or_else(|| RuntimeError::UnknownFunction(name.to_owned()))?;
+                if argument_count != expected {
+                    return Err(RuntimeError::WrongArgumentCount(name.to_owned(), expected, argument_count));
+                }
+
+                if self.stack.len() < argument_count {
+                    return Err(RuntimeError::StackUnderflow);
+                }
+
+                if let Some(max_call_depth) = self.max_call_depth.filter(|max_call_depth| self.frames.len() >= *max_call_depth) {
+                    return Err(RuntimeError::BudgetExceeded(format!("{} calls in progress at once", max_call_depth)));
+                }
+
+                let arguments = self.stack.split_off(self.stack.len() - argument_count);
+                self.frames.push(Frame {
+                    function: name.to_owned(),
+                    variables: HashMap::new(),
+                    arguments,
+                    stack_base: self.stack.len(),
+                    return_address: pc + 1
+                });
+
+                return Ok(Some(self.get_function_start(name)? + 1));
+            },
+
+            // anything the function left on the stack is discarded, except the return value for the caller
+            IntermediateInstr::Return(_) | IntermediateInstr::FuncEnd(_) => {
+                let return_value = match &self.instructions[pc] {
+                    IntermediateInstr::Return(Type::Void) | IntermediateInstr::FuncEnd(_) => None,
+                    _ => Some(self.pop()?)
+                };
+
+                let frame = self.frames.pop().ok_or(RuntimeError::StackUnderflow)?;
+                self.stack.truncate(frame.stack_base);
+                self.stack.extend(return_value);
+                if self.frames.is_empty() {
+                    return Ok(None);
+                }
+
+                return Ok(Some(frame.return_address));
+            },
+
+            IntermediateInstr::FuncStart(_) | IntermediateInstr::Label(_) => {}
+        }
+
+        Ok(Some(pc + 1))
+    }
+
+
+    /**
+     * Stops the program as the overflow handler of `--overflow trap` does, printing the name of the function
+     * the int overflowed in and exiting with code 1.
+     */
+    fn trap_overflow(&mut self, function:&str, output:&mut dyn Write) -> Result<Option<usize>, RuntimeError> {
+        writeln!(output, "integer overflow in function {}", function).map_err(|err| RuntimeError::OutputFailed(err.to_string()))?;
+        self.exit_code = 1;
+        Ok(None)
+    }
+
+
+    fn pop(&mut self) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or(RuntimeError::StackUnderflow)
+    }
+
+
+    fn get_frame(&self) -> Result<&Frame, RuntimeError> {
+        self.frames.last().ok_or(RuntimeError::StackUnderflow)
+    }
+
+
+    fn get_frame_mut(&mut self) -> Result<&mut Frame, RuntimeError> {
+        self.frames.last_mut().ok_or(RuntimeError::StackUnderflow)
+    }
+
+
+    fn get_function_start(&self, name:&str) -> Result<usize, RuntimeError> {
+        self.functions.get(name).copied().ok_or_else(|| RuntimeError::UnknownFunction(name.to_owned()))
+    }
+
+
+    fn get_label(&self, label:&str) -> Result<usize, RuntimeError> {
+        self.labels.get(label).copied().ok_or_else(|| RuntimeError::UnknownLabel(label.to_owned()))
+    }
+}
+
+
+/**
+ * Applies an operator, comparison or intrinsic to the arguments of the `Push` instructions giving its operands,
+ * as running it would, and returns the value it gives. Returns `None` if it would stop the program, such as by
+ * dividing by zero or by an int overflowing with the given overflow mode, or if the instruction does anything
+ * other than work out a value from its operands. Used to work out operations on constants at compile time.
+ */
+pub fn evaluate_operation(instr:&IntermediateInstr, operands:&[Argument], overflow:OverflowMode) -> Option<Value> {
+    let values:Vec<Value> = operands.iter().map(get_argument_value).collect();
+    match (instr, values.as_slice()) {
+        (IntermediateInstr::Add
+          | IntermediateInstr::Sub
+          | IntermediateInstr::Mult
+          | IntermediateInstr::Div
+          | IntermediateInstr::BitwiseAnd
+          | IntermediateInstr::BitwiseOr
+          | IntermediateInstr::BitwiseXor
+          | IntermediateInstr::LeftShiftLogical
+          | IntermediateInstr::RightShiftArithmetic
+          | IntermediateInstr::RightShiftLogical
+          | IntermediateInstr::LogicAnd
+          | IntermediateInstr::LogicOr
+          | IntermediateInstr::LogicXor, [lhs, rhs]) => {
+            if overflow == OverflowMode::Trap && overflows(instr, lhs, rhs) {
+                return None;
+            }
+
+            apply_binary_operator(instr, lhs.clone(), rhs.clone(), "").ok()
+        },
+
+        (IntermediateInstr::GreaterThan
+          | IntermediateInstr::LessThan
+          | IntermediateInstr::GreaterEqual
+          | IntermediateInstr::LessEqual
+          | IntermediateInstr::Equal
+          | IntermediateInstr::NotEqual, [lhs, rhs]) => apply_comparison(instr, lhs, rhs).ok().map(Value::Boolean),
+
+        (IntermediateInstr::Min | IntermediateInstr::Max | IntermediateInstr::Pow, [lhs, rhs]) => apply_intrinsic(instr, lhs.clone(), rhs.clone()).ok(),
+
+        (IntermediateInstr::NumNeg | IntermediateInstr::Complement | IntermediateInstr::LogicNeg | IntermediateInstr::Abs
+          | IntermediateInstr::Round | IntermediateInstr::Floor | IntermediateInstr::Ceil, [operand]) => {
+            if overflow == OverflowMode::Trap && matches!((instr, operand), (IntermediateInstr::NumNeg, Value::Integer(i32::MIN))) {
+                return None;
+            }
+
+            apply_unary_operator(instr, operand.clone()).ok()
+        },
+
+        _ => None
+    }
+}
+
+
+/**
+ * Converts the argument of a `Push` instruction into a value.
+ */
+fn get_argument_value(argument:&Argument) -> Value {
+    match argument {
+        Argument::Byte(value) => Value::Byte(*value),
+        Argument::Integer(value) => Value::Integer(*value),
+        Argument::Long(value) => Value::Long(*value),
+        Argument::Boolean(value) => Value::Boolean(*value),
+        Argument::Char(value) => Value::Char(*value),
+        Argument::Float(value) => Value::Float(*value),
+        Argument::Double(value) => Value::Double(*value),
+        Argument::String(value) => Value::String(value.to_owned())
+    }
+}
+
+
+/**
+ * Returns the text printed for a float to the given number of decimal places, worked out in the same steps as
+ * `__print_float` in the MIPS library so both give the same digits. A `-` is printed first if the sign bit is
+ * set, including for -0.0, then the whole part truncated to an int, then the fractional part scaled up by 10 to
+ * the power of the places, truncated, and padded with leading zeros. Each step is done in single precision, as
+ * it is in MIPS.
+ */
+fn format_float(value:f32, places:u8) -> String {
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let value = value.abs();
+    let whole = value as i32;
+    if places == 0 {
+        return format!("{}{}", sign, whole);
+    }
+
+    let scale = 10_i32.pow(places as u32);
+    let fraction = ((value - whole as f32) * scale as f32) as i32;
+    format!("{}{}.{:0width$}", sign, whole, fraction, width = places as usize)
+}
+
+
+/**
+ * Replaces the escape sequences in a string literal with the characters they represent, in the same way
+ * the assembler does for `.asciiz` strings.
+ */
+fn unescape_string(string:&str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = string.chars();
+    while let Some(character) = chars.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('0') => unescaped.push('\0'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\')
+        }
+    }
+
+    unescaped
+}
+
+
+/**
+ * Applies an arithmetic, bitwise, or logical binary operator to two values of the same type. Integer
+ * arithmetic wraps on overflow, so the caller checks for it first in trap mode.
+ */
+fn apply_binary_operator(instr:&IntermediateInstr, lhs:Value, rhs:Value, function:&str) -> Result<Value, RuntimeError> {
+    macro_rules! integer_op {
+        ($variant:ident, $lhs:expr, $rhs:expr, $unsigned:ty) => {
+            match instr {
+                IntermediateInstr::Add => Value::$variant($lhs.wrapping_add($rhs)),
+                IntermediateInstr::Sub => Value::$variant($lhs.wrapping_sub($rhs)),
+                IntermediateInstr::Mult => Value::$variant($lhs.wrapping_mul($rhs)),
+                IntermediateInstr::Div => {
+                    if $rhs == 0 {
+                        return Err(RuntimeError::DivisionByZero(function.to_owned()));
+                    }
+
+                    Value::$variant($lhs.wrapping_div($rhs))
+                },
+                IntermediateInstr::BitwiseAnd => Value::$variant($lhs & $rhs),
+                IntermediateInstr::BitwiseOr => Value::$variant($lhs | $rhs),
+                IntermediateInstr::BitwiseXor => Value::$variant($lhs ^ $rhs),
+                IntermediateInstr::LeftShiftLogical => Value::$variant($lhs.wrapping_shl($rhs as u32)),
+                IntermediateInstr::RightShiftArithmetic => Value::$variant($lhs.wrapping_shr($rhs as u32)),
+                IntermediateInstr::RightShiftLogical => Value::$variant(($lhs as $unsigned).wrapping_shr($rhs as u32) as _),
+                _ => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?}", instr, Value::$variant($lhs))))
+            }
+        };
+    }
+
+    macro_rules! float_op {
+        ($variant:ident, $lhs:expr, $rhs:expr) => {
+            match instr {
+                IntermediateInstr::Add => Value::$variant($lhs + $rhs),
+                IntermediateInstr::Sub => Value::$variant($lhs - $rhs),
+                IntermediateInstr::Mult => Value::$variant($lhs * $rhs),
+                IntermediateInstr::Div => Value::$variant($lhs / $rhs),
+                _ => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?}", instr, Value::$variant($lhs))))
+            }
+        };
+    }
+
+    let result = match (lhs, rhs) {
+        (Value::Integer(lhs), Value::Integer(rhs)) => integer_op!(Integer, lhs, rhs, u32),
+        (Value::Long(lhs), Value::Long(rhs)) => integer_op!(Long, lhs, rhs, u64),
+        (Value::Byte(lhs), Value::Byte(rhs)) => integer_op!(Byte, lhs, rhs, u8),
+        (Value::Float(lhs), Value::Float(rhs)) => float_op!(Float, lhs, rhs),
+        (Value::Double(lhs), Value::Double(rhs)) => float_op!(Double, lhs, rhs),
+
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => match instr {
+            IntermediateInstr::LogicAnd => Value::Boolean(lhs && rhs),
+            IntermediateInstr::LogicOr => Value::Boolean(lhs || rhs),
+            IntermediateInstr::LogicXor => Value::Boolean(lhs ^ rhs),
+            _ => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to booleans", instr)))
+        },
+
+        (Value::String(lhs), Value::String(rhs)) if matches!(instr, IntermediateInstr::Add) => Value::String(lhs + &rhs),
+        (Value::String(lhs), Value::Char(rhs)) if matches!(instr, IntermediateInstr::Add) => Value::String(format!("{}{}", lhs, rhs)),
+
+        (lhs, rhs) => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?} and {:?}", instr, lhs, rhs)))
+    };
+
+    Ok(result)
+}
+
+
+/**
+ * Returns true if adding, subtracting or multiplying two ints gives a result which does not fit in an int.
+ * Only ints are checked, as in the MIPS backend.
+ */
+fn overflows(instr:&IntermediateInstr, lhs:&Value, rhs:&Value) -> bool {
+    match (instr, lhs, rhs) {
+        (IntermediateInstr::Add, Value::Integer(lhs), Value::Integer(rhs)) => lhs.checked_add(*rhs).is_none(),
+        (IntermediateInstr::Sub, Value::Integer(lhs), Value::Integer(rhs)) => lhs.checked_sub(*rhs).is_none(),
+        (IntermediateInstr::Mult, Value::Integer(lhs), Value::Integer(rhs)) => lhs.checked_mul(*rhs).is_none(),
+        _ => false
+    }
+}
+
+
+/**
+ * Compares two values of the same type with a comparison operator.
+ */
+fn apply_comparison(instr:&IntermediateInstr, lhs:&Value, rhs:&Value) -> Result<bool, RuntimeError> {
+    let ordering = match (lhs, rhs) {
+        (Value::Integer(lhs), Value::Integer(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Long(lhs), Value::Long(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Byte(lhs), Value::Byte(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Double(lhs), Value::Double(rhs)) => lhs.partial_cmp(rhs),
+        (Value::String(lhs), Value::String(rhs)) => lhs.partial_cmp(rhs),
+        (lhs, rhs) => return Err(RuntimeError::TypeMismatch(format!("cannot compare {:?} and {:?}", lhs, rhs)))
+    };
+
+    let ordering = match ordering {
+        Some(ordering) => ordering,
+        None => return Ok(matches!(instr, IntermediateInstr::NotEqual))
+    };
+
+    Ok(match instr {
+        IntermediateInstr::GreaterThan => ordering.is_gt(),
+        IntermediateInstr::LessThan => ordering.is_lt(),
+        IntermediateInstr::GreaterEqual => ordering.is_ge(),
+        IntermediateInstr::LessEqual => ordering.is_le(),
+        IntermediateInstr::Equal => ordering.is_eq(),
+        _ => ordering.is_ne()
+    })
+}
+
+
+/**
+ * Applies the `Min`, `Max`, or `Pow` intrinsic to two values of the same type. As in the MIPS backend, `Pow`
+ * wraps on overflow, gives 0 for a negative exponent, and only uses the low 32 bits of a long exponent.
+ */
+fn apply_intrinsic(instr:&IntermediateInstr, lhs:Value, rhs:Value) -> Result<Value, RuntimeError> {
+    macro_rules! select {
+        ($variant:ident, $lhs:expr, $rhs:expr) => {
+            match instr {
+                IntermediateInstr::Min => Value::$variant(if $rhs < $lhs { $rhs } else { $lhs }),
+                IntermediateInstr::Max => Value::$variant(if $lhs < $rhs { $rhs } else { $lhs }),
+                _ => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?}", instr, Value::$variant($lhs))))
+            }
+        };
+    }
+
+    let result = match (instr, lhs, rhs) {
+        (IntermediateInstr::Pow, Value::Integer(base), Value::Integer(exponent)) => {
+            Value::Integer(if exponent < 0 { 0 } else { base.wrapping_pow(exponent as u32) })
+        },
+
+        (IntermediateInstr::Pow, Value::Long(base), Value::Long(exponent)) => {
+            Value::Long(if exponent < 0 { 0 } else { base.wrapping_pow(exponent as u32) })
+        },
+
+        (_, Value::Integer(lhs), Value::Integer(rhs)) => select!(Integer, lhs, rhs),
+        (_, Value::Long(lhs), Value::Long(rhs)) => select!(Long, lhs, rhs),
+        (_, Value::Float(lhs), Value::Float(rhs)) => select!(Float, lhs, rhs),
+        (_, Value::Double(lhs), Value::Double(rhs)) => select!(Double, lhs, rhs),
+        (instr, lhs, rhs) => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?} and {:?}", instr, lhs, rhs)))
+    };
+
+    Ok(result)
+}
+
+
+/**
+ * Applies a unary operator to a value.
+ */
+fn apply_unary_operator(instr:&IntermediateInstr, operand:Value) -> Result<Value, RuntimeError> {
+    let result = match (instr, operand) {
+        (IntermediateInstr::NumNeg, Value::Integer(value)) => Value::Integer(value.wrapping_neg()),
+        (IntermediateInstr::NumNeg, Value::Long(value)) => Value::Long(value.wrapping_neg()),
+        (IntermediateInstr::NumNeg, Value::Byte(value)) => Value::Byte(value.wrapping_neg()),
+        (IntermediateInstr::NumNeg, Value::Float(value)) => Value::Float(-value),
+        (IntermediateInstr::NumNeg, Value::Double(value)) => Value::Double(-value),
+        (IntermediateInstr::Complement, Value::Integer(value)) => Value::Integer(!value),
+        (IntermediateInstr::Complement, Value::Long(value)) => Value::Long(!value),
+        (IntermediateInstr::Complement, Value::Byte(value)) => Value::Byte(!value),
+        (IntermediateInstr::LogicNeg, Value::Boolean(value)) => Value::Boolean(!value),
+        (IntermediateInstr::Abs, Value::Integer(value)) => Value::Integer(value.wrapping_abs()),
+        (IntermediateInstr::Abs, Value::Long(value)) => Value::Long(value.wrapping_abs()),
+        (IntermediateInstr::Abs, Value::Float(value)) => Value::Float(value.abs()),
+        (IntermediateInstr::Abs, Value::Double(value)) => Value::Double(value.abs()),
+
+        // halfway values are rounded to the even int, as the MIPS round instructions do
+        (IntermediateInstr::Round, Value::Float(value)) => Value::Integer(value.round_ties_even() as i32),
+        (IntermediateInstr::Round, Value::Double(value)) => Value::Integer(value.round_ties_even() as i32),
+        (IntermediateInstr::Floor, Value::Float(value)) => Value::Integer(value.floor() as i32),
+        (IntermediateInstr::Floor, Value::Double(value)) => Value::Integer(value.floor() as i32),
+        (IntermediateInstr::Ceil, Value::Float(value)) => Value::Integer(value.ceil() as i32),
+        (IntermediateInstr::Ceil, Value::Double(value)) => Value::Integer(value.ceil() as i32),
+        (instr, operand) => return Err(RuntimeError::TypeMismatch(format!("cannot apply {:?} to {:?}", instr, operand)))
+    };
+
+    Ok(result)
+}
+
+
+/**
+ * Converts a value into the given type, following the conversions supported by the `Cast` instruction.
+ */
+fn cast_value(value:Value, into:&Type) -> Result<Value, RuntimeError> {
+    let result = match (value, into) {
+        (Value::Integer(value), Type::Byte) => Value::Byte(value as u8),
+        (Value::Integer(value), Type::Long) => Value::Long(value as i64),
+        (Value::Integer(value), Type::Float) => Value::Float(value as f32),
+        (Value::Integer(value), Type::Double) => Value::Double(value as f64),
+        (Value::Integer(value), Type::String) => Value::String(value.to_string()),
+        (Value::Long(value), Type::Integer) => Value::Integer(value as i32),
+        (Value::Byte(value), Type::Char) => Value::Char(value as char),
+        (Value::Byte(value), Type::Integer) => Value::Integer(value as i32),
+        (Value::Byte(value), Type::Long) => Value::Long(value as i64),
+        (Value::Byte(value), Type::Float) => Value::Float(value as f32),
+        (Value::Byte(value), Type::Double) => Value::Double(value as f64),
+        (Value::Byte(value), Type::String) => Value::String(value.to_string()),
+        (Value::Float(value), Type::Integer) => Value::Integer(value as i32),
+        (Value::Float(value), Type::Byte) => Value::Byte(value as u8),
+        (Value::Float(value), Type::Double) => Value::Double(value as f64),
+        (Value::Double(value), Type::Integer) => Value::Integer(value as i32),
+        (Value::Double(value), Type::Byte) => Value::Byte(value as u8),
+        (Value::Double(value), Type::Float) => Value::Float(value as f32),
+        (Value::Char(value), Type::Byte) => Value::Byte(value as u8),
+        (Value::String(value), Type::Integer) => Value::Integer(value.trim().parse().unwrap_or(0)),
+        (Value::String(value), Type::Byte) => Value::Byte(value.trim().parse().unwrap_or(0)),
+        (value, into) => return Err(RuntimeError::TypeMismatch(format!("cannot cast {:?} to {}", value, into)))
+    };
+
+    Ok(result)
+}
@@ -2,12 +2,71 @@ use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::io::{self, BufRead};
 use std::error::Error;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{mpsc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 
-use crate::frontend::intermediate_gen::{IntermediateInstr, Argument};
-use crate::frontend::semantics::{SymbolTable, SymbolTableRow};
-use crate::frontend::ast::Type;
+use crate::frontend::intermediate_gen::{IntermediateInstr, Argument, FunctionCode, FunctionVariables, get_function_slots, get_slot_count, is_byte_sized, split_functions};
+use crate::frontend::semantics::{SymbolTable, RESERVED_PREFIX};
+use crate::frontend::ast::{PrintFormat, Type};
+use crate::errors::{CompilationContext, InvalidOperandType, MissingTargetTemplate, StackSizeTooLarge, UnbalancedCall};
+use crate::backend::{Backend, OverflowMode};
+
+
+/**
+ * The options given on the command line which change the MIPS generated for a program. `debug_info` holds the
+ * variables in the frame of each function when `--debug-info` is given, which are then described at the start
+ * of each function. `frame_checks` guards the frame of every function but `main` with a canary, which is
+ * checked before the function returns. `stack_size` gives the number of bytes to set aside in the data
+ * section for the stack, which the program moves `$sp` into before `main` starts, in place of the stack the
+ * simulator gives it.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CodegenOptions<'a> {
+    pub overflow: OverflowMode,
+    pub distinct_input_buffers: bool,
+    pub no_packing: bool,
+    pub frame_checks: bool,
+    pub stack_size: Option<u64>,
+    pub debug_info: Option<&'a [FunctionVariables]>
+}
+
+
+/**
+ * The number of bytes between the start of the data section and the start of the heap in the default memory
+ * layout of MARS, which the data section, along with any stack set aside in it, must fit into.
+ */
+pub const DATA_SEGMENT_SIZE:u64 = 0x30000;
+
+
+/**
+ * The backend for MIPS assembly, which can be run in the SPIM and MARS simulators, generating code with the
+ * options given on the command line.
+ */
+pub struct MipsBackend<'a> {
+    pub options: CodegenOptions<'a>
+}
+
+impl Backend for MipsBackend<'_> {
+    fn get_name(&self) -> &'static str {
+        "mips"
+    }
+
+    fn get_extension(&self) -> &'static str {
+        "asm"
+    }
+
+    fn get_description(&self) -> &'static str {
+        "MIPS assembly for the SPIM and MARS simulators"
+    }
+
+    fn generate(&self, intermediate_code:Vec<IntermediateInstr>, symbol_table:&SymbolTable, mut writer:&mut dyn Write)
+            -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        generate_mips(intermediate_code, &mut writer, symbol_table, &self.options)
+    }
+}
 
 
 #[allow(dead_code)]
@@ -19,48 +78,243 @@ struct VariableTableRow {
 
 
 /**
- * Calculates the size required for the function frame. Used when invoking a function.
+ * Represents a line of generated MIPS assembly, so that the code can be inspected and changed before it is
+ * written out. Code from templates in *target_code.json* which has not been moved into Rust is kept as `Raw`
+ * text, which may span several lines.
  */
-fn get_frame_size(function_id:&str, symbol_table:&SymbolTable) -> u64 {
-    let mut frame_size = 0;
-    for symbol in &symbol_table.rows {
-        match symbol {
-            SymbolTableRow::Variable {primitive_type, function_id: fid, ..} => {
-                if fid != function_id {
-                    continue;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MipsInstr {
+    Op {opcode:&'static str, operands:Vec<String>, comment:Option<String>},
+    Label(String),
+    Raw(String),
+    Blank
+}
+
+impl MipsInstr {
+    /**
+     * Creates an instruction with the given opcode and operands and no comment.
+     */
+    pub fn op(opcode:&'static str, operands:&[&str]) -> MipsInstr {
+        MipsInstr::Op {
+            opcode,
+            operands: operands.iter().map(|operand| operand.to_string()).collect(),
+            comment: None
+        }
+    }
+
+
+    /**
+     * Returns this instruction with the given comment after it. Has no effect on anything but an `Op`.
+     */
+    pub fn comment(self, text:&str) -> MipsInstr {
+        match self {
+            MipsInstr::Op {opcode, operands, ..} => MipsInstr::Op {opcode, operands, comment: Some(text.to_owned())},
+            other => other
+        }
+    }
+
+
+    /**
+     * Returns the assembly text for this instruction, without a trailing newline. Instructions are indented by
+     * a tab with their operands separated by commas, and labels are not indented.
+     */
+    pub fn render(&self) -> String {
+        match self {
+            MipsInstr::Op {opcode, operands, comment} => {
+                let mut text = format!("\t{}", opcode);
+                if !operands.is_empty() {
+                    text += &format!(" {}", operands.join(", "));
                 }
 
-                // add the size in bytes of the datatype to the frame size
-                match primitive_type {
-                    Type::Float => frame_size += 4,
-                    Type::Double => frame_size += 8,
-                    Type::Char => frame_size += 4,
-                    Type::Byte => frame_size += 4,
-                    Type::Integer => frame_size += 4,
-                    Type::Long => frame_size += 8,
-                    Type::Boolean => frame_size += 4,
-                    Type::String => frame_size += 4, // pointer
-                    Type::Void => panic!("Type void cannot be stored on the stack")
+                if let Some(comment) = comment {
+                    text += &format!(" # {}", comment);
                 }
+
+                text
             },
 
-            _ => {}
+            MipsInstr::Label(label) => format!("{}:", label),
+            MipsInstr::Raw(text) => text.to_owned(),
+            MipsInstr::Blank => String::new()
         }
     }
 
-    frame_size
+
+    /**
+     * Returns the number of machine instructions this represents, not counting labels, comments, blank lines
+     * or assembler directives.
+     */
+    pub fn count_instructions(&self) -> usize {
+        match self {
+            MipsInstr::Op {..} => 1,
+            MipsInstr::Raw(text) => text.lines().filter(|line| {
+                let line = line.split('#').next().unwrap().trim();
+                !line.is_empty() && !line.starts_with('.') && !line.split_whitespace().next().unwrap().ends_with(':')
+            }).count(),
+            MipsInstr::Label(_) | MipsInstr::Blank => 0
+        }
+    }
 }
 
 
 /**
- * Opens the file *target_code.json* and returns the contents as structured data. 
+ * Describes where the variables of a function are kept in its frame. The word slots come first, counting down
+ * from the frame pointer, and are followed by the byte slots of its bytes, chars and bools, which are numbered
+ * on from the last word slot. Each byte slot is packed into a single byte, unless packing is turned off with
+ * `--no-packing` so that every slot takes up a word. As the byte slots come after all of the word slots, the
+ * ints, longs and other values held in words never need padding to keep them aligned. With `--frame-checks`
+ * the word just below the saved frame pointer holds the frame canary, and every slot is moved down by a word
+ * to make room for it.
  */
-fn read_target_code_json() -> serde_json::Value {
-    let mut file = OpenOptions::new().read(true).open("src/backend/target_code.json").expect("Could not read target_code.json");
-    let mut json = String::new();
-    file.read_to_string(&mut json).unwrap();
+#[derive(Debug, Clone, Copy)]
+pub struct FrameLayout {
+    word_slots: usize,
+    byte_slots: usize,
+    packed: bool,
+    canary: bool
+}
+
+impl FrameLayout {
+    pub fn new(function_id:&str, symbol_table:&SymbolTable, packed:bool, canary:bool) -> FrameLayout {
+        let (word_slots, byte_slots) = get_function_slots(function_id, symbol_table);
+        FrameLayout {word_slots, byte_slots, packed, canary}
+    }
+
+
+    /**
+     * Returns the number of bytes at the top of the frame kept for the canary, which is nothing unless the
+     * frame has one.
+     */
+    fn get_canary_size(&self) -> usize {
+        match self.canary {
+            true => 4,
+            false => 0
+        }
+    }
+
 
-    serde_json::from_str(&json).expect("Could not parse JSON from target_code.json")
+    /**
+     * Returns true if a variable of the given type is packed into a single byte, so is stored with `sb` and
+     * loaded with `lbu` rather than taking up a word.
+     */
+    pub fn is_packed(&self, var_type:&Type) -> bool {
+        self.packed && is_byte_sized(var_type)
+    }
+
+
+    /**
+     * Returns the offset below the frame pointer of the variable whose first slot is given. The offset points
+     * at the byte of a packed variable, or otherwise at the last word of the variable, matching the order the
+     * `store` and `load` templates expect the words of longs and doubles in.
+     */
+    pub fn get_var_offset(&self, slot:usize, var_type:&Type) -> usize {
+        match self.is_packed(var_type) {
+            true => 4 * self.word_slots + (slot - self.word_slots) + 1 + self.get_canary_size(),
+            false => 4 * (slot + get_slot_count(var_type)) + self.get_canary_size()
+        }
+    }
+
+
+    /**
+     * Returns the size of the frame in bytes, rounded up to a multiple of 8 so that the frame is made up of
+     * whole doublewords.
+     */
+    pub fn get_size(&self) -> u64 {
+        let byte_area = match self.packed {
+            true => self.byte_slots,
+            false => 4 * self.byte_slots
+        };
+
+        ((4 * self.word_slots + byte_area + self.get_canary_size()) as u64).next_multiple_of(8)
+    }
+}
+
+
+/**
+ * Calculates the size required for the variables in a function's frame, including the copies of its
+ * parameters, with its bytes, chars and bools packed into a byte each if `packed` is true. Does not include
+ * the frame canary of `--frame-checks`.
+ */
+pub fn get_frame_size(function_id:&str, symbol_table:&SymbolTable, packed:bool) -> u64 {
+    FrameLayout::new(function_id, symbol_table, packed, false).get_size()
+}
+
+
+/**
+ * Returns the number of bytes a value of the given type takes up when passed as an argument.
+ */
+fn get_param_size(param_type:&Type) -> usize {
+    match param_type {
+        Type::Long | Type::Double => 8,
+        _ => 4
+    }
+}
+
+
+/**
+ * Returns the number of bytes taken up by all the arguments to the given function, which the caller pushes
+ * before the call and removes once it returns.
+ */
+fn get_argument_area_size(function_id:&str, symbol_table:&SymbolTable) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let param_types = symbol_table.get_function_param_types(&function_id.to_owned())?;
+    Ok(param_types.iter().map(get_param_size).sum())
+}
+
+
+/**
+ * Returns the number of bytes of the stack taken up by each call to the given function: the arguments the
+ * caller pushes, the saved return address and frame pointer, and the frame itself, with its canary under
+ * `--frame-checks`. `main` is started rather than called, so only saves the frame pointer and never has a
+ * canary. The values pushed while evaluating expressions are not counted.
+ */
+pub fn get_call_size(function_id:&str, symbol_table:&SymbolTable, options:&CodegenOptions) -> Result<u64, Box<dyn Error>> {
+    let canary = options.frame_checks && function_id != "main";
+    let frame_size = FrameLayout::new(function_id, symbol_table, !options.no_packing, canary).get_size();
+    match function_id {
+        "main" => Ok(4 + frame_size),
+        _ => Ok(get_argument_area_size(function_id, symbol_table).map_err(|err| err as Box<dyn Error>)? as u64 + 8 + frame_size)
+    }
+}
+
+
+/**
+ * Returns the offset from the frame pointer of the lowest word of the parameter at `index` in the given
+ * function. Arguments are pushed in order before the return address, so the last parameter sits just above
+ * the saved return address and each earlier one is above the parameters which follow it.
+ */
+fn get_param_offset(function_id:&str, index:usize, symbol_table:&SymbolTable) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let param_types = symbol_table.get_function_param_types(&function_id.to_owned())?;
+    let bytes_after:usize = param_types.iter().skip(index + 1).map(get_param_size).sum();
+
+    // skip the saved frame pointer and return address
+    Ok(bytes_after + 8)
+}
+
+
+/**
+ * The path of the file holding the templates of the code generated for each intermediate instruction.
+ */
+pub const TARGET_CODE_PATH:&str = "src/backend/target_code.json";
+
+
+thread_local! {
+    static TEMPLATE_LOOKUPS: RefCell<Option<BTreeSet<Vec<String>>>> = const { RefCell::new(None) };
+}
+
+
+/**
+ * Opens the file *target_code.json* and returns the contents as structured data. The file is only read the
+ * first time, and every later call, from any thread, shares the same copy.
+ */
+fn read_target_code_json() -> &'static serde_json::Value {
+    static TARGET_CODE:OnceLock<serde_json::Value> = OnceLock::new();
+    TARGET_CODE.get_or_init(|| {
+        let mut file = OpenOptions::new().read(true).open(TARGET_CODE_PATH).expect("Could not read target_code.json");
+        let mut json = String::new();
+        file.read_to_string(&mut json).unwrap();
+
+        serde_json::from_str(&json).expect("Could not parse JSON from target_code.json")
+    })
 }
 
 
@@ -76,550 +330,1212 @@ fn insert_target_code_args(instr:&str, original:String, arguments:Vec<String>) -
         panic!("Instruction {} takes {} arguments, but {} were provided", instr, required_arg_count, arguments.len());
     }
 
-    for arg in arguments {
-        new_target_code = new_target_code.replacen("{}", &arg, 1);
-    }
+    for arg in arguments {
+        new_target_code = new_target_code.replacen("{}", &arg, 1);
+    }
+
+    new_target_code += "\n";
+    new_target_code
+}
+
+
+/**
+ * Converts the target code for an instruction from *target_code.json*, which is either a single line or a list
+ * of lines, into the text of the assembly.
+ */
+fn format_target_code(entry:&serde_json::Value) -> String {
+    serde_json::to_string(entry).unwrap().split("\",").map(|item| {
+        item.replace("[", "")
+            .replace("]", "")
+            .replace("\"", "")
+            .trim()
+            .to_string()
+            .replace("\\t", "\t")
+    }).collect::<Vec<String>>().join("\n")
+}
+
+
+/**
+ * Runs `generate`, recording every template looked up in *target_code.json* on this thread while it runs as
+ * the architecture, the instruction and the type of each version followed, such as `["mips", "add", "int"]`.
+ * Returns the result of `generate` with the templates, so the templates a program needs can be found by
+ * generating its code on a single thread.
+ */
+pub fn record_template_lookups<T>(generate:impl FnOnce() -> T) -> (T, BTreeSet<Vec<String>>) {
+    let previous = TEMPLATE_LOOKUPS.with(|lookups| lookups.replace(Some(BTreeSet::new())));
+    let result = generate();
+    let lookups = TEMPLATE_LOOKUPS.with(|lookups| lookups.replace(previous)).unwrap_or_default();
+
+    (result, lookups)
+}
+
+
+/**
+ * Finds the entry in *target_code.json* for an instruction on the given architecture, following `op_types` into
+ * the version of the instruction for each type in turn. Returns a `MissingTargetTemplate` error listing the
+ * types there are versions for if there is no entry, rather than letting invalid code be generated.
+ */
+pub fn find_target_template<'a>(json:&'a serde_json::Value, architecture:&str, instr:&str, op_types:&[&str]) -> Result<&'a serde_json::Value, MissingTargetTemplate> {
+    TEMPLATE_LOOKUPS.with(|lookups| {
+        if let Some(lookups) = lookups.borrow_mut().as_mut() {
+            lookups.insert([architecture, instr].iter().chain(op_types).map(|name| name.to_string()).collect());
+        }
+    });
+
+    let mut entry = &json[architecture][instr];
+    let mut instruction = instr.to_owned();
+    if entry.is_null() {
+        return Err(MissingTargetTemplate {architecture: architecture.to_owned(), instruction, op_type: None, available: vec![]});
+    }
+
+    for op_type in op_types {
+        if entry[op_type].is_null() {
+            return Err(MissingTargetTemplate {
+                architecture: architecture.to_owned(),
+                instruction,
+                op_type: Some(op_type.to_string()),
+                available: entry.as_object().map(|versions| versions.keys().cloned().collect()).unwrap_or_default()
+            });
+        }
+
+        entry = &entry[op_type];
+        instruction = format!("{} {}", instruction, op_type);
+    }
+
+    Ok(entry)
+}
+
+
+/**
+ * Gets the contents of the file *target_code.json* and uses the data contained therein to get the target 
+ * code equivalent for the specified archiecture. Arguments are substituted into the target code where the 
+ * `{}` symbol is found. Returns an error if the file has no entry for the instruction, or for the type when
+ * one is given.
+ * 
+ * #### Examples
+ * `mips_instrs.push(get_target_code("mips", "push", Some("byte"), vec![value.to_string()])?);`
+ * `mips_instrs.push(get_target_code("mips", "out", None, vec![])?);`
+ */
+fn get_target_code(architecture:&str, instr:&str, op_type:Option<&str>, arguments:Vec<String>) -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    let json = read_target_code_json();
+    let entry = find_target_template(json, architecture, instr, op_type.as_slice())?;
+    Ok(MipsInstr::Raw(insert_target_code_args(instr, format_target_code(entry), arguments)))
+}
+
+
+/**
+ * Gets the MIPS target code for an instruction which has a version for each type it works on, looking up the
+ * version for `op_type` by the name of the type.
+ */
+fn get_typed_code(instr:&str, op_type:&Type, arguments:Vec<String>) -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    get_target_code("mips", instr, Some(&op_type.to_string()), arguments)
+}
+
+
+/**
+ * Returns the code to push the given integer constant onto the stack.
+ */
+fn push_int(value:&str) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::op("li", &["$t4", value]).comment("push int"),
+        MipsInstr::op("sw", &["$t4", "0($sp)"]),
+        MipsInstr::op("subi", &["$sp", "$sp", "4"]),
+        MipsInstr::Blank
+    ]
+}
+
+
+/**
+ * Returns the code to pop an integer from the stack and store it in the variable at the given offset below
+ * the frame pointer.
+ */
+fn store_int(offset:usize) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]).comment("store int"),
+        MipsInstr::op("lw", &["$t0", "0($sp)"]),
+        MipsInstr::op("sw", &["$t0", &format!("-{}($fp)", offset)]),
+        MipsInstr::Blank
+    ]
+}
+
+
+/**
+ * Returns the code to push the integer variable at the given offset below the frame pointer onto the stack.
+ */
+fn load_int(offset:usize) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::op("lw", &["$t0", &format!("-{}($fp)", offset)]).comment("load int"),
+        MipsInstr::op("sw", &["$t0", "0($sp)"]),
+        MipsInstr::op("subi", &["$sp", "$sp", "4"]),
+        MipsInstr::Blank
+    ]
+}
+
+
+/**
+ * Returns the code to pop two integers from the stack, apply the given arithmetic instruction with the
+ * first pushed as the left hand side, and push the result. `name` describes the operation in the comment.
+ */
+fn int_arithmetic(opcode:&'static str, name:&str) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]).comment(&format!("{} int", name)),
+        MipsInstr::op("lw", &["$t0", "0($sp)"]),
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]),
+        MipsInstr::op("lw", &["$t2", "0($sp)"]),
+        MipsInstr::op(opcode, &["$t0", "$t2", "$t0"]),
+        MipsInstr::op("sw", &["$t0", "0($sp)"]),
+        MipsInstr::op("subi", &["$sp", "$sp", "4"]),
+        MipsInstr::Blank
+    ]
+}
+
+
+/**
+ * Returns the code to pop two integers from the stack, add, subtract or multiply them with the first pushed as
+ * the left hand side, and push the result, jumping to the integer overflow handler with the label of the name
+ * of the function in `$a3` if the result does not fit in an int. An addition overflows when the result has
+ * a different sign to both operands, a subtraction when the operands have different signs and the result has
+ * a different sign to the left hand side, and a multiplication when the high word of the product is not the
+ * sign of the low word.
+ */
+fn trapping_int_arithmetic(instr:&IntermediateInstr, function_label:&str) -> Vec<MipsInstr> {
+    let (name, mut check) = match instr {
+        IntermediateInstr::Add => ("add", vec![
+            MipsInstr::op("addu", &["$t1", "$t2", "$t0"]),
+            MipsInstr::op("xor", &["$t3", "$t1", "$t2"]),
+            MipsInstr::op("xor", &["$t4", "$t1", "$t0"]),
+            MipsInstr::op("and", &["$t3", "$t3", "$t4"])
+        ]),
+
+        IntermediateInstr::Sub => ("sub", vec![
+            MipsInstr::op("subu", &["$t1", "$t2", "$t0"]),
+            MipsInstr::op("xor", &["$t3", "$t2", "$t0"]),
+            MipsInstr::op("xor", &["$t4", "$t2", "$t1"]),
+            MipsInstr::op("and", &["$t3", "$t3", "$t4"])
+        ]),
+
+        _ => ("multiply", vec![
+            MipsInstr::op("mult", &["$t2", "$t0"]),
+            MipsInstr::op("mflo", &["$t1"]),
+            MipsInstr::op("mfhi", &["$t3"]),
+            MipsInstr::op("sra", &["$t4", "$t1", "31"]),
+            MipsInstr::op("xor", &["$t3", "$t3", "$t4"])
+        ])
+    };
+
+    // a multiplication has overflowed if the xor is anything but zero, the others only if it is negative
+    let branch = match instr {
+        IntermediateInstr::Mult => MipsInstr::op("bne", &["$t3", "$zero", &get_overflow_handler_label()]),
+        _ => MipsInstr::op("bltz", &["$t3", &get_overflow_handler_label()])
+    };
+
+    let mut code = vec![
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]).comment(&format!("{} int, trapping on overflow", name)),
+        MipsInstr::op("lw", &["$t0", "0($sp)"]),
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]),
+        MipsInstr::op("lw", &["$t2", "0($sp)"])
+    ];
+
+    code.append(&mut check);
+    code.extend([
+        MipsInstr::op("la", &["$a3", function_label]),
+        branch,
+        MipsInstr::op("sw", &["$t1", "0($sp)"]),
+        MipsInstr::op("subi", &["$sp", "$sp", "4"]),
+        MipsInstr::Blank
+    ]);
+
+    code
+}
+
+
+/**
+ * Returns the code to negate the integer on top of the stack, jumping to the integer overflow handler with
+ * the label of the name of the function in `$a3` if it is the smallest int, which is the only one whose
+ * negation does not fit in an int and so the only one which is negative both before and after.
+ */
+fn trapping_int_negation(function_label:&str) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::op("addi", &["$sp", "$sp", "4"]).comment("numerical negation int, trapping on overflow"),
+        MipsInstr::op("lw", &["$t0", "0($sp)"]),
+        MipsInstr::op("subu", &["$t1", "$zero", "$t0"]),
+        MipsInstr::op("and", &["$t3", "$t1", "$t0"]),
+        MipsInstr::op("la", &["$a3", function_label]),
+        MipsInstr::op("bltz", &["$t3", &get_overflow_handler_label()]),
+        MipsInstr::op("sw", &["$t1", "0($sp)"]),
+        MipsInstr::op("subi", &["$sp", "$sp", "4"]),
+        MipsInstr::Blank
+    ]
+}
+
+
+/**
+ * Returns the code to jump to the given label.
+ */
+fn jump(label:&str) -> Vec<MipsInstr> {
+    vec![MipsInstr::op("j", &[label]), MipsInstr::Blank]
+}
+
+
+/**
+ * The runtime libraries which can be added after the functions of a program, in the order they are added.
+ */
+pub const LIBRARIES:[&str; 4] = ["string_mips", "heap_mips", "float_mips", "math64_mips"];
+
+
+/**
+ * Returns the lines of the code contained in the specified library.
+ * 
+ * #### Examples
+ * `add_library("string_mips");`
+ */
+pub fn add_library(library_name:&str) -> Vec<String> {
+    let file = OpenOptions::new().read(true).open(format!("src/backend/{}.asm", library_name)).unwrap();
+    let lines:Vec<String> = io::BufReader::new(file).lines().map(|l| l.unwrap()).collect();
+    lines
+}
+
+
+/**
+ * Exclusively handles the generation of code concerning the `Cast` intermediate instruction. Extracted from
+ * `generate_mips` to properly handle the double-nested types needed for the JSON code to be interpreted
+ * correctly. Returns an error if *target_code.json* has no code for the cast.
+ */
+fn generate_cast_code(architecture:&str, from:Type, into:Type)  -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    let json = read_target_code_json();
+    let entry = find_target_template(json, architecture, "cast", &[&from.to_string(), &into.to_string()])?;
+    Ok(MipsInstr::Raw(format_target_code(entry) + "\n"))
+}
+
+
+/**
+ * Represents the assembler directives used to store constants in the data section, in the order the groups
+ * of constants are written out.
+ */
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum DataDirective {
+    Float,
+    Double,
+    Byte,
+    Asciiz
+}
+
+impl DataDirective {
+    const ORDER:[DataDirective; 4] = [DataDirective::Float, DataDirective::Double, DataDirective::Byte, DataDirective::Asciiz];
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            DataDirective::Float => "float",
+            DataDirective::Double => "double",
+            DataDirective::Byte => "byte",
+            DataDirective::Asciiz => "asciiz"
+        }
+    }
+
+    /**
+     * Returns the power of 2 the address of each entry must be a multiple of, if the entries need aligning.
+     */
+    fn get_alignment(&self) -> Option<u8> {
+        match self {
+            DataDirective::Float => Some(2),
+            DataDirective::Double => Some(3),
+            DataDirective::Byte | DataDirective::Asciiz => None
+        }
+    }
+}
+
+
+/**
+ * Returns the 64 bit FNV-1a hash of the string in hex, which is used to label strings in the data section. It
+ * is written out here rather than using the standard library's hasher, as that may change between releases
+ * of Rust and the labels must be the same every time a program is compiled.
+ */
+fn hash_string(value:&str) -> String {
+    let hash = value.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("{:016x}", hash)
+}
+
+
+/**
+ * Returns the directive and value which initialise a static variable in the data section. Every static is
+ * stored in whole words, the same as in a frame, so the `load_static` and `store_static` templates can copy
+ * it word by word. The first word of a long holds its upper half, matching the order `push` leaves the words
+ * on the stack in, while a double is stored the same way `l.d` expects.
+ */
+fn get_static_data(value:&Argument) -> String {
+    match value {
+        Argument::Byte(value) => format!(".word {}", value),
+        Argument::Integer(value) => format!(".word {}", value),
+        Argument::Long(value) => format!(".word {}, {}", (*value >> 32) as i32, *value as i32),
+        Argument::Boolean(value) => format!(".word {}", *value as i32),
+        Argument::Char(value) => format!(".word {}", *value as u32),
+        Argument::Float(value) => format!(".float {}", value),
+        Argument::Double(value) => format!(".double {}", value),
+        Argument::String(value) => panic!("Static variables cannot be strings, but one was given the value {:?}", value)
+    }
+}
+
+
+/**
+ * Returns the value a char is stored with in the data section, which is the char in quotes if it is printable
+ * and its code otherwise, as a char written with an escape such as `'\n'` cannot be put between quotes as it is.
+ */
+fn get_char_data(value:char) -> String {
+    match (value.is_ascii_graphic() || value == ' ') && !matches!(value, '\'' | '\\') {
+        true => format!("'{}'", value),
+        false => (value as u32).to_string()
+    }
+}
+
+
+/**
+ * Returns the number of bytes a string written to the data section with `.asciiz` takes up, including the
+ * '\0' after it. The value is given in quotes as it is written out, and an escape such as `\n` is one byte.
+ */
+fn get_asciiz_size(value:&str) -> u64 {
+    let mut chars = value[1..value.len() - 1].chars();
+    let mut size = 1;
+    while let Some(next) = chars.next() {
+        let next = match next {
+            '\\' => chars.next().unwrap_or(next),
+            other => other
+        };
+
+        size += next.len_utf8() as u64;
+    }
+
+    size
+}
+
+
+/**
+ * Returns the number of bytes a static variable takes up in the data section, given the directive and value
+ * it is initialised with by `get_static_data`.
+ */
+fn get_static_size(data:&str) -> u64 {
+    match data.starts_with(".double") {
+        true => 8,
+        false => 4 * data.split(',').count() as u64
+    }
+}
+
+
+/**
+ * The number of bytes each kind of entry in the data section takes up, for `--size-budget`. Strings include
+ * the '\0' after them, and the padding added to align floats and doubles is not counted.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DataSizes {
+    pub strings: u64,
+    pub chars: u64,
+    pub floats: u64,
+    pub doubles: u64,
+    pub statics: u64,
+    pub jump_tables: u64,
+    pub input_buffers: u64,
+    pub stack: u64
+}
+
+impl DataSizes {
+    /**
+     * Returns the name and size of each kind of entry, in the order they are reported.
+     */
+    pub fn get_categories(&self) -> [(&'static str, u64); 8] {
+        [
+            ("strings", self.strings),
+            ("chars", self.chars),
+            ("floats", self.floats),
+            ("doubles", self.doubles),
+            ("statics", self.statics),
+            ("jump tables", self.jump_tables),
+            ("input buffers", self.input_buffers),
+            ("stack", self.stack)
+        ]
+    }
+
+
+    pub fn get_total(&self) -> u64 {
+        self.get_categories().iter().map(|(_, size)| size).sum()
+    }
+}
+
+
+/**
+ * Holds every constant in the program which has to be stored in the data section, collected in a separate
+ * pass before any code is generated. Identical constants share one entry, and each entry is labelled
+ * `__<directive>_<key>`, where the key is worked out from the constant itself: the bits of a float or
+ * double, the code of a char, or a hash of a string. The labels therefore only change when the constant does,
+ * and the same constant has the same label in every program. The buffers string input is read into are kept
+ * here too, along with their sizes in bytes, as are the tables of labels each `TableSwitch` jumps through
+ * and the size of the stack set aside with `--stack-size`.
+ */
+#[derive(Debug)]
+struct DataSection {
+    entries:HashMap<DataDirective, Vec<(String, String)>>,
+    labels:HashMap<usize, String>,
+    statics:Vec<(String, String)>,
+    jump_tables:Vec<(String, Vec<String>)>,
+    input_buffers:Vec<(String, usize)>,
+    stack_size:Option<u64>
+}
+
+impl DataSection {
+    /**
+     * Finds every `Push` in the intermediate code whose constant has to be stored in the data section, along
+     * with the message of every `Trap` and the function name of every `CheckIndex`, and assigns it a label,
+     * reusing the label of an identical constant if there already is one. Static variables are collected
+     * separately under the labels the intermediate code gives them, as each one is written to at runtime and
+     * so cannot be shared. Each `TableSwitch` is given its own table of labels, numbered in the order they
+     * appear.
+     */
+    fn collect(intermediate_code:&[IntermediateInstr]) -> DataSection {
+        let mut data_section = DataSection {entries: HashMap::new(), labels: HashMap::new(), statics: vec![], jump_tables: vec![], input_buffers: vec![], stack_size: None};
+        for (index, instr) in intermediate_code.iter().enumerate() {
+            let (directive, value, key) = match instr {
+                IntermediateInstr::DeclareStatic(_, label, value) => {
+                    data_section.statics.push((label.to_owned(), get_static_data(value)));
+                    continue;
+                },
+
+                IntermediateInstr::TableSwitch(_, labels, _) => {
+                    let label = format!("{}table_{}", RESERVED_PREFIX, data_section.jump_tables.len());
+                    data_section.jump_tables.push((label.clone(), labels.clone()));
+                    data_section.labels.insert(index, label);
+                    continue;
+                },
+
+                IntermediateInstr::Push(_, Argument::Float(value)) => (DataDirective::Float, value.to_string(), format!("{:08x}", value.to_bits())),
+                IntermediateInstr::Push(_, Argument::Double(value)) => (DataDirective::Double, value.to_string(), format!("{:016x}", value.to_bits())),
+                IntermediateInstr::Push(_, Argument::Char(value)) => (DataDirective::Byte, get_char_data(*value), format!("{:02x}", *value as u32)),
+                IntermediateInstr::Push(_, Argument::String(value))
+                  | IntermediateInstr::Trap(value)
+                  | IntermediateInstr::CheckIndex(value) => (DataDirective::Asciiz, format!("\"{}\"", value), hash_string(value)),
+                _ => continue
+            };
+
+            let label = data_section.add_constant(directive, value, &key);
+            data_section.labels.insert(index, label);
+        }
+
+        data_section
+    }
+
+
+    /**
+     * Gives every `In` which reads a string a buffer in the data section with room for the characters, a
+     * newline and '\0', labelled under the index of the `In`. All of them share one buffer big enough for
+     * the longest unless `distinct` is true, in which case each has its own. A shared buffer is overwritten
+     * by each input, so only the last string read is kept.
+     */
+    fn collect_input_buffers(&mut self, intermediate_code:&[IntermediateInstr], distinct:bool) {
+        let shared_label = format!("{}input_buffer", RESERVED_PREFIX);
+        for (index, instr) in intermediate_code.iter().enumerate() {
+            let length = match instr {
+                IntermediateInstr::In(Type::Char, _) => continue,
+                IntermediateInstr::In(_, length) => length + 2,
+                _ => continue
+            };
+
+            if distinct {
+                let label = format!("{}_{}", shared_label, self.input_buffers.len());
+                self.input_buffers.push((label.clone(), length));
+                self.labels.insert(index, label);
+                continue;
+            }
+
+            match self.input_buffers.first_mut() {
+                Some((_, size)) => *size = length.max(*size),
+                None => self.input_buffers.push((shared_label.clone(), length))
+            }
+
+            self.labels.insert(index, shared_label.clone());
+        }
+    }
+
+
+    /**
+     * Adds the name of every function which does arithmetic to the data section under the index of its
+     * `FuncStart`, for the trapping arithmetic of `--overflow trap` to pass to the overflow handler. Returns
+     * true if there are any, in which case the handler is needed.
+     */
+    fn collect_function_names(&mut self, intermediate_code:&[IntermediateInstr]) -> bool {
+        let mut function_start = None;
+        let mut found = false;
+        for (index, instr) in intermediate_code.iter().enumerate() {
+            match instr {
+                IntermediateInstr::FuncStart(name) => function_start = Some((index, name)),
+                IntermediateInstr::Add | IntermediateInstr::Sub | IntermediateInstr::Mult | IntermediateInstr::NumNeg => {
+                    if let Some((start, name)) = function_start.take() {
+                        let label = self.add_constant(DataDirective::Asciiz, format!("\"{}\"", name), &hash_string(name));
+                        self.labels.insert(start, label);
+                        found = true;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        found
+    }
+
+
+    /**
+     * Adds the name of every function other than `main` to the data section under the index of its
+     * `FuncStart`, for the frame checks of `--frame-checks` to pass to the handler for corrupted frames.
+     * Returns true if there are any, in which case the handler is needed.
+     */
+    fn collect_returning_function_names(&mut self, intermediate_code:&[IntermediateInstr]) -> bool {
+        let mut found = false;
+        for (index, instr) in intermediate_code.iter().enumerate() {
+            if let IntermediateInstr::FuncStart(name) = instr {
+                if name != "main" {
+                    let label = self.add_constant(DataDirective::Asciiz, format!("\"{}\"", name), &hash_string(name));
+                    self.labels.insert(index, label);
+                    found = true;
+                }
+            }
+        }
+
+        found
+    }
+
+
+    /**
+     * Adds a constant to the data section and returns its label, made from the key worked out from the
+     * constant, reusing the label of an identical constant if there already is one. Two different strings
+     * whose hashes clash are told apart by adding a number to the label of the later one. Used directly for
+     * constants no instruction pushes, such as the text of the out of range index message.
+     */
+    fn add_constant(&mut self, directive:DataDirective, value:String, key:&str) -> String {
+        let entries = self.entries.entry(directive).or_default();
+        if let Some((label, _)) = entries.iter().find(|(_, existing)| existing == &value) {
+            return label.to_owned();
+        }
+
+        let base = format!("{}{}_{}", RESERVED_PREFIX, directive.get_name(), key);
+        let mut label = base.clone();
+        for clash in 1.. {
+            if !entries.iter().any(|(existing, _)| existing == &label) {
+                break;
+            }
+
+            label = format!("{}_{}", base, clash);
+        }
+
+        entries.push((label.clone(), value));
+        label
+    }
+
+
+    /**
+     * Returns the label of the constant pushed by the instruction at the given index.
+     */
+    fn get_label(&self, index:usize) -> String {
+        self.labels.get(&index).expect("Constant was not collected into the data section").to_owned()
+    }
+
+
+    /**
+     * Returns the number of bytes taken up by each kind of entry in the data section.
+     */
+    fn get_sizes(&self) -> DataSizes {
+        let get_entries = |directive| self.entries.get(&directive).into_iter().flatten();
+        DataSizes {
+            strings: get_entries(DataDirective::Asciiz).map(|(_, value)| get_asciiz_size(value)).sum(),
+            chars: get_entries(DataDirective::Byte).count() as u64,
+            floats: 4 * get_entries(DataDirective::Float).count() as u64,
+            doubles: 8 * get_entries(DataDirective::Double).count() as u64,
+            statics: self.statics.iter().map(|(_, data)| get_static_size(data)).sum(),
+            jump_tables: self.jump_tables.iter().map(|(_, labels)| 4 * labels.len() as u64).sum(),
+            input_buffers: self.input_buffers.iter().map(|(_, size)| *size as u64).sum(),
+            stack: self.stack_size.unwrap_or(0)
+        }
+    }
+
+
+    /**
+     * Writes the data section, starting with the static variables in the order they are declared, followed by
+     * the jump tables, and then grouping the constants by directive in the order each is first used. Each group
+     * of floats or doubles is aligned to the size of its values, as `l.d` needs its address to be a multiple
+     * of 8. The stack comes next, aligned to a doubleword like the frames in it, and the input buffers go
+     * last, as their sizes would leave anything after them unaligned.
+     */
+    fn write<W:Write>(&self, writer:&mut W) -> Result<(), Box<dyn Error>> {
+        write!(writer, ".data:")?;
+        if !self.statics.is_empty() || !self.jump_tables.is_empty() {
+            write!(writer, "\n\t.align 2")?;
+        }
+
+        for (label, data) in &self.statics {
+            write!(writer, "\n\t{}: {}", label, data)?;
+        }
+
+        for (label, labels) in &self.jump_tables {
+            write!(writer, "\n\t{}: .word {}", label, labels.join(", "))?;
+        }
+
+        for directive in DataDirective::ORDER {
+            let name = directive.get_name();
+            if let (Some(alignment), Some(_)) = (directive.get_alignment(), self.entries.get(&directive)) {
+                write!(writer, "\n\t.align {}", alignment)?;
+            }
+
+            for (label, value) in self.entries.get(&directive).into_iter().flatten() {
+                write!(writer, "\n\t{}: .{} {}", label, name, value)?;
+            }
+        }
+
+        if let Some(stack_size) = self.stack_size {
+            write!(writer, "\n\t.align 3\n\t{}: .space {}", get_stack_label(), stack_size)?;
+        }
+
+        for (label, size) in &self.input_buffers {
+            write!(writer, "\n\t{}: .space {}", label, size)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/**
+ * Returns the label of the epilogue at the end of the given function, which restores the caller's frame and
+ * returns. Every return statement in the function stores its value in the return registers and jumps here,
+ * so the frame teardown is only generated once and the evaluation stack is discarded whatever its depth.
+ */
+fn get_epilogue_label(function:&str) -> String {
+    format!("{}fn_{}_epilogue", RESERVED_PREFIX, function)
+}
+
+
+/**
+ * Returns the label of the space in the data section set aside for the stack with `--stack-size`.
+ */
+fn get_stack_label() -> String {
+    format!("{}stack", RESERVED_PREFIX)
+}
+
+
+/**
+ * Returns the code run before `main` with `--stack-size`, which moves the stack pointer to the top word of
+ * the space set aside for the stack. The stack grows down from there, so the first word `main` saves is the
+ * last of the space.
+ */
+fn get_startup_code(stack_size:u64) -> Vec<MipsInstr> {
+    vec![
+        MipsInstr::Blank,
+        MipsInstr::Blank,
+        get_banner("startup"),
+        MipsInstr::op("la", &["$sp", &get_stack_label()]).comment(&format!("use the {} byte stack in the data section", stack_size)),
+        MipsInstr::op("li", &["$t0", &(stack_size - 4).to_string()]),
+        MipsInstr::op("addu", &["$sp", "$sp", "$t0"])
+    ]
+}
+
+
+/**
+ * Returns the label at the end of the program, which halts it. `main` jumps here when it returns.
+ */
+pub fn get_end_label() -> String {
+    format!("{}end", RESERVED_PREFIX)
+}
+
+
+/**
+ * Returns the label of the handler `CheckIndex` jumps to when an index is out of range.
+ */
+fn get_index_handler_label() -> String {
+    format!("{}index_out_of_range", RESERVED_PREFIX)
+}
+
+
+/**
+ * Returns the code of the handler which prints the index, the length of the string and the name of the
+ * function held in `$a1`, `$a2` and `$a3` by `CheckIndex`, then exits with code 1. The text of the message
+ * is added to the data section.
+ */
+fn get_index_handler_code(data_section:&mut DataSection) -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    let text = ["Index ", " is out of range for a string of length ", " in function ", "\\n"];
+    let mut args = vec![get_index_handler_label()];
+    args.extend(text.map(|part| data_section.add_constant(DataDirective::Asciiz, format!("\"{}\"", part), &hash_string(part))));
+    get_target_code("mips", "index_out_of_range", None, args)
+}
+
+
+/**
+ * Returns the label of the name of the given function in the data section, which is only there for
+ * `--overflow trap` and `--frame-checks`.
+ */
+fn get_function_name_label(function:&FunctionCode, data_section:&DataSection) -> String {
+    data_section.get_label(function.instructions[0].0)
+}
+
+
+/**
+ * Returns the label of the handler the trapping int arithmetic of `--overflow trap` jumps to on overflow.
+ */
+fn get_overflow_handler_label() -> String {
+    format!("{}integer_overflow", RESERVED_PREFIX)
+}
+
 
-    new_target_code += "\n";
-    new_target_code
+/**
+ * Returns the code of the handler which prints that an int has overflowed in the function whose name is held
+ * in `$a3`, then exits with code 1. The text of the message is added to the data section.
+ */
+fn get_overflow_handler_code(data_section:&mut DataSection) -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    let text = ["integer overflow in function ", "\\n"];
+    let mut args = vec![get_overflow_handler_label()];
+    args.extend(text.map(|part| data_section.add_constant(DataDirective::Asciiz, format!("\"{}\"", part), &hash_string(part))));
+    get_target_code("mips", "integer_overflow", None, args)
 }
 
 
 /**
- * Gets the contents of the file *target_code.json* and uses the data contained therein to get the target 
- * code equivalent for the specified archiecture. Arguments are substituted into the target code where the 
- * `{}` symbol is found.
- * 
- * #### Examples
- * `mips_instrs.push(get_target_code("mips", "push", Some("byte"), vec![value.to_string()]));`
- * `mips_instrs.push(get_target_code("mips", "out", None, vec![]));`
+ * The word written just below the saved frame pointer by each function with `--frame-checks`. Code which
+ * writes past the top of the frame overwrites it before it reaches the saved frame pointer and return address.
  */
-fn get_target_code(architecture:&str, instr:&str, op_type:Option<&str>, arguments:Vec<String>) -> String {
-    let json = read_target_code_json();
-    let target_code:String = match op_type {
-        Some(op_type) => {
-            serde_json::to_string(&json[architecture][instr][op_type]).unwrap().split("\",").map(|item| {
-                item.replace("[", "")
-                    .replace("]", "")
-                    .replace("\"", "")
-                    .trim()
-                    .to_string()
-                    .replace("\\t", "\t")
-            }).collect::<Vec<String>>().join("\n")
-        },
+const FRAME_CANARY:&str = "0x5afec0de";
 
-        None => {
-            serde_json::to_string(&json[architecture][instr]).unwrap().split("\",").map(|item| {
-                item.replace("[", "")
-                    .replace("]", "")
-                    .replace("\"", "")
-                    .trim()
-                    .to_string()
-                    .replace("\\t", "\t")
-            }).collect::<Vec<String>>().join("\n")
-        }
-    };
 
-    insert_target_code_args(instr, target_code, arguments)
+/**
+ * Returns the label of the handler the frame checks of `--frame-checks` jump to when a canary has changed.
+ */
+fn get_frame_handler_label() -> String {
+    format!("{}stack_frame_corrupted", RESERVED_PREFIX)
 }
 
 
 /**
- * Prepends the code contained in the specified library to the source code.
- * 
- * #### Examples
- * `add_library("string_mips.asm");`
+ * Returns the code of the handler which prints that the frame of the function whose name is held in `$a3`
+ * has been corrupted, then exits with code 1. The text of the message is added to the data section.
  */
-#[allow(dead_code)]
-fn add_library(library_name:&str) -> Vec<String> {
-    let file = OpenOptions::new().read(true).open(format!("src/backend/{}.asm", library_name)).unwrap();
-    let lines:Vec<String> = io::BufReader::new(file).lines().map(|l| l.unwrap()).collect();
-    lines
+fn get_frame_handler_code(data_section:&mut DataSection) -> Result<MipsInstr, Box<dyn Error + Send + Sync>> {
+    let text = ["stack frame corrupted in ", "\\n"];
+    let mut args = vec![get_frame_handler_label()];
+    args.extend(text.map(|part| data_section.add_constant(DataDirective::Asciiz, format!("\"{}\"", part), &hash_string(part))));
+    get_target_code("mips", "stack_frame_corrupted", None, args)
 }
 
 
 /**
- * Derives the next label from a static variable. Label is in the format `_t_<hex>` where `<hex>` is a
- * hexadecimal number uniquely identifying the label. 
- * 
- * For example, we start at "_t_1", then "_t_2", and the 32nd is "_t_20".
+ * Returns a comment marking the start of a part of the text section, such as a function.
  */
-fn get_next_label() -> String {
-    static NEXT_LABEL:AtomicUsize = AtomicUsize::new(1);
-    let next_label = NEXT_LABEL.fetch_add(1, Ordering::Relaxed);
-    format!("_t_{:x}", next_label)
+fn get_banner(title:&str) -> MipsInstr {
+    MipsInstr::Raw(format!("# {:=<78}", format!("==== {} ", title)))
 }
 
 
 /**
- * Exclusively handles the generation of code concerning the `Cast` intermediate instruction. Extracted from
- * `generate_mips` to properly handle the double-nested types needed for the JSON code to be interpreted
- * correctly.
+ * Returns the debug information for a function, which is a comment listing each of its named variables with
+ * its type and where it is in the frame, followed by an `.eqv` constant for each holding its offset from the
+ * frame pointer, such as `.eqv main_x_offset, -4`, so the variable can be found by name while stepping through
+ * the program in MARS. A name used for more than one variable in the function has a number added to the end
+ * of its constant after the first, as the constants cannot be defined twice.
  */
-fn generate_cast_code(architecture:&str, from:Type, into:Type)  -> Result<String, Box<dyn Error>> {
-    let json = read_target_code_json();
-    let mut target_code = serde_json::to_string(&json[architecture]["cast"][from.to_string()][into.to_string()])
-                        .expect(&format!("Could not convert from {} to {}", from.to_string(), into.to_string()))
-                        .split("\",")
-                        .map(|item| {
-        item.replace("[", "")
-            .replace("]", "")
-            .replace("\"", "")
-            .trim()
-            .to_string()
-            .replace("\\t", "\t")
-    }).collect::<Vec<String>>().join("\n");
+fn get_debug_info_code(function:&FunctionVariables, layout:&FrameLayout) -> Vec<MipsInstr> {
+    let mut code = vec![match function.lines {
+        Some((first, last)) => MipsInstr::Raw(format!("# frame of {}, from line {} to line {}", function.function, first, last)),
+        None => MipsInstr::Raw(format!("# frame of {}", function.function))
+    }];
+
+    let mut names:Vec<String> = vec![];
+    for variable in &function.variables {
+        let offset = layout.get_var_offset(variable.slot, &variable.var_type);
+        let declared = match variable.line {
+            Some(line) => format!("declared on line {}", line),
+            None => String::from("parameter")
+        };
+
+        code.push(MipsInstr::Raw(format!("#   {} ({}) at -{}($fp), {}", variable.identifier, variable.var_type, offset, declared)));
+
+        let base = format!("{}_{}", function.function, variable.identifier);
+        let mut name = base.clone();
+        let mut count = 1;
+        while names.contains(&name) {
+            count += 1;
+            name = format!("{}_{}", base, count);
+        }
+
+        names.push(name);
+    }
+
+    for (name, variable) in names.iter().zip(&function.variables) {
+        code.push(MipsInstr::Raw(format!(".eqv {}_offset, -{}", name, layout.get_var_offset(variable.slot, &variable.var_type))));
+    }
 
-    target_code += "\n";
-    Ok(target_code)
+    code
 }
 
 
 /**
- * Generates the final MIPS assembly code that can then be compiled to native binary using a separate tool.
+ * Writes out and removes the given code, each part on a new line, returning the number of instructions
+ * written. Every reserved label the code uses is added to `referenced_labels`, which is used to work out
+ * which of the runtime libraries are needed.
  */
-pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, symbol_table:&SymbolTable) -> Result<(), Box<dyn Error>> {
-    let mut file = OpenOptions::new().write(true).truncate(true).create(true).open(filename)?;
+fn write_instrs<W:Write>(writer:&mut W, mips_instrs:&mut Vec<MipsInstr>, referenced_labels:&mut HashSet<String>) -> io::Result<usize> {
+    let mut count = 0;
+    for code in mips_instrs.drain(..) {
+        let text = code.render();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap();
+            referenced_labels.extend(line.split(|c:char| !c.is_ascii_alphanumeric() && c != '_')
+                                         .filter(|word| word.starts_with(RESERVED_PREFIX))
+                                         .map(str::to_owned));
+        }
 
-    let mut text_section:Vec<String> = vec![String::from(".data:")];
-    let mut mips_instrs:Vec<String> = vec![String::from("\n\n.text:")];
+        write!(writer, "\n{}", text)?;
+        count += code.count_instructions();
+    }
+
+    Ok(count)
+}
 
-    let mut stack_id_offset_map: HashMap<usize, usize> = HashMap::new();
-    let mut current_var_offset:usize = 0;
-    let mut stack_types:Vec<Type> = vec![];
 
-    mips_instrs.push("\tj main # start program execution\n\n".to_owned());
-    // mips_instrs.append(&mut add_library("math64_mips"));
-    mips_instrs.append(&mut add_library("string_mips"));
+/**
+ * Generates the MIPS code for one function, starting with a banner giving its name. Only reads the data
+ * section, symbol table and options, so the functions of a program can be generated at the same time on
+ * different threads.
+ */
+fn generate_function_mips(function:&FunctionCode, data_section:&DataSection, symbol_table:&SymbolTable, options:&CodegenOptions)
+        -> Result<Vec<MipsInstr>, Box<dyn Error + Send + Sync>> {
+    let current_function = &function.name;
+    let trap_overflow = options.overflow == OverflowMode::Trap;
+    let frame_checks = options.frame_checks && current_function != "main";
+    let layout = FrameLayout::new(current_function, symbol_table, !options.no_packing, frame_checks);
+    let mut stack_types:Vec<Type> = vec![];
+    let mut mips_instrs:Vec<MipsInstr> = vec![MipsInstr::Blank, MipsInstr::Blank, get_banner(&format!("function {}", current_function))];
+    if let Some(function) = options.debug_info.and_then(|functions| functions.iter().find(|function| &function.function == current_function)) {
+        mips_instrs.extend(get_debug_info_code(function, &layout));
+    }
 
-    for instr in intermediate_code {
+    let mut instrs = function.instructions.iter().map(|(index, instr)| (*index, instr.clone())).peekable();
+    while let Some((index, instr)) = instrs.next() {
+        let _context = CompilationContext::enter(format!("function '{}', instruction {} ({})", current_function, index, instr.to_string().trim()));
         match instr {
+            IntermediateInstr::FuncStart(name) if frame_checks => {
+                mips_instrs.push(get_target_code("mips", "start_func_checked", None, vec![name, FRAME_CANARY.to_string(), layout.get_size().to_string()])?);
+            },
+
             IntermediateInstr::FuncStart(name) => {
-                let frame_size = get_frame_size(&name, symbol_table);
-                mips_instrs.push(get_target_code("mips", "start_func", None, vec![name, frame_size.to_string()]));
+                mips_instrs.push(get_target_code("mips", "start_func", None, vec![name, layout.get_size().to_string()])?);
             },
 
             IntermediateInstr::FuncEnd(name) => {
                 if name == "main" {
-                    mips_instrs.push(get_target_code("mips", "end_main", None, vec![]));
+                    mips_instrs.push(get_target_code("mips", "end_main", None, vec![get_epilogue_label(&name)])?);
+                } else if frame_checks {
+                    mips_instrs.push(get_target_code("mips", "end_func_checked", None, vec![
+                        get_epilogue_label(&name), name, FRAME_CANARY.to_string(), get_function_name_label(function, data_section), get_frame_handler_label()
+                    ])?);
                 } else {
-                    mips_instrs.push(get_target_code("mips", "end_func", None, vec![name]));
+                    mips_instrs.push(get_target_code("mips", "end_func", None, vec![get_epilogue_label(&name), name])?);
                 }
             },
 
+            IntermediateInstr::Trap(_) => {
+                mips_instrs.push(get_target_code("mips", "trap", None, vec![data_section.get_label(index)])?);
+            },
+
             // Push an integer to the stack, use registers $t0 and $t2 to allow for future implementation of long datatype
-            IntermediateInstr::Push(_, var) => {
+            IntermediateInstr::Push(push_type, var) => {
                 match var {
                     Argument::Integer(value) => {
                         stack_types.push(Type::Integer);
-                        mips_instrs.push(get_target_code("mips", "push", Some("int"), vec![value.to_string()]));
+                        mips_instrs.extend(push_int(&value.to_string()));
                     },
 
                     Argument::Long(value) => {
                         stack_types.push(Type::Long);
                         let upper_bits:u64 = (value as u64 & 0xFFFF_FFFF_0000_0000) >> 32;
                         let lower_bits:u64 = value as u64 & 0xFFFF_FFFF;
-                        mips_instrs.push(get_target_code("mips", "push", Some("long"), vec![
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![
                             upper_bits.to_string(),
                             lower_bits.to_string()
-                        ]));
+                        ])?);
                     },
 
                     Argument::Byte(value) => {
                         stack_types.push(Type::Byte);
-                        mips_instrs.push(get_target_code("mips", "push", Some("byte"), vec![value.to_string()]));
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![value.to_string()])?);
                     },
 
-                    Argument::Float(value) => {
+                    Argument::Float(_) => {
                         stack_types.push(Type::Float);
 
-                        let label = get_next_label();
-                        text_section.push(format!("\t{}: .float {}", label, value));
-                        mips_instrs.push(get_target_code("mips", "push", Some("float"), vec![label]));
+                        let label = data_section.get_label(index);
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![label])?);
                     },
 
-                    Argument::Double(value) => {
+                    Argument::Double(_) => {
                         stack_types.push(Type::Double);
 
-                        let label = get_next_label();
-                        text_section.push(format!("\t{}: .double {}", label, value));
-                        mips_instrs.push(get_target_code("mips", "push", Some("double"), vec![label]));
+                        let label = data_section.get_label(index);
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![label])?);
                     },
 
-                    Argument::Char(value) => {
+                    Argument::Char(_) => {
                         stack_types.push(Type::Char);
 
-                        let label = get_next_label();
-                        text_section.push(format!("\t{}: .byte '{}'", label, value));
-                        mips_instrs.push(get_target_code("mips", "push", Some("char"), vec![label]));
+                        let label = data_section.get_label(index);
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![label])?);
                     },
 
                     Argument::Boolean(value) => {
                         stack_types.push(Type::Boolean);
                         match value {
-                            true => mips_instrs.push(get_target_code("mips", "push", Some("bool"), vec![String::from("1")])),
-                            false => mips_instrs.push(get_target_code("mips", "push", Some("bool"), vec![String::from("0")])),
-                        }
-                    },
-
-                    Argument::String(value) => {
-                        stack_types.push(Type::String);
-
-                        let label = get_next_label();
-                        text_section.push(format!("\t{}: .asciiz \"{}\"", label, value));
-                        mips_instrs.push(get_target_code("mips", "push", Some("string"), vec![label]));
-                    }
-                }
-            },
-
-            IntermediateInstr::Store(var_type, id) => {
-                match var_type {
-                    Type::Integer => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
+                            true => mips_instrs.push(get_typed_code("push", &push_type, vec![String::from("1")])?),
+                            false => mips_instrs.push(get_typed_code("push", &push_type, vec![String::from("0")])?),
                         }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("int"), vec![stack_id_offset_map.get(&id).unwrap().to_string()]));
-                        stack_types.pop();
-                    },
-
-                    Type::Long => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 8;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("long"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string(),
-                            (stack_id_offset_map.get(&id).unwrap() - 4).to_string()
-                        ]));
-
-                        stack_types.pop();
-                    },
-
-                    Type::Byte => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("byte"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string()
-                        ]));
-
-                        stack_types.pop();
-                    },
-
-                    Type::Float => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("float"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string()
-                        ]));
-
-                        stack_types.pop();
                     },
 
-                    Type::Double => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 8;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("double"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string(),
-                            (stack_id_offset_map.get(&id).unwrap() - 4).to_string()
-                        ]));
-
-                        stack_types.pop();
-                    },
-
-                    Type::Char => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("char"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string()
-                        ]));
-
-                        stack_types.pop();
-                    },
+                    // a string literal which is printed straight away is printed from its label, skipping the stack
+                    Argument::String(_) if matches!(instrs.peek(), Some((_, IntermediateInstr::Out(_, PrintFormat::Plain)))) => {
+                        instrs.next();
 
-                    Type::Boolean => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-    
-                        mips_instrs.push(get_target_code("mips", "store", Some("bool"), vec![
-                            stack_id_offset_map.get(&id).unwrap().to_string()
-                        ]));
-    
-                        stack_types.pop();
+                        let label = data_section.get_label(index);
+                        mips_instrs.push(get_target_code("mips", "out_literal", None, vec![label])?);
                     },
 
-                    Type::String => {
-                        // if the key does not exist, add a new key to represent a new local variable
-                        if !stack_id_offset_map.contains_key(&id) {
-                            current_var_offset += 4;
-                            stack_id_offset_map.insert(id, current_var_offset);
-                        }
-
-                        mips_instrs.push(get_target_code("mips", "store", Some("string"), vec![stack_id_offset_map.get(&id).unwrap().to_string()]));
-                        stack_types.pop();
-                    },
+                    Argument::String(_) => {
+                        stack_types.push(Type::String);
 
-                    Type::Void => panic!("Cannot store type Void")
+                        let label = data_section.get_label(index);
+                        mips_instrs.push(get_typed_code("push", &push_type, vec![label])?);
+                    }
                 }
             },
 
-            IntermediateInstr::Load(var_type, id) => {
-                match var_type {
-                    Type::Integer => {
-                        stack_types.push(Type::Integer);
-
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("int"), vec![offset.to_string()]));
-                    },
-
-                    Type::Long => {
-                        stack_types.push(Type::Long);
-
-                        let offset = stack_id_offset_map.get(&id).unwrap();
-                        mips_instrs.push(get_target_code("mips", "load", Some("long"), vec![
-                            offset.to_string(), (offset - 4).to_string()
-                        ]));
-                    },
-
-                    Type::Byte => {
-                        stack_types.push(Type::Byte);
-
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("byte"), vec![offset.to_string()]));
-                    },
+            IntermediateInstr::Store(var_type, slot) => {
+                let offset = match var_type {
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("Store", Type::Void))),
+                    _ => layout.get_var_offset(slot, &var_type)
+                };
 
-                    Type::Float => {
-                        stack_types.push(Type::Float);
+                match var_type {
+                    _ if layout.is_packed(&var_type) => mips_instrs.push(get_typed_code("store_packed", &var_type, vec![offset.to_string()])?),
+                    Type::Integer => mips_instrs.extend(store_int(offset)),
+                    Type::Long | Type::Double => mips_instrs.push(get_typed_code("store", &var_type, vec![
+                        offset.to_string(), (offset - 4).to_string()
+                    ])?),
 
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("float"), vec![offset.to_string()]));
-                    },
+                    _ => mips_instrs.push(get_typed_code("store", &var_type, vec![offset.to_string()])?)
+                }
 
-                    Type::Double => {
-                        stack_types.push(Type::Double);
+                stack_types.pop();
+            },
 
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("double"), vec![
-                            offset.to_string(), (offset - 4).to_string()
-                        ]));
-                    },
+            IntermediateInstr::StoreStatic(var_type, label) => {
+                if var_type == Type::Void {
+                    return Err(Box::new(InvalidOperandType::new("StoreStatic", Type::Void)));
+                }
 
-                    Type::Char => {
-                        stack_types.push(Type::Char);
+                mips_instrs.push(get_typed_code("store_static", &var_type, vec![label])?);
+                stack_types.pop();
+            },
 
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("char"), vec![offset.to_string()]));
-                    },
+            IntermediateInstr::LoadStatic(var_type, label) => {
+                if var_type == Type::Void {
+                    return Err(Box::new(InvalidOperandType::new("LoadStatic", Type::Void)));
+                }
 
-                    Type::Boolean => {
-                        stack_types.push(Type::Boolean);
+                mips_instrs.push(get_typed_code("load_static", &var_type, vec![label])?);
+                stack_types.push(var_type);
+            },
 
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("bool"), vec![offset.to_string()]));
-                    },
+            // the value of a static is written into the data section, so there is nothing to run
+            IntermediateInstr::DeclareStatic(..) => {},
 
-                    Type::String => {
-                        stack_types.push(Type::String);
+            IntermediateInstr::Load(var_type, slot) => {
+                let offset = match var_type {
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("Load", Type::Void))),
+                    _ => layout.get_var_offset(slot, &var_type)
+                };
 
-                        let offset = stack_id_offset_map.get(&id).unwrap_or(&0);
-                        mips_instrs.push(get_target_code("mips", "load", Some("string"), vec![offset.to_string()]));
-                    },
+                match var_type {
+                    _ if layout.is_packed(&var_type) => mips_instrs.push(get_typed_code("load_packed", &var_type, vec![offset.to_string()])?),
+                    Type::Integer => mips_instrs.extend(load_int(offset)),
+                    Type::Long | Type::Double => mips_instrs.push(get_typed_code("load", &var_type, vec![
+                        offset.to_string(), (offset - 4).to_string()
+                    ])?),
 
-                    Type::Void => panic!("Cannot load type Void")
+                    _ => mips_instrs.push(get_typed_code("load", &var_type, vec![offset.to_string()])?)
                 }
+
+                stack_types.push(var_type);
             },
 
             IntermediateInstr::Return(return_type) => {
-                match return_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "return", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "return", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "return", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "return", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "return", Some("double"), vec![])),
-                    Type::Char => mips_instrs.push(get_target_code("mips", "return", Some("char"), vec![])),
-                    Type::Boolean => mips_instrs.push(get_target_code("mips", "return", Some("bool"), vec![])),
-                    Type::String => mips_instrs.push(get_target_code("mips", "return", Some("string"), vec![])),
-                    Type::Void => panic!("Cannot return type Void")
+                if return_type != Type::Void {
+                    mips_instrs.push(get_typed_code("return", &return_type, vec![])?);
+                    stack_types.pop();
                 }
 
-                stack_types.pop();
+                mips_instrs.extend(jump(&get_epilogue_label(current_function)));
             },
 
             IntermediateInstr::Add => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "add", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "add", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "add", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "add", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "add", Some("double"), vec![])),
-                    Type::String => mips_instrs.push(get_target_code("mips", "add", Some("string"), vec![])),
-                    Type::Char | Type::Boolean | Type::Void => panic!("Cannot apply + operator to type {:?}", op_type)
+                    Type::Integer if trap_overflow => mips_instrs.extend(trapping_int_arithmetic(&instr, &get_function_name_label(function, data_section))),
+                    Type::Integer => mips_instrs.extend(int_arithmetic("addu", "add")),
+                    Type::Long | Type::Byte | Type::Float | Type::Double | Type::String => mips_instrs.push(get_typed_code("add", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Add", other)))
                 }
             },
 
             IntermediateInstr::Sub => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "sub", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "sub", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "sub", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "sub", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "sub", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply - operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer if trap_overflow => mips_instrs.extend(trapping_int_arithmetic(&instr, &get_function_name_label(function, data_section))),
+                    Type::Integer => mips_instrs.extend(int_arithmetic("subu", "sub")),
+                    Type::Long | Type::Byte | Type::Float | Type::Double => mips_instrs.push(get_typed_code("sub", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Sub", other)))
                 }
             },
             
             IntermediateInstr::Mult => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "mult", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "mult", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "mult", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "mult", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "mult", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply * operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer if trap_overflow => mips_instrs.extend(trapping_int_arithmetic(&instr, &get_function_name_label(function, data_section))),
+                    Type::Integer => mips_instrs.extend(int_arithmetic("mul", "multiply")),
+                    Type::Long | Type::Byte | Type::Float | Type::Double => mips_instrs.push(get_typed_code("mult", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Mult", other)))
                 }
             },
 
             IntermediateInstr::Div => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "div", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "div", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "div", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "div", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "div", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply / operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer => mips_instrs.extend(int_arithmetic("div", "divide")),
+                    Type::Long | Type::Byte | Type::Float | Type::Double => mips_instrs.push(get_typed_code("div", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Div", other)))
                 }
             },
 
             IntermediateInstr::BitwiseAnd => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "bitwise_and", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "bitwise_and", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "bitwise_and", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply & operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Boolean => mips_instrs.push(get_typed_code("bitwise_and", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("BitwiseAnd", other)))
                 }
             },
 
             IntermediateInstr::BitwiseOr => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "bitwise_or", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "bitwise_or", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "bitwise_or", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply | operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Boolean => mips_instrs.push(get_typed_code("bitwise_or", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("BitwiseOr", other)))
                 }
             },
 
             IntermediateInstr::BitwiseXor => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "bitwise_xor", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "bitwise_xor", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "bitwise_xor", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply ^ operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Boolean => mips_instrs.push(get_typed_code("bitwise_xor", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("BitwiseXor", other)))
                 }
             },
 
             IntermediateInstr::NumNeg => {
                 let op_type = stack_types.last().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "numerical_neg", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "numerical_neg", Some("long"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "numerical_neg", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "numerical_neg", Some("double"), vec![])),
-                    Type::Byte | Type::Char | Type::Void => panic!("Numerical negation cannot be applied to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer if trap_overflow => mips_instrs.extend(trapping_int_negation(&get_function_name_label(function, data_section))),
+                    Type::Integer | Type::Long | Type::Float | Type::Double => mips_instrs.push(get_typed_code("numerical_neg", op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("NumNeg", other.clone())))
+                }
+            },
+
+            IntermediateInstr::Abs => {
+                let op_type = stack_types.last().unwrap();
+                match op_type {
+                    Type::Integer | Type::Long | Type::Float | Type::Double => mips_instrs.push(get_typed_code("abs", op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Abs", other.clone())))
+                }
+            },
+
+            IntermediateInstr::Round | IntermediateInstr::Floor | IntermediateInstr::Ceil => {
+                let (name, template) = match instr {
+                    IntermediateInstr::Round => ("Round", "round"),
+                    IntermediateInstr::Floor => ("Floor", "floor"),
+                    _ => ("Ceil", "ceil")
+                };
+
+                let op_type = stack_types.pop().unwrap();
+                match op_type {
+                    Type::Float | Type::Double => mips_instrs.push(get_typed_code(template, &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new(name, other)))
+                }
+
+                stack_types.push(Type::Integer);
+            },
+
+            IntermediateInstr::Min | IntermediateInstr::Max => {
+                let (name, template) = match instr {
+                    IntermediateInstr::Min => ("Min", "min"),
+                    _ => ("Max", "max")
+                };
+
+                let op_type = stack_types.pop().unwrap();
+                match op_type {
+                    Type::Integer | Type::Long | Type::Float | Type::Double => mips_instrs.push(get_typed_code(template, &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new(name, other)))
+                }
+            },
+
+            // integer powers are calculated with a loop, so each needs its own labels
+            IntermediateInstr::Pow => {
+                let loop_label = format!("{}pow_{}_loop", RESERVED_PREFIX, index);
+                let end_label = format!("{}pow_{}_end", RESERVED_PREFIX, index);
+                let labels = vec![loop_label.clone(), loop_label.clone(), end_label.clone(), loop_label, end_label];
+
+                let op_type = stack_types.pop().unwrap();
+                match op_type {
+                    Type::Integer | Type::Long => mips_instrs.push(get_typed_code("pow", &op_type, labels)?),
+                    other => return Err(Box::new(InvalidOperandType::new("Pow", other)))
                 }
             },
 
             IntermediateInstr::Complement => {
                 let op_type = stack_types.last().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "complement", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "complement", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "complement", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply ~ operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte => mips_instrs.push(get_typed_code("complement", op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Complement", other.clone())))
                 }
             },
 
             IntermediateInstr::LogicNeg => {
                 let op_type = stack_types.last().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "logical_neg", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "logical_neg", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "logical_neg", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "logical_neg", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "logical_neg", Some("double"), vec![])),
-                    Type::Boolean => mips_instrs.push(get_target_code("mips", "logical_neg", Some("bool"), vec![])),
-                    Type::Char | Type::Void => panic!("Logical negation cannot be applied to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double | Type::Boolean => {
+                        mips_instrs.push(get_typed_code("logical_neg", op_type, vec![])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("LogicNeg", other.clone())))
                 }
             },
 
             IntermediateInstr::LeftShiftLogical => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "sll", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "sll", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "sll", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply >> operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte => mips_instrs.push(get_typed_code("sll", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("LeftShiftLogical", other)))
                 }
             },
 
             IntermediateInstr::RightShiftLogical => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "srl", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "srl", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "srl", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply << operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte => mips_instrs.push(get_typed_code("srl", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("RightShiftLogical", other)))
                 }
             },
 
             IntermediateInstr::RightShiftArithmetic => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "sra", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "sra", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "sra", Some("byte"), vec![])),
-                    Type::Float | Type::Double | Type::Char | Type::Void => panic!("Cannot apply >>> operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte => mips_instrs.push(get_typed_code("sra", &op_type, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("RightShiftArithmetic", other)))
                 }
             },
          
@@ -628,15 +1544,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_equal", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_equal", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_equal", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_equal", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_equal", Some("double"), vec![])),
-                    Type::Char => mips_instrs.push(get_target_code("mips", "test_equal", Some("char"), vec![])),
-                    Type::Boolean => mips_instrs.push(get_target_code("mips", "test_equal", Some("bool"), vec![])),
-                    Type::String => mips_instrs.push(get_target_code("mips", "test_equal", Some("string"), vec![])),
-                    Type::Void => panic!("Cannot apply == operator to type {:?}", op_type)
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double | Type::Char | Type::Boolean | Type::String => {
+                        mips_instrs.push(get_typed_code("test_equal", &op_type, vec![])?);
+                    },
+
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("Equal", Type::Void)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -647,15 +1559,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_unequal", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_unequal", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_unequal", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_unequal", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_unequal", Some("double"), vec![])),
-                    Type::Char => mips_instrs.push(get_target_code("mips", "test_unequal", Some("char"), vec![])),
-                    Type::Boolean => mips_instrs.push(get_target_code("mips", "test_unequal", Some("bool"), vec![])),
-                    Type::String => mips_instrs.push(get_target_code("mips", "test_unequal", Some("string"), vec![])),
-                    Type::Void => panic!("Cannot apply != operator to type {:?}", op_type)
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double | Type::Char | Type::Boolean | Type::String => {
+                        mips_instrs.push(get_typed_code("test_unequal", &op_type, vec![])?);
+                    },
+
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("NotEqual", Type::Void)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -666,13 +1574,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_greater_than", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_greater_than", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_greater_than", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_greater_than", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_greater_than", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply > operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double => {
+                        mips_instrs.push(get_typed_code("test_greater_than", &op_type, vec![])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("GreaterThan", other)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -683,13 +1589,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_greater_equal", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_greater_equal", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_greater_equal", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_greater_equal", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_greater_equal", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply >= operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double => {
+                        mips_instrs.push(get_typed_code("test_greater_equal", &op_type, vec![])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("GreaterEqual", other)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -700,13 +1604,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_less_than", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_less_than", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_less_than", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_less_than", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_less_than", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply < operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double => {
+                        mips_instrs.push(get_typed_code("test_less_than", &op_type, vec![])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("LessThan", other)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -717,13 +1619,11 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
                 stack_types.pop();
 
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "test_less_equal", Some("int"), vec![])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "test_less_equal", Some("long"), vec![])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "test_less_equal", Some("byte"), vec![])),
-                    Type::Float => mips_instrs.push(get_target_code("mips", "test_less_equal", Some("float"), vec![])),
-                    Type::Double => mips_instrs.push(get_target_code("mips", "test_less_equal", Some("double"), vec![])),
-                    Type::Char | Type::Void => panic!("Cannot apply <= operator to type {:?}", op_type),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double => {
+                        mips_instrs.push(get_typed_code("test_less_equal", &op_type, vec![])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("LessEqual", other)))
                 }
 
                 stack_types.push(Type::Byte);
@@ -731,86 +1631,363 @@ pub fn generate_mips(intermediate_code:Vec<IntermediateInstr>, filename:&str, sy
 
             IntermediateInstr::LogicAnd => {
                 stack_types.pop();
-                mips_instrs.push(get_target_code("mips", "logical_and", None, vec![]));
+                mips_instrs.push(get_target_code("mips", "logical_and", None, vec![])?);
             },
 
             IntermediateInstr::LogicOr => {
                 stack_types.pop();
-                mips_instrs.push(get_target_code("mips", "logical_or", None, vec![]));
+                mips_instrs.push(get_target_code("mips", "logical_or", None, vec![])?);
             },
 
             IntermediateInstr::LogicXor => {
                 stack_types.pop();
-                mips_instrs.push(get_target_code("mips", "logical_xor", None, vec![]));
+                mips_instrs.push(get_target_code("mips", "logical_xor", None, vec![])?);
             },
 
             IntermediateInstr::JumpZero(label) => {
                 let op_type = stack_types.pop().unwrap();
                 match op_type {
-                    Type::Integer => mips_instrs.push(get_target_code("mips", "jump_zero", Some("int"), vec![label])),
-                    Type::Long => mips_instrs.push(get_target_code("mips", "jump_zero", Some("long"), vec![label])),
-                    Type::Byte => mips_instrs.push(get_target_code("mips", "jump_zero", Some("byte"), vec![label])),
-                    _ => todo!()
+                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double | Type::Boolean => {
+                        mips_instrs.push(get_typed_code("jump_zero", &op_type, vec![label])?);
+                    },
+
+                    other => return Err(Box::new(InvalidOperandType::new("JumpZero", other)))
                 }
             },
 
-            IntermediateInstr::Call(func_name, return_type) => {
-                let frame_size = get_frame_size(&func_name, symbol_table);
-                mips_instrs.push(get_target_code("mips", "call", Some(&return_type.to_string()), vec![func_name.clone(), func_name, frame_size.to_string()]));
+            IntermediateInstr::Call(func_name, return_type, argument_count) => {
+                // the arguments are the values on top of the evaluation stack, and there must be one per parameter
+                let expected = symbol_table.get_function_param_types(&func_name)?.len();
+                if argument_count != expected || stack_types.len() < argument_count {
+                    let found = argument_count.min(stack_types.len());
+                    return Err(Box::new(UnbalancedCall {function: func_name, expected, found}));
+                }
+
+                stack_types.truncate(stack_types.len() - argument_count);
+
+                // the caller removes the arguments and the saved return address once the call returns, and values
+                // already on the evaluation stack are in memory above them so nothing needs to be spilled
+                let pushed_size = get_argument_area_size(&func_name, symbol_table)? + 4;
+                mips_instrs.push(get_typed_code("call", &return_type, vec![func_name.clone(), func_name, pushed_size.to_string()])?);
                 if return_type != Type::Void {
                     stack_types.push(return_type);
                 }
             },
 
-            IntermediateInstr::LoadParam(param_type, offset) => {
+            IntermediateInstr::LoadParam(param_type, index) => {
+                let offset = get_param_offset(current_function, index, symbol_table)?;
                 match param_type {
                     Type::Integer | Type::Byte | Type::Float | Type::Char | Type::Boolean | Type::String => {
-                        println!("Param type: {:?}", param_type.to_string());
                         mips_instrs.push(get_target_code("mips", "load_param", 
                             Some(&param_type.to_string()), 
-                            vec![((offset + 2) * 4).to_string()]
-                        ));
+                            vec![offset.to_string()]
+                        )?);
                     },
 
+                    // the high word of a two word parameter is the one further from the frame pointer
                     Type::Long | Type::Double => {
                         mips_instrs.push(get_target_code("mips", "load_param", 
                             Some(&param_type.to_string()), 
                             vec![
-                                ((offset + 2) * 4).to_string(),
-                                ((offset + 3) * 4).to_string()
+                                (offset + 4).to_string(),
+                                offset.to_string()
                             ]
-                        ));
+                        )?);
+                    },
+
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("LoadParam", Type::Void)))
+                }
+            },
+
+            IntermediateInstr::Out(out_type, format) => {
+                stack_types.pop().expect("Stack types stack is empty");
+                let template = match format {
+                    PrintFormat::Plain => "out",
+                    PrintFormat::Hex => "out_hex",
+                    PrintFormat::Precision(_) => "out_precision"
+                };
+
+                match (&out_type, format) {
+                    (Type::String | Type::Char | Type::Byte | Type::Integer, PrintFormat::Plain)
+                      | (Type::Byte | Type::Integer, PrintFormat::Hex) => {
+                        mips_instrs.push(get_typed_code(template, &out_type, vec![])?);
+                    },
+
+                    (Type::Float, PrintFormat::Precision(places)) => {
+                        mips_instrs.push(get_typed_code(template, &out_type, vec![places.to_string(), places.to_string()])?);
                     },
 
-                    Type::Void => panic!("Cannot load parameter of type Void")
+                    _ => return Err(Box::new(InvalidOperandType::new("Out", out_type)))
+                }
+            },
+
+            IntermediateInstr::StrLen => {
+                match stack_types.pop() {
+                    Some(Type::String) => mips_instrs.push(get_target_code("mips", "str_len", None, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("StrLen", other.unwrap_or(Type::Void))))
+                }
+
+                stack_types.push(Type::Integer);
+            },
+
+            IntermediateInstr::CheckIndex(_) => {
+                let args = vec![data_section.get_label(index), get_index_handler_label()];
+                mips_instrs.push(get_target_code("mips", "check_index", None, args)?);
+            },
+
+            IntermediateInstr::Alloc => {
+                match stack_types.pop() {
+                    Some(Type::Integer) => mips_instrs.push(get_target_code("mips", "alloc", None, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("Alloc", other.unwrap_or(Type::Void))))
                 }
+
+                stack_types.push(Type::Long);
             },
 
-            IntermediateInstr::Out => {
-                if stack_types.pop().expect("Stack types stack is empty") != Type::String {
-                    panic!("Invalid type found for Out instruction - only strings can be printed");
+            IntermediateInstr::StrIndex => {
+                stack_types.pop();
+                match stack_types.pop() {
+                    Some(Type::String) => mips_instrs.push(get_target_code("mips", "str_index", None, vec![])?),
+                    other => return Err(Box::new(InvalidOperandType::new("StrIndex", other.unwrap_or(Type::Void))))
                 }
 
-                mips_instrs.push(get_target_code("mips", "out", None, vec![]));
+                stack_types.push(Type::Char);
+            },
+
+            IntermediateInstr::In(Type::Char, _) => {
+                stack_types.push(Type::Char);
+                mips_instrs.push(get_target_code("mips", "in_char", None, vec![])?)
             },
 
-            IntermediateInstr::In(length) => {
+            IntermediateInstr::In(_, length) => {
                 stack_types.push(Type::String);
-                mips_instrs.push(get_target_code("mips", "in", None, vec![length.to_string(), length.to_string()]))
+                mips_instrs.push(get_target_code("mips", "in", None, vec![length.to_string(), data_section.get_label(index)])?)
+            },
+
+            IntermediateInstr::Pop(pop_type) => {
+                match pop_type {
+                    Type::Void => return Err(Box::new(InvalidOperandType::new("Pop", Type::Void))),
+                    _ => mips_instrs.push(get_typed_code("pop", &pop_type, vec![])?)
+                }
+
+                stack_types.pop();
+            },
+
+            IntermediateInstr::Cast(from, into) => mips_instrs.push(generate_cast_code("mips", from, into)?),
+            IntermediateInstr::Jump(label) => mips_instrs.extend(jump(&label)),
+            IntermediateInstr::TableSwitch(low, labels, default) => {
+                match stack_types.pop() {
+                    Some(Type::Integer | Type::Char) => {},
+                    other => return Err(Box::new(InvalidOperandType::new("TableSwitch", other.unwrap_or(Type::Void))))
+                }
+
+                let args = vec![low.to_string(), labels.len().to_string(), default, data_section.get_label(index)];
+                mips_instrs.push(get_target_code("mips", "table_switch", None, args)?);
             },
 
-            IntermediateInstr::Cast(from, into) => mips_instrs.push(generate_cast_code("mips", from, into).unwrap()),
-            IntermediateInstr::Jump(label) => mips_instrs.push(get_target_code("mips", "jump", None, vec![label])),
-            IntermediateInstr::Label(label) => mips_instrs.push(get_target_code("mips", "label", None, vec![label]))
+            IntermediateInstr::Label(label) => mips_instrs.extend([MipsInstr::Label(label), MipsInstr::Blank])
+        }
+    }
+
+    Ok(mips_instrs)
+}
+
+
+/**
+ * Generates the final MIPS assembly code that can then be compiled to native binary using a separate tool,
+ * and writes it to the given writer, such as a file or stdout. The functions are generated on as many threads
+ * as the host has cores, see `generate_mips_with_threads`.
+ *
+ * Every value on the evaluation stack is kept in memory below `$sp`, so the code for each intermediate
+ * instruction loads its operands into registers and stores its result before the next one starts. All of
+ * the `$t`, `$f` and `$a` registers are therefore caller-saved and never hold a value across a call: only
+ * `$sp`, `$fp` and the return address are preserved, with return values passed in `$a1`, or `$a0` and `$a1`
+ * for two word values.
+ *
+ * Bytes, chars and bools take a whole word both on the stack and in a frame, and are always zero extended to
+ * fill it, as bytes are unsigned. They are therefore read and written with `lw` and `sw` like ints, and any
+ * operation which can carry a byte past 255 masks its result back into range before storing it.
+ *
+ * The evaluation stack is only aligned to 4 bytes, so doubles on it and in a frame are always moved a word
+ * at a time with `lwc1` and `swc1`. `l.d` is only used on constants in the data section, which are aligned.
+ *
+ * Int arithmetic wraps on overflow unless `options` asks for it to trap, in which case each add, subtract,
+ * multiply and negation checks its result and jumps to a handler which prints the name of the function and
+ * exits with code 1, generated once for the program.
+ *
+ * Returns the number of MIPS instructions generated for each function, not including the runtime library.
+ */
+pub fn generate_mips<W:Write>(intermediate_code:Vec<IntermediateInstr>, writer:&mut W, symbol_table:&SymbolTable, options:&CodegenOptions)
+        -> Result<HashMap<String, usize>, Box<dyn Error>> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    generate_mips_with_threads(intermediate_code, writer, symbol_table, options, threads)
+}
+
+
+/**
+ * Collects every entry of the data section for the intermediate code, and generates the handlers the code
+ * can jump to, whose messages are added to the data section as well.
+ */
+fn collect_data_section(intermediate_code:&[IntermediateInstr], options:&CodegenOptions) -> Result<(DataSection, Vec<MipsInstr>), Box<dyn Error + Send + Sync>> {
+    let mut data_section = DataSection::collect(intermediate_code);
+    data_section.collect_input_buffers(intermediate_code, options.distinct_input_buffers);
+    data_section.stack_size = options.stack_size;
+
+    // the handler for indexes out of range is only generated once, and only if something can jump to it
+    let mut handlers = vec![];
+    if intermediate_code.iter().any(|instr| matches!(instr, IntermediateInstr::CheckIndex(_))) {
+        handlers.push(get_index_handler_code(&mut data_section)?);
+    }
+
+    // likewise the overflow handler, which is only needed if some function does arithmetic
+    if options.overflow == OverflowMode::Trap && data_section.collect_function_names(intermediate_code) {
+        handlers.push(get_overflow_handler_code(&mut data_section)?);
+    }
+
+    // and the handler for corrupted frames, which is only needed if some function returns
+    if options.frame_checks && data_section.collect_returning_function_names(intermediate_code) {
+        handlers.push(get_frame_handler_code(&mut data_section)?);
+    }
+
+    let data_size = data_section.get_sizes().get_total();
+    if let Some(stack_size) = options.stack_size.filter(|_| data_size > DATA_SEGMENT_SIZE) {
+        return Err(Box::new(StackSizeTooLarge {stack_size, data_size, limit: DATA_SEGMENT_SIZE}));
+    }
+
+    Ok((data_section, handlers))
+}
+
+
+/**
+ * Returns the number of bytes each kind of entry takes up in the data section of the MIPS generated for the
+ * intermediate code with the given options, without generating any code.
+ */
+pub fn get_data_sizes(intermediate_code:&[IntermediateInstr], options:&CodegenOptions) -> Result<DataSizes, Box<dyn Error>> {
+    Ok(collect_data_section(intermediate_code, options).map_err(|err| err as Box<dyn Error>)?.0.get_sizes())
+}
+
+
+/**
+ * Generates the MIPS assembly code for the intermediate code on the given number of threads and writes it to
+ * the given writer. Every constant is collected into the data section before any code is generated, so the
+ * threads share it without changing it. The code of each function is written out as soon as it and every
+ * function before it have been generated, in the same order however many threads there are, giving the same
+ * output each time. The first function which cannot be generated stops the others and its error is returned.
+ *
+ * Returns the number of MIPS instructions generated for each function, not including the runtime library.
+ */
+pub fn generate_mips_with_threads<W:Write>(intermediate_code:Vec<IntermediateInstr>, writer:&mut W, symbol_table:&SymbolTable, options:&CodegenOptions,
+                                          threads:usize) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+    let (data_section, handlers) = collect_data_section(&intermediate_code, options).map_err(|err| err as Box<dyn Error>)?;
+    data_section.write(writer)?;
+
+    // main goes first so that the program starts in it without jumping there, and the other functions follow
+    // in alphabetical order so that moving a function around the source does not move it in the output
+    let mut functions = split_functions(intermediate_code);
+    functions.sort_by(|a, b| (a.name != "main", &a.name).cmp(&(b.name != "main", &b.name)));
+
+    write!(writer, "\n\n.text:")?;
+    let mut mips_instrs:Vec<MipsInstr> = vec![get_banner("contents")];
+    if options.stack_size.is_some() {
+        mips_instrs.push(MipsInstr::Raw(String::from("#   startup")));
+    }
+
+    mips_instrs.extend(functions.iter().map(|function| MipsInstr::Raw(format!("#   fn_{}", function.name))));
+    mips_instrs.push(MipsInstr::Raw(String::from("#   then any runtime library routines the program uses")));
+
+    // the startup code goes before main, so the program falls through into main once the stack is moved
+    if let Some(stack_size) = options.stack_size {
+        mips_instrs.extend(get_startup_code(stack_size));
+    }
+
+    let mut referenced_labels:HashSet<String> = HashSet::new();
+    write_instrs(writer, &mut mips_instrs, &mut referenced_labels)?;
+
+    let mut instruction_counts:HashMap<String, usize> = HashMap::new();
+    let generate = |function| generate_function_mips(function, &data_section, symbol_table, options);
+    let failed = AtomicBool::new(false);
+    match threads {
+        0 | 1 => {
+            for function in &functions {
+                mips_instrs = generate(function).map_err(|err| err as Box<dyn Error>)?;
+                let count = write_instrs(writer, &mut mips_instrs, &mut referenced_labels)?;
+                instruction_counts.insert(function.name.clone(), count);
+            }
+        },
+
+        // each thread takes a run of neighbouring functions and sends the code of each back as it is generated,
+        // which is held until the functions before it have been written
+        _ => thread::scope(|scope| -> Result<(), Box<dyn Error>> {
+            let chunk_size = functions.len().div_ceil(threads).max(1);
+            let (sender, receiver) = mpsc::channel();
+            for (chunk_index, chunk) in functions.chunks(chunk_size).enumerate() {
+                let (sender, failed, generate) = (sender.clone(), &failed, &generate);
+                scope.spawn(move || {
+                    for (offset, function) in chunk.iter().enumerate() {
+                        if failed.load(Ordering::Relaxed) {
+                            break;
+                        }
+
+                        let code = generate(function);
+                        failed.fetch_or(code.is_err(), Ordering::Relaxed);
+                        if sender.send((chunk_index * chunk_size + offset, code)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+
+            drop(sender);
+            let mut generated:HashMap<usize, Vec<MipsInstr>> = HashMap::new();
+            for (index, code) in receiver {
+                generated.insert(index, code.map_err(|err| {
+                    failed.store(true, Ordering::Relaxed);
+                    err as Box<dyn Error>
+                })?);
+
+                while let Some(code) = generated.remove(&instruction_counts.len()) {
+                    mips_instrs = code;
+                    let count = write_instrs(writer, &mut mips_instrs, &mut referenced_labels)?;
+                    instruction_counts.insert(functions[instruction_counts.len()].name.clone(), count);
+                }
+            }
+
+            Ok(())
+        })?
+    }
+
+    // only the libraries with a routine the functions jump to are added
+    for library in LIBRARIES {
+        let code = add_library(library);
+        if code.iter().filter_map(|line| line.trim_end().strip_suffix(':')).any(|label| referenced_labels.contains(label)) {
+            mips_instrs.extend([MipsInstr::Blank, MipsInstr::Blank, get_banner(&format!("runtime library {}", library))]);
+            mips_instrs.extend(code.into_iter().map(MipsInstr::Raw));
         }
     }
 
-    mips_instrs.push("\nend:".to_owned());
-    mips_instrs.push("\tli $v0, 10 # halt syscall".to_owned());
-    mips_instrs.push("\tsyscall".to_owned());
+    for handler in handlers {
+        mips_instrs.extend([MipsInstr::Blank, handler]);
+    }
+
+    mips_instrs.push(MipsInstr::Blank);
+    mips_instrs.push(MipsInstr::Label(get_end_label()));
+    mips_instrs.push(MipsInstr::op("li", &["$v0", "10"]).comment("halt syscall"));
+    mips_instrs.push(MipsInstr::op("syscall", &[]));
+    write_instrs(writer, &mut mips_instrs, &mut referenced_labels)?;
+
+    writer.flush()?;
+    Ok(instruction_counts)
+}
 
-    file.write(text_section.join("\n").as_bytes()).expect("Could not write target text section to file");
-    file.write(mips_instrs.join("\n").as_bytes()).expect("Could not write target code to file");
 
-    Ok(())
+/**
+ * Generates the final MIPS assembly code for the intermediate code with the default options and returns it
+ * as a `String`.
+ */
+#[allow(dead_code)]
+pub fn generate_mips_to_string(intermediate_code:Vec<IntermediateInstr>, symbol_table:&SymbolTable) -> Result<String, Box<dyn Error>> {
+    let mut buffer:Vec<u8> = vec![];
+    generate_mips(intermediate_code, &mut buffer, symbol_table, &CodegenOptions::default())?;
+
+    Ok(String::from_utf8(buffer)?)
 }
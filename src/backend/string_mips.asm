@@ -138,6 +138,43 @@ __strcat:
 
 
 
+# Takes the maximum number of characters to read in $a0 and the address of the buffer to read
+# into in $a1, reads a line of input, and returns a pointer to it in $a0. The trailing newline (or
+# carriage return and newline) is removed so the result can be compared to a literal, and anything
+# past the maximum number of characters is cut off. The buffer must have room for the characters,
+# a newline, and '\0'.
+__input_string:
+    move $t0, $a0
+    addi $t1, $t0, 2 # length of the buffer
+    move $t2, $a1
+
+    li $v0, 8
+    move $a0, $t2
+    move $a1, $t1
+    syscall # read a line into the buffer
+
+    add $t3, $t2, $t0
+    sb $zero, 0($t3) # cut off any characters past the maximum
+    move $t3, $t2
+
+# find the first newline or carriage return and end the string there
+__input_string_loop:
+    lb $t4, 0($t3)
+    beqz $t4, __input_string_end
+    beq $t4, 10, __input_string_strip
+    beq $t4, 13, __input_string_strip
+    addi $t3, $t3, 1
+    j __input_string_loop
+
+__input_string_strip:
+    sb $zero, 0($t3)
+
+__input_string_end:
+    move $a0, $t2
+    jr $ra
+
+
+
 # Takes a number in $a0 and outputs a pointer to its string representation in $a0, which
 # will be no more than 12 bytes.
 __tostring_int:
@@ -280,4 +317,44 @@ __fromstring_int_handle_sign:
 __fromstring_int_end:	
 	move $a0, $t1
 	jr $ra
-    
\ No newline at end of file
+    
+
+
+# Takes a number in $a0 and prints it in hexadecimal as an unsigned number with a 0x prefix and
+# no leading zeros, such as 0xff. Used instead of syscall 34 as that is only supported by MARS.
+__print_hex:
+    move $t0, $a0 # move argument into $t0
+    li $v0, 11
+
+    li $a0, 0x30
+    syscall # print '0'
+    li $a0, 0x78
+    syscall # print 'x'
+
+    li $t1, 28 # shift for the current digit, starting from the most significant
+    move $t2, $zero # becomes non-zero once a non-zero digit has been found
+
+__print_hex_loop:
+    srlv $t3, $t0, $t1
+    andi $t3, $t3, 0xf # get the current digit
+
+    # skip leading zeros, but always print the last digit so that 0 is printed as 0x0
+    or $t2, $t2, $t3
+    bnez $t2, __print_hex_digit
+    bnez $t1, __print_hex_next
+
+__print_hex_digit:
+    addi $a0, $t3, 0x30 # convert 0-9 to ASCII
+    blt $t3, 10, __print_hex_print
+    addi $a0, $t3, 0x57 # convert 10-15 to ASCII 'a'-'f'
+
+__print_hex_print:
+    syscall
+
+__print_hex_next:
+    beqz $t1, __print_hex_end
+    subi $t1, $t1, 4
+    j __print_hex_loop
+
+__print_hex_end:
+    jr $ra
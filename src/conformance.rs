@@ -0,0 +1,395 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::backend::interpreter::Value;
+use crate::frontend;
+use crate::frontend::ast::Type;
+
+
+/**
+ * The binary operators of expressions, with the name each gives to the programs testing it.
+ */
+const BINARY_OPERATORS:[(&str, &str); 10] = [
+    ("+", "add"), ("-", "sub"), ("*", "mul"), ("/", "div"), ("&", "and"), ("|", "or"), ("^", "xor"),
+    ("<<", "shl"), (">>", "shr"), (">>>", "ushr")
+];
+
+/**
+ * The unary operators of expressions, with the name each gives to the programs testing it.
+ */
+const UNARY_OPERATORS:[(&str, &str); 3] = [("-", "neg"), ("!", "not"), ("~", "complement")];
+
+/**
+ * The comparison operators of boolean expressions, with the name each gives to the programs testing it.
+ */
+const COMPARISON_OPERATORS:[(&str, &str); 6] = [("==", "eq"), ("!=", "ne"), (">", "gt"), (">=", "ge"), ("<", "lt"), ("<=", "le")];
+
+/**
+ * The connectors of boolean expressions, which only take bools, with the name each gives to the programs
+ * testing it.
+ */
+const BOOLEAN_CONNECTORS:[(&str, &str); 3] = [("&&", "land"), ("||", "lor"), ("^^", "lxor")];
+
+
+/**
+ * Represents a program written by `gen-conformance`, which applies one operator to every pair of values from
+ * the grid for one type and prints each result, starting with a comment block giving the output the language
+ * says it should print.
+ */
+#[derive(Debug)]
+pub struct ConformanceProgram {
+    pub name: String,
+    pub source: String
+}
+
+
+/**
+ * Returns the values each operator is applied to for the given type, being zero, one, minus one, and the
+ * largest and smallest values of the type where it has them.
+ */
+pub fn get_operand_grid(value_type:&Type) -> Vec<Value> {
+    match value_type {
+        Type::Byte => vec![Value::Byte(0), Value::Byte(1), Value::Byte(127), Value::Byte(128), Value::Byte(u8::MAX)],
+        Type::Integer => vec![Value::Integer(0), Value::Integer(1), Value::Integer(-1), Value::Integer(i32::MAX), Value::Integer(i32::MIN)],
+        Type::Long => vec![Value::Long(0), Value::Long(1), Value::Long(-1), Value::Long(i64::MAX), Value::Long(i64::MIN)],
+        Type::Float => vec![Value::Float(0.0), Value::Float(1.0), Value::Float(-1.0), Value::Float(f32::MAX), Value::Float(f32::MIN)],
+        Type::Double => vec![Value::Double(0.0), Value::Double(1.0), Value::Double(-1.0), Value::Double(f64::MAX), Value::Double(f64::MIN)],
+        Type::Char => vec![Value::Char('0'), Value::Char('a'), Value::Char('z')],
+        Type::Boolean => vec![Value::Boolean(false), Value::Boolean(true)],
+        Type::String => vec![Value::String(String::new()), Value::String(String::from("a")), Value::String(String::from("iridescent"))],
+        Type::Void => vec![]
+    }
+}
+
+
+/**
+ * Returns the amounts values of the given type are shifted by, which are only those smaller than the number of
+ * bits in the type, as larger shifts are not given the same meaning by every target.
+ */
+fn get_shift_grid(value_type:&Type) -> Vec<Value> {
+    match value_type {
+        Type::Byte => vec![Value::Byte(0), Value::Byte(1), Value::Byte(7)],
+        Type::Integer => vec![Value::Integer(0), Value::Integer(1), Value::Integer(31)],
+        Type::Long => vec![Value::Long(0), Value::Long(1), Value::Long(63)],
+        _ => vec![]
+    }
+}
+
+
+/**
+ * Returns the source of a term giving the value, written with the negation operator if it is negative as
+ * literals cannot be. The smallest int and long are written as one less than the negation of the largest, as
+ * their magnitude does not fit in the type.
+ */
+pub fn get_literal(value:&Value) -> String {
+    match value {
+        Value::Byte(value) => format!("{}b", value),
+        Value::Integer(i32::MIN) => format!("(({})-, 1)-", i32::MAX),
+        Value::Integer(value) if *value < 0 => format!("({})-", value.unsigned_abs()),
+        Value::Integer(value) => value.to_string(),
+        Value::Long(i64::MIN) => format!("(({}l)-, 1l)-", i64::MAX),
+        Value::Long(value) if *value < 0 => format!("({}l)-", value.unsigned_abs()),
+        Value::Long(value) => format!("{}l", value),
+        Value::Float(value) => get_float_literal(&value.abs().to_string(), value.is_sign_negative(), ""),
+        Value::Double(value) => get_float_literal(&value.abs().to_string(), value.is_sign_negative(), "d"),
+        Value::Char(value) => format!("'{}'", value),
+        Value::Boolean(value) => value.to_string(),
+        Value::String(value) => format!("\"{}\"", value)
+    }
+}
+
+
+/**
+ * Takes the digits of the magnitude of a float or double and returns a term giving it, adding the fraction
+ * which float literals must have if the digits are a whole number.
+ */
+fn get_float_literal(digits:&str, negative:bool, suffix:&str) -> String {
+    let literal = match digits.contains('.') {
+        true => format!("{}{}", digits, suffix),
+        false => format!("{}.0{}", digits, suffix)
+    };
+
+    match negative {
+        true => format!("({})-", literal),
+        false => literal
+    }
+}
+
+
+/**
+ * Applies a binary operator to two values of the same type as the language defines it, which is what the
+ * interpreter and the MIPS backend are checked against. Integer arithmetic wraps on overflow, and shifting
+ * right is logical for `>>` and arithmetic for `>>>`. Returns `None` where the result is not a value, which is
+ * dividing an integer by zero.
+ */
+pub fn evaluate_binary(symbol:&str, lhs:&Value, rhs:&Value) -> Option<Value> {
+    macro_rules! integer_op {
+        ($variant:ident, $lhs:expr, $rhs:expr, $unsigned:ty) => {
+            match symbol {
+                "+" => Value::$variant($lhs.wrapping_add($rhs)),
+                "-" => Value::$variant($lhs.wrapping_sub($rhs)),
+                "*" => Value::$variant($lhs.wrapping_mul($rhs)),
+                "/" if $rhs == 0 => return None,
+                "/" => Value::$variant($lhs.wrapping_div($rhs)),
+                "&" => Value::$variant($lhs & $rhs),
+                "|" => Value::$variant($lhs | $rhs),
+                "^" => Value::$variant($lhs ^ $rhs),
+                "<<" => Value::$variant($lhs << $rhs),
+                ">>" => Value::$variant((($lhs as $unsigned) >> $rhs) as _),
+                ">>>" => Value::$variant($lhs >> $rhs),
+                _ => return None
+            }
+        };
+    }
+
+    macro_rules! float_op {
+        ($variant:ident, $lhs:expr, $rhs:expr) => {
+            match symbol {
+                "+" => Value::$variant($lhs + $rhs),
+                "-" => Value::$variant($lhs - $rhs),
+                "*" => Value::$variant($lhs * $rhs),
+                "/" => Value::$variant($lhs / $rhs),
+                _ => return None
+            }
+        };
+    }
+
+    Some(match (lhs, rhs) {
+        (Value::Byte(lhs), Value::Byte(rhs)) => integer_op!(Byte, *lhs, *rhs, u8),
+        (Value::Integer(lhs), Value::Integer(rhs)) => integer_op!(Integer, *lhs, *rhs, u32),
+        (Value::Long(lhs), Value::Long(rhs)) => integer_op!(Long, *lhs, *rhs, u64),
+        (Value::Float(lhs), Value::Float(rhs)) => float_op!(Float, *lhs, *rhs),
+        (Value::Double(lhs), Value::Double(rhs)) => float_op!(Double, *lhs, *rhs),
+        (Value::String(lhs), Value::String(rhs)) if symbol == "+" => Value::String(format!("{}{}", lhs, rhs)),
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => match symbol {
+            "&&" => Value::Boolean(*lhs && *rhs),
+            "||" => Value::Boolean(*lhs || *rhs),
+            "^^" => Value::Boolean(lhs ^ rhs),
+            _ => return None
+        },
+        _ => return None
+    })
+}
+
+
+/**
+ * Applies a unary operator to a value as the language defines it, where negating the smallest int or long
+ * wraps around to itself.
+ */
+pub fn evaluate_unary(symbol:&str, operand:&Value) -> Option<Value> {
+    Some(match (symbol, operand) {
+        ("-", Value::Integer(value)) => Value::Integer(value.wrapping_neg()),
+        ("-", Value::Long(value)) => Value::Long(value.wrapping_neg()),
+        ("-", Value::Float(value)) => Value::Float(-value),
+        ("-", Value::Double(value)) => Value::Double(-value),
+        ("~", Value::Byte(value)) => Value::Byte(!value),
+        ("~", Value::Integer(value)) => Value::Integer(!value),
+        ("~", Value::Long(value)) => Value::Long(!value),
+        ("!", Value::Boolean(value)) => Value::Boolean(!value),
+        _ => return None
+    })
+}
+
+
+/**
+ * Compares two values of the same type as the language defines it, where bytes are unsigned, chars are
+ * ordered by their code and a comparison with NaN is only true for `!=`.
+ */
+pub fn evaluate_comparison(symbol:&str, lhs:&Value, rhs:&Value) -> Option<bool> {
+    let ordering = match (lhs, rhs) {
+        (Value::Byte(lhs), Value::Byte(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Integer(lhs), Value::Integer(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Long(lhs), Value::Long(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Float(lhs), Value::Float(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Double(lhs), Value::Double(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Char(lhs), Value::Char(rhs)) => lhs.partial_cmp(rhs),
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => lhs.partial_cmp(rhs),
+        (Value::String(lhs), Value::String(rhs)) => lhs.partial_cmp(rhs),
+        _ => return None
+    };
+
+    let Some(ordering) = ordering else {
+        return Some(symbol == "!=");
+    };
+
+    match symbol {
+        "==" => Some(ordering.is_eq()),
+        "!=" => Some(ordering.is_ne()),
+        ">" => Some(ordering.is_gt()),
+        ">=" => Some(ordering.is_ge()),
+        "<" => Some(ordering.is_lt()),
+        "<=" => Some(ordering.is_le()),
+        _ => None
+    }
+}
+
+
+/**
+ * Returns the statements which print the result of a case, held in `r<index>`, along with the line they
+ * should print, or `None` if the result cannot be checked. Bytes, ints and strings are printed as they are,
+ * bools by name, and longs as their high and low words since they cannot be printed. Floats and doubles
+ * cannot be printed exactly, so they are compared with a literal of the expected result and whether they
+ * were equal is printed, which leaves out results which are infinite or NaN as they have no literal.
+ */
+fn get_result_check(index:usize, result:&Value) -> Option<(Vec<String>, String)> {
+    let label = format!("print << \"{}: \"", index);
+    Some(match result {
+        Value::Byte(_) | Value::Integer(_) => (vec![format!("{} << r{} << \"\\n\";", label, index)], format!("{}: {}", index, result)),
+        Value::String(value) => (vec![format!("{} << \"[\" << r{} << \"]\\n\";", label, index)], format!("{}: [{}]", index, value)),
+        Value::Boolean(value) => (
+            vec![
+                format!("let string s{0} = r{0} ? \"true\" : \"false\";", index),
+                format!("{} << s{} << \"\\n\";", label, index)
+            ],
+            format!("{}: {}", index, value)
+        ),
+
+        Value::Long(value) => (
+            vec![
+                format!("let long t{0} = (r{0}, 32l)>>;", index),
+                format!("let int h{0} = int(t{0});", index),
+                format!("let int l{0} = int(r{0});", index),
+                format!("{} << h{1} << \" \" << l{1} << \"\\n\";", label, index)
+            ],
+            format!("{}: {} {}", index, (value >> 32) as i32, *value as i32)
+        ),
+
+        Value::Float(value) if !value.is_finite() => return None,
+        Value::Double(value) if !value.is_finite() => return None,
+        Value::Float(_) | Value::Double(_) => (
+            vec![
+                format!("let string s{0} = (r{0}, {1})== ? \"true\" : \"false\";", index, get_literal(result)),
+                format!("{} << s{} << \"\\n\";", label, index)
+            ],
+            format!("{}: true", index)
+        ),
+
+        Value::Char(_) => return None
+    })
+}
+
+
+/**
+ * Builds a program from its cases, each being its index, a comment describing it, the declarations of its
+ * operands and its result `r<index>`, and the value of the result. Cases whose result cannot be checked are
+ * left out.
+ */
+fn build_program(name:String, cases:Vec<(usize, String, Vec<String>, Value)>) -> ConformanceProgram {
+    let mut body = vec![];
+    let mut expected = vec![];
+    for (index, description, declarations, result) in cases {
+        let Some((statements, line)) = get_result_check(index, &result) else {
+            continue;
+        };
+
+        body.push(format!("    /* {} */", description));
+        body.extend(declarations.iter().chain(&statements).map(|statement| format!("    {}", statement)));
+        expected.push(line);
+    }
+
+    let source = format!("/*\nexit: 0\noutput:\n{}\n*/\nfn void main() {{\n{}\n}}\n", expected.join("\n"), body.join("\n"));
+    ConformanceProgram {name, source}
+}
+
+
+/**
+ * Returns a program for every binary, unary and comparison operator and every type semantic analysis accepts
+ * it on, applying it to each value or pair of values from the grid for the type. Each program prints one line
+ * per case, and the output it should give is worked out here, apart from either backend, so that the backends
+ * can be checked against the language rather than only against each other.
+ */
+pub fn get_conformance_programs() -> Vec<ConformanceProgram> {
+    let types = [Type::Byte, Type::Integer, Type::Long, Type::Char, Type::Boolean, Type::Float, Type::Double, Type::String];
+    let mut programs = vec![];
+    for value_type in &types {
+        let grid = get_operand_grid(value_type);
+        let pairs = |rhs_grid:Vec<Value>| -> Vec<(Value, Value)> {
+            grid.iter().flat_map(|lhs| rhs_grid.iter().map(|rhs| (lhs.clone(), rhs.clone()))).collect()
+        };
+
+        let mut binary = vec![];
+        for (symbol, name) in BINARY_OPERATORS {
+            let operator = frontend::ast::get_binary_operator_from_str(symbol).unwrap();
+            if frontend::semantics::get_operator_types(&operator).contains(value_type) {
+                let rhs_grid = match symbol {
+                    "<<" | ">>" | ">>>" => get_shift_grid(value_type),
+                    _ => grid.clone()
+                };
+
+                binary.push((symbol, name, value_type.clone(), false, pairs(rhs_grid)));
+            }
+        }
+
+        for (symbol, name) in COMPARISON_OPERATORS {
+            let operator = frontend::ast::get_boolean_operator_from_str(symbol).unwrap();
+            if frontend::semantics::get_comparison_types(&operator).contains(value_type) {
+                binary.push((symbol, name, Type::Boolean, true, pairs(grid.clone())));
+            }
+        }
+
+        if *value_type == Type::Boolean {
+            binary.extend(BOOLEAN_CONNECTORS.map(|(symbol, name)| (symbol, name, Type::Boolean, false, pairs(grid.clone()))));
+        }
+
+        for (symbol, name, result_type, is_comparison, operands) in binary {
+            let cases = operands.into_iter().enumerate().filter_map(|(index, (lhs, rhs))| {
+                let result = match is_comparison {
+                    true => Value::Boolean(evaluate_comparison(symbol, &lhs, &rhs)?),
+                    false => evaluate_binary(symbol, &lhs, &rhs)?
+                };
+
+                // a connector cannot start the value of a declaration, as `(a, b)&` is read as a bitwise and first
+                let result_declaration = match BOOLEAN_CONNECTORS.iter().any(|(connector, _)| *connector == symbol) {
+                    true => format!("let mut bool r{0} = false;\n    if (a{0}, b{0}){1} {{\n        r{0} = true;\n    }}", index, symbol),
+                    false => format!("let {} r{1} = (a{1}, b{1}){2};", result_type, index, symbol)
+                };
+
+                let declarations = vec![
+                    format!("let {} a{} = {};", value_type, index, get_literal(&lhs)),
+                    format!("let {} b{} = {};", value_type, index, get_literal(&rhs)),
+                    result_declaration
+                ];
+
+                Some((index, format!("{} {} {}", lhs, symbol, rhs), declarations, result))
+            });
+
+            programs.push(build_program(format!("{}_{}", name, value_type), cases.collect()));
+        }
+
+        for (symbol, name) in UNARY_OPERATORS {
+            let operator = frontend::ast::get_unary_operator_from_str(symbol).unwrap();
+            if !frontend::semantics::get_operator_types(&operator).contains(value_type) {
+                continue;
+            }
+
+            let cases = grid.iter().enumerate().filter_map(|(index, operand)| {
+                let declarations = vec![
+                    format!("let {} a{} = {};", value_type, index, get_literal(operand)),
+                    format!("let {} r{1} = (a{1}){2};", value_type, index, symbol)
+                ];
+
+                Some((index, format!("{}{}", symbol, operand), declarations, evaluate_unary(symbol, operand)?))
+            });
+
+            programs.push(build_program(format!("{}_{}", name, value_type), cases.collect()));
+        }
+    }
+
+    programs
+}
+
+
+/**
+ * Writes every conformance program into the given directory as `<operator>_<type>.iri`, creating it if it does
+ * not exist, and returns how many were written.
+ */
+pub fn write_conformance_programs(dir:&Path) -> Result<usize, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    let programs = get_conformance_programs();
+    for program in &programs {
+        fs::write(dir.join(format!("{}.iri", program.name)), &program.source)?;
+    }
+
+    Ok(programs.len())
+}
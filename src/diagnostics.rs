@@ -0,0 +1,308 @@
+use std::{error::Error, fmt};
+
+use serde_json::json;
+
+use crate::errors::*;
+use crate::frontend::parser::Rule;
+
+
+/**
+ * How serious a diagnostic is: errors stop the program from compiling, while warnings do not.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning")
+        }
+    }
+}
+
+
+/**
+ * The format diagnostics are printed in, chosen with `--message-format`.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageFormat {
+    Human,
+    Json
+}
+
+impl MessageFormat {
+    /**
+     * Takes the value given to `--message-format` and returns the format it names, or an error if it is not
+     * `human` or `json`.
+     */
+    pub fn from_name(name:&str) -> Result<MessageFormat, String> {
+        match name {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            other => Err(format!("{} is not a valid message format, expected human or json", other))
+        }
+    }
+
+
+    /**
+     * Renders the diagnostic in this format to be printed to stderr. JSON is always a single line, while human
+     * readable errors from building the AST are followed by the line of source they are about.
+     */
+    pub fn render(&self, diagnostic:&Diagnostic) -> String {
+        match self {
+            MessageFormat::Human => diagnostic.to_string(),
+            MessageFormat::Json => diagnostic.to_json()
+        }
+    }
+}
+
+
+/**
+ * Represents an error or warning about a program, along with where in the program it was found if that is
+ * known. Both the human readable and JSON output are generated from this, so they always agree.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end: Option<(usize, usize)>,
+    pub source_line: Option<String>
+}
+
+impl Diagnostic {
+    /**
+     * Creates the diagnostic for a warning found in the given file.
+     */
+    pub fn from_warning(warning:&Warning, file:&str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: warning.get_code(),
+            message: warning.to_string(),
+            file: file.to_owned(),
+            line: warning.get_line(),
+            column: None,
+            end: None,
+            source_line: None
+        }
+    }
+
+
+    /**
+     * Creates the diagnostic for a warning about the given file as a whole, rather than a line of it.
+     */
+    pub fn from_file_warning(code:&'static str, message:String, file:&str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            file: file.to_owned(),
+            line: None,
+            column: None,
+            end: None,
+            source_line: None
+        }
+    }
+
+
+    /**
+     * Creates the diagnostic for an error which stopped the given file from compiling. Errors from the grammar
+     * give the line and column of the problem and the end of the span if there is one, while other errors
+     * give the line if they record it. Errors from building the AST also give the line of source they are
+     * about, so it can be shown with the problem underlined. Errors from one file of a program built from a
+     * directory are given the path of that file instead of `file`.
+     */
+    pub fn from_error(error:&(dyn Error + 'static), file:&str) -> Diagnostic {
+        // errors in one file of a program built from a directory are reported as being in that file
+        if let Some(error) = error.downcast_ref::<InFile>() {
+            let mut diagnostic = Diagnostic::from_error(error.error.as_ref(), &error.path);
+            diagnostic.message = format!("{}: {}", error.path, diagnostic.message);
+            return diagnostic;
+        }
+
+        let mut diagnostic = Diagnostic {
+            severity: Severity::Error,
+            code: get_error_code(error),
+            message: error.to_string(),
+            file: file.to_owned(),
+            line: None,
+            column: None,
+            end: None,
+            source_line: None
+        };
+
+        if let Some(error) = error.downcast_ref::<pest::error::Error<Rule>>() {
+            diagnostic.message = error.variant.message().to_string();
+            match error.line_col {
+                pest::error::LineColLocation::Pos((line, column)) => {
+                    diagnostic.line = Some(line);
+                    diagnostic.column = Some(column);
+                },
+
+                pest::error::LineColLocation::Span((line, column), end) => {
+                    diagnostic.line = Some(line);
+                    diagnostic.column = Some(column);
+                    diagnostic.end = Some(end);
+                }
+            }
+        } else if let Some(error) = error.downcast_ref::<ParseError>() {
+            diagnostic.line = Some(error.line);
+            diagnostic.column = Some(error.column);
+            diagnostic.end = Some(error.end);
+            diagnostic.source_line = Some(error.source_line.clone());
+        } else if let Some(error) = error.downcast_ref::<NestingTooDeep>() {
+            diagnostic.line = Some(error.line);
+            diagnostic.column = Some(error.column);
+            diagnostic.end = Some((error.line, error.column + 1));
+            diagnostic.source_line = Some(error.source_line.clone());
+        } else if let Some(error) = error.downcast_ref::<DuplicateSymbolError>() {
+            diagnostic.line = error.duplicate_line;
+        } else if let Some(error) = error.downcast_ref::<UseBeforeDeclaration>() {
+            diagnostic.line = error.used_line;
+        } else if let Some(error) = error.downcast_ref::<NonConstantStaticInitialiser>() {
+            diagnostic.line = Some(error.line);
+        } else if let Some(error) = error.downcast_ref::<InvalidRepeatCount>() {
+            diagnostic.line = Some(error.line);
+        } else if let Some(error) = error.downcast_ref::<UnsupportedOperatorType>() {
+            diagnostic.line = error.line;
+        } else if let Some(error) = error.downcast_ref::<MismatchedOperandTypes>() {
+            diagnostic.line = error.line;
+        } else if let Some(error) = error.downcast_ref::<ReservedIdentifier>() {
+            diagnostic.line = error.line;
+        }
+
+        diagnostic
+    }
+
+
+    /**
+     * Returns the diagnostic as a JSON object on a single line, for editors and other tools to read. The
+     * object has the following fields:
+     *  - severity: either "error" or "warning",
+     *  - code: a short name for the kind of problem, such as "symbol-not-found",
+     *  - message: the same description of the problem given in the human readable output,
+     *  - file: the path of the file being compiled, as given on the command line,
+     *  - line: the line the problem is on, counting from 1, or null if it is not known,
+     *  - column: the column the problem starts at, counting from 1, or null if it is not known,
+     *  - end: only present if the problem covers a span, an object with the line and column it ends at
+     */
+    pub fn to_json(&self) -> String {
+        let mut object = json!({
+            "severity": self.severity.to_string(),
+            "code": self.code,
+            "message": self.message,
+            "file": self.file,
+            "line": self.line,
+            "column": self.column
+        });
+
+        if let Some((line, column)) = self.end {
+            object["end"] = json!({"line": line, "column": column});
+        }
+
+        object.to_string()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /**
+     * Formats the diagnostic for people to read. Messages from the grammar do not say where the problem is, so
+     * the line and column are added when the column is known. If the line of source is known, it is shown
+     * below the message with carets under the problem, such as:
+     * 
+     * ```text
+     * Error: Could not parse statement on line 3: ...
+     *  3 |     x y;
+     *    |     ^^^^
+     * ```
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "Error: {}", self.message)?,
+            Severity::Warning => write!(f, "Warning: {}", self.message)?
+        }
+
+        match (self.line, self.column, &self.source_line) {
+            (Some(line), Some(column), Some(source_line)) => {
+                // the carets run to the end of the problem, or to the end of the line if it continues past it
+                let width = source_line.chars().count();
+                let end = match self.end {
+                    Some((end_line, end_column)) if end_line == line => end_column.min(width + 1),
+                    _ => width + 1
+                };
+
+                // tabs before the problem are kept so the carets line up however wide they are shown
+                let indent:String = source_line.chars().take(column - 1).map(|c| if c == '\t' {'\t'} else {' '}).collect();
+                let gutter = " ".repeat(line.to_string().len());
+                write!(f, "\n {} | {}", line, source_line)?;
+                write!(f, "\n {} | {}{}", gutter, indent, "^".repeat(end.saturating_sub(column).max(1)))
+            },
+
+            (Some(line), Some(column), None) => write!(f, " on line {}, column {}.", line, column),
+            _ => Ok(())
+        }
+    }
+}
+
+
+/**
+ * Returns the code identifying the kind of the given error, or "error" for errors which do not come from
+ * the compiler itself, such as failing to read the input file.
+ */
+fn get_error_code(error:&(dyn Error + 'static)) -> &'static str {
+    let codes:[(bool, &'static str); 44] = [
+        (error.is::<pest::error::Error<Rule>>(), "syntax-error"),
+        (error.is::<ParseError>(), "parse-error"),
+        (error.is::<SyntaxErrors>(), "syntax-errors"),
+        (error.is::<SymbolNotFoundError>(), "symbol-not-found"),
+        (error.is::<IncorrectDatatype>(), "incorrect-datatype"),
+        (error.is::<IncorrectNumArguments>(), "incorrect-num-arguments"),
+        (error.is::<BadFunctionReturn>(), "bad-function-return"),
+        (error.is::<MissingReturnValue>(), "missing-return-value"),
+        (error.is::<UnexpectedReturnValue>(), "unexpected-return-value"),
+        (error.is::<NoIntrinsicOverload>(), "no-intrinsic-overload"),
+        (error.is::<UnusedIntrinsicResult>(), "unused-intrinsic-result"),
+        (error.is::<ImmutableReassignmentError>(), "immutable-reassignment"),
+        (error.is::<RecursiveInlineFunction>(), "recursive-inline-function"),
+        (error.is::<ImpureFunction>(), "impure-function"),
+        (error.is::<DuplicateSymbolError>(), "duplicate-symbol"),
+        (error.is::<UseBeforeDeclaration>(), "use-before-declaration"),
+        (error.is::<NonConstantStaticInitialiser>(), "non-constant-static-initialiser"),
+        (error.is::<InvalidRepeatCount>(), "invalid-repeat-count"),
+        (error.is::<EmptyInput>(), "empty-input"),
+        (error.is::<NonBooleanCondition>(), "non-boolean-condition"),
+        (error.is::<IndefLoopWithoutBreak>(), "loop-without-break"),
+        (error.is::<MismatchedTernaryArms>(), "mismatched-ternary-arms"),
+        (error.is::<VoidValueUsed>(), "void-value-used"),
+        (error.is::<ReservedIdentifier>(), "reserved-identifier"),
+        (error.is::<UnsupportedOperatorType>(), "unsupported-operator-type"),
+        (error.is::<MismatchedOperandTypes>(), "mismatched-operand-types"),
+        (error.is::<ChainedComparison>(), "chained-comparison"),
+        (error.is::<NestingTooDeep>(), "nesting-too-deep"),
+        (error.is::<UnsupportedCast>(), "unsupported-cast"),
+        (error.is::<NotIterable>(), "not-iterable"),
+        (error.is::<MismatchedElementType>(), "mismatched-element-type"),
+        (error.is::<UnprintableValue>(), "unprintable-value"),
+        (error.is::<InputFileError>(), "input-file-error"),
+        (error.is::<OutputFileError>(), "output-file-error"),
+        (error.is::<OutputOverwritesInput>(), "output-overwrites-input"),
+        (error.is::<InvalidOption>(), "invalid-option"),
+        (error.is::<NoSourceFiles>(), "no-source-files"),
+        (error.is::<SizeBudgetExceeded>(), "size-budget-exceeded"),
+        (error.is::<StackSizeTooLarge>(), "stack-size-too-large"),
+        (error.is::<MissingMainFunction>(), "missing-main-function"),
+        (error.is::<InvalidOperandType>(), "internal-error"),
+        (error.is::<UnbalancedCall>(), "internal-error"),
+        (error.is::<MissingTargetTemplate>(), "internal-error"),
+        (error.is::<RuntimeError>(), "runtime-error")
+    ];
+
+    codes.iter().find(|(matches, _)| *matches).map(|(_, code)| *code).unwrap_or("error")
+}
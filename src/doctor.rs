@@ -0,0 +1,378 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+
+use crate::backend::OverflowMode;
+use crate::backend::mips::{self, CodegenOptions};
+use crate::errors::MissingTargetTemplate;
+use crate::frontend;
+use crate::frontend::ast::{PrintFormat, Type};
+use crate::frontend::parser::DEFAULT_NESTING_LIMIT;
+use crate::frontend::semantics::RESERVED_PREFIX;
+use crate::optimisation;
+use crate::optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY;
+
+
+/**
+ * Programs using the parts of the language which are not tied to a single type, so that the templates for
+ * them are checked as well as those for each operation on each type.
+ */
+const FEATURE_PROGRAMS:[&str; 5] = [
+    "fn void main() {\n    let string s = input 8;\n    let char c = input char;\n    print << \"read \" << s << c;\n}",
+    "fn void main() {\n    let string s = \"abc\";\n    for char c in s {\n        print << c;\n    }\n}",
+    "fn int count() {\n    let static mut int calls = 0;\n    calls = (calls, 1)+;\n    return calls;\n}\nfn void main() {\n    let int n = count();\n}",
+    "fn void main() {\n    let char c = input char;\n    if (c, 'a')== {\n        print << 1;\n    } else if (c, 'b')== {\n        print << 2;\n    } else if (c, 'c')== {\n        print << 3;\n    } else if (c, 'd')== {\n        print << 4;\n    }\n}",
+    "fn void main() {\n    let bool a = true;\n    let bool b = ((a, a)&&, (a, a)||)^^;\n    let int n = b ? 1 : 2;\n    while b {\n        break;\n    }\n}"
+];
+
+
+/**
+ * Represents everything found to be wrong with the target code and runtime libraries, and the operations each
+ * architecture in *target_code.json* has code for, as found by `iridescent doctor`.
+ */
+#[derive(Debug)]
+pub struct DoctorReport {
+    pub problems: Vec<String>,
+    pub features: Vec<String>
+}
+
+
+/**
+ * Returns a literal of the given type which can be used to declare a variable of it.
+ */
+pub fn get_sample_literal(value_type:&Type) -> &'static str {
+    match value_type {
+        Type::Byte => "3b",
+        Type::Integer => "3",
+        Type::Long => "3l",
+        Type::Char => "'a'",
+        Type::Boolean => "true",
+        Type::Float => "2.5",
+        Type::Double => "2.5d",
+        Type::String => "\"a\"",
+        Type::Void => panic!("there are no void literals")
+    }
+}
+
+
+/**
+ * Returns a program for each operation, intrinsic, cast and print format on each type that semantic analysis
+ * accepts, and for passing each type to and returning it from a function, followed by the programs using
+ * the parts of the language which work on any type. Between them they make the backend emit every template
+ * it can.
+ */
+pub fn get_coverage_programs() -> Vec<String> {
+    let types = [Type::Byte, Type::Integer, Type::Long, Type::Char, Type::Boolean, Type::Float, Type::Double, Type::String];
+    let mut programs = vec![];
+    for value_type in &types {
+        let declare = format!("fn void main() {{\n    let mut {} a = {};\n", value_type, get_sample_literal(value_type));
+        let mut statements = vec![];
+
+        programs.push(format!(
+            "fn {0} pass({0} x) {{\n    return x;\n}}\n{1}    a = pass(a);\n    pass(a);\n}}", value_type, declare
+        ));
+
+        for symbol in ["+", "-", "*", "/", "&", "|", "^", "<<", ">>", ">>>"] {
+            let operator = frontend::ast::get_binary_operator_from_str(symbol).unwrap();
+            if frontend::semantics::get_operator_types(&operator).contains(value_type) {
+                statements.push(format!("let {} b = (a, a){};", value_type, symbol));
+            }
+        }
+
+        for symbol in ["-", "!", "~"] {
+            let operator = frontend::ast::get_unary_operator_from_str(symbol).unwrap();
+            if frontend::semantics::get_operator_types(&operator).contains(value_type) {
+                statements.push(format!("let {} b = (a){};", value_type, symbol));
+            }
+        }
+
+        for symbol in ["==", "!=", ">", ">=", "<", "<="] {
+            let operator = frontend::ast::get_boolean_operator_from_str(symbol).unwrap();
+            if frontend::semantics::get_comparison_types(&operator).contains(value_type) {
+                statements.push(format!("let bool b = (a, a){};", symbol));
+            }
+        }
+
+        for name in ["abs", "min", "max", "pow", "round", "floor", "ceil", "alloc"] {
+            let intrinsic = frontend::ast::get_intrinsic_from_str(name).unwrap();
+            if frontend::semantics::get_intrinsic_types(&intrinsic).contains(value_type) {
+                let arguments = match name {
+                    "min" | "max" | "pow" => "a, a",
+                    _ => "a"
+                };
+
+                let result_type = match name {
+                    "round" | "floor" | "ceil" => Type::Integer,
+                    "alloc" => Type::Long,
+                    _ => value_type.clone()
+                };
+
+                statements.push(format!("let {} b = {}({});", result_type, name, arguments));
+            }
+        }
+
+        for into in frontend::semantics::get_cast_types(value_type) {
+            statements.push(format!("let {0} b = {0}(a);", into));
+        }
+
+        for (format, suffix) in [(PrintFormat::Plain, ""), (PrintFormat::Hex, ":hex"), (PrintFormat::Precision(2), ":2")] {
+            if frontend::semantics::get_printable_types(&format).contains(value_type) {
+                statements.push(format!("print << a{};", suffix));
+            }
+        }
+
+        programs.extend(statements.iter().map(|statement| format!("{}    {}\n}}", declare, statement)));
+    }
+
+    programs.extend(FEATURE_PROGRAMS.iter().map(|program| program.to_string()));
+    programs
+}
+
+
+/**
+ * Generates the MIPS for each of the coverage programs, with jump tables built, bounds checks added and int
+ * overflow trapped so that the code for those is generated too, both with and without frame checks, and returns every template the backend looked
+ * up in *target_code.json* as the architecture, the instruction and the type of each version. A missing
+ * template stops the rest of its function being generated but is still returned. Returns an error if a
+ * coverage program cannot be compiled for any other reason, which is a bug in the programs.
+ */
+pub fn find_emitted_templates() -> Result<BTreeSet<Vec<String>>, Box<dyn Error>> {
+    let mut templates = BTreeSet::new();
+    for (source, frame_checks) in get_coverage_programs().into_iter().flat_map(|source| [(source.clone(), false), (source, true)]) {
+        let options = CodegenOptions {overflow: OverflowMode::Trap, frame_checks, ..CodegenOptions::default()};
+        let (result, lookups) = mips::record_template_lookups(|| -> Result<_, Box<dyn Error>> {
+            let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT)?;
+            let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+            let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+
+            let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table)?;
+            let instructions = optimisation::jump_tables::build_jump_tables(instructions, DEFAULT_JUMP_TABLE_DENSITY);
+            let instructions = frontend::intermediate_gen::add_bounds_checks(instructions);
+            mips::generate_mips_with_threads(instructions, &mut vec![], &symbol_table, &options, 1)
+        });
+
+        if let Err(err) = result {
+            if err.downcast_ref::<MissingTargetTemplate>().is_none() {
+                return Err(format!("{} in the program\n{}", err, source).into());
+            }
+        }
+
+        templates.extend(lookups);
+    }
+
+    Ok(templates)
+}
+
+
+/**
+ * Returns the lines of a template, which is either a single line or a list of lines, or `None` if it is
+ * neither.
+ */
+fn get_template_lines(template:&serde_json::Value) -> Option<Vec<&str>> {
+    match template {
+        serde_json::Value::String(line) => Some(vec![line]),
+        serde_json::Value::Array(lines) => lines.iter().map(|line| line.as_str()).collect(),
+        _ => None
+    }
+}
+
+
+/**
+ * Walks the entries of an instruction in *target_code.json*, calling `visit` with the names of the types
+ * followed to reach each template and the template. An entry is a template, or an object holding a version
+ * of the instruction for each type, which may itself hold a version for each of a second type as casts do.
+ */
+fn visit_templates<'a>(entry:&'a serde_json::Value, path:&mut Vec<&'a str>, visit:&mut impl FnMut(&[&str], &'a serde_json::Value)) {
+    match entry.as_object() {
+        Some(versions) => {
+            for (op_type, version) in versions {
+                path.push(op_type);
+                visit_templates(version, path, visit);
+                path.pop();
+            }
+        },
+        None => visit(path, entry)
+    }
+}
+
+
+/**
+ * Checks that *target_code.json* holds a template for everything in `emitted`, as returned by
+ * `find_emitted_templates`, and that every entry in it is a line of code or a list of lines rather than
+ * anything which would be written out as `null` or a number. Returns a description of each problem found.
+ */
+pub fn check_target_code(table:&serde_json::Value, emitted:&BTreeSet<Vec<String>>) -> Vec<String> {
+    let mut problems = vec![];
+    let architectures = match table.as_object() {
+        Some(architectures) => architectures,
+        None => return vec![String::from("target_code.json does not hold an object with an entry for each architecture")]
+    };
+
+    for (architecture, instructions) in architectures {
+        let instructions = match instructions.as_object() {
+            Some(instructions) => instructions,
+            None => {
+                problems.push(format!("The {} entry in target_code.json does not hold an object with an entry for each instruction", architecture));
+                continue;
+            }
+        };
+
+        for (instr, entry) in instructions {
+            visit_templates(entry, &mut vec![], &mut |path, template| {
+                if get_template_lines(template).is_none() {
+                    problems.push(format!("The {} code for {} is {}, not a line of code or a list of lines", architecture,
+                        [instr.as_str()].iter().chain(path).copied().collect::<Vec<_>>().join(" "), template));
+                }
+            });
+        }
+    }
+
+    for key in emitted {
+        let op_types:Vec<&str> = key[2..].iter().map(String::as_str).collect();
+        if mips::find_target_template(table, &key[0], &key[1], &op_types).is_err() {
+            problems.push(format!("target_code.json has no {} code for {}, which the backend can emit", key[0], key[1..].join(" ")));
+        }
+    }
+
+    problems
+}
+
+
+/**
+ * Takes a line of a runtime library and returns the label it defines, if any, or an error if it is not a
+ * blank line, comment, label, directive or an instruction followed by a comma separated list of registers,
+ * numbers, labels and offsets.
+ */
+fn parse_library_line(line:&str) -> Result<Option<&str>, ()> {
+    let code = line.split('#').next().unwrap().trim();
+    if code.is_empty() || code.starts_with('.') {
+        return Ok(None);
+    }
+
+    if let Some(label) = code.strip_suffix(':') {
+        return match !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') && !line.starts_with(char::is_whitespace) {
+            true => Ok(Some(label)),
+            false => Err(())
+        };
+    }
+
+    let (opcode, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let valid_opcode = opcode.starts_with(|c:char| c.is_ascii_lowercase()) && opcode.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.');
+    let valid_operands = operands.trim().is_empty() || operands.split(',').map(str::trim).all(|operand| {
+        !operand.is_empty() && operand.chars().all(|c| c.is_ascii_alphanumeric() || "$_()-".contains(c))
+    });
+    match valid_opcode && valid_operands && line.starts_with(char::is_whitespace) {
+        true => Ok(None),
+        false => Err(())
+    }
+}
+
+
+/**
+ * Checks that each runtime library, given as its name and lines, is made up only of labels at the start of
+ * a line and indented instructions, and that every reserved label the MIPS templates in *target_code.json*
+ * refer to, such as the routine in `jal __strlen`, is defined by one of the libraries or by the backend
+ * itself. Returns a description of each problem found.
+ */
+pub fn check_libraries(table:&serde_json::Value, libraries:&[(&str, Vec<String>)]) -> Vec<String> {
+    let mut problems = vec![];
+    let mut defined:HashMap<String, &str> = HashMap::from([(mips::get_end_label(), "the backend")]);
+    for (name, lines) in libraries {
+        for (index, line) in lines.iter().enumerate() {
+            match parse_library_line(line) {
+                Ok(Some(label)) => {
+                    if let Some(other) = defined.insert(label.to_owned(), name) {
+                        problems.push(format!("{}.asm defines {} on line {}, which is already defined by {}", name, label, index + 1, other));
+                    }
+                },
+                Ok(None) => {},
+                Err(()) => problems.push(format!("Line {} of {}.asm is not a label or an instruction: {}", index + 1, name, line.trim()))
+            }
+        }
+    }
+
+    let mut referenced:BTreeMap<&str, String> = BTreeMap::new();
+    if let Some(instructions) = table["mips"].as_object() {
+        for (instr, entry) in instructions {
+            visit_templates(entry, &mut vec![], &mut |path, template| {
+                let lines = get_template_lines(template).unwrap_or_default();
+                let code = lines.iter().map(|line| line.split('#').next().unwrap());
+                for word in code.flat_map(|code| code.split(|c:char| c.is_whitespace() || c == ',')) {
+                    // labels built from an argument, such as the epilogue of the function, are made by the backend
+                    if word.starts_with(RESERVED_PREFIX) && !word.contains("{}") {
+                        referenced.entry(word).or_insert_with(|| [instr.as_str()].iter().chain(path).copied().collect::<Vec<_>>().join(" "));
+                    }
+                }
+            });
+        }
+    }
+
+    for (label, instr) in referenced {
+        if !defined.contains_key(label) {
+            problems.push(format!("The mips code for {} refers to {}, which no runtime library defines", instr, label));
+        }
+    }
+
+    problems
+}
+
+
+/**
+ * Returns a line for each architecture in *target_code.json* saying how many of the templates the backend can
+ * emit it has, followed by a line for each instruction it has code for listing the types, or pairs of types
+ * for casts, it has a version for.
+ */
+pub fn get_feature_matrix(table:&serde_json::Value, emitted:&BTreeSet<Vec<String>>) -> Vec<String> {
+    let mut lines = vec![];
+    for (architecture, instructions) in table.as_object().into_iter().flatten() {
+        let wanted:Vec<&Vec<String>> = emitted.iter().filter(|key| &key[0] == architecture).collect();
+        let present = wanted.iter().filter(|key| {
+            let op_types:Vec<&str> = key[2..].iter().map(String::as_str).collect();
+            mips::find_target_template(table, architecture, &key[1], &op_types).is_ok()
+        }).count();
+
+        lines.push(format!("{}: {} of the {} templates the backend can emit", architecture, present, wanted.len()));
+        let instructions = instructions.as_object().into_iter().flatten();
+        let width = instructions.clone().map(|(instr, _)| instr.len()).max().unwrap_or(0);
+        for (instr, entry) in instructions {
+            let mut versions = vec![];
+            visit_templates(entry, &mut vec![], &mut |path, _| versions.push(path.join("->")));
+
+            let versions = match versions.as_slice() {
+                [untyped] if untyped.is_empty() => String::from("-"),
+                _ => versions.join(" ")
+            };
+
+            lines.push(format!("    {:width$}  {}", instr, versions, width = width));
+        }
+    }
+
+    lines
+}
+
+
+/**
+ * Checks *target_code.json* and the runtime libraries for `iridescent doctor` without compiling a program,
+ * reading them afresh from disk so that a table which cannot be parsed is reported rather than stopping the
+ * compiler.
+ */
+pub fn run_doctor() -> DoctorReport {
+    let table:serde_json::Value = match fs::read_to_string(mips::TARGET_CODE_PATH) {
+        Ok(json) => match serde_json::from_str(&json) {
+            Ok(table) => table,
+            Err(err) => return DoctorReport {problems: vec![format!("Could not parse {}: {}", mips::TARGET_CODE_PATH, err)], features: vec![]}
+        },
+        Err(err) => return DoctorReport {problems: vec![format!("Could not read {}: {}", mips::TARGET_CODE_PATH, err)], features: vec![]}
+    };
+
+    let emitted = match find_emitted_templates() {
+        Ok(emitted) => emitted,
+        Err(err) => return DoctorReport {problems: vec![format!("Could not compile the coverage programs: {}", err)], features: vec![]}
+    };
+
+    let libraries:Vec<(&str, Vec<String>)> = mips::LIBRARIES.iter().map(|name| (*name, mips::add_library(name))).collect();
+    let mut problems = check_target_code(&table, &emitted);
+    problems.extend(check_libraries(&table, &libraries));
+
+    DoctorReport {problems, features: get_feature_matrix(&table, &emitted)}
+}
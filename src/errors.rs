@@ -1,13 +1,38 @@
-use std::{error::Error, fmt};
+use std::{cell::RefCell, error::Error, fmt, io, panic};
+
+use crate::frontend::ast::{PrintFormat, Type};
+use crate::frontend::semantics::RESERVED_PREFIX;
 
 
 #[derive(Debug)]
-pub struct SymbolNotFoundError(pub String);
+pub struct SymbolNotFoundError {
+    pub identifier: String,
+    pub function: Option<String>,
+    pub suggestion: Option<String>
+}
 impl Error for SymbolNotFoundError {}
 
+impl SymbolNotFoundError {
+    pub fn new(identifier:&str) -> SymbolNotFoundError {
+        SymbolNotFoundError {
+            identifier: identifier.to_owned(),
+            function: None,
+            suggestion: None
+        }
+    }
+}
+
 impl fmt::Display for SymbolNotFoundError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Could not find symbol {} in this scope.", self.0)
+        match &self.function {
+            Some(function) => write!(f, "Could not find symbol {} called in function {}.", self.identifier, function)?,
+            None => write!(f, "Could not find symbol {} in this scope.", self.identifier)?
+        }
+
+        match &self.suggestion {
+            Some(suggestion) => write!(f, " Did you mean `{}`?", suggestion),
+            None => Ok(())
+        }
     }
 }
 
@@ -45,6 +70,53 @@ impl fmt::Display for BadFunctionReturn {
 }
 
 
+#[derive(Debug)]
+pub struct MissingReturnValue(pub String);
+impl Error for MissingReturnValue {}
+
+impl fmt::Display for MissingReturnValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function {} does not have the void return type, so every return statement in it must return a value.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnexpectedReturnValue(pub String);
+impl Error for UnexpectedReturnValue {}
+
+impl fmt::Display for UnexpectedReturnValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function {} has the void return type, so its return statements cannot return a value.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NoIntrinsicOverload {
+    pub function: String,
+    pub argument_type: Type
+}
+impl Error for NoIntrinsicOverload {}
+
+impl fmt::Display for NoIntrinsicOverload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "There is no version of intrinsic function {} which takes arguments of type {}.", self.function, self.argument_type)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnusedIntrinsicResult(pub String);
+impl Error for UnusedIntrinsicResult {}
+
+impl fmt::Display for UnusedIntrinsicResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The result of intrinsic function {} is not used, so the call does nothing.", self.0)
+    }
+}
+
+
 #[derive(Debug)]
 pub struct ImmutableReassignmentError(pub String);
 impl Error for ImmutableReassignmentError {}
@@ -54,3 +126,819 @@ impl fmt::Display for ImmutableReassignmentError {
         write!(f, "Cannot reassign constant variable {}", self.0)
     }
 }
+
+
+#[derive(Debug)]
+pub struct RecursiveInlineFunction(pub String);
+impl Error for RecursiveInlineFunction {}
+
+impl fmt::Display for RecursiveInlineFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function {} is marked @inline but is recursive, so cannot be inlined.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct ImpureFunction {
+    pub function: String,
+    pub reason: String
+}
+impl Error for ImpureFunction {}
+
+impl fmt::Display for ImpureFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function {} is marked @pure but {}.", self.function, self.reason)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct DuplicateSymbolError {
+    pub identifier: String,
+    pub existing_kind: String,
+    pub existing_location: String,
+    pub existing_line: Option<usize>,
+    pub duplicate_kind: String,
+    pub duplicate_location: String,
+    pub duplicate_line: Option<usize>
+}
+impl Error for DuplicateSymbolError {}
+
+/**
+ * Describes where a symbol is defined, giving the line if it is known.
+ */
+fn describe_definition(location:&str, line:Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{} on line {}", location, line),
+        None => location.to_owned()
+    }
+}
+
+impl fmt::Display for DuplicateSymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "Duplicate identifier {}: {} in {} is already defined as a {} in {}.", self.identifier,
+            self.duplicate_kind, describe_definition(&self.duplicate_location, self.duplicate_line),
+            self.existing_kind, describe_definition(&self.existing_location, self.existing_line)
+        )
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UseBeforeDeclaration {
+    pub identifier: String,
+    pub declared_line: Option<usize>,
+    pub used_line: Option<usize>
+}
+impl Error for UseBeforeDeclaration {}
+
+impl fmt::Display for UseBeforeDeclaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Variable {} is used", self.identifier)?;
+        if let Some(used_line) = self.used_line {
+            write!(f, " on line {}", used_line)?;
+        }
+
+        match self.declared_line {
+            Some(declared_line) => write!(f, " before it is declared on line {}.", declared_line),
+            None => write!(f, " before it is declared.")
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NonConstantStaticInitialiser {
+    pub identifier: String,
+    pub line: usize
+}
+impl Error for NonConstantStaticInitialiser {}
+
+impl fmt::Display for NonConstantStaticInitialiser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "Static variable {} on line {} must be initialised with a literal or a cast of one, as its value is set before the program runs.", 
+            self.identifier, self.line
+        )
+    }
+}
+
+
+#[derive(Debug)]
+pub struct InvalidRepeatCount {
+    pub found: Type,
+    pub line: usize
+}
+impl Error for InvalidRepeatCount {}
+
+impl fmt::Display for InvalidRepeatCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The number of times the repeat loop on line {} repeats must be an int or long, not {}.", self.line, self.found)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct EmptyInput;
+impl Error for EmptyInput {}
+
+impl fmt::Display for EmptyInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Input must read at least 1 character.")
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NonBooleanCondition(pub Type);
+impl Error for NonBooleanCondition {}
+
+impl fmt::Display for NonBooleanCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Conditions must be of type bool, not {}.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct IndefLoopWithoutBreak;
+impl Error for IndefLoopWithoutBreak {}
+
+impl fmt::Display for IndefLoopWithoutBreak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Indefinite loop must contain a break statement which can run, as it has no condition to end it.")
+    }
+}
+
+
+#[derive(Debug)]
+pub struct MismatchedTernaryArms(pub Type, pub Type);
+impl Error for MismatchedTernaryArms {}
+
+impl fmt::Display for MismatchedTernaryArms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "Both arms of a ternary expression must have the same type, found {} and {}.", 
+            self.0, self.1
+        )
+    }
+}
+
+
+#[derive(Debug)]
+pub struct VoidValueUsed {
+    pub function: String,
+    pub context: String
+}
+impl Error for VoidValueUsed {}
+
+impl fmt::Display for VoidValueUsed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The call {}() returns void, so its value cannot be used in {}.", self.function, self.context)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct ReservedIdentifier {
+    pub identifier: String,
+    pub line: Option<usize>
+}
+impl Error for ReservedIdentifier {}
+
+impl fmt::Display for ReservedIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.identifier.starts_with(RESERVED_PREFIX) {
+            true => write!(f, "Identifier {} is invalid, as identifiers starting with {} are reserved for the compiler", self.identifier, RESERVED_PREFIX)?,
+            false => write!(f, "Identifier {} is invalid, as it is a type name, keyword or literal", self.identifier)?
+        }
+
+        match self.line {
+            Some(line) => write!(f, " on line {}.", line),
+            None => write!(f, ".")
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub construct: String,
+    pub problem: String,
+    pub line: usize,
+    pub column: usize,
+    pub end: (usize, usize),
+    pub source_line: String
+}
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not parse {} on line {}: {}.", self.construct, self.line, self.problem)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct SyntaxErrors {
+    pub count: usize
+}
+impl Error for SyntaxErrors {}
+
+impl fmt::Display for SyntaxErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not compile the program, as {} syntax errors were found in it.", self.count)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NestingTooDeep {
+    pub limit: usize,
+    pub line: usize,
+    pub column: usize,
+    pub source_line: String
+}
+impl Error for NestingTooDeep {}
+
+impl fmt::Display for NestingTooDeep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The bracket on line {} is nested more than {} levels deep, which is the most the compiler allows. ", self.line, self.limit)?;
+        write!(f, "Store part of the expression in a variable, or raise the limit with --max-nesting.")
+    }
+}
+
+
+#[derive(Debug)]
+pub struct ChainedComparison {
+    pub operator: &'static str
+}
+impl Error for ChainedComparison {}
+
+impl fmt::Display for ChainedComparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The {} comparison is applied to the result of another comparison, but comparisons cannot be chained. ", self.operator)?;
+        write!(f, "Compare each pair of values separately and join them with &&, such as ((a, b)<, (b, c)<)&& instead of ((a, b)<, c)<.")
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnsupportedOperatorType {
+    pub operator: &'static str,
+    pub operand_type: Type,
+    pub line: Option<usize>
+}
+impl Error for UnsupportedOperatorType {}
+
+impl fmt::Display for UnsupportedOperatorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The {} operator cannot be applied to values of type {}", self.operator, self.operand_type)?;
+        match self.line {
+            Some(line) => write!(f, " on line {}.", line),
+            None => write!(f, ".")
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct MismatchedOperandTypes {
+    pub operator: &'static str,
+    pub lhs_type: Type,
+    pub rhs_type: Type,
+    pub line: Option<usize>
+}
+impl Error for MismatchedOperandTypes {}
+
+impl fmt::Display for MismatchedOperandTypes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The {} operator cannot be applied to values of types {} and {}", self.operator, self.lhs_type, self.rhs_type)?;
+        match self.line {
+            Some(line) => write!(f, " on line {}, as both must have the same type.", line),
+            None => write!(f, ", as both must have the same type.")
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnsupportedCast {
+    pub from: Type,
+    pub into: Type,
+    pub line: usize
+}
+impl Error for UnsupportedCast {}
+
+impl fmt::Display for UnsupportedCast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The cast on line {} cannot convert values of type {} to {}.", self.line, self.from, self.into)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NotIterable {
+    pub identifier: String,
+    pub value_type: Type,
+    pub line: usize
+}
+impl Error for NotIterable {}
+
+impl fmt::Display for NotIterable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The for each loop on line {} cannot iterate over {}, as it is of type {} and only strings can be iterated over.",
+               self.line, self.identifier, self.value_type)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct MismatchedElementType {
+    pub element_type: Type,
+    pub iterable_type: Type,
+    pub line: usize
+}
+impl Error for MismatchedElementType {}
+
+impl fmt::Display for MismatchedElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The for each loop on line {} declares its element as {}, but the elements of a {} are of type char.",
+               self.line, self.element_type, self.iterable_type)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnprintableValue {
+    pub value_type: Type,
+    pub format: PrintFormat
+}
+impl Error for UnprintableValue {}
+
+impl fmt::Display for UnprintableValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.format, &self.value_type) {
+            (PrintFormat::Plain, Type::Float) => write!(f, "Floats must be printed to a number of decimal places, such as x:2."),
+            (PrintFormat::Plain, _) => write!(f, "Values of type {} cannot be printed, only strings, chars, bytes and ints can.", self.value_type),
+            (PrintFormat::Hex, _) => write!(f, "Values of type {} cannot be printed in hex, only bytes and ints can.", self.value_type),
+            (PrintFormat::Precision(_), _) => write!(f, "Values of type {} cannot be printed to a number of decimal places, only floats can.", self.value_type)
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct InvalidOperandType {
+    pub instruction: String,
+    pub operand_type: Type
+}
+impl Error for InvalidOperandType {}
+
+impl InvalidOperandType {
+    pub fn new(instruction:&str, operand_type:Type) -> InvalidOperandType {
+        InvalidOperandType {
+            instruction: instruction.to_owned(),
+            operand_type
+        }
+    }
+}
+
+impl fmt::Display for InvalidOperandType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Internal compiler error: the {} instruction cannot be applied to type {}, but this was not caught before code generation. This is a bug in the compiler.", 
+            self.instruction, self.operand_type)
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+pub struct MissingTargetTemplate {
+    pub architecture: String,
+    pub instruction: String,
+    pub op_type: Option<String>,
+    pub available: Vec<String>
+}
+impl Error for MissingTargetTemplate {}
+
+impl fmt::Display for MissingTargetTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Internal compiler error: target_code.json has no {} code for the {} instruction", self.architecture, self.instruction)?;
+        if let Some(op_type) = &self.op_type {
+            write!(f, " on {}", op_type)?;
+            match self.available.is_empty() {
+                true => write!(f, ", and has no version of it for any type")?,
+                false => write!(f, ", only on {}", self.available.join(", "))?
+            }
+        }
+
+        write!(f, ". This is a bug in the compiler.")
+    }
+}
+
+
+#[derive(Debug)]
+pub struct UnbalancedCall {
+    pub function: String,
+    pub expected: usize,
+    pub found: usize
+}
+impl Error for UnbalancedCall {}
+
+impl fmt::Display for UnbalancedCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Internal compiler error: the call to {} needs {} arguments on the evaluation stack, but found {}. This is a bug in the compiler.",
+            self.function, self.expected, self.found)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct InternalCompilerError(pub String);
+impl Error for InternalCompilerError {}
+
+impl fmt::Display for InternalCompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Internal compiler error: {}. This is a bug in the compiler.", self.0)
+    }
+}
+
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct MalformedIntermediate {
+    pub line: usize,
+    pub text: String,
+    pub reason: String
+}
+impl Error for MalformedIntermediate {}
+
+impl fmt::Display for MalformedIntermediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not read the intermediate code `{}` on line {}: {}.", self.text, self.line, self.reason)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct InputFileError {
+    pub path: String,
+    pub reason: Option<String>
+}
+impl Error for InputFileError {}
+
+impl InputFileError {
+    /**
+     * Creates the error for an input file which could not be read, keeping the reason unless it is simply
+     * that the file does not exist.
+     */
+    pub fn new(path:&str, reason:&io::Error) -> InputFileError {
+        InputFileError {
+            path: path.to_owned(),
+            reason: (reason.kind() != io::ErrorKind::NotFound).then(|| reason.to_string())
+        }
+    }
+}
+
+impl fmt::Display for InputFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "Could not read input file {}: {}.", self.path, reason),
+            None => write!(f, "No such file: {}.", self.path)
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct OutputFileError {
+    pub path: String,
+    pub reason: String
+}
+impl Error for OutputFileError {}
+
+impl OutputFileError {
+    pub fn new(path:&str, reason:&dyn Error) -> OutputFileError {
+        OutputFileError {
+            path: path.to_owned(),
+            reason: reason.to_string()
+        }
+    }
+}
+
+impl fmt::Display for OutputFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not write output file {}: {}.", self.path, self.reason)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct OutputOverwritesInput(pub String);
+impl Error for OutputOverwritesInput {}
+
+impl fmt::Display for OutputOverwritesInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Refusing to write output to {} as it is the input file.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct InvalidOption(pub String);
+impl Error for InvalidOption {}
+
+impl fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct SizeBudgetExceeded {
+    pub total: u64,
+    pub budget: u64
+}
+impl Error for SizeBudgetExceeded {}
+
+impl fmt::Display for SizeBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The program takes up {} bytes of code and data, which is {} bytes over the size budget of {} bytes.", self.total, self.total - self.budget, self.budget)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct StackSizeTooLarge {
+    pub stack_size: u64,
+    pub data_size: u64,
+    pub limit: u64
+}
+impl Error for StackSizeTooLarge {}
+
+impl fmt::Display for StackSizeTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "A stack of {} bytes makes the data section {} bytes, but only {} bytes fit before the heap.", self.stack_size, self.data_size, self.limit)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct NoSourceFiles(pub String);
+impl Error for NoSourceFiles {}
+
+impl fmt::Display for NoSourceFiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not find any .iri files in {}.", self.0)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct MissingMainFunction(pub String);
+impl Error for MissingMainFunction {}
+
+impl fmt::Display for MissingMainFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "None of the files in {} declare a main function.", self.0)
+    }
+}
+
+
+/**
+ * An error found in one file of a program built from a directory, which is reported with the path of the
+ * file it is in.
+ */
+#[derive(Debug)]
+pub struct InFile {
+    pub path: String,
+    pub error: Box<dyn Error>
+}
+impl Error for InFile {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+impl fmt::Display for InFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.error)
+    }
+}
+
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    DivisionByZero(String),
+    StackUnderflow,
+    UninitialisedVariable(usize),
+    InvalidParameter(String, usize),
+    UnknownFunction(String),
+    WrongArgumentCount(String, usize, usize),
+    UnknownLabel(String),
+    UnknownStatic(String),
+    TypeMismatch(String),
+    IndexOutOfRange(i32, usize),
+    InvalidAllocation(i32),
+    BudgetExceeded(String),
+    InputFailed(String),
+    OutputFailed(String)
+}
+impl Error for RuntimeError {}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::DivisionByZero(function) => write!(f, "Division by zero in function {}.", function),
+            RuntimeError::StackUnderflow => write!(f, "Tried to pop from an empty stack."),
+            RuntimeError::UninitialisedVariable(id) => write!(f, "Variable {} was read before it was stored.", id),
+            RuntimeError::InvalidParameter(function, index) => write!(f, "Function {} has no parameter at index {}.", function, index),
+            RuntimeError::UnknownFunction(function) => write!(f, "Could not find function {}.", function),
+            RuntimeError::WrongArgumentCount(function, expected, found) => {
+                write!(f, "Function {} takes {} arguments, but was called with {}.", function, expected, found)
+            },
+            RuntimeError::UnknownLabel(label) => write!(f, "Could not find label {}.", label),
+            RuntimeError::UnknownStatic(label) => write!(f, "Could not find static variable {}.", label),
+            RuntimeError::TypeMismatch(message) => write!(f, "Type mismatch: {}.", message),
+            RuntimeError::IndexOutOfRange(index, length) => write!(f, "Index {} is out of range for a string of length {}.", index, length),
+            RuntimeError::InvalidAllocation(bytes) => write!(f, "Cannot allocate {} bytes.", bytes),
+            RuntimeError::BudgetExceeded(budget) => write!(f, "The program was stopped as it went over its limit of {}.", budget),
+            RuntimeError::InputFailed(message) => write!(f, "Could not read input: {}.", message),
+            RuntimeError::OutputFailed(message) => write!(f, "Could not write output: {}.", message)
+        }
+    }
+}
+
+
+
+/**
+ * Represents a problem with a program which does not stop it from compiling, but is probably a mistake.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum Warning {
+    ConditionAlwaysTrue {construct: &'static str, line: usize},
+    ConditionAlwaysFalse {construct: &'static str, line: usize},
+    CastTruncatesValue {into: String, before: String, after: String, line: usize},
+    EmptyLoopBody {construct: &'static str, line: usize},
+    UnusedExpression {line: usize},
+    UnreachableBranch {line: usize, earlier_line: usize},
+    DuplicateCondition {line: usize, earlier_line: usize},
+    UnreachableFunction {identifier: String},
+    InputOverwritten {identifier: String, line: usize, earlier_line: usize},
+    LoopNeverBreaks {line: usize},
+    ShadowsVariable {identifier: String, line: usize, outer_line: Option<usize>}
+}
+
+impl Warning {
+    /**
+     * Returns the code identifying the kind of warning in diagnostics.
+     */
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            Warning::ConditionAlwaysTrue {..} => "condition-always-true",
+            Warning::ConditionAlwaysFalse {..} => "condition-always-false",
+            Warning::CastTruncatesValue {..} => "cast-truncates-value",
+            Warning::EmptyLoopBody {..} => "empty-loop-body",
+            Warning::UnusedExpression {..} => "unused-expression",
+            Warning::UnreachableBranch {..} => "unreachable-branch",
+            Warning::DuplicateCondition {..} => "duplicate-condition",
+            Warning::UnreachableFunction {..} => "unreachable-function",
+            Warning::InputOverwritten {..} => "input-overwritten",
+            Warning::LoopNeverBreaks {..} => "loop-never-breaks",
+            Warning::ShadowsVariable {..} => "shadows-variable"
+        }
+    }
+
+
+    /**
+     * Returns the line of the program the warning is about, or `None` if it is about a whole function. Warnings
+     * about two branches of an if statement give the line of the later branch, and say which earlier branch it
+     * conflicts with in their message.
+     */
+    pub fn get_line(&self) -> Option<usize> {
+        match self {
+            Warning::ConditionAlwaysTrue {line, ..}
+              | Warning::ConditionAlwaysFalse {line, ..}
+              | Warning::CastTruncatesValue {line, ..}
+              | Warning::EmptyLoopBody {line, ..}
+              | Warning::UnusedExpression {line}
+              | Warning::UnreachableBranch {line, ..}
+              | Warning::DuplicateCondition {line, ..}
+              | Warning::InputOverwritten {line, ..}
+              | Warning::LoopNeverBreaks {line}
+              | Warning::ShadowsVariable {line, ..} => Some(*line),
+            Warning::UnreachableFunction {..} => None
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::ConditionAlwaysTrue {construct, line} => write!(f, "The condition of the {} on line {} is always true.", construct, line),
+            Warning::ConditionAlwaysFalse {construct, line} => write!(f, "The condition of the {} on line {} is always false.", construct, line),
+            Warning::CastTruncatesValue {into, before, after, line} => {
+                write!(f, "The cast on line {} changes {} to {} when converting it to {}.", line, before, after, into)
+            },
+            Warning::EmptyLoopBody {construct, line} => {
+                write!(f, "The {} on line {} has an empty body, so it either does nothing or never ends.", construct, line)
+            },
+            Warning::UnusedExpression {line} => {
+                write!(f, "The value of the expression on line {} is discarded, and working it out has no side effects.", line)
+            },
+            Warning::UnreachableBranch {line, earlier_line} => {
+                write!(f, "The branch on line {} can never run, as the condition of the branch on line {} is always true.", line, earlier_line)
+            },
+            Warning::DuplicateCondition {line, earlier_line} => {
+                write!(f, "The condition of the branch on line {} is the same as that of the branch on line {}, so it can never run.", line, earlier_line)
+            },
+            Warning::UnreachableFunction {identifier} => {
+                write!(f, "The function {} is never called by main or any function main calls, so it is never run.", identifier)
+            },
+            Warning::InputOverwritten {identifier, line, earlier_line} => {
+                write!(f, "The input on line {} overwrites the input stored in {} on line {} before it is next read, as every input shares one buffer. Pass --distinct-input-buffers to give each input its own.", line, identifier, earlier_line)
+            },
+            Warning::LoopNeverBreaks {line} => {
+                write!(f, "The indefinite loop starting on line {} never ends, as the constant conditions guarding its breaks mean none of them can run.", line)
+            },
+            Warning::ShadowsVariable {identifier, line, outer_line: Some(outer_line)} => {
+                write!(f, "The variable {} declared on line {} hides the variable of the same name declared on line {}, which cannot be used until the end of the block.", identifier, line, outer_line)
+            },
+            Warning::ShadowsVariable {identifier, line, outer_line: None} => {
+                write!(f, "The variable {} declared on line {} hides the parameter of the same name, which cannot be used until the end of the block.", identifier, line)
+            }
+        }
+    }
+}
+
+
+thread_local! {
+    static COMPILATION_CONTEXT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+
+/**
+ * Records what the compiler is currently working on, such as the function and instruction being compiled, so
+ * that a panic from a bug in the compiler can say where it happened. The previous context is restored when
+ * this is dropped, so contexts can be nested.
+ */
+pub struct CompilationContext {
+    previous: Option<String>
+}
+
+impl CompilationContext {
+    pub fn enter(description:String) -> CompilationContext {
+        let previous = COMPILATION_CONTEXT.with(|context| context.replace(Some(description)));
+        CompilationContext {previous}
+    }
+}
+
+impl Drop for CompilationContext {
+    fn drop(&mut self) {
+        COMPILATION_CONTEXT.with(|context| *context.borrow_mut() = self.previous.take());
+    }
+}
+
+
+/**
+ * Returns the message reported for a panic raised while compiling the given context, including where in the
+ * compiler it was raised if that is known.
+ */
+pub fn describe_internal_error(context:&str, message:&str, location:Option<String>) -> String {
+    let mut description = format!("internal compiler error while compiling {}: {}", context, message);
+    if let Some(location) = location {
+        description.push_str(&format!("\n  raised at {}", location));
+    }
+
+    description.push_str("\nThis is a bug in the compiler, please report it along with the program being compiled.");
+    description
+}
+
+
+/**
+ * Installs a panic hook which reports panics raised while a `CompilationContext` is active as internal
+ * compiler errors, saying what was being compiled and asking the user to report the bug. Panics raised
+ * outside of any context are reported by the default hook as before.
+ */
+pub fn install_internal_error_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let context = match COMPILATION_CONTEXT.with(|context| context.borrow().clone()) {
+            Some(context) => context,
+            None => return default_hook(info)
+        };
+
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.to_owned(),
+                None => "unknown error".to_owned()
+            }
+        };
+
+        eprintln!("{}", describe_internal_error(&context, &message, info.location().map(|location| location.to_string())));
+    }));
+}
@@ -1,4 +1,6 @@
 pub mod parser;
 pub mod ast;
 pub mod semantics;
+pub mod trace;
 pub mod intermediate_gen;
+pub mod call_graph;
@@ -1,4 +1,5 @@
-use std::string::ToString;
+use std::fmt;
+use std::num::ParseIntError;
 
 
 /**
@@ -17,19 +18,25 @@ pub enum Type {
     String
 }
 
-impl ToString for Type {
-    fn to_string(&self) -> String {
-        match self {
-            Type::Void => String::from("void"),
-            Type::Byte => String::from("byte"),
-            Type::Integer => String::from("int"),
-            Type::Long => String::from("long"),
-            Type::Char => String::from("char"),
-            Type::Boolean => String::from("bool"),
-            Type::Float => String::from("float"),
-            Type::Double => String::from("double"),
-            Type::String => String::from("string")
-        }
+/**
+ * Types are displayed by the names they are written with in source code, which are also the keys used for
+ * them in *target_code.json*.
+ */
+impl fmt::Display for Type {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Void => "void",
+            Type::Byte => "byte",
+            Type::Integer => "int",
+            Type::Long => "long",
+            Type::Char => "char",
+            Type::Boolean => "bool",
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::String => "string"
+        };
+
+        write!(f, "{}", name)
     }
 }
 
@@ -97,6 +104,42 @@ pub enum BooleanConnector {
 }
 
 
+/**
+ * Represents the math functions built into the language, along with `alloc` which gives out heap memory, which
+ * are called in the same way as user functions.
+ */
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Intrinsic {
+    Abs,
+    Min,
+    Max,
+    Pow,
+    Round,
+    Floor,
+    Ceil,
+    Alloc
+}
+
+
+/**
+ * Represents how a term in a print statement is written out, where `Hex` is chosen with the `:hex` suffix
+ * and prints an integer in hexadecimal, and `Precision` is chosen with a number suffix such as `:2` and prints
+ * a float truncated to that many decimal places.
+ */
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum PrintFormat {
+    Plain,
+    Hex,
+    Precision(u8)
+}
+
+
+/**
+ * The most decimal places a float can be printed to, as a float only holds about 7 significant digits.
+ */
+pub const MAX_PRINT_PRECISION:u8 = 6;
+
+
 /**
  * Represents the mutability of a variable.
  */
@@ -107,11 +150,28 @@ pub enum Mutability {
 }
 
 
+/**
+ * Represents where a variable is kept. `Frame` variables get a fresh copy in each call of their function,
+ * while `Static` variables are declared with `let static`, live in the data section and are initialised once
+ * when the program starts, so they keep their value between calls.
+ */
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Storage {
+    Frame,
+    Static
+}
+
+
 /**
  * Represents a node in the AST, including information about the node such as:
  *  - identifier
  *  - literal value
  *  - datatype
+ *
+ * Fields named `resolved_type`, and the other `Option<Type>` fields set alongside them, are `None` when the
+ * parser builds the node and are filled in by `semantic_validation`, which returns the decorated AST. Code
+ * generation reads the types it needs from them rather than working them out again, so it always agrees with
+ * the types semantic analysis checked.
  */
 #[derive(PartialEq, Debug, Clone)]
 pub enum ASTNode {
@@ -120,28 +180,42 @@ pub enum ASTNode {
         identifier: String,
         parameters: Vec<ASTNode>,
         statements: Vec<ASTNode>,
-        scope: usize
+        scope: usize,
+        inline: bool,
+        pure: bool,
+        file: usize,
+        line: usize
     },
 
     Parameter {
         param_type: Type,
-        identifier: String
+        identifier: String,
+        line: usize
     },
 
     ReturnStatement {
-        expression: Box<ASTNode>
+        expression: Option<Box<ASTNode>>
     },
 
     VarDeclStatement {
         var_type: Type,
         mutability: Mutability,
+        storage: Storage,
         identifier: String,
-        value: Box<ASTNode>
+        value: Box<ASTNode>,
+        line: usize
     },
 
     VarAssignStatement {
         identifier: String,
-        value: Box<ASTNode>
+        value: Box<ASTNode>,
+        line: usize
+    },
+
+    ExpressionStatement {
+        expression: Box<ASTNode>,
+        line: usize,
+        resolved_type: Option<Type>
     },
 
     Expression {
@@ -190,7 +264,8 @@ pub enum ASTNode {
     IfStatement {
         condition: Box<ASTNode>,
         statements: Vec<ASTNode>,
-        scope: usize
+        scope: usize,
+        line: usize
     },
 
     ElseStatement {
@@ -200,7 +275,9 @@ pub enum ASTNode {
 
     TypeCast {
         from: Box<ASTNode>,
-        into: Type
+        into: Type,
+        line: usize,
+        resolved_type: Option<Type>
     },
 
     IndefLoop {
@@ -211,24 +288,40 @@ pub enum ASTNode {
     WhileLoop {
         condition: Box<ASTNode>,
         statements: Vec<ASTNode>,
-        scope: usize
+        scope: usize,
+        line: usize
     },
 
     ForLoop {
         control_type: Type,
+        control_mutability: Mutability,
         control_identifier: String,
         control_initial: Box<ASTNode>,
         limit: Box<ASTNode>,
         step: Box<ASTNode>,
         statements: Vec<ASTNode>,
-        scope: usize
+        scope: usize,
+        line: usize,
+        resolved_type: Option<Type>
+    },
+
+    ForEachLoop {
+        element_type: Type,
+        element_identifier: String,
+        iterable: Box<ASTNode>,
+        statements: Vec<ASTNode>,
+        scope: usize,
+        line: usize
     },
 
     PrintStatement {
-        terms: Vec<ASTNode>
+        terms: Vec<(ASTNode, PrintFormat)>,
+        resolved_types: Option<Vec<Type>>
     },
 
-    InputStatement(usize),
+    InputStatement(Type, usize),
+    SizeOf(String),
+    TypeOf(String),
     Identifier(String),
     Break,
     Continue
@@ -236,167 +329,499 @@ pub enum ASTNode {
 
 
 /**
- * Takes a string representing a primitive type and returns `Type` struct object representing it.
+ * Returns the name of the kind of node given, without any of its children, for use in error messages.
+ */
+pub fn get_node_name(node:&ASTNode) -> &'static str {
+    match node {
+        ASTNode::Function {..} => "function",
+        ASTNode::Parameter {..} => "parameter",
+        ASTNode::ReturnStatement {..} => "return statement",
+        ASTNode::VarDeclStatement {..} => "variable declaration",
+        ASTNode::VarAssignStatement {..} => "variable assignment",
+        ASTNode::ExpressionStatement {..} => "expression statement",
+        ASTNode::Expression {..} => "expression",
+        ASTNode::Term {..} => "term",
+        ASTNode::Value {..} => "value",
+        ASTNode::FunctionCall {..} => "function call",
+        ASTNode::BooleanTerm {..} => "boolean term",
+        ASTNode::BooleanExpression {..} => "boolean expression",
+        ASTNode::TernaryExpression {..} => "ternary expression",
+        ASTNode::IfElifElseStatement {..} => "if statement",
+        ASTNode::IfStatement {..} => "if statement",
+        ASTNode::ElseStatement {..} => "else statement",
+        ASTNode::TypeCast {..} => "type cast",
+        ASTNode::SizeOf(_) => "sizeof operator",
+        ASTNode::TypeOf(_) => "typeof operator",
+        ASTNode::IndefLoop {..} => "indefinite loop",
+        ASTNode::WhileLoop {..} => "while loop",
+        ASTNode::ForLoop {control_identifier, ..} if is_repeat_control(control_identifier) => "repeat loop",
+        ASTNode::ForLoop {..} => "for loop",
+        ASTNode::ForEachLoop {..} => "for each loop",
+        ASTNode::PrintStatement {..} => "print statement",
+        ASTNode::InputStatement(..) => "input statement",
+        ASTNode::Identifier(_) => "identifier",
+        ASTNode::Break => "break statement",
+        ASTNode::Continue => "continue statement"
+    }
+}
+
+
+/**
+ * The start of the name of the hidden control variable of the for loop a repeat loop is turned into, which is
+ * followed by the scope of the loop. Identifiers cannot contain `$`, so it never clashes with a variable in
+ * the program.
+ */
+pub const REPEAT_CONTROL_PREFIX:&str = "$repeat_";
+
+
+/**
+ * Returns true if the given control variable of a for loop is the hidden one of a repeat loop.
+ */
+pub fn is_repeat_control(control_identifier:&str) -> bool {
+    control_identifier.starts_with(REPEAT_CONTROL_PREFIX)
+}
+
+
+/**
+ * Returns true if the limit of a for loop gives the same value on every iteration, in which case it only
+ * needs to be evaluated once before the loop starts. This is the case when the limit calls no functions,
+ * reads no input, and uses neither the control variable nor any variable assigned in the body of the loop.
+ */
+pub fn is_loop_invariant(limit:&ASTNode, control_identifier:&str, statements:&Vec<ASTNode>) -> bool {
+    let mut assigned = vec![control_identifier.to_string()];
+    for statement in statements {
+        get_assigned_identifiers(statement, &mut assigned);
+    }
+
+    is_pure_expression(limit, &assigned)
+}
+
+
+/**
+ * Returns true if the expression calls no functions, reads no input and uses none of the given identifiers.
+ */
+fn is_pure_expression(node:&ASTNode, assigned:&Vec<String>) -> bool {
+    match node {
+        ASTNode::FunctionCall {..} | ASTNode::InputStatement(..) => false,
+        ASTNode::Identifier(identifier) => !assigned.contains(identifier),
+        ASTNode::Value {..} | ASTNode::SizeOf(_) | ASTNode::TypeOf(_) => true,
+
+        ASTNode::Term {child} => is_pure_expression(child, assigned),
+        ASTNode::TypeCast {from, ..} => is_pure_expression(from, assigned),
+
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            is_pure_expression(lhs, assigned) && rhs.as_ref().is_none_or(|rhs| is_pure_expression(rhs, assigned))
+        },
+
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            is_pure_expression(condition, assigned)
+              && is_pure_expression(if_true, assigned)
+              && is_pure_expression(if_false, assigned)
+        },
+
+        _ => false
+    }
+}
+
+
+/**
+ * Returns true if evaluating the expression may do more than give a value, which is the case when it calls a
+ * function or reads input.
+ */
+pub fn has_side_effects(expression:&ASTNode) -> bool {
+    !is_pure_expression(expression, &vec![])
+}
+
+
+/**
+ * Returns true if the two expressions are written the same way, ignoring the lines they are on, so that the
+ * same condition copied onto another line is found to be the same.
+ */
+pub fn is_same_expression(a:&ASTNode, b:&ASTNode) -> bool {
+    let is_same_rhs = |a:&Option<Box<ASTNode>>, b:&Option<Box<ASTNode>>| match (a, b) {
+        (Some(a), Some(b)) => is_same_expression(a, b),
+        (a, b) => a.is_none() && b.is_none()
+    };
+
+    match (a, b) {
+        (ASTNode::Expression {lhs: a_lhs, operator: a_operator, rhs: a_rhs}, ASTNode::Expression {lhs: b_lhs, operator: b_operator, rhs: b_rhs}) => {
+            a_operator == b_operator && is_same_expression(a_lhs, b_lhs) && is_same_rhs(a_rhs, b_rhs)
+        },
+
+        (ASTNode::BooleanTerm {lhs: a_lhs, operator: a_operator, rhs: a_rhs}, ASTNode::BooleanTerm {lhs: b_lhs, operator: b_operator, rhs: b_rhs}) => {
+            a_operator == b_operator && is_same_expression(a_lhs, b_lhs) && is_same_rhs(a_rhs, b_rhs)
+        },
+
+        (ASTNode::BooleanExpression {lhs: a_lhs, operator: a_operator, connector: a_connector, rhs: a_rhs},
+         ASTNode::BooleanExpression {lhs: b_lhs, operator: b_operator, connector: b_connector, rhs: b_rhs}) => {
+            a_operator == b_operator && a_connector == b_connector && is_same_expression(a_lhs, b_lhs) && is_same_rhs(a_rhs, b_rhs)
+        },
+
+        (ASTNode::TernaryExpression {condition: a_condition, if_true: a_true, if_false: a_false},
+         ASTNode::TernaryExpression {condition: b_condition, if_true: b_true, if_false: b_false}) => {
+            is_same_expression(a_condition, b_condition) && is_same_expression(a_true, b_true) && is_same_expression(a_false, b_false)
+        },
+
+        (ASTNode::FunctionCall {identifier: a_identifier, arguments: a_arguments}, ASTNode::FunctionCall {identifier: b_identifier, arguments: b_arguments}) => {
+            a_identifier == b_identifier && a_arguments.len() == b_arguments.len()
+                && a_arguments.iter().zip(b_arguments).all(|(a, b)| is_same_expression(a, b))
+        },
+
+        (ASTNode::TypeCast {from: a_from, into: a_into, ..}, ASTNode::TypeCast {from: b_from, into: b_into, ..}) => {
+            a_into == b_into && is_same_expression(a_from, b_from)
+        },
+
+        (ASTNode::Term {child: a_child}, ASTNode::Term {child: b_child}) => is_same_expression(a_child, b_child),
+        (a, b) => a == b
+    }
+}
+
+
+/**
+ * Walks this section of the AST and adds the identifier of every variable assigned within it to `assigned`,
+ * including those assigned in nested blocks and loops.
+ */
+fn get_assigned_identifiers(node:&ASTNode, assigned:&mut Vec<String>) {
+    match node {
+        ASTNode::VarAssignStatement {identifier, ..} => assigned.push(identifier.to_string()),
+
+        ASTNode::IfElifElseStatement {statements}
+          | ASTNode::IfStatement {statements, ..}
+          | ASTNode::ElseStatement {statements, ..}
+          | ASTNode::IndefLoop {statements, ..}
+          | ASTNode::WhileLoop {statements, ..} => {
+            for statement in statements {
+                get_assigned_identifiers(statement, assigned);
+            }
+        },
+
+        ASTNode::ForLoop {control_identifier: assigned_identifier, statements, ..}
+          | ASTNode::ForEachLoop {element_identifier: assigned_identifier, statements, ..} => {
+            assigned.push(assigned_identifier.to_string());
+            for statement in statements {
+                get_assigned_identifiers(statement, assigned);
+            }
+        },
+
+        _ => {}
+    }
+}
+
+
+/**
+ * Returns the first and last lines of the statements which record the line they are on, including those in
+ * nested blocks and loops, or `None` if none of them do. Statements such as returns and prints do not record
+ * their line, so the range may not cover every line of the block.
+ */
+pub fn get_line_range(statements:&[ASTNode]) -> Option<(usize, usize)> {
+    statements.iter().fold(None, |range, statement| {
+        let (line, nested) = match statement {
+            ASTNode::VarDeclStatement {line, ..}
+              | ASTNode::VarAssignStatement {line, ..}
+              | ASTNode::ExpressionStatement {line, ..} => (Some(*line), None),
+            ASTNode::IfStatement {statements, line, ..}
+              | ASTNode::WhileLoop {statements, line, ..}
+              | ASTNode::ForLoop {statements, line, ..}
+              | ASTNode::ForEachLoop {statements, line, ..} => (Some(*line), get_line_range(statements)),
+            ASTNode::IfElifElseStatement {statements}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..} => (None, get_line_range(statements)),
+            _ => (None, None)
+        };
+
+        [range, line.map(|line| (line, line)), nested].into_iter().flatten().reduce(|(first, last), (start, end)| {
+            (first.min(start), last.max(end))
+        })
+    })
+}
+
+
+/**
+ * Takes a string representing a primitive type and returns `Type` struct object representing it, or `None`
+ * if it is not the name of a type.
  * 
  * ### Examples
  * `assert_eq!("int", Type::Integer)`
  * 
  * `assert_eq!("void", Type::Void)`
  */
-pub fn get_type_from_string(type_str:&str) -> Type {
+pub fn get_type_from_string(type_str:&str) -> Option<Type> {
     match type_str {
-        "void" => Type::Void,
-        "byte" => Type::Byte,
-        "int" => Type::Integer,
-        "bool" => Type::Boolean,
-        "long" => Type::Long,
-        "char" => Type::Char,
-        "float" => Type::Float,
-        "double" => Type::Double, 
-        "string" => Type::String,
-        _ => panic!("Unknown type {}", type_str)
+        "void" => Some(Type::Void),
+        "byte" => Some(Type::Byte),
+        "int" => Some(Type::Integer),
+        "bool" => Some(Type::Boolean),
+        "long" => Some(Type::Long),
+        "char" => Some(Type::Char),
+        "float" => Some(Type::Float),
+        "double" => Some(Type::Double),
+        "string" => Some(Type::String),
+        _ => None
     } 
 }
 
 
+/**
+ * Returns the number of bytes a value of the given type takes up in a frame, as given by `sizeof`, or `None`
+ * for void. Bytes, chars and bools take a whole word, and strings are stored as a pointer.
+ */
+pub fn get_type_size(value_type:&Type) -> Option<i32> {
+    match value_type {
+        Type::Void => None,
+        Type::Long | Type::Double => Some(8),
+        _ => Some(4)
+    }
+}
+
+
 /**
  * Takes a string representing a boolean operator and returns a `BooleanOperator` struct object
- * representing it.
+ * representing it, or `None` if it is not a boolean operator.
  * 
  * ### Examples
  * `assert_eq!(">=", BooleanOperator::GreaterOrEqual)`
  */
-pub fn get_boolean_operator_from_str(operator_str:&str) -> BooleanOperator {
+pub fn get_boolean_operator_from_str(operator_str:&str) -> Option<BooleanOperator> {
     match operator_str {
-        "==" => BooleanOperator::Equal,
-        "!=" => BooleanOperator::NotEqual,
-        ">" => BooleanOperator::Greater,
-        ">=" => BooleanOperator::GreaterOrEqual,
-        "<" => BooleanOperator::Less,
-        "<=" => BooleanOperator::LessOrEqual,
-        "!" => BooleanOperator::Invert,
-        _ => panic!("Unknown boolean operator {}", operator_str)
+        "==" => Some(BooleanOperator::Equal),
+        "!=" => Some(BooleanOperator::NotEqual),
+        ">" => Some(BooleanOperator::Greater),
+        ">=" => Some(BooleanOperator::GreaterOrEqual),
+        "<" => Some(BooleanOperator::Less),
+        "<=" => Some(BooleanOperator::LessOrEqual),
+        "!" => Some(BooleanOperator::Invert),
+        _ => None
     }
 }
 
 
 /**
  * Takes a string representing a boolean connector and returns a `BooleanConnector` struct object
- * representing it.
+ * representing it, or `None` if it is not a boolean connector.
  * 
  * ### Examples
  * `assert_eq!("&&", BooleanConnector::And)`
  */
-pub fn get_boolean_connector_from_str(connector_str:&str) -> BooleanConnector {
+pub fn get_boolean_connector_from_str(connector_str:&str) -> Option<BooleanConnector> {
     match connector_str {
-        "&&" => BooleanConnector::And,
-        "||" => BooleanConnector::Or,
-        "^^" => BooleanConnector::XOr,
-        _ => panic!("Unknown boolean connector {}", connector_str)
+        "&&" => Some(BooleanConnector::And),
+        "||" => Some(BooleanConnector::Or),
+        "^^" => Some(BooleanConnector::XOr),
+        _ => None
     }
 }
 
 
 /**
  * Takes a string representing a unary operator and returns an `Operator` struct object 
- * representing it.
+ * representing it, or `None` if it is not an operator.
  * 
  * ### Examples
  * `assert_eq!("!", Type::LogicalNegation)`
  */
-pub fn get_unary_operator_from_str(operator_str:&str) -> Operator {
+pub fn get_unary_operator_from_str(operator_str:&str) -> Option<Operator> {
     match operator_str {
-        "!" => Operator::NegateLogical,
-        "-" => Operator::NegateNumerical,
-        "~" => Operator::Complement,
-        _ => panic!("Unknown operator {}", operator_str)
+        "!" => Some(Operator::NegateLogical),
+        "-" => Some(Operator::NegateNumerical),
+        "~" => Some(Operator::Complement),
+        _ => None
+    }
+}
+
+
+/**
+ * Takes an operator and returns the symbol used for it in a program, such as `+` for addition.
+ */
+pub fn get_operator_symbol(operator:&Operator) -> &'static str {
+    match operator {
+        Operator::NegateNumerical | Operator::Subtraction => "-",
+        Operator::NegateLogical => "!",
+        Operator::Complement => "~",
+        Operator::Addition => "+",
+        Operator::Multiplication => "*",
+        Operator::Division => "/",
+        Operator::And => "&",
+        Operator::Or => "|",
+        Operator::XOr => "^",
+        Operator::LeftShiftLogical => "<<",
+        Operator::RightShiftArithmetic => ">>",
+        Operator::RightShiftLogical => ">>>"
+    }
+}
+
+
+/**
+ * Takes a boolean operator and returns the symbol used for it in a program, such as `<=` for less than or
+ * equal to.
+ */
+pub fn get_boolean_operator_symbol(operator:&BooleanOperator) -> &'static str {
+    match operator {
+        BooleanOperator::Equal => "==",
+        BooleanOperator::NotEqual => "!=",
+        BooleanOperator::Greater => ">",
+        BooleanOperator::GreaterOrEqual => ">=",
+        BooleanOperator::Less => "<",
+        BooleanOperator::LessOrEqual => "<=",
+        BooleanOperator::Invert => "!"
+    }
+}
+
+
+/**
+ * Takes a boolean connector and returns the symbol used for it in a program, such as `&&` for and.
+ */
+pub fn get_boolean_connector_symbol(connector:&BooleanConnector) -> &'static str {
+    match connector {
+        BooleanConnector::And => "&&",
+        BooleanConnector::Or => "||",
+        BooleanConnector::XOr => "^^"
     }
 }
 
 
 /**
  * Takes a string representing a unary operator and returns an `Operator` struct object 
- * representing it.
+ * representing it, or `None` if it is not an operator.
  * 
  * ### Examples
  * `assert_eq!("+", Type::Addition)`
  * 
  * `assert_eq!("-", Type::Subtraction)`
  */
-pub fn get_binary_operator_from_str(operator_str:&str) -> Operator {
+pub fn get_binary_operator_from_str(operator_str:&str) -> Option<Operator> {
     match operator_str {
-        "+" => Operator::Addition,
-        "-" => Operator::Subtraction,
-        "*" => Operator::Multiplication,
-        "/" => Operator::Division,
-        "&" => Operator::And,
-        "|" => Operator::Or,
-        "^" => Operator::XOr,
-        "<<" => Operator::LeftShiftLogical,
-        ">>>" => Operator::RightShiftArithmetic,
-        ">>" => Operator::RightShiftLogical,
-        _ => panic!("Unknown operator {}", operator_str)
+        "+" => Some(Operator::Addition),
+        "-" => Some(Operator::Subtraction),
+        "*" => Some(Operator::Multiplication),
+        "/" => Some(Operator::Division),
+        "&" => Some(Operator::And),
+        "|" => Some(Operator::Or),
+        "^" => Some(Operator::XOr),
+        "<<" => Some(Operator::LeftShiftLogical),
+        ">>>" => Some(Operator::RightShiftArithmetic),
+        ">>" => Some(Operator::RightShiftLogical),
+        _ => None
+    }
+}
+
+
+/**
+ * Takes the identifier of a function and returns the `Intrinsic` it refers to, or `None` if it is not the
+ * name of an intrinsic function.
+ * 
+ * ### Examples
+ * `assert_eq!(get_intrinsic_from_str("abs"), Some(Intrinsic::Abs))`
+ * 
+ * `assert_eq!(get_intrinsic_from_str("fibonacci"), None)`
+ */
+pub fn get_intrinsic_from_str(identifier:&str) -> Option<Intrinsic> {
+    match identifier {
+        "abs" => Some(Intrinsic::Abs),
+        "min" => Some(Intrinsic::Min),
+        "max" => Some(Intrinsic::Max),
+        "pow" => Some(Intrinsic::Pow),
+        "round" => Some(Intrinsic::Round),
+        "floor" => Some(Intrinsic::Floor),
+        "ceil" => Some(Intrinsic::Ceil),
+        "alloc" => Some(Intrinsic::Alloc),
+        _ => None
     }
 }
 
 
 /**
  * Takes a string representing a mutability modifier of mutable or constant and returns the corresponding
- * representation from the `Mutability` enum.
+ * representation from the `Mutability` enum, or `None` if it is neither.
  * 
  * ### Examples
  * `assert_eq!("mut", Mutability::Mutabile)`
  * 
  * `assert_eq!("const", Mutability::Constant)`
  */
-pub fn get_mutability_from_str(mutability_str:&str) -> Mutability {
+pub fn get_mutability_from_str(mutability_str:&str) -> Option<Mutability> {
     match mutability_str {
-        "mut" => Mutability::Mutable,
-        "const" => Mutability::Constant,
-        _ => panic!("Unknown mutability modifier {}", mutability_str)
+        "mut" => Some(Mutability::Mutable),
+        "const" => Some(Mutability::Constant),
+        _ => None
     }
 }
 
 
 /**
  * Takes a string representing a number in decimal, binary (prefix "0b"), or hexadecimal (prefix "0x") and
- * returns the corresponding number.
+ * returns the corresponding number, or an error if the number does not fit in an i64.
  * 
  * ### Examples
- * `assert_eq!(get_int_from_str_literal("0xFA"), 250);`
+ * `assert_eq!(get_int_from_str_literal("0xFA"), Ok(250));`
  * 
- * `assert_eq!(get_int_from_str_literal("0b1101"), 13);`
+ * `assert_eq!(get_int_from_str_literal("0b1101"), Ok(13));`
  * 
- * `assert_eq!(get_int_from_str_literal("20"), 20);`
+ * `assert_eq!(get_int_from_str_literal("20"), Ok(20));`
  */
-pub fn get_int_from_str_literal(literal:&str) -> i64 {
+pub fn get_int_from_str_literal(literal:&str) -> Result<i64, ParseIntError> {
     let mut literal = literal;
     if literal.ends_with("l") | literal.ends_with("b") {
         literal = &literal[0..literal.len() - 1];
     };
 
-    if literal.starts_with("0b") {
-        return i64::from_str_radix(&literal[2..], 2).unwrap();
-    } else if literal.starts_with("0x") {
-        return i64::from_str_radix(&literal[2..], 16).unwrap();
+    if let Some(binary) = literal.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else if let Some(hexadecimal) = literal.strip_prefix("0x") {
+        i64::from_str_radix(hexadecimal, 16)
     } else {
-        return literal.parse().unwrap();
+        literal.parse()
     }
 }
 
 
 /**
- * Takes a string of either "true" or "false" and returns the corresponding boolean value.
+ * Takes a string of either "true" or "false" and returns the corresponding boolean value, or `None` if it
+ * is neither.
  * 
  * ### Examples
- * `assert_eq!(get_bool_from_str_literal("true"), true);`
+ * `assert_eq!(get_bool_from_str_literal("true"), Some(true));`
  * 
- * `assert_eq!(get_bool_from_str_literal("false"), false);`
+ * `assert_eq!(get_bool_from_str_literal("false"), Some(false));`
  */
-pub fn get_bool_from_str_literal(literal:&str) -> bool {
+pub fn get_bool_from_str_literal(literal:&str) -> Option<bool> {
     match literal {
-        "true" => true,
-        "false" => false,
-        _ => panic!("Invalid boolean literal {}", literal)
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None
+    }
+}
+
+
+/**
+ * Takes a char literal including its quotes and returns the char it stands for, or `None` if it uses an
+ * escape which does not exist. The escapes are `\n`, `\t`, `\r`, `\0`, `\\`, `\'` and `\"`, along with
+ * `\x` followed by two hex digits giving the code of the char.
+ * 
+ * ### Examples
+ * `assert_eq!(get_char_from_str_literal("'a'"), Some('a'));`
+ * 
+ * `assert_eq!(get_char_from_str_literal("'\\x41'"), Some('A'));`
+ */
+pub fn get_char_from_str_literal(literal:&str) -> Option<char> {
+    let inner = literal.strip_prefix('\'')?.strip_suffix('\'')?;
+    let escape = match inner.strip_prefix('\\') {
+        Some(escape) => escape,
+        None => {
+            let mut chars = inner.chars();
+            return chars.next().filter(|_| chars.next().is_none());
+        }
+    };
+
+    match escape {
+        "n" => Some('\n'),
+        "t" => Some('\t'),
+        "r" => Some('\r'),
+        "0" => Some('\0'),
+        "\\" => Some('\\'),
+        "'" => Some('\''),
+        "\"" => Some('"'),
+        _ => u8::from_str_radix(escape.strip_prefix('x').filter(|code| code.len() == 2)?, 16).ok().map(char::from)
     }
 }
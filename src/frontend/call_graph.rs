@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::Warning;
+use super::ast::ASTNode;
+use super::semantics::get_called_functions;
+
+
+/**
+ * Represents which functions of a program call which others. Calls to intrinsics are left out, unless the
+ * program declares a function of the same name which takes precedence over the intrinsic.
+ */
+#[derive(Debug)]
+pub struct CallGraph {
+    functions: Vec<String>,
+    calls: HashMap<String, Vec<String>>
+}
+
+impl CallGraph {
+    /**
+     * Builds the call graph of the program from every function call in the body of each function, in any
+     * position such as an argument, a condition or a loop header. Each function is only listed once in the
+     * calls of another, however many times it is called there.
+     */
+    pub fn new(root:&[ASTNode]) -> CallGraph {
+        let functions:Vec<String> = root.iter().filter_map(|node| match node {
+            ASTNode::Function {identifier, ..} => Some(identifier.to_owned()),
+            _ => None
+        }).collect();
+
+        let mut calls = HashMap::new();
+        for node in root {
+            if let ASTNode::Function {identifier, ..} = node {
+                let mut called = vec![];
+                get_called_functions(node, &mut called);
+
+                let mut callees:Vec<String> = vec![];
+                for callee in called {
+                    if functions.contains(&callee) && !callees.contains(&callee) {
+                        callees.push(callee);
+                    }
+                }
+
+                calls.insert(identifier.to_owned(), callees);
+            }
+        }
+
+        CallGraph {functions, calls}
+    }
+
+
+    /**
+     * Returns every function in the program, in the order they are declared.
+     */
+    pub fn get_functions(&self) -> &[String] {
+        &self.functions
+    }
+
+
+    /**
+     * Returns the functions the given function calls directly, in the order they are first called.
+     */
+    pub fn get_calls(&self, identifier:&str) -> &[String] {
+        self.calls.get(identifier).map_or(&[], Vec::as_slice)
+    }
+
+
+    /**
+     * Returns each cycle of recursive functions, being the functions which can each call all of the others
+     * and themselves, either directly or through other functions. A function which only calls itself is a
+     * cycle of its own. The cycles, and the functions in each, are in the order the functions are declared.
+     */
+    pub fn get_cycles(&self) -> Vec<Vec<&str>> {
+        let mut cycles:Vec<Vec<&str>> = vec![];
+        for function in &self.functions {
+            let callees = self.get_callees(function);
+            if !callees.contains(function.as_str()) || cycles.iter().flatten().any(|member| member == function) {
+                continue;
+            }
+
+            cycles.push(self.functions.iter()
+                .map(String::as_str)
+                .filter(|callee| callees.contains(callee) && self.get_callees(callee).contains(function.as_str()))
+                .collect());
+        }
+
+        cycles
+    }
+
+
+    /**
+     * Returns every function which can be called from the given function, either directly or through other
+     * functions. The function itself is only included if it is recursive.
+     */
+    fn get_callees(&self, identifier:&str) -> HashSet<&str> {
+        let mut callees = HashSet::new();
+        let mut pending:Vec<&str> = self.calls.get(identifier).map_or(vec![], |calls| calls.iter().map(String::as_str).collect());
+        while let Some(callee) = pending.pop() {
+            if callees.insert(callee) {
+                pending.extend(self.calls.get(callee).into_iter().flatten().map(String::as_str));
+            }
+        }
+
+        callees
+    }
+
+
+    /**
+     * Returns every function which `main` can call, along with `main` itself, or `None` if the program has no
+     * `main` function.
+     */
+    pub fn get_reachable(&self) -> Option<HashSet<&str>> {
+        if !self.functions.iter().any(|function| function == "main") {
+            return None;
+        }
+
+        let mut reachable = self.get_callees("main");
+        reachable.insert("main");
+        Some(reachable)
+    }
+
+
+    /**
+     * Takes some of the top-level nodes of the program this graph was built from, such as those of one of its
+     * files, and returns a warning for each function among them which `main` never calls, either directly or
+     * through other functions.
+     */
+    pub fn find_unreachable_functions(&self, nodes:&[ASTNode]) -> Vec<Warning> {
+        let reachable = match self.get_reachable() {
+            Some(reachable) => reachable,
+            None => return vec![]
+        };
+
+        nodes.iter().filter_map(|node| match node {
+            ASTNode::Function {identifier, ..} if !reachable.contains(identifier.as_str()) => {
+                Some(Warning::UnreachableFunction {identifier: identifier.to_owned()})
+            },
+            _ => None
+        }).collect()
+    }
+
+
+    /**
+     * Returns the graph in the Graphviz dot language, with a node for each function and an edge from each
+     * function to each function it calls. Recursive functions and the calls which make up their cycles are
+     * drawn in red, and functions which `main` never calls are drawn dashed.
+     */
+    pub fn to_dot(&self) -> String {
+        let reachable = self.get_reachable();
+        let mut dot = String::from("digraph calls {\n");
+        for function in &self.functions {
+            let mut attributes = vec![];
+            if self.get_callees(function).contains(function.as_str()) {
+                attributes.push("color=red");
+            }
+
+            if reachable.as_ref().is_some_and(|reachable| !reachable.contains(function.as_str())) {
+                attributes.push("style=dashed");
+            }
+
+            match attributes.is_empty() {
+                true => dot.push_str(&format!("    \"{}\";\n", function)),
+                false => dot.push_str(&format!("    \"{}\" [{}];\n", function, attributes.join(", ")))
+            }
+        }
+
+        for function in &self.functions {
+            for callee in &self.calls[function] {
+                match self.get_callees(callee).contains(function.as_str()) {
+                    true => dot.push_str(&format!("    \"{}\" -> \"{}\" [color=red];\n", function, callee)),
+                    false => dot.push_str(&format!("    \"{}\" -> \"{}\";\n", function, callee))
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
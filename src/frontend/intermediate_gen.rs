@@ -1,15 +1,17 @@
 use super::ast::*;
-use crate::frontend::semantics::SymbolTable;
+use crate::frontend::semantics::{evaluate_constant_term, SymbolTable, SymbolTableRow, RESERVED_PREFIX};
+use crate::errors::{CompilationContext, InternalCompilerError, MalformedIntermediate};
 
 use std::fmt;
+use std::str::FromStr;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::{Cell, RefCell};
 
 
 /**
  * Represents possible arguments to intermediate code instrs
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Argument {
     Byte(u8),
     Integer(i32),
@@ -25,7 +27,7 @@ pub enum Argument {
 /**
  * Used to represent the instruction set of the intermediate code language
  */
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum IntermediateInstr {
     Add,
     Sub,
@@ -49,53 +51,456 @@ pub enum IntermediateInstr {
     LessEqual,
     Equal,
     NotEqual,
-    Out,
-    In(usize),
+    Out(Type, PrintFormat),
+    In(Type, usize),
     LoadParam(Type, usize), // not implemented
     Jump(String),
     JumpZero(String),
-    Call(String, Type),
+    TableSwitch(i32, Vec<String>, String), // lowest value, label for each value from it, label for any other value
+    Call(String, Type, usize),
     Push(Type, Argument),
     Load(Type, usize),
     Store(Type, usize),
+    DeclareStatic(Type, String, Argument),
+    LoadStatic(Type, String),
+    StoreStatic(Type, String),
     Return(Type),
+    Trap(String),
     FuncStart(String),
     FuncEnd(String),
     Label(String),
     Cast(Type, Type), // not implemented
+    Pop(Type),
+    Abs,
+    Min,
+    Max,
+    Pow,
+    Round,
+    Floor,
+    Ceil,
+    StrLen,
+    StrIndex,
+    CheckIndex(String),
+    Alloc
 }
 
+impl fmt::Display for Argument {
+    /**
+     * Formats the argument in the same way as a literal in a program, so the type of the value is clear.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Argument::Byte(value) => write!(f, "{}b", value),
+            Argument::Integer(value) => write!(f, "{}", value),
+            Argument::Long(value) => write!(f, "{}l", value),
+            Argument::Boolean(value) => write!(f, "{}", value),
+            Argument::Char(value) => write!(f, "{:?}", value),
+            Argument::Float(value) => write!(f, "{:?}", value),
+            Argument::Double(value) => write!(f, "{:?}d", value),
+            Argument::String(value) => write!(f, "{:?}", value)
+        }
+    }
+}
+
+
+/**
+ * Returns the name an instruction is written with, such as `push` or `jz`.
+ */
+fn get_instr_mnemonic(instr:&IntermediateInstr) -> &'static str {
+    match instr {
+        IntermediateInstr::Add => "add",
+        IntermediateInstr::Sub => "sub",
+        IntermediateInstr::Div => "div",
+        IntermediateInstr::Mult => "mult",
+        IntermediateInstr::BitwiseAnd => "and",
+        IntermediateInstr::BitwiseOr => "or",
+        IntermediateInstr::BitwiseXor => "xor",
+        IntermediateInstr::Complement => "complement",
+        IntermediateInstr::LogicNeg => "logic_neg",
+        IntermediateInstr::LogicAnd => "logic_and",
+        IntermediateInstr::LogicOr => "logic_or",
+        IntermediateInstr::LogicXor => "logic_xor",
+        IntermediateInstr::LeftShiftLogical => "sll",
+        IntermediateInstr::RightShiftArithmetic => "sra",
+        IntermediateInstr::RightShiftLogical => "srl",
+        IntermediateInstr::NumNeg => "neg",
+        IntermediateInstr::GreaterThan => "gt",
+        IntermediateInstr::LessThan => "lt",
+        IntermediateInstr::GreaterEqual => "ge",
+        IntermediateInstr::LessEqual => "le",
+        IntermediateInstr::Equal => "eq",
+        IntermediateInstr::NotEqual => "ne",
+        IntermediateInstr::Out(..) => "out",
+        IntermediateInstr::In(..) => "in",
+        IntermediateInstr::LoadParam(..) => "load_param",
+        IntermediateInstr::Jump(_) => "jmp",
+        IntermediateInstr::JumpZero(_) => "jz",
+        IntermediateInstr::TableSwitch(..) => "table_switch",
+        IntermediateInstr::Call(..) => "call",
+        IntermediateInstr::Push(..) => "push",
+        IntermediateInstr::Load(..) => "load",
+        IntermediateInstr::Store(..) => "store",
+        IntermediateInstr::DeclareStatic(..) => "static",
+        IntermediateInstr::LoadStatic(..) => "load_static",
+        IntermediateInstr::StoreStatic(..) => "store_static",
+        IntermediateInstr::Return(_) => "return",
+        IntermediateInstr::Trap(_) => "trap",
+        IntermediateInstr::FuncStart(_) => "func_start",
+        IntermediateInstr::FuncEnd(_) => "func_end",
+        IntermediateInstr::Label(_) => "label",
+        IntermediateInstr::Cast(..) => "cast",
+        IntermediateInstr::Pop(_) => "pop",
+        IntermediateInstr::Abs => "abs",
+        IntermediateInstr::Min => "min",
+        IntermediateInstr::Max => "max",
+        IntermediateInstr::Pow => "pow",
+        IntermediateInstr::Round => "round",
+        IntermediateInstr::Floor => "floor",
+        IntermediateInstr::Ceil => "ceil",
+        IntermediateInstr::StrLen => "str_len",
+        IntermediateInstr::StrIndex => "str_index",
+        IntermediateInstr::CheckIndex(_) => "check_index",
+        IntermediateInstr::Alloc => "alloc"
+    }
+}
+
+
+/**
+ * The instructions which take no operands, which are read back from intermediate code by their mnemonic.
+ */
+const OPERANDLESS_INSTRS:[IntermediateInstr; 32] = [
+    IntermediateInstr::Add, IntermediateInstr::Sub, IntermediateInstr::Div, IntermediateInstr::Mult,
+    IntermediateInstr::BitwiseAnd, IntermediateInstr::BitwiseOr, IntermediateInstr::BitwiseXor, IntermediateInstr::Complement,
+    IntermediateInstr::LogicNeg, IntermediateInstr::LogicAnd, IntermediateInstr::LogicOr, IntermediateInstr::LogicXor,
+    IntermediateInstr::LeftShiftLogical, IntermediateInstr::RightShiftArithmetic, IntermediateInstr::RightShiftLogical,
+    IntermediateInstr::NumNeg, IntermediateInstr::GreaterThan, IntermediateInstr::LessThan, IntermediateInstr::GreaterEqual,
+    IntermediateInstr::LessEqual, IntermediateInstr::Equal, IntermediateInstr::NotEqual, IntermediateInstr::Abs,
+    IntermediateInstr::Min, IntermediateInstr::Max, IntermediateInstr::Pow, IntermediateInstr::Round, IntermediateInstr::Floor,
+    IntermediateInstr::Ceil, IntermediateInstr::StrLen, IntermediateInstr::StrIndex, IntermediateInstr::Alloc
+];
+
+
+/**
+ * Returns the short name a type is written with in intermediate code, which gives the size of the value
+ * where it has one, such as `i32` for an int or `f64` for a double.
+ */
+fn get_ir_type_name(value_type:&Type) -> &'static str {
+    match value_type {
+        Type::Void => "void",
+        Type::Byte => "u8",
+        Type::Integer => "i32",
+        Type::Long => "i64",
+        Type::Char => "char",
+        Type::Boolean => "bool",
+        Type::Float => "f32",
+        Type::Double => "f64",
+        Type::String => "str"
+    }
+}
+
+
+/**
+ * Takes the short name of a type in intermediate code and returns the type it names.
+ */
+fn parse_ir_type(name:&str) -> Result<Type, String> {
+    match name {
+        "void" => Ok(Type::Void),
+        "u8" => Ok(Type::Byte),
+        "i32" => Ok(Type::Integer),
+        "i64" => Ok(Type::Long),
+        "char" => Ok(Type::Char),
+        "bool" => Ok(Type::Boolean),
+        "f32" => Ok(Type::Float),
+        "f64" => Ok(Type::Double),
+        "str" => Ok(Type::String),
+        other => Err(format!("{} is not a type", other))
+    }
+}
+
+
+/**
+ * Takes a string or char written between the given quotes with Rust's escapes, as `Debug` writes them, and
+ * returns its value without the quotes or escapes.
+ */
+fn parse_quoted(text:&str, quote:char) -> Result<String, String> {
+    let invalid = || format!("{} is not a valid quoted value", text);
+    let inner = text.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)).ok_or_else(invalid)?;
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(next) = chars.next() {
+        if next != '\\' {
+            value.push(next);
+            continue;
+        }
+
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(escaped @ ('\\' | '"' | '\'')) => escaped,
+            Some('u') => {
+                // written as \u{XXXX}, so the opening brace is skipped and the code runs up to the closing one
+                let code:String = chars.by_ref().skip(1).take_while(|next| *next != '}').collect();
+                u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).ok_or_else(invalid)?
+            },
+            _ => return Err(invalid())
+        };
+
+        value.push(escaped);
+    }
+
+    Ok(value)
+}
+
+
+impl FromStr for Argument {
+    type Err = String;
+
+    /**
+     * Reads an argument written in the format given by its `Display`, where the suffix or quotes around the
+     * value give which kind of argument it is.
+     */
+    fn from_str(text:&str) -> Result<Argument, String> {
+        let invalid = || format!("{} is not a valid value", text);
+        if text.starts_with('"') {
+            return parse_quoted(text, '"').map(Argument::String);
+        }
+
+        if text.starts_with('\'') {
+            let value = parse_quoted(text, '\'')?;
+            let mut chars = value.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(value), None) => Ok(Argument::Char(value)),
+                _ => Err(invalid())
+            };
+        }
+
+        if let Ok(value) = text.parse::<bool>() {
+            return Ok(Argument::Boolean(value));
+        }
+
+        if let Some(value) = text.strip_suffix('b') {
+            return value.parse().map(Argument::Byte).map_err(|_| invalid());
+        }
+
+        if let Some(value) = text.strip_suffix('l') {
+            return value.parse().map(Argument::Long).map_err(|_| invalid());
+        }
+
+        if let Some(value) = text.strip_suffix('d') {
+            return value.parse().map(Argument::Double).map_err(|_| invalid());
+        }
+
+        match text.parse::<i32>() {
+            Ok(value) => Ok(Argument::Integer(value)),
+            Err(_) => text.parse().map(Argument::Float).map_err(|_| invalid())
+        }
+    }
+}
+
+
 impl fmt::Display for IntermediateInstr {
+    /**
+     * Formats the instruction in an assembly-like format, as its mnemonic followed by its operands. The type
+     * an instruction works on is given after a dot in its short form, such as `push.i32 5`, `store.f64 %3`
+     * for a local variable, and `call fib/1 -> i32` for a call taking one argument. Functions are separated
+     * by blank lines and labels are written on their own, such as `__l_4:`, with every other instruction indented.
+     * The format can be read back with `parse_intermediate`, and is stable, as the IR snapshot tests compare
+     * against it.
+     */
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic = get_instr_mnemonic(self);
         match self {
-            IntermediateInstr::FuncStart(_) => write!(f, "\n\n{:?}", self),
-            IntermediateInstr::FuncEnd(_) => write!(f, "{:?}", self),
+            IntermediateInstr::FuncStart(name) => write!(f, "\n\n{} {}", mnemonic, name),
+            IntermediateInstr::FuncEnd(name) => write!(f, "{} {}", mnemonic, name),
             IntermediateInstr::Label(label) => write!(f, "\n{}:", label),
-            _ => write!(f, "    {:?}", self)
+            IntermediateInstr::Out(out_type, PrintFormat::Plain) => write!(f, "    {}.{}", mnemonic, get_ir_type_name(out_type)),
+            IntermediateInstr::Out(out_type, PrintFormat::Hex) => write!(f, "    {}.{} hex", mnemonic, get_ir_type_name(out_type)),
+            IntermediateInstr::Out(out_type, PrintFormat::Precision(places)) => write!(f, "    {}.{} {}", mnemonic, get_ir_type_name(out_type), places),
+            IntermediateInstr::In(Type::Char, _) => write!(f, "    {}.char", mnemonic),
+            IntermediateInstr::In(in_type, length) => write!(f, "    {}.{} {}", mnemonic, get_ir_type_name(in_type), length),
+            IntermediateInstr::Jump(label) | IntermediateInstr::JumpZero(label) => write!(f, "    {} {}", mnemonic, label),
+            IntermediateInstr::TableSwitch(low, labels, default) => {
+                write!(f, "    {} {} [{}] else {}", mnemonic, low, labels.join(", "), default)
+            },
+
+            IntermediateInstr::Call(name, return_type, argument_count) => {
+                write!(f, "    {} {}/{} -> {}", mnemonic, name, argument_count, get_ir_type_name(return_type))
+            },
+
+            IntermediateInstr::Push(push_type, value) => write!(f, "    {}.{} {}", mnemonic, get_ir_type_name(push_type), value),
+            IntermediateInstr::LoadParam(value_type, index) => write!(f, "    {}.{} {}", mnemonic, get_ir_type_name(value_type), index),
+            IntermediateInstr::Load(value_type, index)
+              | IntermediateInstr::Store(value_type, index) => write!(f, "    {}.{} %{}", mnemonic, get_ir_type_name(value_type), index),
+            IntermediateInstr::DeclareStatic(value_type, label, value) => {
+                write!(f, "    {}.{} {} {}", mnemonic, get_ir_type_name(value_type), label, value)
+            },
+
+            IntermediateInstr::LoadStatic(value_type, label)
+              | IntermediateInstr::StoreStatic(value_type, label) => write!(f, "    {}.{} {}", mnemonic, get_ir_type_name(value_type), label),
+            IntermediateInstr::Return(value_type) | IntermediateInstr::Pop(value_type) => {
+                write!(f, "    {}.{}", mnemonic, get_ir_type_name(value_type))
+            },
+
+            IntermediateInstr::Cast(from, into) => write!(f, "    {} {} -> {}", mnemonic, get_ir_type_name(from), get_ir_type_name(into)),
+            IntermediateInstr::Trap(message) => write!(f, "    {} {:?}", mnemonic, message),
+            IntermediateInstr::CheckIndex(function) => write!(f, "    {} {}", mnemonic, function),
+            _ => write!(f, "    {}", mnemonic)
+        }
+    }
+}
+
+
+impl FromStr for IntermediateInstr {
+    type Err = String;
+
+    /**
+     * Reads a single instruction written in the format given by its `Display`, ignoring the indentation.
+     * Returns a description of the problem if the line is not an instruction.
+     */
+    fn from_str(line:&str) -> Result<IntermediateInstr, String> {
+        let line = line.trim();
+        if let Some(label) = line.strip_suffix(':').filter(|label| !label.contains(char::is_whitespace)) {
+            return Ok(IntermediateInstr::Label(label.to_owned()));
+        }
+
+        let (head, operands) = line.split_once(' ').unwrap_or((line, ""));
+        let (mnemonic, value_type) = match head.split_once('.') {
+            Some((mnemonic, type_name)) => (mnemonic, Some(parse_ir_type(type_name)?)),
+            None => (head, None)
+        };
+
+        let parse_index = |text:&str| text.parse::<usize>().map_err(|_| format!("{} is not a valid index", text));
+        let parse_name = |text:&str| match text.is_empty() || text.contains(char::is_whitespace) {
+            true => Err(format!("{} expects a single name", mnemonic)),
+            false => Ok(text.to_owned())
+        };
+
+        match (mnemonic, value_type) {
+            ("out", Some(out_type)) => match operands {
+                "" => Ok(IntermediateInstr::Out(out_type, PrintFormat::Plain)),
+                "hex" => Ok(IntermediateInstr::Out(out_type, PrintFormat::Hex)),
+                other => match other.parse::<u8>() {
+                    Ok(places) => Ok(IntermediateInstr::Out(out_type, PrintFormat::Precision(places))),
+                    Err(_) => Err(format!("{} is not a print format", other))
+                }
+            },
+
+            ("in", Some(Type::Char)) if operands.is_empty() => Ok(IntermediateInstr::In(Type::Char, 1)),
+            ("in", Some(in_type)) => Ok(IntermediateInstr::In(in_type, parse_index(operands)?)),
+            ("load_param", Some(value_type)) => Ok(IntermediateInstr::LoadParam(value_type, parse_index(operands)?)),
+            ("load", Some(value_type)) | ("store", Some(value_type)) => {
+                let index = parse_index(operands.strip_prefix('%').ok_or_else(|| format!("{} expects a local such as %0", mnemonic))?)?;
+                match mnemonic {
+                    "load" => Ok(IntermediateInstr::Load(value_type, index)),
+                    _ => Ok(IntermediateInstr::Store(value_type, index))
+                }
+            },
+
+            ("push", Some(push_type)) => Ok(IntermediateInstr::Push(push_type, operands.parse()?)),
+            ("static", Some(value_type)) => {
+                let (label, value) = operands.split_once(' ').ok_or("static expects a label and a value")?;
+                Ok(IntermediateInstr::DeclareStatic(value_type, parse_name(label)?, value.parse()?))
+            },
+
+            ("load_static", Some(value_type)) => Ok(IntermediateInstr::LoadStatic(value_type, parse_name(operands)?)),
+            ("store_static", Some(value_type)) => Ok(IntermediateInstr::StoreStatic(value_type, parse_name(operands)?)),
+            ("return", Some(value_type)) if operands.is_empty() => Ok(IntermediateInstr::Return(value_type)),
+            ("pop", Some(value_type)) if operands.is_empty() => Ok(IntermediateInstr::Pop(value_type)),
+            ("jmp", None) => Ok(IntermediateInstr::Jump(parse_name(operands)?)),
+            ("jz", None) => Ok(IntermediateInstr::JumpZero(parse_name(operands)?)),
+            ("table_switch", None) => {
+                let (low, rest) = operands.split_once(" [").ok_or("table_switch expects the lowest value and a list of labels")?;
+                let (labels, default) = rest.split_once("] else ").ok_or("table_switch expects a default label after else")?;
+                let labels:Result<Vec<String>, String> = labels.split(", ").map(parse_name).collect();
+                let low = low.parse().map_err(|_| format!("{} is not a valid value", low))?;
+                Ok(IntermediateInstr::TableSwitch(low, labels?, parse_name(default)?))
+            },
+
+            ("call", None) => {
+                let (callee, return_type) = operands.split_once(" -> ").ok_or("call expects a return type after ->")?;
+                let (name, argument_count) = callee.rsplit_once('/').ok_or("call expects the number of arguments after /")?;
+                Ok(IntermediateInstr::Call(parse_name(name)?, parse_ir_type(return_type)?, parse_index(argument_count)?))
+            },
+
+            ("cast", None) => {
+                let (from, into) = operands.split_once(" -> ").ok_or("cast expects the type cast to after ->")?;
+                Ok(IntermediateInstr::Cast(parse_ir_type(from)?, parse_ir_type(into)?))
+            },
+
+            ("trap", None) => Ok(IntermediateInstr::Trap(parse_quoted(operands, '"')?)),
+            ("func_start", None) => Ok(IntermediateInstr::FuncStart(parse_name(operands)?)),
+            ("func_end", None) => Ok(IntermediateInstr::FuncEnd(parse_name(operands)?)),
+            ("check_index", None) => Ok(IntermediateInstr::CheckIndex(parse_name(operands)?)),
+            (_, None) if operands.is_empty() => OPERANDLESS_INSTRS.iter()
+                                                                  .find(|instr| get_instr_mnemonic(instr) == mnemonic)
+                                                                  .cloned()
+                                                                  .ok_or_else(|| format!("{} is not an instruction", mnemonic)),
+            _ => Err(format!("{} does not take these operands", head))
         }
     }
 }
 
 
 /**
- * Used to map identifiers to address, type pairs
+ * Reads intermediate code written one instruction per line in the format given by the `Display` of
+ * `IntermediateInstr`, such as the output of `--emit-ir`, skipping blank lines. Returns an error naming
+ * the first line which could not be read.
  */
-#[derive(Debug)]
+#[allow(dead_code)]
+pub fn parse_intermediate(text:&str) -> Result<Vec<IntermediateInstr>, MalformedIntermediate> {
+    text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(index, line)| {
+        line.parse().map_err(|reason| MalformedIntermediate {line: index + 1, text: line.trim().to_owned(), reason})
+    }).collect()
+}
+
+
+/**
+ * Used to map identifiers to address, type pairs. A static variable is kept in the data section under its
+ * `static_label` instead of in the frame, so its address is not used.
+ */
+#[derive(Debug, Clone)]
 pub struct AddrTypePair {
     address: usize,
-    var_type: Type
+    var_type: Type,
+    static_label: Option<String>
+}
+
+impl AddrTypePair {
+    /**
+     * Returns the instruction which pushes the value of the variable onto the stack.
+     */
+    fn gen_load_code(&self) -> IntermediateInstr {
+        match &self.static_label {
+            Some(label) => IntermediateInstr::LoadStatic(self.var_type.clone(), label.to_owned()),
+            None => IntermediateInstr::Load(self.var_type.clone(), self.address)
+        }
+    }
+
+    /**
+     * Returns the instruction which pops the top of the stack into the variable.
+     */
+    fn gen_store_code(&self) -> IntermediateInstr {
+        match &self.static_label {
+            Some(label) => IntermediateInstr::StoreStatic(self.var_type.clone(), label.to_owned()),
+            None => IntermediateInstr::Store(self.var_type.clone(), self.address)
+        }
+    }
 }
 
 
 /**
  * Contains the current context of the labels in the intermediate code, such as:
- *  - ieie_return_label: label for the end of the current if, else if, else block,
+ *  - ieie_return_labels: labels for the ends of the enclosing if, else if, else blocks, innermost last,
  *  - loop_break_label: label for the end of the current loop block,
- *  - loop_continue_label: label for the start of the current loop block
+ *  - loop_continue_label: label jumped to by `continue` in the current loop block, which is the start of
+ *    the loop except in a for loop, where it is the step
  */
 #[derive(Clone)]
 struct LabelContext {
-    ieie_return_label:Option<String>,
+    ieie_return_labels:Vec<String>,
     loop_break_label:Option<String>,
     loop_continue_label:Option<String>
 }
@@ -103,14 +508,22 @@ struct LabelContext {
 impl LabelContext {
     fn new() -> LabelContext {
         LabelContext {
-            ieie_return_label: None,
+            ieie_return_labels: vec![],
             loop_break_label: None,
             loop_continue_label: None
         }
     }
 
-    fn update_ieie(&mut self, label:String) {
-        self.ieie_return_label = Some(label)
+    fn push_ieie(&mut self, label:String) {
+        self.ieie_return_labels.push(label)
+    }
+
+    fn pop_ieie(&mut self) {
+        self.ieie_return_labels.pop();
+    }
+
+    fn current_ieie(&self) -> &str {
+        self.ieie_return_labels.last().expect("if statement generated outside of an if, else if, else block")
     }
 
     fn update_break(&mut self, label:String) {
@@ -120,6 +533,19 @@ impl LabelContext {
     fn update_continue(&mut self, label:String) {
         self.loop_continue_label = Some(label)
     }
+
+    /**
+     * Returns the break and continue labels of the current loop, so they can be restored with
+     * `restore_loop()` once a loop nested inside it ends.
+     */
+    fn get_loop(&self) -> (Option<String>, Option<String>) {
+        (self.loop_break_label.clone(), self.loop_continue_label.clone())
+    }
+
+    fn restore_loop(&mut self, (break_label, continue_label):(Option<String>, Option<String>)) {
+        self.loop_break_label = break_label;
+        self.loop_continue_label = continue_label;
+    }
 }
 
 
@@ -161,6 +587,23 @@ fn gen_boolean_operator_code(operator:&BooleanOperator) -> IntermediateInstr {
 }
 
 
+/**
+ * Takes an intrinsic function and returns the intermediate stack instr which computes it
+ */
+fn gen_intrinsic_code(intrinsic:&Intrinsic) -> IntermediateInstr {
+    match intrinsic {
+        Intrinsic::Abs => IntermediateInstr::Abs,
+        Intrinsic::Min => IntermediateInstr::Min,
+        Intrinsic::Max => IntermediateInstr::Max,
+        Intrinsic::Pow => IntermediateInstr::Pow,
+        Intrinsic::Round => IntermediateInstr::Round,
+        Intrinsic::Floor => IntermediateInstr::Floor,
+        Intrinsic::Ceil => IntermediateInstr::Ceil,
+        Intrinsic::Alloc => IntermediateInstr::Alloc
+    }
+}
+
+
 fn gen_boolean_connector_code(connector:&BooleanConnector) -> IntermediateInstr {
     match connector {
         BooleanConnector::And => IntermediateInstr::LogicAnd,
@@ -179,15 +622,524 @@ fn get_var_repr(func_id:&str, id:&str) -> String {
 
 
 /**
- * Derives the next label from a static variable. Label is an underscore '_' followed by a hex representation
- * of the number of the label. 
+ * Looks up a variable of the given function in the memory map. Semantic analysis rejects any use of a
+ * variable which is not in scope, so a variable missing from the map is a bug in the compiler, and the error
+ * names the variables the map does hold for the function to help find where it went missing.
+ */
+fn resolve_var<'a>(func_id:&str, id:&str, memory_map:&'a HashMap<String, AddrTypePair>) -> Result<&'a AddrTypePair, InternalCompilerError> {
+    memory_map.get(&get_var_repr(func_id, id)).ok_or_else(|| {
+        let prefix = get_var_repr(func_id, "");
+        let mut known:Vec<&str> = memory_map.keys().filter_map(|key| key.strip_prefix(&prefix)).collect();
+        known.sort();
+
+        InternalCompilerError(format!("the variable {} of function {} is not in the memory map, which only holds [{}] for that function",
+            id, func_id, known.join(", ")))
+    })
+}
+
+
+/**
+ * Represents a named variable kept in the frame of a function, and the first slot it is kept in, along with
+ * the line it is declared on, which parameters do not have.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameVariable {
+    pub identifier: String,
+    pub var_type: Type,
+    pub slot: usize,
+    pub line: Option<usize>
+}
+
+
+/**
+ * Holds every named variable kept in the frame of a function, in the order they are declared, along with the
+ * range of lines the function's statements are on if any of them record it. Used to describe the frame in
+ * the debug information given with `--debug-info`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionVariables {
+    pub function: String,
+    pub lines: Option<(usize, usize)>,
+    pub variables: Vec<FrameVariable>
+}
+
+
+thread_local! {
+    static NEXT_LABEL: Cell<usize> = const { Cell::new(1) };
+    static NEXT_ADDRESS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    static FRAME_VARIABLES: RefCell<Vec<FunctionVariables>> = const { RefCell::new(vec![]) };
+}
+
+
+/**
+ * Records that a variable is kept in the frame, starting at the given slot. Variables the compiler adds, such
+ * as the control variable of a repeat loop, are only added to the memory map. This is kept out of
+ * `gen_intermediate_code` so that building the map entry does not add to the stack used by each level of
+ * its recursion.
+ */
+fn add_frame_variable(memory_map:&mut HashMap<String, AddrTypePair>, func_name:&str, identifier:&str, address:usize, var_type:&Type,
+                      line:Option<usize>) {
+    let metadata = AddrTypePair {address, var_type: var_type.clone(), static_label: None};
+    memory_map.insert(get_var_repr(func_name, identifier), metadata);
+
+    if !is_repeat_control(identifier) {
+        let variable = FrameVariable {identifier: identifier.to_owned(), var_type: var_type.clone(), slot: address, line};
+        FRAME_VARIABLES.with(|functions| functions.borrow_mut().last_mut().map(|function| function.variables.push(variable)));
+    }
+}
+
+
+/**
+ * Returns the label of the data section entry holding a static variable, which is the reserved prefix
+ * `__static_` followed by the names of the function and variable, such as `__static_main_count`. If another
+ * static already has that label, such as one of the same name in a different block of the function, a number
+ * is added to the end to keep them apart.
+ */
+fn get_static_label(func_name:&str, identifier:&str, instructions:&[IntermediateInstr]) -> String {
+    let is_taken = |label:&str| instructions.iter().any(|instr| matches!(instr, IntermediateInstr::DeclareStatic(_, taken, _) if taken == label));
+
+    let base = format!("{}static_{}_{}", RESERVED_PREFIX, func_name, identifier);
+    let mut label = base.clone();
+    let mut count = 1;
+    while is_taken(&label) {
+        count += 1;
+        label = format!("{}_{}", base, count);
+    }
+
+    label
+}
+
+
+/**
+ * Converts a literal from the AST into the argument of a `Push` or `DeclareStatic` instruction.
+ */
+fn get_literal_argument(literal:Literal) -> Argument {
+    match literal {
+        Literal::Byte(byte) => Argument::Byte(byte),
+        Literal::Integer(int) => Argument::Integer(int),
+        Literal::Long(long) => Argument::Long(long),
+        Literal::Boolean(boolean) => Argument::Boolean(boolean),
+        Literal::Char(character) => Argument::Char(character),
+        Literal::Float(float) => Argument::Float(float),
+        Literal::Double(double) => Argument::Double(double),
+        Literal::String(string) => Argument::String(string),
+    }
+}
+
+
+/**
+ * Derives the next label from a counter which is reset for each program. Label is the reserved prefix `__l_`
+ * followed by a hex representation of the number of the label, so it cannot collide with a user identifier. 
  * 
- * For example, we start at "_1", then "_2", and the 32nd is "_20".
+ * For example, we start at "__l_1", then "__l_2", and the 32nd is "__l_20".
  */
 fn get_next_label() -> String {
-    static NEXT_LABEL:AtomicUsize = AtomicUsize::new(1);
-    let next_label = NEXT_LABEL.fetch_add(1, Ordering::Relaxed);
-    format!("_{:x}", next_label)
+    let next_label = NEXT_LABEL.with(|label| label.replace(label.get() + 1));
+    format!("{}l_{:x}", RESERVED_PREFIX, next_label)
+}
+
+
+/**
+ * Returns the number of slots a variable of the given type takes up in a frame, which is two for longs and
+ * doubles and one for everything else.
+ */
+pub fn get_slot_count(var_type:&Type) -> usize {
+    match var_type {
+        Type::Long | Type::Double => 2,
+        Type::Void => panic!("Type void cannot be stored on the stack"),
+        _ => 1
+    }
+}
+
+
+/**
+ * Returns true if a variable of the given type fits in a single byte, so is given a byte slot, which the
+ * backend can pack into one byte of the frame, instead of a word slot.
+ */
+pub fn is_byte_sized(var_type:&Type) -> bool {
+    matches!(var_type, Type::Byte | Type::Char | Type::Boolean)
+}
+
+
+/**
+ * Returns the number of word slots and byte slots needed for the variables of a scope and everything nested
+ * in it. Blocks nested directly in the same scope can never both be running, so they share their slots and
+ * only the largest counts.
+ */
+fn get_scope_slots(scope:usize, symbol_table:&SymbolTable) -> (usize, usize) {
+    let (mut own_words, mut own_bytes) = (0, 0);
+    let (mut child_words, mut child_bytes) = (0, 0);
+    for symbol in &symbol_table.rows {
+        match symbol {
+            SymbolTableRow::Variable {primitive_type, parent_scope, storage: Storage::Frame, ..} if *parent_scope == scope => {
+                match is_byte_sized(primitive_type) {
+                    true => own_bytes += get_slot_count(primitive_type),
+                    false => own_words += get_slot_count(primitive_type)
+                }
+            },
+
+            SymbolTableRow::ScopeBlock {scope: child, parent_scope, ..} if *parent_scope == scope => {
+                let (words, bytes) = get_scope_slots(*child, symbol_table);
+                child_words = child_words.max(words);
+                child_bytes = child_bytes.max(bytes);
+            },
+
+            _ => {}
+        }
+    }
+
+    (own_words + child_words, own_bytes + child_bytes)
+}
+
+
+/**
+ * Returns the number of word slots and byte slots needed for the variables in a function's frame, including
+ * the copies of its parameters.
+ */
+pub fn get_function_slots(function_id:&str, symbol_table:&SymbolTable) -> (usize, usize) {
+    let scope = symbol_table.rows.iter().find_map(|symbol| match symbol {
+        SymbolTableRow::Function {identifier, scope, ..} if identifier == function_id => Some(*scope),
+        _ => None
+    }).unwrap_or_else(|| panic!("Function {} is not in the symbol table", function_id));
+
+    get_scope_slots(scope, symbol_table)
+}
+
+
+/**
+ * Starts allocating slots for the frame of the function with the given identifier and statements. Word slots
+ * are counted from the start of the frame, and byte slots carry on from the last word slot the function can
+ * need, so that no two variables which are alive at once share a slot number.
+ */
+fn start_frame(function_id:&str, statements:&[ASTNode], symbol_table:&SymbolTable) {
+    let (word_slots, _) = get_function_slots(function_id, symbol_table);
+    NEXT_ADDRESS.with(|address| address.set((0, word_slots)));
+
+    let function = FunctionVariables {function: function_id.to_owned(), lines: get_line_range(statements), variables: vec![]};
+    FRAME_VARIABLES.with(|functions| functions.borrow_mut().push(function));
+}
+
+
+/**
+ * Returns the first of the slots allocated for a variable of the given type, which is a byte slot for
+ * bytes, chars and bools and a word slot for everything else. Slots are reused once the block which declared
+ * the variable ends, so the slots of blocks which can never both be running, such as an if body and its
+ * else body, overlap.
+ */
+fn allocate_slots(var_type:&Type) -> usize {
+    NEXT_ADDRESS.with(|address| {
+        let (next_word, next_byte) = address.get();
+        let slots = get_slot_count(var_type);
+        match is_byte_sized(var_type) {
+            true => {
+                address.set((next_word, next_byte + slots));
+                next_byte
+            },
+
+            false => {
+                address.set((next_word + slots, next_byte));
+                next_word
+            }
+        }
+    })
+}
+
+
+/**
+ * Generates the code for the statements of a block with its own scope, after which the slots of the
+ * variables declared in it are free to be used by whatever comes next.
+ */
+fn gen_block_code(statements:&[ASTNode], instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>,
+            func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    let first_free = NEXT_ADDRESS.with(|address| address.get());
+    for statement in statements {
+        gen_statement_code(statement, instructions, memory_map, func_name, label_context, symbol_table)?;
+    }
+
+    NEXT_ADDRESS.with(|address| address.set(first_free));
+    Ok(())
+}
+
+
+/**
+ * Generates the intermediate code for a statement. The variables declared by a statement with its own scope,
+ * such as a loop, are removed from the memory map after it, so a variable of the same name in an enclosing
+ * block which they hid is used again.
+ */
+fn gen_statement_code(statement:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>, 
+            func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    let line = match statement {
+        ASTNode::VarDeclStatement {line, ..}
+          | ASTNode::VarAssignStatement {line, ..}
+          | ASTNode::ExpressionStatement {line, ..} => format!(" on line {}", line),
+        _ => String::new()
+    };
+
+    let enclosing = match statement {
+        ASTNode::IfStatement {..}
+          | ASTNode::ElseStatement {..}
+          | ASTNode::IndefLoop {..}
+          | ASTNode::WhileLoop {..}
+          | ASTNode::ForLoop {..}
+          | ASTNode::ForEachLoop {..} => Some(memory_map.clone()),
+        _ => None
+    };
+
+    let _context = CompilationContext::enter(format!("function '{}', {}{}", func_name, get_node_name(statement), line));
+    gen_intermediate_code(statement, instructions, memory_map, func_name, label_context, symbol_table)?;
+    if let Some(enclosing) = enclosing {
+        *memory_map = enclosing;
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Represents the limit of a for loop, along with the type semantic analysis resolved for it and the type of
+ * the loop's control variable.
+ */
+struct LoopLimit<'a> {
+    limit:&'a ASTNode,
+    limit_type:&'a Type,
+    control_type:&'a Type
+}
+
+
+/**
+ * Generates the code for the limit of a for loop, casting it from the type semantic analysis resolved for it
+ * to the type of the control variable if they differ, which is only the case for a repeat loop repeating a
+ * long number of times.
+ */
+fn gen_loop_limit_code(loop_limit:&LoopLimit, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>,
+                       func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    gen_intermediate_code(loop_limit.limit, instructions, memory_map, func_name, label_context, symbol_table)?;
+    if loop_limit.limit_type != loop_limit.control_type {
+        instructions.push(IntermediateInstr::Cast(loop_limit.limit_type.clone(), loop_limit.control_type.clone()));
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Returns a type filled in on a node by semantic analysis, which has always run on the AST code is generated
+ * for.
+ */
+fn get_resolved_type<'a>(resolved_type:&'a Option<Type>, node:&ASTNode) -> &'a Type {
+    resolved_type.as_ref().unwrap_or_else(|| panic!("the type of the {} was not resolved by semantic analysis", get_node_name(node)))
+}
+
+
+/**
+ * Generates the intermediate code for a for each loop over the chars of a string. This is kept out of
+ * `gen_intermediate_code` as its locals would otherwise make every level of that recursion use more stack.
+ */
+fn gen_for_each_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>, 
+            func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    let (element_type, element_identifier, iterable, statements, line) = match root {
+        ASTNode::ForEachLoop {element_type, element_identifier, iterable, statements, line, ..} => (element_type, element_identifier, iterable, statements, *line),
+        other => panic!("{:?} is not a for each loop", other)
+    };
+
+    // keep the string, its length and the index of the current char in hidden variables until the loop ends
+    let first_free = NEXT_ADDRESS.with(|address| address.get());
+    let string_address = allocate_slots(&Type::String);
+    gen_intermediate_code(iterable, instructions, memory_map, func_name, label_context, symbol_table)?;
+    instructions.push(IntermediateInstr::Store(Type::String, string_address));
+
+    let length_address = allocate_slots(&Type::Integer);
+    instructions.push(IntermediateInstr::Load(Type::String, string_address));
+    instructions.push(IntermediateInstr::StrLen);
+    instructions.push(IntermediateInstr::Store(Type::Integer, length_address));
+
+    let index_address = allocate_slots(&Type::Integer);
+    instructions.push(IntermediateInstr::Push(Type::Integer, Argument::Integer(0)));
+    instructions.push(IntermediateInstr::Store(Type::Integer, index_address));
+
+    let element_address = allocate_slots(element_type);
+    add_frame_variable(memory_map, func_name, element_identifier, element_address, element_type, Some(line));
+
+    // continue jumps to the step so the index is still moved on to the next char
+    let enclosing_loop = label_context.get_loop();
+    let start_label = get_next_label();
+    let step_label = get_next_label();
+    let return_label = get_next_label();
+    label_context.update_continue(step_label.clone());
+    label_context.update_break(return_label.clone());
+    instructions.push(IntermediateInstr::Label(start_label.clone()));
+
+    instructions.push(IntermediateInstr::Load(Type::Integer, index_address));
+    instructions.push(IntermediateInstr::Load(Type::Integer, length_address));
+    instructions.push(IntermediateInstr::LessThan);
+    instructions.push(IntermediateInstr::JumpZero(return_label.clone()));
+
+    // fetch the current char into the element before running the body
+    instructions.push(IntermediateInstr::Load(Type::String, string_address));
+    instructions.push(IntermediateInstr::Load(Type::Integer, index_address));
+    instructions.push(IntermediateInstr::StrIndex);
+    instructions.push(IntermediateInstr::Store(element_type.clone(), element_address));
+    gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
+
+    instructions.push(IntermediateInstr::Label(step_label));
+    instructions.push(IntermediateInstr::Load(Type::Integer, index_address));
+    instructions.push(IntermediateInstr::Push(Type::Integer, Argument::Integer(1)));
+    instructions.push(IntermediateInstr::Add);
+    instructions.push(IntermediateInstr::Store(Type::Integer, index_address));
+    instructions.push(IntermediateInstr::Jump(start_label));
+
+    instructions.push(IntermediateInstr::Label(return_label));
+    label_context.restore_loop(enclosing_loop);
+    NEXT_ADDRESS.with(|address| address.set(first_free));
+    Ok(())
+}
+
+
+/**
+ * Generates the intermediate code for a function, storing each parameter into the frame before the code of
+ * its statements. This is kept out of `gen_intermediate_code` for the same reason as `gen_for_each_code`.
+ */
+fn gen_function_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>,
+            label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    let (func_id, statements, parameters, return_type) = match root {
+        ASTNode::Function {identifier, statements, parameters, return_type, ..} => (identifier, statements, parameters, return_type),
+        other => panic!("{:?} is not a function", other)
+    };
+
+    instructions.push(IntermediateInstr::FuncStart(func_id.to_owned()));
+    start_frame(func_id, statements, symbol_table);
+
+    // parameters are loaded by their position in this function's parameter list, and each backend works
+    // out where that is from the types of the parameters
+    for (param_index, param) in parameters.iter().enumerate() {
+        gen_intermediate_code(param, instructions, memory_map, func_id, label_context, symbol_table)?;
+        match param {
+            ASTNode::Parameter {param_type, identifier: param_id, ..} => {
+                instructions.push(IntermediateInstr::LoadParam(param_type.clone(), param_index));
+                
+                let metadata = resolve_var(func_id, param_id, memory_map)?;
+                instructions.push(IntermediateInstr::Store(param_type.clone(), metadata.address));
+            },
+
+            _ => panic!("Detected non-parameter node in function parameter list")
+        }
+    }
+
+    for stmt in statements {
+        gen_statement_code(stmt, instructions, memory_map, func_id, label_context, symbol_table)?;
+    }
+
+    // only reached if control falls off the end of a function which should have returned a value
+    if return_type != &Type::Void {
+        let message = format!("error: function {} returned without a value\\n", func_id);
+        instructions.push(IntermediateInstr::Trap(message));
+    }
+
+    instructions.push(IntermediateInstr::FuncEnd(func_id.to_owned()));
+
+    Ok(())
+}
+
+
+/**
+ * Generates the intermediate code for a for loop, including a repeat loop. This is kept out of
+ * `gen_intermediate_code` for the same reason as `gen_for_each_code`.
+ */
+fn gen_for_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>,
+            func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
+    let (control_type, control_identifier, control_initial, limit, step, statements, line, resolved_type) = match root {
+        ASTNode::ForLoop {control_type, control_identifier, control_initial, limit, step, statements, line, resolved_type, ..} => {
+            (control_type, control_identifier, control_initial, limit, step, statements, line, resolved_type)
+        },
+        other => panic!("{:?} is not a for loop", other)
+    };
+
+    let loop_limit = LoopLimit {limit, limit_type: get_resolved_type(resolved_type, root), control_type};
+
+    // get initial control value
+    gen_intermediate_code(control_initial, instructions, memory_map, func_name, label_context, symbol_table)?;
+
+    // add control variable to memory map and memory, where it and the hoisted limit stay until the loop ends
+    let first_free = NEXT_ADDRESS.with(|address| address.get());
+    let address = allocate_slots(control_type);
+    add_frame_variable(memory_map, func_name, control_identifier, address, control_type, Some(*line));
+    instructions.push(IntermediateInstr::Store(control_type.clone(), address));
+
+    // evaluate the limit once into a hidden variable if it cannot change while the loop runs
+    let limit_address = match is_loop_invariant(limit, control_identifier, statements) {
+        true => {
+            let limit_address = allocate_slots(control_type);
+            gen_loop_limit_code(&loop_limit, instructions, memory_map, func_name, label_context, symbol_table)?;
+            instructions.push(IntermediateInstr::Store(control_type.clone(), limit_address));
+            Some(limit_address)
+        },
+        false => None
+    };
+
+    // add start label, with continue jumping to the step so the control variable is still updated
+    let enclosing_loop = label_context.get_loop();
+    let start_label = get_next_label();
+    let step_label = get_next_label();
+    let return_label = get_next_label();
+    label_context.update_continue(step_label.clone());
+    label_context.update_break(return_label.clone());
+    instructions.push(IntermediateInstr::Label(start_label.clone()));
+
+    // generate condition code, evaluating the limit again before every iteration unless it was hoisted
+    let metadata = resolve_var(func_name, control_identifier, memory_map)?;
+    instructions.push(IntermediateInstr::Load(metadata.var_type.clone(), metadata.address));
+    match limit_address {
+        Some(limit_address) => instructions.push(IntermediateInstr::Load(control_type.clone(), limit_address)),
+        None => gen_loop_limit_code(&loop_limit, instructions, memory_map, func_name, label_context, symbol_table)?
+    }
+    instructions.push(IntermediateInstr::LessThan);
+    instructions.push(IntermediateInstr::JumpZero(return_label.clone()));
+
+    // generate statement block code
+    gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
+
+    // generate step code
+    instructions.push(IntermediateInstr::Label(step_label));
+    gen_intermediate_code(step, instructions, memory_map, func_name, label_context, symbol_table)?;
+
+    // add step to control variable value
+    let metadata = resolve_var(func_name, control_identifier, memory_map)?;
+    instructions.push(IntermediateInstr::Load(metadata.var_type.clone(), metadata.address));
+    instructions.push(IntermediateInstr::Add);
+
+    // store result of control variable
+    let metadata = resolve_var(func_name, control_identifier, memory_map)?;
+    instructions.push(IntermediateInstr::Store(metadata.var_type.clone(), metadata.address));
+
+    // go back to start of loop
+    instructions.push(IntermediateInstr::Jump(start_label.to_string()));
+
+    // add return label
+    instructions.push(IntermediateInstr::Label(return_label.clone()));
+    label_context.restore_loop(enclosing_loop);
+    NEXT_ADDRESS.with(|address| address.set(first_free));
+    Ok(())
+}
+
+
+/**
+ * Generates the declaration of a static variable, which only records its label and initial value, as it is
+ * initialised in the data section and so nothing runs where it is declared. This is kept out of
+ * `gen_intermediate_code` for the same reason as `gen_for_each_code`.
+ */
+fn gen_static_decl_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>, func_name:&str) {
+    let (identifier, value, var_type) = match root {
+        ASTNode::VarDeclStatement {identifier, value, var_type, ..} => (identifier, value, var_type),
+        other => panic!("{:?} is not a variable declaration", other)
+    };
+
+    let initial_value = evaluate_constant_term(value, &HashMap::new())
+        .unwrap_or_else(|| panic!("Static variable {} does not have a constant value", identifier));
+    let label = get_static_label(func_name, identifier, instructions);
+
+    let metadata = AddrTypePair {address: 0, var_type: var_type.clone(), static_label: Some(label.clone())};
+    memory_map.insert(get_var_repr(func_name, identifier), metadata);
+    instructions.push(IntermediateInstr::DeclareStatic(var_type.clone(), label, get_literal_argument(initial_value)));
 }
 
 
@@ -196,79 +1148,73 @@ fn get_next_label() -> String {
  * code of the sub nodes. Adding instructions to instructions vec is done through passing a mutable reference,
  * which is modified.
  * 
- * Requires the memory map, which maps identifiers to their scope and type, and a primitive type only when
- * handling a function to ensure the correct return type instr.
+ * Requires the memory map, which maps identifiers to their scope and type, and the name of the function the
+ * node is in. Returns an error if the node uses a variable the memory map does not hold.
+ * 
+ * Operands of expressions and arguments of calls are always evaluated from left to right, so in
+ * `(f(), g())+` and `h(f(), g())` the call to `f` is made before the call to `g`. This is a rule of the
+ * language, as either call may print or read input, and the optimisation passes must not reorder anything
+ * with side effects.
  */
 fn gen_intermediate_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>, memory_map:&mut HashMap<String, AddrTypePair>, 
-            primitive_type:Option<Type>, func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) {
-    static NEXT_ADDRESS:AtomicUsize = AtomicUsize::new(0);
+            func_name:&str, label_context:&mut LabelContext, symbol_table:&SymbolTable) -> Result<(), Box<InternalCompilerError>> {
     match root {
-        ASTNode::Function {identifier: func_id, statements, return_type, parameters, ..} => {
-            instructions.push(IntermediateInstr::FuncStart(func_id.to_owned()));
-
-            let mut param_index = 0;
-            for param in parameters {
-                gen_intermediate_code(param, instructions, memory_map, None, func_id, label_context, symbol_table);
-                match param {
-                    ASTNode::Parameter {param_type, identifier: param_id} => {
-                        instructions.push(IntermediateInstr::LoadParam(param_type.clone(), param_index));
-                        
-                        let metadata = memory_map.get(&get_var_repr(func_id, param_id)).unwrap();
-                        instructions.push(IntermediateInstr::Store(param_type.clone(), metadata.address));
-
-                        match param_type {
-                            Type::Long | Type::Double => param_index += 2,
-                            Type::Void => param_index += 0,
-                            _ => param_index += 1
-                        }
-                    },
-
-                    _ => panic!("Detected non-parameter node in function parameter list")
-                }
-            }
+        ASTNode::Function {..} => gen_function_code(root, instructions, memory_map, label_context, symbol_table)?,
 
-            for stmt in statements {
-                gen_intermediate_code(stmt, instructions, memory_map, Some(return_type.clone()), func_id, label_context, symbol_table);
+        ASTNode::ReturnStatement {expression} => {
+            if let Some(expression) = expression {
+                gen_intermediate_code(expression, instructions, memory_map, func_name, label_context, symbol_table)?;
             }
 
-            instructions.push(IntermediateInstr::FuncEnd(func_id.to_owned()));
+            // looked up from the symbol table as the return may be inside a nested block
+            let return_type = symbol_table.get_func_return_type(func_name).unwrap();
+            instructions.push(IntermediateInstr::Return(return_type))
         },
 
-        ASTNode::ReturnStatement {expression} => {
-            gen_intermediate_code(expression, instructions, memory_map, None, func_name, label_context, symbol_table);
-            instructions.push(IntermediateInstr::Return(primitive_type.unwrap()))
-        },
+        ASTNode::VarDeclStatement {storage: Storage::Static, ..} => gen_static_decl_code(root, instructions, memory_map, func_name),
 
-        ASTNode::VarDeclStatement {identifier, value, var_type, ..} => {
+        ASTNode::VarDeclStatement {identifier, value, var_type, line, ..} => {
             match &**value {
                 ASTNode::Expression {..} 
                   | ASTNode::TernaryExpression {..}
-                  | ASTNode::InputStatement(_) => gen_intermediate_code(value, instructions, memory_map, None, func_name, label_context, symbol_table),
-                _ => panic!("Cannot generate intermdeiate code in variable assignment for {:?}", value)
+                  | ASTNode::BooleanExpression {..}
+                  | ASTNode::InputStatement(..) => gen_intermediate_code(value, instructions, memory_map, func_name, label_context, symbol_table)?,
+                other => return Err(Box::new(InternalCompilerError(format!("the variable {} cannot be given the value of a {}", identifier, get_node_name(other)))))
             }
 
-            let address = NEXT_ADDRESS.fetch_add(1, Ordering::Relaxed);
-            memory_map.insert(get_var_repr(func_name, identifier), AddrTypePair {address: address, var_type: var_type.clone()});
+            let address = allocate_slots(var_type);
+            add_frame_variable(memory_map, func_name, identifier, address, var_type, Some(*line));
             instructions.push(IntermediateInstr::Store(var_type.clone(), address));
         },
 
-        ASTNode::VarAssignStatement {identifier, value} => {
+        ASTNode::VarAssignStatement {identifier, value, ..} => {
             match &**value {
-                ASTNode::Expression {..} => {
-                    gen_intermediate_code(value, instructions, memory_map, None, func_name, label_context, symbol_table);
+                ASTNode::Expression {..} 
+                  | ASTNode::TernaryExpression {..}
+                  | ASTNode::BooleanExpression {..}
+                  | ASTNode::InputStatement(..) => gen_intermediate_code(value, instructions, memory_map, func_name, label_context, symbol_table)?,
+                other => return Err(Box::new(InternalCompilerError(format!("the variable {} cannot be given the value of a {}", identifier, get_node_name(other)))))
+            }
 
-                    let metadata = memory_map.get(&get_var_repr(func_name, identifier)).unwrap();
-                    instructions.push(IntermediateInstr::Store(metadata.var_type.clone(), metadata.address));
-                },
-                _ => {}
+            let metadata = resolve_var(func_name, identifier, memory_map)?;
+            instructions.push(metadata.gen_store_code());
+        },
+
+        // the value of an expression used as a statement is discarded to keep the stack balanced
+        ASTNode::ExpressionStatement {expression, resolved_type, ..} => {
+            gen_intermediate_code(expression, instructions, memory_map, func_name, label_context, symbol_table)?;
+            let value_type = get_resolved_type(resolved_type, root);
+            if value_type != &Type::Void {
+                instructions.push(IntermediateInstr::Pop(value_type.clone()));
             }
         },
 
+        // the left operand is evaluated before the right, and both before the operator is applied
         ASTNode::Expression {rhs, lhs, operator} => {
-            gen_intermediate_code(&*lhs, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(lhs, instructions, memory_map, func_name, label_context, symbol_table)?;
 
             match rhs {
-                Some(rhs) => gen_intermediate_code(rhs, instructions, memory_map, None, func_name, label_context, symbol_table),
+                Some(rhs) => gen_intermediate_code(rhs, instructions, memory_map, func_name, label_context, symbol_table)?,
                 None => {}
             }
 
@@ -278,76 +1224,86 @@ fn gen_intermediate_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>
             }
         },
 
-        ASTNode::Term {child} => gen_intermediate_code(child, instructions, memory_map, None, func_name, label_context, symbol_table),
+        ASTNode::Term {child} => gen_intermediate_code(child, instructions, memory_map, func_name, label_context, symbol_table)?,
 
         ASTNode::Value {literal_type, value} => {
-            let argument = match value.clone() {
-                Literal::Byte(byte) => Argument::Byte(byte),
-                Literal::Integer(int) => Argument::Integer(int),
-                Literal::Long(long) => Argument::Long(long),
-                Literal::Boolean(boolean) => Argument::Boolean(boolean),
-                Literal::Char(character) => Argument::Char(character),
-                Literal::Float(float) => Argument::Float(float),
-                Literal::Double(double) => Argument::Double(double),
-                Literal::String(string) => Argument::String(string),
-            };
-            instructions.push(IntermediateInstr::Push(literal_type.clone(), argument));
+            instructions.push(IntermediateInstr::Push(literal_type.clone(), get_literal_argument(value.clone())));
         },
 
         ASTNode::Identifier(identifier) => {
-            let metadata = memory_map.get(&get_var_repr(func_name, identifier)).unwrap();
-            instructions.push(IntermediateInstr::Load(metadata.var_type.clone(), metadata.address));
+            let metadata = resolve_var(func_name, identifier, memory_map)?;
+            instructions.push(metadata.gen_load_code());
+        },
+
+        // both are worked out from the type of the variable, so nothing is read at runtime
+        ASTNode::SizeOf(identifier) => {
+            let var_type = &resolve_var(func_name, identifier, memory_map)?.var_type;
+            let size = get_type_size(var_type).expect("Variables cannot be void");
+            instructions.push(IntermediateInstr::Push(Type::Integer, Argument::Integer(size)));
+        },
+
+        ASTNode::TypeOf(identifier) => {
+            let var_type = &resolve_var(func_name, identifier, memory_map)?.var_type;
+            instructions.push(IntermediateInstr::Push(Type::String, Argument::String(var_type.to_string())));
         },
 
-        ASTNode::Parameter {param_type, identifier} => {
-            let address = NEXT_ADDRESS.fetch_add(1, Ordering::Relaxed);
-            memory_map.insert(get_var_repr(func_name, identifier), AddrTypePair {address: address, var_type: param_type.clone()});
+        ASTNode::Parameter {param_type, identifier, ..} => {
+            let address = allocate_slots(param_type);
+            add_frame_variable(memory_map, func_name, identifier, address, param_type, None);
         },
 
+        // arguments are pushed from first to last, so they are evaluated in the order they are written
         ASTNode::FunctionCall {identifier, arguments} => {
             for arg in arguments {
-                gen_intermediate_code(arg, instructions, memory_map, None, func_name, label_context, symbol_table);
+                gen_intermediate_code(arg, instructions, memory_map, func_name, label_context, symbol_table)?;
             }
             
-            let return_type = symbol_table.get_func_return_type(identifier).unwrap();
-            instructions.push(IntermediateInstr::Call(identifier.to_string(), return_type.clone()));
+            // semantic analysis only lets a call resolve to an intrinsic if there is no user function of that name,
+            // and rejects calls to functions which do not exist
+            match (symbol_table.get_func_return_type(identifier), get_intrinsic_from_str(identifier)) {
+                (Ok(return_type), _) => {
+                    instructions.push(IntermediateInstr::Call(identifier.to_string(), return_type, arguments.len()))
+                },
+                (Err(_), Some(intrinsic)) => instructions.push(gen_intrinsic_code(&intrinsic)),
+                (Err(_), None) => {
+                    return Err(Box::new(InternalCompilerError(format!("the function {} is called but was not found by semantic analysis", identifier))));
+                }
+            }
         },
 
         ASTNode::IfElifElseStatement {statements} => {
             let return_label = get_next_label();
-            label_context.update_ieie(return_label.clone());
+            label_context.push_ieie(return_label.clone());
 
             for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
+                gen_statement_code(statement, instructions, memory_map, func_name, label_context, symbol_table)?;
             }
 
+            label_context.pop_ieie();
+
             instructions.push(IntermediateInstr::Label(return_label));
         },
 
         ASTNode::IfStatement {condition, statements, ..} => {
             let label = get_next_label();
-            gen_intermediate_code(condition, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(condition, instructions, memory_map, func_name, label_context, symbol_table)?;
             instructions.push(IntermediateInstr::JumpZero(label.clone()));
-            for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
-            }
+            gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
 
-            let return_label = label_context.ieie_return_label.as_ref().unwrap();
-            instructions.push(IntermediateInstr::Jump(return_label.to_string()));
+            instructions.push(IntermediateInstr::Jump(label_context.current_ieie().to_string()));
             instructions.push(IntermediateInstr::Label(label));
         },
 
         ASTNode::ElseStatement {statements, ..} => {
-            for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
-            }
+            gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
         },
 
+        // both sides of a connector are always evaluated, left first, as connectors do not short circuit
         ASTNode::BooleanExpression {lhs, rhs, operator, connector} => {
-            gen_intermediate_code(lhs, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(lhs, instructions, memory_map, func_name, label_context, symbol_table)?;
             match rhs {
                 Some(rhs) => {
-                    gen_intermediate_code(rhs, instructions, memory_map, None, func_name, label_context, symbol_table);
+                    gen_intermediate_code(rhs, instructions, memory_map, func_name, label_context, symbol_table)?;
                 },
                 None => {}
             }
@@ -370,10 +1326,10 @@ fn gen_intermediate_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>
         },
 
         ASTNode::BooleanTerm {lhs, operator, rhs} => {
-            gen_intermediate_code(lhs, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(lhs, instructions, memory_map, func_name, label_context, symbol_table)?;
             match rhs {
                 Some(rhs) => {
-                    gen_intermediate_code(rhs, instructions, memory_map, None, func_name, label_context, symbol_table);
+                    gen_intermediate_code(rhs, instructions, memory_map, func_name, label_context, symbol_table)?;
                 },
                 None => {}
             }
@@ -387,95 +1343,49 @@ fn gen_intermediate_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>
             }
         },
 
-        ASTNode::TypeCast {from, into} => {
-            gen_intermediate_code(from, instructions, memory_map, None, func_name, label_context, symbol_table);
-            let from_type = match &**from {
-                ASTNode::Identifier(identifier) => &memory_map.get(&get_var_repr(func_name, &identifier)).unwrap().var_type,
-                ASTNode::Value {literal_type, ..} => literal_type,
-                other => panic!("{:?} is not a valid target for a type cast expression", other)
-            };
-
+        ASTNode::TypeCast {from, into, resolved_type, ..} => {
+            gen_intermediate_code(from, instructions, memory_map, func_name, label_context, symbol_table)?;
+            let from_type = get_resolved_type(resolved_type, root);
             instructions.push(IntermediateInstr::Cast(from_type.clone(), into.clone()));
         },
 
         ASTNode::IndefLoop {statements, ..} => {
+            let enclosing_loop = label_context.get_loop();
             let continue_label = get_next_label();
             let return_label = get_next_label();
             label_context.update_continue(continue_label.clone());
             label_context.update_break(return_label.clone());
 
             instructions.push(IntermediateInstr::Label(continue_label.clone()));
-            for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
-            }
+            gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
 
             instructions.push(IntermediateInstr::Jump(continue_label));
             instructions.push(IntermediateInstr::Label(return_label.clone()));
+            label_context.restore_loop(enclosing_loop);
         },
 
         ASTNode::WhileLoop {statements, condition, ..} => {
+            let enclosing_loop = label_context.get_loop();
             let start_label = get_next_label();
             let return_label = get_next_label();
             label_context.update_continue(start_label.clone());
             label_context.update_break(return_label.clone());
             instructions.push(IntermediateInstr::Label(start_label.clone()));
 
-            gen_intermediate_code(condition, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(condition, instructions, memory_map, func_name, label_context, symbol_table)?;
             instructions.push(IntermediateInstr::JumpZero(return_label.clone()));
 
-            for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
-            }
+            gen_block_code(statements, instructions, memory_map, func_name, label_context, symbol_table)?;
 
             instructions.push(IntermediateInstr::Jump(start_label.to_string()));
             instructions.push(IntermediateInstr::Label(return_label));
+            label_context.restore_loop(enclosing_loop);
         },
 
-        ASTNode::ForLoop {control_type, control_identifier, control_initial, limit, step, statements, ..} => {
-            // get initial control value
-            gen_intermediate_code(control_initial, instructions, memory_map, None, func_name, label_context, symbol_table);
-
-            // add control variable to memory map and memory
-            let address = NEXT_ADDRESS.fetch_add(1, Ordering::Relaxed);
-            memory_map.insert(get_var_repr(func_name, control_identifier), AddrTypePair {address: address, var_type: control_type.clone()});
-            instructions.push(IntermediateInstr::Store(control_type.clone(), address));
-
-            // add start label
-            let start_label = get_next_label();
-            let return_label = get_next_label();
-            label_context.update_continue(start_label.clone());
-            label_context.update_break(return_label.clone());
-            instructions.push(IntermediateInstr::Label(start_label.clone()));
-
-            // generate condition code
-            gen_intermediate_code(limit, instructions, memory_map, None, func_name, label_context, symbol_table);
-            let metadata = memory_map.get(&get_var_repr(func_name, control_identifier)).unwrap();
-            instructions.push(IntermediateInstr::Load(metadata.var_type.clone(), metadata.address));
-            instructions.push(IntermediateInstr::LessThan);
-            instructions.push(IntermediateInstr::JumpZero(return_label.clone()));
-
-            // generate statement block code
-            for statement in statements {
-                gen_intermediate_code(statement, instructions, memory_map, None, func_name, label_context, symbol_table);
-            }
-
-            // generate step code
-            gen_intermediate_code(step, instructions, memory_map, None, func_name, label_context, symbol_table);
-
-            // add step to control variable value
-            let metadata = memory_map.get(&get_var_repr(func_name, control_identifier)).unwrap();
-            instructions.push(IntermediateInstr::Load(metadata.var_type.clone(), metadata.address));
-            instructions.push(IntermediateInstr::Add);
+        ASTNode::ForLoop {..} => gen_for_code(root, instructions, memory_map, func_name, label_context, symbol_table)?,
 
-            // store result of control variable
-            let metadata = memory_map.get(&get_var_repr(func_name, control_identifier)).unwrap();
-            instructions.push(IntermediateInstr::Store(metadata.var_type.clone(), metadata.address));
-
-            // go back to start of loop
-            instructions.push(IntermediateInstr::Jump(start_label.to_string()));
-
-            // add return label
-            instructions.push(IntermediateInstr::Label(return_label.clone()));
+        ASTNode::ForEachLoop {..} => {
+            gen_for_each_code(root, instructions, memory_map, func_name, label_context, symbol_table)?;
         },
 
         ASTNode::Break => {
@@ -490,44 +1400,124 @@ fn gen_intermediate_code(root:&ASTNode, instructions:&mut Vec<IntermediateInstr>
             // get flags and generate condition code
             let return_label = get_next_label();
             let false_label = get_next_label();
-            gen_intermediate_code(condition, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(condition, instructions, memory_map, func_name, label_context, symbol_table)?;
 
             // jump to false, generate code for true value, jump to end
             instructions.push(IntermediateInstr::JumpZero(false_label.clone()));
-            gen_intermediate_code(if_true, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(if_true, instructions, memory_map, func_name, label_context, symbol_table)?;
             instructions.push(IntermediateInstr::Jump(return_label.to_string()));
 
             // generate code for false value
             instructions.push(IntermediateInstr::Label(false_label));
-            gen_intermediate_code(if_false, instructions, memory_map, None, func_name, label_context, symbol_table);
+            gen_intermediate_code(if_false, instructions, memory_map, func_name, label_context, symbol_table)?;
 
             // end of the expression
             instructions.push(IntermediateInstr::Label(return_label));
         },
 
-        ASTNode::PrintStatement {terms} => {
-            for term in terms {
-                gen_intermediate_code(term, instructions, memory_map, None, func_name, label_context, symbol_table);
-                instructions.push(IntermediateInstr::Out);
+        ASTNode::PrintStatement {terms, resolved_types} => {
+            let term_types = resolved_types.as_ref().expect("the types of the print terms were not resolved by semantic analysis");
+            for ((term, format), term_type) in terms.iter().zip(term_types) {
+                gen_intermediate_code(term, instructions, memory_map, func_name, label_context, symbol_table)?;
+                instructions.push(IntermediateInstr::Out(term_type.clone(), *format));
             }
         },
 
-        ASTNode::InputStatement(length) => instructions.push(IntermediateInstr::In(*length))
+        ASTNode::InputStatement(input_type, length) => instructions.push(IntermediateInstr::In(input_type.clone(), *length))
     }
+
+    Ok(())
 }
 
 
 /**
  * Takes the root node vector of the program's AST and returns a vector representing the intermediate code of
- * the program.
+ * the program. Returns an error if the AST refers to a variable which was never declared, which semantic
+ * analysis should have rejected.
  */
-pub fn generate_program_intermediate(ast:Vec<ASTNode>, symbol_table:&SymbolTable) -> Vec<IntermediateInstr> {
+#[allow(dead_code)]
+pub fn generate_program_intermediate(ast:Vec<ASTNode>, symbol_table:&SymbolTable) -> Result<Vec<IntermediateInstr>, InternalCompilerError> {
+    Ok(generate_program_intermediate_with_variables(ast, symbol_table)?.0)
+}
+
+
+/**
+ * Generates the intermediate code of the program as `generate_program_intermediate` does, and also returns
+ * the named variables kept in the frame of each function, in the order the functions are declared.
+ */
+pub fn generate_program_intermediate_with_variables(ast:Vec<ASTNode>, symbol_table:&SymbolTable)
+        -> Result<(Vec<IntermediateInstr>, Vec<FunctionVariables>), InternalCompilerError> {
+    // labels and addresses start again for each program so compiling it twice gives the same code
+    NEXT_LABEL.with(|label| label.set(1));
+    NEXT_ADDRESS.with(|address| address.set((0, 0)));
+    FRAME_VARIABLES.with(|functions| functions.borrow_mut().clear());
+
     let mut instructions = vec![];
     let mut memory_map:HashMap<String, AddrTypePair> = HashMap::new();
     let context = LabelContext::new();
     for top_level in ast {
-        gen_intermediate_code(&top_level, &mut instructions, &mut memory_map, None, "global", &mut context.clone(), &symbol_table);
+        gen_intermediate_code(&top_level, &mut instructions, &mut memory_map, "global", &mut context.clone(), symbol_table).map_err(|err| *err)?;
+    }
+
+    Ok((instructions, FRAME_VARIABLES.with(|functions| functions.take())))
+}
+
+
+/**
+ * Adds a `CheckIndex` before every `StrIndex` in the intermediate code, so that indexing past either end of
+ * a string stops the program with a message naming the function instead of reading whatever memory is
+ * there. Used when the program is compiled with `--checked`.
+ */
+pub fn add_bounds_checks(instructions:Vec<IntermediateInstr>) -> Vec<IntermediateInstr> {
+    let mut checked = Vec::with_capacity(instructions.len());
+    let mut current_function = String::from("global");
+    for instr in instructions {
+        match &instr {
+            IntermediateInstr::FuncStart(name) => current_function = name.to_owned(),
+            IntermediateInstr::StrIndex => checked.push(IntermediateInstr::CheckIndex(current_function.clone())),
+            _ => {}
+        }
+
+        checked.push(instr);
+    }
+
+    checked
+}
+
+
+/**
+ * Holds the intermediate code of one function, from its `FuncStart` to its `FuncEnd`, with the index each
+ * instruction had in the code of the whole program so that constants collected from it can still be found.
+ */
+#[derive(Debug)]
+pub struct FunctionCode {
+    pub name: String,
+    pub instructions: Vec<(usize, IntermediateInstr)>
+}
+
+
+/**
+ * Splits the intermediate code of a program at the `FuncStart` and `FuncEnd` of each function, returning
+ * the functions in the order they were declared. Panics if any instruction is outside of a function, as
+ * every statement is generated inside one.
+ */
+pub fn split_functions(intermediate_code:Vec<IntermediateInstr>) -> Vec<FunctionCode> {
+    let mut functions:Vec<FunctionCode> = vec![];
+    let mut in_function = false;
+    for (index, instr) in intermediate_code.into_iter().enumerate() {
+        match &instr {
+            IntermediateInstr::FuncStart(name) => {
+                functions.push(FunctionCode {name: name.to_owned(), instructions: vec![]});
+                in_function = true;
+            },
+
+            IntermediateInstr::FuncEnd(_) if in_function => in_function = false,
+            _ if in_function => {},
+            other => panic!("{:?} is not inside a function", other)
+        }
+
+        functions.last_mut().unwrap().instructions.push((index, instr));
     }
 
-    instructions
+    functions
 }
@@ -1,9 +1,12 @@
 use std::fs::OpenOptions;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 use std::error::Error;
+use std::ops::Range;
 use pest::Parser;
+use pest::iterators::{Pair, Pairs};
 
 use super::ast::*;
+use crate::errors::{InputFileError, NestingTooDeep, ParseError, ReservedIdentifier};
 
 
 #[derive(Parser)]
@@ -11,6 +14,32 @@ use super::ast::*;
 pub struct IridescentParser;
 
 
+/**
+ * The type names, keywords and literals of the language, which cannot be used as identifiers.
+ */
+const RESERVED_WORDS:[&str; 31] = [
+    "int", "long", "byte", "bool", "char", "float", "double", "string", "void",
+    "fn", "return", "let", "static", "mut", "const", "if", "else", "for", "until", "step", "while", "loop", "repeat", "break", "continue",
+    "print", "input", "sizeof", "typeof",
+    "true", "false"
+];
+
+
+/**
+ * The top-level nodes of every function of a program which could be parsed, along with the errors found in
+ * the others.
+ */
+pub type PartialProgram = (Vec<ASTNode>, Vec<Box<dyn Error>>);
+
+
+/**
+ * The deepest brackets and braces can be nested in a program unless a different limit is given. Every
+ * operator puts its operands in brackets, so this also limits how deep expressions can be, which keeps the
+ * recursive passes over the AST well within the stack.
+ */
+pub const DEFAULT_NESTING_LIMIT:usize = 512;
+
+
 /**
  * Represents a symbol in the parser AST
  */
@@ -58,56 +87,187 @@ impl SymbolTable {
 }
 
 
+/**
+ * The rules of the kinds of statement which can appear in a block, in the order they are listed in errors.
+ */
+const STATEMENT_RULES:[Rule; 12] = [
+    Rule::var_decl, Rule::var_assign, Rule::if_structure, Rule::expr_stmt, Rule::indef_loop, Rule::while_loop,
+    Rule::for_loop, Rule::for_each_loop, Rule::repeat_loop, Rule::print, Rule::return_stmt, Rule::continue_stmt
+];
+
+
+/**
+ * Returns a human readable name for the construct represented by the given rule, for use in error messages.
+ */
+fn get_construct_name(rule:Rule) -> &'static str {
+    match rule {
+        Rule::function_decl => "function declaration",
+        Rule::parameter => "parameter",
+        Rule::function_call => "function call",
+        Rule::print => "print statement",
+        Rule::print_term => "printed term",
+        Rule::input => "input expression",
+        Rule::term => "term",
+        Rule::expression => "expression",
+        Rule::value => "value",
+        Rule::statement => "statement",
+        Rule::ternary_expr => "ternary expression",
+        Rule::type_cast => "type cast",
+        Rule::size_of => "sizeof operator",
+        Rule::type_of => "typeof operator",
+        Rule::indef_loop => "indefinite loop",
+        Rule::for_loop => "for loop",
+        Rule::for_each_loop => "for each loop",
+        Rule::repeat_loop => "repeat loop",
+        Rule::while_loop => "while loop",
+        Rule::boolean_term => "boolean term",
+        Rule::boolean_expr => "boolean expression",
+        Rule::if_stmt => "if statement",
+        Rule::elif_stmt => "else if statement",
+        Rule::else_stmt => "else statement",
+        Rule::if_structure => "if structure",
+        Rule::var_decl => "variable declaration",
+        Rule::var_assign => "variable assignment",
+        Rule::expr_stmt => "expression statement",
+        Rule::return_stmt => "return statement",
+        Rule::continue_stmt | Rule::break_stmt => "loop control statement",
+        _ => "program"
+    }
+}
+
+
+/**
+ * Returns a `ParseError` for the construct represented by `pair` describing what went wrong, pointing at the
+ * source text of `location`, which is either the construct itself or one of its children.
+ */
+fn parse_error_at(pair:&Pair<Rule>, location:&Pair<Rule>, problem:String) -> ParseError {
+    let span = location.as_span();
+    let (line, column) = span.start_pos().line_col();
+    ParseError {
+        construct: get_construct_name(pair.as_rule()).to_owned(),
+        problem,
+        line,
+        column,
+        end: span.end_pos().line_col(),
+        source_line: span.start_pos().line_of().trim_end_matches(['\r', '\n']).to_owned()
+    }
+}
+
+
+/**
+ * Returns a `ParseError` for the construct represented by `pair` describing what went wrong.
+ */
+fn parse_error(pair:&Pair<Rule>, problem:String) -> ParseError {
+    parse_error_at(pair, pair, problem)
+}
+
+
+/**
+ * Returns a `ParseError` for when `token` was found somewhere in the construct represented by `pair` that
+ * it is not valid.
+ */
+fn unexpected_token(pair:&Pair<Rule>, token:&Pair<Rule>) -> ParseError {
+    parse_error_at(pair, token, format!("unexpected {} '{}'", get_construct_name(token.as_rule()), token.as_str()))
+}
+
+
+/**
+ * Returns a `ParseError` for when `token` is in the place of a statement but is not any kind of statement,
+ * listing the kinds of statement which could be there instead.
+ */
+fn unknown_statement(pair:&Pair<Rule>, token:&Pair<Rule>) -> ParseError {
+    let mut kinds:Vec<&str> = STATEMENT_RULES.iter().map(|rule| get_construct_name(*rule)).collect();
+    let last = kinds.pop().unwrap_or_default();
+    parse_error_at(pair, token, format!("unknown statement '{}', expected a {} or {}", token.as_str(), kinds.join(", "), last))
+}
+
+
+/**
+ * Takes a token from the construct represented by `pair` and converts its text with `convert`, returning an
+ * error saying it is an unknown `kind` if it cannot be converted.
+ */
+fn convert_token<T>(pair:&Pair<Rule>, token:&Pair<Rule>, kind:&str, convert:fn(&str) -> Option<T>) -> Result<T, ParseError> {
+    convert(token.as_str()).ok_or_else(|| parse_error_at(pair, token, format!("unknown {} '{}'", kind, token.as_str())))
+}
+
+
+/**
+ * Takes the next token from the children of the construct represented by `pair`, returning an error
+ * saying that `expected` is missing if there are no more children.
+ */
+fn next_token<'i>(tokens:&mut Pairs<'i, Rule>, pair:&Pair<'i, Rule>, expected:&str) -> Result<Pair<'i, Rule>, ParseError> {
+    tokens.next().ok_or_else(|| parse_error(pair, format!("missing {}", expected)))
+}
+
+
 /**
  * Takes a `Pair` representing an expression or a term and returns an `Expression` struct representing
  * that pair and its children. If the pair is a term, then it will be made the single child of a new
  * `Expression` node.
  */
-fn get_expr_from_expr_or_term(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn get_expr_from_expr_or_term(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     match pair.as_rule() {
         Rule::expression => build_ast_from_expression(pair),
         Rule::ternary_expr => build_ast_from_ternary_expr(pair),
         Rule::input => build_ast_from_input_expression(pair),
+        Rule::boolean_expr => build_ast_from_boolean_expression(pair),
         Rule::term => {
-            ASTNode::Expression {
-                lhs: Box::new(build_ast_from_term(pair)),
+            Ok(ASTNode::Expression {
+                lhs: Box::new(build_ast_from_term(pair)?),
                 operator: None,
                 rhs: None
-            }
+            })
         },
-        _ => panic!("Could not parse expression {:?}", pair.as_str())
+        _ => Err(parse_error(&pair, format!("could not parse expression '{}'", pair.as_str())))
     }
 }
 
 
 /**
- * Takes a string representing a path to a file and returns the contents of the file as a `String`. Will
- * return an error if the file cannot be opened or read.
+ * Takes a string representing a path to a file and returns the contents of the file as a `String`, or
+ * everything on stdin if the path is `-`. Will return an error if the file cannot be opened or read.
  * 
  * ### Examples
  * `let contents:String = get_file_contents("hello_world.iri")`
  */
 fn get_file_contents(filename:&str) -> Result<String, Box<dyn Error>> {
-    let mut file = OpenOptions::new().read(true).open(filename)?;
     let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    match filename {
+        "-" => io::stdin().read_to_string(&mut contents)?,
+        _ => OpenOptions::new().read(true)
+                               .open(filename)
+                               .and_then(|mut file| file.read_to_string(&mut contents))
+                               .map_err(|err| InputFileError::new(filename, &err))?
+    };
 
     Ok(contents)
 }
 
 
 /**
- * Takes a `Pair` representing an input expression such as `input 40` and returns a subtree of the AST
- * representing that node.
+ * Takes a `Pair` representing an input expression such as `input 40` or `input char` and returns a subtree
+ * of the AST representing that node.
  */
-fn build_ast_from_input_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let length:usize = i64::try_from(get_int_from_str_literal(parent.next().unwrap().as_str()))
-                    .ok().expect("Could not convert int literal to i64")
-                    .try_into()
-                    .expect("Could not convert int literal to usize");
+fn build_ast_from_input_expression(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let length_token = next_token(&mut parent, &pair, "input length")?;
+    if length_token.as_rule() == Rule::input_char {
+        return Ok(ASTNode::InputStatement(Type::Char, 1));
+    }
+
+    // the length is needed before any variable has a type, so only the size of a type can be given
+    if length_token.as_rule() == Rule::size_of {
+        return match build_ast_from_size_of(length_token.clone())? {
+            ASTNode::Value {value: Literal::Integer(length), ..} => Ok(ASTNode::InputStatement(Type::String, length as usize)),
+            _ => Err(parse_error_at(&pair, &length_token, "input length must be the size of a type, not of a variable".to_owned()))
+        };
+    }
+
+    let length = get_int_from_str_literal(length_token.as_str()).ok()
+                                                                .and_then(|length| usize::try_from(length).ok())
+                                                                .ok_or_else(|| parse_error(&pair, format!("input length {} is out of range", length_token.as_str())))?;
     
-    ASTNode::InputStatement(length)
+    Ok(ASTNode::InputStatement(Type::String, length))
 }
 
 
@@ -115,60 +275,74 @@ fn build_ast_from_input_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode
  * Takes a `Pair` representing a ternary expression and returns a subtree of the AST representing that
  * node, including children.
  */
-fn build_ast_from_ternary_expr(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let conditon = build_ast_from_boolean_expression(parent.next().unwrap());
-    let if_true = build_ast_from_term(parent.next().unwrap());
-    let if_false = build_ast_from_term(parent.next().unwrap());
+fn build_ast_from_ternary_expr(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let conditon = build_ast_from_boolean_expression(next_token(&mut parent, &pair, "condition")?)?;
+    let if_true = build_ast_from_term(next_token(&mut parent, &pair, "value if true")?)?;
+    let if_false = build_ast_from_term(next_token(&mut parent, &pair, "value if false")?)?;
 
-    ASTNode::TernaryExpression {
+    Ok(ASTNode::TernaryExpression {
         condition: Box::new(conditon),
         if_true: Box::new(if_true),
         if_false: Box::new(if_false)
-    }
+    })
+}
+
+
+/**
+ * Takes a `Pair` representing an integer literal and returns its value as the integer type `T`, or an error
+ * if it does not fit into `T`.
+ */
+fn get_int_literal_value<T:TryFrom<i64>>(pair:&Pair<Rule>, value:&Pair<Rule>, type_name:&str) -> Result<T, ParseError> {
+    get_int_from_str_literal(value.as_str()).ok()
+                                            .and_then(|literal| T::try_from(literal).ok())
+                                            .ok_or_else(|| parse_error(pair, format!("{} is out of range for type {}", value.as_str(), type_name)))
 }
 
 
 /**
  * Takes a `Pair` representing a value and returns it as a subtree of the AST, including children nodes.
  */
-fn build_ast_from_value(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_value(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let value = parent.next().unwrap();
-    match value.as_rule() {
+    let value = next_token(&mut parent, &pair, "literal")?;
+    Ok(match value.as_rule() {
         Rule::byte_literal => ASTNode::Value {
             literal_type: Type::Byte,
-            value: Literal::Byte(u8::try_from(get_int_from_str_literal(value.as_str())).ok().expect("Could not convert int literal to i16"))
+            value: Literal::Byte(get_int_literal_value(&pair, &value, "byte")?)
         },
 
         Rule::int_literal => ASTNode::Value {
             literal_type: Type::Integer, 
-            value: Literal::Integer(i32::try_from(get_int_from_str_literal(value.as_str())).ok().expect("Could not convert int literal to i32"))
+            value: Literal::Integer(get_int_literal_value(&pair, &value, "int")?)
         },
 
         Rule::long_literal => ASTNode::Value {
             literal_type: Type::Long,
-            value: Literal::Long(i64::try_from(get_int_from_str_literal(value.as_str())).ok().expect("Could not convert int literal to i64"))
+            value: Literal::Long(get_int_literal_value(&pair, &value, "long")?)
         },
 
         Rule::char_literal => ASTNode::Value {
             literal_type: Type::Char,
-            value: Literal::Char(value.as_str().chars().nth(1).unwrap())
+            value: Literal::Char(get_char_from_str_literal(value.as_str()).ok_or_else(|| {
+                parse_error_at(&pair, &value, format!("unknown escape in char literal {}", value.as_str()))
+            })?)
         },
 
         Rule::bool_literal => ASTNode::Value {
             literal_type: Type::Boolean,
-            value: Literal::Boolean(get_bool_from_str_literal(value.as_str()))
+            value: Literal::Boolean(convert_token(&pair, &value, "boolean literal", get_bool_from_str_literal)?)
         },
 
         Rule::float_literal => ASTNode::Value {
             literal_type: Type::Float,
-            value: Literal::Float(value.as_str().parse().expect("Could not convert int literal to f32"))
+            value: Literal::Float(value.as_str().parse().map_err(|_| parse_error(&pair, format!("{} is not a valid float", value.as_str())))?)
         },
 
         Rule::double_literal => ASTNode::Value {
             literal_type: Type::Double,
-            value: Literal::Double(value.as_str()[..value.as_str().len() - 1].parse().expect("Could not convert int literal to f64"))
+            value: Literal::Double(value.as_str()[..value.as_str().len() - 1].parse()
+                                                                              .map_err(|_| parse_error(&pair, format!("{} is not a valid double", value.as_str())))?)
         },
 
         Rule::string_literal => ASTNode::Value {
@@ -176,74 +350,97 @@ fn build_ast_from_value(pair: pest::iterators::Pair<Rule>) -> ASTNode {
             value: Literal::String(value.as_str()[1..value.as_str().len() - 1].to_string())
         },
 
-        _ => panic!("Could not parse value {:?}", pair.as_str())
-    }
+        _ => return Err(unexpected_token(&pair, &value))
+    })
 }
 
 
-fn build_ast_from_identifier(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_identifier(pair: Pair<Rule>) -> ASTNode {
     ASTNode::Identifier(pair.as_str().to_string())
 }
 
 
+/**
+ * Takes a `Pair` representing a `sizeof` operator and returns the size as an int literal if it is given a type,
+ * or a `SizeOf` node if it is given a variable, which is replaced by the size of the variable's type once it
+ * is known.
+ */
+fn build_ast_from_size_of(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let operand = next_token(&mut parent, &pair, "type or identifier")?;
+    if operand.as_rule() == Rule::identifier {
+        return Ok(ASTNode::SizeOf(operand.as_str().to_owned()));
+    }
+
+    let operand_type = convert_token(&pair, &operand, "type", get_type_from_string)?;
+    let size = get_type_size(&operand_type).ok_or_else(|| parse_error_at(&pair, &operand, format!("type {} has no size", operand_type)))?;
+    Ok(ASTNode::Value {literal_type: Type::Integer, value: Literal::Integer(size)})
+}
+
+
 /**
  * Takes a `Pair` representing a variable type cast and returns it as a subtree of the AST, including 
  * children nodes.
  */
-fn build_ast_from_cast(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_cast(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let into = get_type_from_string(parent.next().unwrap().as_str());
+    let into = convert_token(&pair, &next_token(&mut parent, &pair, "target type")?, "type", get_type_from_string)?;
 
-    let from_token = parent.next().unwrap();
+    let from_token = next_token(&mut parent, &pair, "value to cast")?;
     let from = match from_token.as_rule() {
-        Rule::value => build_ast_from_value(from_token),
+        Rule::value => build_ast_from_value(from_token)?,
         Rule::identifier => build_ast_from_identifier(from_token),
-        other => panic!("{:?} is not a valid target for a cast statement", other)
+        _ => return Err(unexpected_token(&pair, &from_token))
     };
 
-    ASTNode::TypeCast {
+    Ok(ASTNode::TypeCast {
         from: Box::new(from),
-        into: into
-    }
+        into,
+        line: pair.as_span().start_pos().line_col().0,
+        resolved_type: None
+    })
 }
 
 
 /**
  * Takes a `Pair` representing a term and returns it as a subtree of the AST, including children nodes.
  */
-fn build_ast_from_term(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_term(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let child_token = parent.next().unwrap();
+    let child_token = next_token(&mut parent, &pair, "value")?;
     let child = match child_token.as_rule() {
-        Rule::value => build_ast_from_value(child_token),
+        Rule::value => build_ast_from_value(child_token)?,
         Rule::identifier => build_ast_from_identifier(child_token),
-        Rule::function_call => build_ast_from_function_call(child_token),
-        Rule::expression => build_ast_from_expression(child_token),
-        Rule::type_cast => build_ast_from_cast(child_token),
-        _ => panic!("Could not parse term {:?}", pair.as_str())
+        Rule::function_call => build_ast_from_function_call(child_token)?,
+        Rule::expression => build_ast_from_expression(child_token)?,
+        Rule::type_cast => build_ast_from_cast(child_token)?,
+        Rule::size_of => build_ast_from_size_of(child_token)?,
+        Rule::type_of => ASTNode::TypeOf(next_token(&mut child_token.clone().into_inner(), &child_token, "identifier")?.as_str().to_owned()),
+        _ => return Err(unexpected_token(&pair, &child_token))
     };
 
-    ASTNode::Term {
+    Ok(ASTNode::Term {
         child: Box::new(child)
-    }
+    })
 }
 
 
 /**
  * Takes a `Pair` representing a function call and returns it as a subtree of the AST including chld nodes.
  */
-fn build_ast_from_function_call(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_function_call(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let identifier = parent.next().unwrap().as_str().to_string();
+    let identifier = next_token(&mut parent, &pair, "function name")?.as_str().to_string();
     let arguments = match parent.next() {
         Some(args_list) => {
-            let mut parent = args_list.into_inner();
             let mut args = vec![];
-            while let Some(arg) = parent.next() {
+            for arg in args_list.into_inner() {
                 args.push(match arg.as_rule() {
                     Rule::identifier => build_ast_from_identifier(arg),
-                    Rule::value => build_ast_from_value(arg),
-                    _ => panic!("Could not parse argument {:?}", pair.as_str())
+                    Rule::value => build_ast_from_value(arg)?,
+                    Rule::function_call => build_ast_from_function_call(arg)?,
+                    Rule::boolean_expr => build_ast_from_boolean_expression(arg)?,
+                    _ => return Err(unexpected_token(&pair, &arg))
                 });
             }
 
@@ -252,10 +449,10 @@ fn build_ast_from_function_call(pair: pest::iterators::Pair<Rule>) -> ASTNode {
         None => vec![]
     };
 
-    ASTNode::FunctionCall {
+    Ok(ASTNode::FunctionCall {
         identifier: identifier,
         arguments: arguments
-    }
+    })
 }
 
 
@@ -263,18 +460,18 @@ fn build_ast_from_function_call(pair: pest::iterators::Pair<Rule>) -> ASTNode {
  * Takes a `Pair` representing an expression and returns it as a subtree of the AST, including 
  * children nodes.
  */
-fn build_ast_from_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_expression(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     // get the left hand side of the expression from the first token
     let mut parent = pair.clone().into_inner();
-    let child = parent.next().unwrap();
+    let child = next_token(&mut parent, &pair, "left hand side")?;
     let term = match child.as_rule() {
-        Rule::term => build_ast_from_term(child),
+        Rule::term => build_ast_from_term(child)?,
         Rule::value => {
             ASTNode::Term {
-                child: Box::new(build_ast_from_value(child))
+                child: Box::new(build_ast_from_value(child)?)
             }
         },
-        _ => panic!("Could not parse expression {:?}", pair.as_str())
+        _ => return Err(unexpected_token(&pair, &child))
     };
     
     // get the operator and right hand side of the expression if they exist
@@ -287,13 +484,13 @@ fn build_ast_from_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode {
     operator = match parent.next() { 
         Some(token) => {
             match token.as_rule() {
-                Rule::unary_operator => Some(get_unary_operator_from_str(token.as_str())),
+                Rule::unary_operator => Some(convert_token(&pair, &token, "operator", get_unary_operator_from_str)?),
                 Rule::term => { // get the right hand side if there is one from the 2nd child of the expression
-                    rhs = Some(Box::new(build_ast_from_term(token)));
-                    Some(get_binary_operator_from_str(parent.next().unwrap().as_str()))
+                    rhs = Some(Box::new(build_ast_from_term(token)?));
+                    Some(convert_token(&pair, &next_token(&mut parent, &pair, "operator")?, "operator", get_binary_operator_from_str)?)
                 }
 
-                _ => panic!("Could not parse expression {:?}", pair.as_str())
+                _ => return Err(unexpected_token(&pair, &token))
             }
         },
 
@@ -301,25 +498,28 @@ fn build_ast_from_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode {
     };
 
     // build and return the expression node
-    ASTNode::Expression {
+    Ok(ASTNode::Expression {
         lhs: lhs,
         operator: operator,
         rhs: rhs
-    }
+    })
 }
 
 
 /**
  * Takes a `Pair` representing a return statement and returns it as a subtree of the AST, including 
- * children nodes.
+ * children nodes. The expression is `None` for a `return;` from a void function.
  */
-fn build_ast_from_return_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.clone().into_inner();
-    let expression = build_ast_from_expression(parent.next().unwrap());
+fn build_ast_from_return_stmt(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let expression = match pair.into_inner().next() {
+        Some(token) if token.as_rule() == Rule::boolean_expr => Some(build_ast_from_boolean_expression(token)?),
+        Some(token) => Some(get_expr_from_expr_or_term(token)?),
+        None => None
+    };
 
-    ASTNode::ReturnStatement {
-        expression: Box::new(expression)
-    }
+    Ok(ASTNode::ReturnStatement {
+        expression: expression.map(Box::new)
+    })
 }
 
 
@@ -327,26 +527,38 @@ fn build_ast_from_return_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode {
  * Takes a `Pair` representing a variable declaration statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_var_decl_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.clone().into_inner().next().unwrap().into_inner();
-    let mutability = match parent.peek().unwrap().as_rule() {
-        Rule::mutability_mod => get_mutability_from_str(parent.next().unwrap().as_str()),
+fn build_ast_from_var_decl_stmt(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let (line, _) = pair.as_span().start_pos().line_col();
+    let mut parent = pair.clone().into_inner();
+    let storage = match parent.peek().map(|token| token.as_rule()) {
+        Some(Rule::storage_mod) => {
+            parent.next();
+            Storage::Static
+        },
+        _ => Storage::Frame
+    };
+
+    let first = parent.peek().ok_or_else(|| parse_error(&pair, "missing type".to_owned()))?;
+    let mutability = match first.as_rule() {
+        Rule::mutability_mod => convert_token(&pair, &next_token(&mut parent, &pair, "mutability")?, "mutability modifier", get_mutability_from_str)?,
         Rule::primitive_type => Mutability::Constant,
-        _ => panic!("Could not parse variable declaration {:?}", pair.as_str())
+        _ => return Err(unexpected_token(&pair, &first))
     };
 
-    let var_type = get_type_from_string(parent.next().unwrap().as_str());
-    let identifier = parent.next().unwrap().as_str().to_string();
+    let var_type = convert_token(&pair, &next_token(&mut parent, &pair, "type")?, "type", get_type_from_string)?;
+    let identifier = next_token(&mut parent, &pair, "identifier")?.as_str().to_string();
 
-    let value_token = parent.next().unwrap();
-    let value = get_expr_from_expr_or_term(value_token);
+    let value_token = next_token(&mut parent, &pair, "value")?;
+    let value = get_expr_from_expr_or_term(value_token)?;
 
-    ASTNode::VarDeclStatement {
+    Ok(ASTNode::VarDeclStatement {
         var_type: var_type,
         mutability: mutability,
+        storage,
         identifier: identifier,
-        value: Box::new(value)
-    }
+        value: Box::new(value),
+        line
+    })
 }
 
 
@@ -354,17 +566,19 @@ fn build_ast_from_var_decl_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode {
  * Takes a `Pair` representing a variable assignment statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_var_assign_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.clone().into_inner().next().unwrap().into_inner();
-    let identifier = parent.next().unwrap().as_str().to_string();
+fn build_ast_from_var_assign_stmt(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let (line, _) = pair.as_span().start_pos().line_col();
+    let mut parent = pair.clone().into_inner();
+    let identifier = next_token(&mut parent, &pair, "identifier")?.as_str().to_string();
 
-    let value_token = parent.next().unwrap();
-    let value = get_expr_from_expr_or_term(value_token);
+    let value_token = next_token(&mut parent, &pair, "value")?;
+    let value = get_expr_from_expr_or_term(value_token)?;
     
-    ASTNode::VarAssignStatement {
+    Ok(ASTNode::VarAssignStatement {
         identifier: identifier,
-        value: Box::new(value)
-    }
+        value: Box::new(value),
+        line
+    })
 }
 
 
@@ -372,56 +586,47 @@ fn build_ast_from_var_assign_stmt(pair: pest::iterators::Pair<Rule>) -> ASTNode
  * Takes a `Pair` representing a boolean term and returns a subtree of the AST including
  * children nodes.
  */
-fn build_ast_from_boolean_term(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let token = parent.next().unwrap();
+fn build_ast_from_boolean_term(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let token = next_token(&mut parent, &pair, "left hand side")?;
 
     let lhs = match token.as_rule() {
-        Rule::term => build_ast_from_term(token),
-        Rule::boolean_term => build_ast_from_boolean_term(token),
-        unknown => panic!("Invalid token for boolean term: {:?}", unknown)
+        Rule::term => build_ast_from_term(token)?,
+        Rule::boolean_term => build_ast_from_boolean_term(token)?,
+        _ => return Err(unexpected_token(&pair, &token))
     };
 
     let mut operator:Option<BooleanOperator> = None;
     let mut rhs:Option<Box<ASTNode>> = None;
-    match parent.peek() {
-        Some(_) => {
-            let token = parent.next().unwrap();
-            match token.as_rule() {
-                Rule::boolean_unary_operator => {
-                    operator = Some(get_boolean_operator_from_str(token.as_str()));
-                },
-                Rule::term => {
-                    rhs = Some(Box::new(build_ast_from_term(token)))
-                },
-                Rule::boolean_term => {
-                    rhs = Some(Box::new(build_ast_from_boolean_term(token)))
-                },
-                unknown => panic!("Invalid token for boolean term: {:?}", unknown)
-            }
+    if let Some(token) = parent.next() {
+        match token.as_rule() {
+            Rule::boolean_unary_operator => {
+                operator = Some(convert_token(&pair, &token, "boolean operator", get_boolean_operator_from_str)?);
+            },
+            Rule::term => {
+                rhs = Some(Box::new(build_ast_from_term(token)?))
+            },
+            Rule::boolean_term => {
+                rhs = Some(Box::new(build_ast_from_boolean_term(token)?))
+            },
+            _ => return Err(unexpected_token(&pair, &token))
+        }
 
-            match parent.next() {
-                Some(op) => {
-                    match op.as_rule() {
-                        Rule::boolean_binary_operator => {
-                            operator = Some(get_boolean_operator_from_str(op.as_str()));
-                        }
-                        unknown => panic!("{:?} is not a valid binary boolean operator token", unknown)
-                    }
+        if let Some(op) = parent.next() {
+            match op.as_rule() {
+                Rule::boolean_binary_operator => {
+                    operator = Some(convert_token(&pair, &op, "boolean operator", get_boolean_operator_from_str)?);
                 }
-
-                None => {}
+                _ => return Err(unexpected_token(&pair, &op))
             }
-        },
-
-        None => {}
-    };
+        }
+    }
 
-    ASTNode::BooleanTerm {
+    Ok(ASTNode::BooleanTerm {
         lhs: Box::new(lhs),
         rhs: rhs,
         operator: operator
-    }
+    })
 }
 
 
@@ -429,72 +634,72 @@ fn build_ast_from_boolean_term(pair: pest::iterators::Pair<Rule>) -> ASTNode {
  * Takes a `Pair` representing a boolean expression and returns a subtree of the AST including
  * children nodes.
  */
-fn build_ast_from_boolean_expression(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let token = parent.next().unwrap();
+fn build_ast_from_boolean_expression(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let token = next_token(&mut parent, &pair, "left hand side")?;
 
     let lhs = Box::new(match token.as_rule() {
-        Rule::boolean_expr => build_ast_from_boolean_expression(token),
-        Rule::boolean_term => build_ast_from_boolean_term(token),
-        Rule::term => build_ast_from_term(token),
-        unknown => panic!("Invalid token for boolean expression: {:?}", unknown)
+        Rule::boolean_expr => build_ast_from_boolean_expression(token)?,
+        Rule::boolean_term => build_ast_from_boolean_term(token)?,
+        Rule::term => build_ast_from_term(token)?,
+        _ => return Err(unexpected_token(&pair, &token))
     });
     
     let mut connector:Option<BooleanConnector> = None;
     let mut operator:Option<BooleanOperator> = None;
-    let rhs  = match parent.peek() {
-        Some(_) => {
-            let token = parent.next().unwrap();
+    let rhs = match parent.next() {
+        Some(token) => {
             match token.as_rule() {
-                Rule::boolean_expr => {
-                    let operator_or_connector = parent.next().unwrap();
+                Rule::boolean_expr | Rule::boolean_term => {
+                    let operator_or_connector = next_token(&mut parent, &pair, "connector or operator")?;
                     match operator_or_connector.as_rule() {
                         Rule::boolean_connector => {
-                            connector = Some(get_boolean_connector_from_str(operator_or_connector.as_str()));
+                            connector = Some(convert_token(&pair, &operator_or_connector, "boolean connector", get_boolean_connector_from_str)?);
                         },
 
                         Rule::boolean_binary_operator => {
-                            operator = Some(get_boolean_operator_from_str(operator_or_connector.as_str()))
+                            operator = Some(convert_token(&pair, &operator_or_connector, "boolean operator", get_boolean_operator_from_str)?)
                         },
 
-                        unknown => panic!("Invalid token for boolean expression: {:?}", unknown)
+                        _ => return Err(unexpected_token(&pair, &operator_or_connector))
                     }
-                    Some(Box::new(build_ast_from_boolean_expression(token)))
-                },
-                
-                Rule::boolean_term => {
-                    let operator_or_connector = parent.next().unwrap();
-                    match operator_or_connector.as_rule() {
-                        Rule::boolean_connector => {
-                            connector = Some(get_boolean_connector_from_str(operator_or_connector.as_str()));
-                        },
 
-                        Rule::boolean_binary_operator => {
-                            operator = Some(get_boolean_operator_from_str(operator_or_connector.as_str()))
-                        },
-
-                        unknown => panic!("Invalid token for boolean expression: {:?}", unknown)
+                    match token.as_rule() {
+                        Rule::boolean_expr => Some(Box::new(build_ast_from_boolean_expression(token)?)),
+                        _ => Some(Box::new(build_ast_from_boolean_term(token)?))
                     }
-                    Some(Box::new(build_ast_from_boolean_term(token)))
                 },
 
                 Rule::boolean_unary_operator => {
-                    operator = Some(get_boolean_operator_from_str(token.as_str()));                    
+                    operator = Some(convert_token(&pair, &token, "boolean operator", get_boolean_operator_from_str)?);                    
                     None
                 },
-                unknown => panic!("Invalid token for boolean expression: {:?}", unknown)
+                _ => return Err(unexpected_token(&pair, &token))
             }
         },
 
         None => None
     };
 
-    ASTNode::BooleanExpression {
+    Ok(ASTNode::BooleanExpression {
         lhs: lhs,
         rhs: rhs,
         connector: connector,
         operator: operator
+    })
+}
+
+
+/**
+ * Takes the remaining children of a block construct and returns them as a list of statement subtrees.
+ */
+fn build_ast_from_statements(tokens:Pairs<Rule>, symbol_table:&mut SymbolTable) -> Result<Vec<ASTNode>, ParseError> {
+    let mut statements = vec![];
+    for statement in tokens {
+        statements.push(build_ast_from_statement(statement, symbol_table)?);
     }
+
+    Ok(statements)
 }
 
 
@@ -502,21 +707,19 @@ fn build_ast_from_boolean_expression(pair: pest::iterators::Pair<Rule>) -> ASTNo
  * Takes a `Pair` representing an if statement and returns it as a subtree of the AST, including 
  * children nodes.
  */
-fn build_ast_from_if_stmt(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let boolean_expr = build_ast_from_boolean_expression(parent.next().unwrap());
-
-    let mut statements = vec![];
-    while let Some(statement) = parent.next() {
-        statements.push(build_ast_from_statement(statement, symbol_table));
-    }
+fn build_ast_from_if_stmt(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
+    let (line, _) = pair.as_span().start_pos().line_col();
+    let mut parent = pair.clone().into_inner();
+    let boolean_expr = build_ast_from_boolean_expression(next_token(&mut parent, &pair, "condition")?)?;
+    let statements = build_ast_from_statements(parent, symbol_table)?;
 
     let scope = symbol_table.add();
-    ASTNode::IfStatement {
+    Ok(ASTNode::IfStatement {
         condition: Box::new(boolean_expr),
         statements: statements,
-        scope: scope
-    }
+        scope,
+        line
+    })
 }
 
 
@@ -524,18 +727,14 @@ fn build_ast_from_if_stmt(pair: pest::iterators::Pair<Rule>, symbol_table: &mut
  * Takes a `Pair` representing an else statement and returns it as a subtree of the AST, including 
  * children nodes.
  */
-fn build_ast_from_else_stmt(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let mut statements = vec![];
-    while let Some(statement) = parent.next() {
-        statements.push(build_ast_from_statement(statement, symbol_table));
-    }
+fn build_ast_from_else_stmt(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
+    let statements = build_ast_from_statements(pair.into_inner(), symbol_table)?;
 
     let scope = symbol_table.add();
-    ASTNode::ElseStatement {
+    Ok(ASTNode::ElseStatement {
         statements: statements,
         scope: scope
-    }
+    })
 }
 
 
@@ -543,21 +742,20 @@ fn build_ast_from_else_stmt(pair: pest::iterators::Pair<Rule>, symbol_table: &mu
  * Takes a `Pair` representing an if-else-if-else statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_if_structure(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
-    let mut parent = pair.clone().into_inner();
+fn build_ast_from_if_structure(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
     let mut statements = vec![];
-    while let Some(token) = parent.next() {
+    for token in pair.clone().into_inner() {
         statements.push(match token.as_rule() {
-            Rule::if_stmt => build_ast_from_if_stmt(token, symbol_table),
-            Rule::elif_stmt => build_ast_from_if_stmt(token, symbol_table),
-            Rule::else_stmt => build_ast_from_else_stmt(token, symbol_table),
-            unknown => panic!("Invalid token for if statement: {:?}", unknown)
+            Rule::if_stmt => build_ast_from_if_stmt(token, symbol_table)?,
+            Rule::elif_stmt => build_ast_from_if_stmt(token, symbol_table)?,
+            Rule::else_stmt => build_ast_from_else_stmt(token, symbol_table)?,
+            _ => return Err(unexpected_token(&pair, &token))
         });
     }
 
-    ASTNode::IfElifElseStatement {
+    Ok(ASTNode::IfElifElseStatement {
         statements: statements
-    }
+    })
 }
 
 
@@ -565,18 +763,14 @@ fn build_ast_from_if_structure(pair: pest::iterators::Pair<Rule>, symbol_table:
  * Takes a `Pair` representing an indefinite loop statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_indef_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
-    let mut parent = pair.clone().into_inner();
-    let mut statements = vec![];
-    while let Some(token) = parent.next() {
-        statements.push(build_ast_from_statement(token, symbol_table));
-    }
+fn build_ast_from_indef_loop(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
+    let statements = build_ast_from_statements(pair.into_inner(), symbol_table)?;
 
     let scope = symbol_table.add();
-    ASTNode::IndefLoop {
+    Ok(ASTNode::IndefLoop {
         statements: statements,
         scope: scope
-    }
+    })
 }
 
 
@@ -584,22 +778,18 @@ fn build_ast_from_indef_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &m
  * Takes a `Pair` representing a while loop statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_while_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
+fn build_ast_from_while_loop(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let token = parent.next().unwrap();
-    let condition = build_ast_from_boolean_expression(token);
-
-    let mut statements = vec![];
-    while let Some(token) = parent.next() {
-        statements.push(build_ast_from_statement(token, symbol_table));
-    }
+    let condition = build_ast_from_boolean_expression(next_token(&mut parent, &pair, "condition")?)?;
+    let statements = build_ast_from_statements(parent, symbol_table)?;
 
     let scope = symbol_table.add();
-    ASTNode::WhileLoop {
+    Ok(ASTNode::WhileLoop {
         condition: Box::new(condition),
         statements: statements,
-        scope: scope
-    }
+        scope,
+        line: pair.as_span().start_pos().line_col().0
+    })
 }
 
 
@@ -607,74 +797,109 @@ fn build_ast_from_while_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &m
  * Takes a `Pair` representing a for loop statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_for_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
+fn build_ast_from_for_loop(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let control_type = get_type_from_string(parent.next().unwrap().as_str());
-    let control_identifier = parent.next().unwrap().as_str().to_string();
-
-    let control_initial_token = parent.next().unwrap();
-    let control_initial = match control_initial_token.as_rule() {
-        Rule::expression => build_ast_from_expression(control_initial_token),
-        Rule::term => build_ast_from_term(control_initial_token),
-        unknown => panic!("{:?} is not a valid initialiser for a for loop control value", unknown)
-    };
 
-    let limit_token = parent.next().unwrap();
-    let limit = match limit_token.as_rule() {
-        Rule::expression => build_ast_from_expression(limit_token),
-        Rule::term => build_ast_from_term(limit_token),
-        unknown => panic!("{:?} is not a valid limit for a for loop", unknown)
+    // the control variable can only be assigned to in the body if it is declared mutable
+    let control_mutability = match parent.peek().map(|token| token.as_rule()) {
+        Some(Rule::mutability_mod) => convert_token(&pair, &next_token(&mut parent, &pair, "mutability")?, "mutability modifier", get_mutability_from_str)?,
+        _ => Mutability::Constant
     };
 
-    let step = match parent.peek() {
-        Some(token) => {
-            match token.as_rule() {
-                Rule::expression => {
-                    let token = parent.next().unwrap();
-                    get_expr_from_expr_or_term(token)
-                }
+    let control_type = convert_token(&pair, &next_token(&mut parent, &pair, "control variable type")?, "type", get_type_from_string)?;
+    let control_identifier = next_token(&mut parent, &pair, "control variable identifier")?.as_str().to_string();
 
-                Rule::term => {
-                    let token = parent.next().unwrap();
-                    ASTNode::Expression {
-                        lhs: Box::new(build_ast_from_term(token)),
-                        operator: None,
-                        rhs: None
-                    }
-                }
+    let control_initial = get_expr_from_expr_or_term(next_token(&mut parent, &pair, "initial value")?)?;
+    let limit = get_expr_from_expr_or_term(next_token(&mut parent, &pair, "limit")?)?;
 
-                _ => ASTNode::Term {
-                    child: Box::new(ASTNode::Value {
-                        literal_type: control_type.clone(),
-                        value: Literal::Integer(1)
-                    })
-                }
-            }
-        },
-
-        None => ASTNode::Term {
+    let default_step = ASTNode::Expression {
+        lhs: Box::new(ASTNode::Term {
             child: Box::new(ASTNode::Value {
                 literal_type: control_type.clone(),
                 value: Literal::Integer(1)
             })
-        }
+        }),
+        operator: None,
+        rhs: None
     };
 
-    let mut statements = vec![];
-    while let Some(token) = parent.next() {
-        statements.push(build_ast_from_statement(token, symbol_table));
-    }
+    let step = match parent.peek().map(|token| token.as_rule()) {
+        Some(Rule::expression | Rule::ternary_expr | Rule::term) => get_expr_from_expr_or_term(next_token(&mut parent, &pair, "step")?)?,
+        _ => default_step
+    };
+
+    let statements = build_ast_from_statements(parent, symbol_table)?;
 
     let scope = symbol_table.add();
-    ASTNode::ForLoop {
+    Ok(ASTNode::ForLoop {
         control_type: control_type,
+        control_mutability,
         control_identifier: control_identifier,
         control_initial: Box::new(control_initial),
         limit: Box::new(limit),
         step: Box::new(step),
         statements: statements,
-        scope: scope
-    }
+        scope,
+        line: pair.as_span().start_pos().line_col().0,
+        resolved_type: None
+    })
+}
+
+
+/**
+ * Takes a `Pair` representing a repeat loop such as `repeat 3 { ... }` and returns the for loop it stands for,
+ * which counts a hidden int control variable up from 0 to the number of times to repeat in steps of 1. The
+ * control variable is named after the scope of the loop, so nested repeat loops each have their own.
+ */
+fn build_ast_from_repeat_loop(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let count = get_expr_from_expr_or_term(next_token(&mut parent, &pair, "number of times to repeat")?)?;
+    let statements = build_ast_from_statements(parent, symbol_table)?;
+
+    let get_int = |value| ASTNode::Expression {
+        lhs: Box::new(ASTNode::Term {
+            child: Box::new(ASTNode::Value {literal_type: Type::Integer, value: Literal::Integer(value)})
+        }),
+        operator: None,
+        rhs: None
+    };
+
+    let scope = symbol_table.add();
+    Ok(ASTNode::ForLoop {
+        control_type: Type::Integer,
+        control_mutability: Mutability::Constant,
+        control_identifier: format!("{}{}", REPEAT_CONTROL_PREFIX, scope),
+        control_initial: Box::new(get_int(0)),
+        limit: Box::new(count),
+        step: Box::new(get_int(1)),
+        statements,
+        scope,
+        line: pair.as_span().start_pos().line_col().0,
+        resolved_type: None
+    })
+}
+
+
+/**
+ * Takes a `Pair` representing a for each loop statement and returns it as a subtree of the AST, 
+ * including children nodes.
+ */
+fn build_ast_from_for_each_loop(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let element_type = convert_token(&pair, &next_token(&mut parent, &pair, "element type")?, "type", get_type_from_string)?;
+    let element_identifier = next_token(&mut parent, &pair, "element identifier")?.as_str().to_string();
+    let iterable = next_token(&mut parent, &pair, "iterated variable")?.as_str().to_string();
+    let statements = build_ast_from_statements(parent, symbol_table)?;
+
+    let scope = symbol_table.add();
+    Ok(ASTNode::ForEachLoop {
+        element_type,
+        element_identifier,
+        iterable: Box::new(ASTNode::Identifier(iterable)),
+        statements,
+        scope,
+        line: pair.as_span().start_pos().line_col().0
+    })
 }
 
 
@@ -682,11 +907,26 @@ fn build_ast_from_for_loop(pair: pest::iterators::Pair<Rule>, symbol_table: &mut
  * Takes a `Pair` representing a `break` or `continue` statement and dispatches it to the 
  * relevant AST builder function.
  */
-fn build_ast_from_loop_ctrl(pair: pest::iterators::Pair<Rule>) -> ASTNode {
+fn build_ast_from_loop_ctrl(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     match pair.as_rule() {
-        Rule::continue_stmt => ASTNode::Continue,
-        Rule::break_stmt => ASTNode::Break,
-        other => panic!("{:?} is not a valid return or continue statement", other)
+        Rule::continue_stmt => Ok(ASTNode::Continue),
+        Rule::break_stmt => Ok(ASTNode::Break),
+        _ => Err(parse_error(&pair, format!("'{}' is not a valid break or continue statement", pair.as_str())))
+    }
+}
+
+
+/**
+ * Takes a `Pair` representing the format suffix of a print term, such as `:hex` or `:2`, and returns the
+ * format it chooses, or an error if it gives more decimal places than `MAX_PRINT_PRECISION`.
+ */
+fn get_print_format(term_pair:&Pair<Rule>, token:&Pair<Rule>) -> Result<PrintFormat, ParseError> {
+    match &token.as_str()[1..] {
+        "hex" => Ok(PrintFormat::Hex),
+        places => match places.parse::<u8>() {
+            Ok(places) if places <= MAX_PRINT_PRECISION => Ok(PrintFormat::Precision(places)),
+            _ => Err(parse_error_at(term_pair, token, format!("a float can be printed to at most {} decimal places, not {}", MAX_PRINT_PRECISION, places)))
+        }
     }
 }
 
@@ -695,42 +935,77 @@ fn build_ast_from_loop_ctrl(pair: pest::iterators::Pair<Rule>) -> ASTNode {
  * Takes a `Pair` representing a print statement and returns it as a subtree of the AST, 
  * including children nodes.
  */
-fn build_ast_from_print(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut parent = pair.into_inner();
+fn build_ast_from_print(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
     let mut terms = vec![];
-    while let Some(token) = parent.next() {
-        match token.as_rule() {
-            Rule::identifier => terms.push(build_ast_from_identifier(token)),
-            Rule::value => terms.push(build_ast_from_value(token)),
-            other => panic!("Cannot print type: {:?}", other)
+    for term_pair in pair.clone().into_inner() {
+        let mut term = None;
+        let mut format = PrintFormat::Plain;
+        for token in term_pair.clone().into_inner() {
+            match token.as_rule() {
+                Rule::identifier => term = Some(build_ast_from_identifier(token)),
+                Rule::value => term = Some(build_ast_from_value(token)?),
+                Rule::print_format => format = get_print_format(&term_pair, &token)?,
+                _ => return Err(unexpected_token(&term_pair, &token))
+            }
         }
-    }
 
-    ASTNode::PrintStatement {
-        terms: terms
+        terms.push((term.ok_or_else(|| parse_error(&term_pair, "nothing to print".to_owned()))?, format));
     }
+
+    Ok(ASTNode::PrintStatement {
+        terms,
+        resolved_types: None
+    })
+}
+
+
+/**
+ * Takes a `Pair` representing an expression used as a statement and returns it as a subtree of the AST,
+ * including children nodes. A function call on its own is kept as the call rather than being wrapped in an
+ * expression.
+ */
+fn build_ast_from_expr_stmt(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let token = next_token(&mut parent, &pair, "expression")?;
+    let call = match token.as_rule() {
+        Rule::term => token.clone().into_inner().next().filter(|child| child.as_rule() == Rule::function_call),
+        _ => None
+    };
+
+    let expression = match call {
+        Some(call) => build_ast_from_function_call(call)?,
+        None => get_expr_from_expr_or_term(token)?
+    };
+
+    Ok(ASTNode::ExpressionStatement {
+        expression: Box::new(expression),
+        line: pair.as_span().start_pos().line_col().0,
+        resolved_type: None
+    })
 }
 
 
 /**
  * Takes a `Pair` representing a statement and dispatches it to the relevant AST builder function.
  */
-fn build_ast_from_statement(pair: pest::iterators::Pair<Rule>, symbol_table: &mut SymbolTable) -> ASTNode {
+fn build_ast_from_statement(pair: Pair<Rule>, symbol_table: &mut SymbolTable) -> Result<ASTNode, ParseError> {
     let mut parent = pair.clone().into_inner();
-    let token = parent.next().unwrap();
+    let token = next_token(&mut parent, &pair, "statement body")?;
     match token.as_rule() {
-        Rule::return_stmt => build_ast_from_return_stmt(pair),
-        Rule::var_decl => build_ast_from_var_decl_stmt(pair),
-        Rule::var_assign => build_ast_from_var_assign_stmt(pair),
-        Rule::if_structure => build_ast_from_if_structure(pair.into_inner().next().unwrap(), symbol_table),
-        Rule::function_call => build_ast_from_function_call(pair.into_inner().next().unwrap()),
-        Rule::indef_loop => build_ast_from_indef_loop(pair.into_inner().next().unwrap(), symbol_table),
-        Rule::while_loop => build_ast_from_while_loop(pair.into_inner().next().unwrap(), symbol_table),
-        Rule::for_loop => build_ast_from_for_loop(pair.into_inner().next().unwrap(), symbol_table),
-        Rule::continue_stmt => build_ast_from_loop_ctrl(pair.into_inner().next().unwrap()),
-        Rule::break_stmt => build_ast_from_loop_ctrl(pair.into_inner().next().unwrap()),
-        Rule::print => build_ast_from_print(pair.into_inner().next().unwrap()),
-        _ => panic!("Could not parse statement \"{:?}\"", token.as_rule())
+        Rule::return_stmt => build_ast_from_return_stmt(token),
+        Rule::var_decl => build_ast_from_var_decl_stmt(token),
+        Rule::var_assign => build_ast_from_var_assign_stmt(token),
+        Rule::if_structure => build_ast_from_if_structure(token, symbol_table),
+        Rule::expr_stmt => build_ast_from_expr_stmt(token),
+        Rule::indef_loop => build_ast_from_indef_loop(token, symbol_table),
+        Rule::while_loop => build_ast_from_while_loop(token, symbol_table),
+        Rule::for_loop => build_ast_from_for_loop(token, symbol_table),
+        Rule::for_each_loop => build_ast_from_for_each_loop(token, symbol_table),
+        Rule::repeat_loop => build_ast_from_repeat_loop(token, symbol_table),
+        Rule::continue_stmt => build_ast_from_loop_ctrl(token),
+        Rule::break_stmt => build_ast_from_loop_ctrl(token),
+        Rule::print => build_ast_from_print(token),
+        _ => Err(unknown_statement(&pair, &token))
     }
 }
 
@@ -738,75 +1013,306 @@ fn build_ast_from_statement(pair: pest::iterators::Pair<Rule>, symbol_table: &mu
 /**
  * Takes a `Pair` representing a parameter and returns it as a subtree of the AST, including children nodes.
  */
-fn build_ast_from_param(pair: pest::iterators::Pair<Rule>) -> ASTNode {
-    let mut param = pair.into_inner();
-    let param_type = get_type_from_string(param.next().unwrap().as_str());
-    let param_identifier = param.next().unwrap().as_str().to_owned();
-    ASTNode::Parameter {
+fn build_ast_from_param(pair: Pair<Rule>) -> Result<ASTNode, ParseError> {
+    let mut param = pair.clone().into_inner();
+    let param_type = convert_token(&pair, &next_token(&mut param, &pair, "type")?, "type", get_type_from_string)?;
+    let param_identifier = next_token(&mut param, &pair, "identifier")?.as_str().to_owned();
+    Ok(ASTNode::Parameter {
         param_type: param_type,
-        identifier: param_identifier
-    }
+        identifier: param_identifier,
+        line: pair.as_span().start_pos().line_col().0
+    })
 }
 
 
 /**
  * Takes a `Pair` representing a function and returns it as a subtree of the AST, including children nodes.
  */
-fn build_ast_from_function(pair: pest::iterators::Pair<Rule>, symbol_table:&mut SymbolTable) -> ASTNode {
-    let mut parent = pair.into_inner();
-    let return_type = get_type_from_string(parent.next().unwrap().as_str());
-    let identifier = parent.next().unwrap().as_str().to_owned();
-    let mut parameters = vec![];
-    let mut statements = vec![];
+fn build_ast_from_function(pair: Pair<Rule>, symbol_table:&mut SymbolTable, file:usize) -> Result<ASTNode, ParseError> {
+    let mut parent = pair.clone().into_inner();
+    let (mut inline, mut pure) = (false, false);
+    loop {
+        match parent.peek().map(|token| token.as_rule()) {
+            Some(Rule::inline_annotation) => inline = true,
+            Some(Rule::pure_annotation) => pure = true,
+            _ => break
+        }
 
-    match parent.peek().unwrap().as_rule() {
-        Rule::param_list => {
-            let mut param_list_parent = parent.next().unwrap().into_inner();
-            while let Some(param) = param_list_parent.next() {
-                parameters.push(build_ast_from_param(param));
-            }
-        },
+        parent.next();
+    }
+
+    let return_type = convert_token(&pair, &next_token(&mut parent, &pair, "return type")?, "type", get_type_from_string)?;
+    let identifier_token = next_token(&mut parent, &pair, "function name")?;
+    let (line, _) = identifier_token.as_span().start_pos().line_col();
+    let identifier = identifier_token.as_str().to_owned();
+    let mut parameters = vec![];
 
-        _ => {}
+    if parent.peek().map(|token| token.as_rule()) == Some(Rule::param_list) {
+        for param in next_token(&mut parent, &pair, "parameter list")?.into_inner() {
+            parameters.push(build_ast_from_param(param)?);
+        }
     }
 
     let scope = symbol_table.add();
-    while let Some(statement) = parent.next() {
-        statements.push(build_ast_from_statement(statement, symbol_table));
-    }
+    let statements = build_ast_from_statements(parent, symbol_table)?;
 
-    ASTNode::Function {
+    Ok(ASTNode::Function {
         return_type: return_type,
         identifier: identifier,
         parameters: parameters,
         statements: statements,
-        scope: scope
+        scope,
+        inline,
+        pure,
+        file,
+        line
+    })
+}
+
+
+/**
+ * Checks that no identifier in the program is one of the reserved words, returning an error giving the line
+ * of the first one found. The grammar alone allows most of them, so something like `fn int int()` would
+ * otherwise cause confusing errors later on.
+ */
+fn validate_identifiers(program:&Pair<Rule>) -> Result<(), ReservedIdentifier> {
+    for pair in program.clone().into_inner().flatten() {
+        if pair.as_rule() == Rule::identifier && RESERVED_WORDS.contains(&pair.as_str()) {
+            return Err(ReservedIdentifier {
+                identifier: pair.as_str().to_owned(),
+                line: Some(pair.as_span().start_pos().line_col().0)
+            });
+        }
     }
+
+    Ok(())
 }
 
 
 /**
- * Takes a filename and returns a vector of `ASTNode` structs which represent the AST subtrees of the
- * top-level nodes in the Iridescent AST, such as function declarations, struct definitions, and 
- * include statements.
+ * Checks that brackets and braces are nested no deeper than `limit` in the source, returning an error at the
+ * first one past it. This is done before the source is parsed, as the grammar and every pass over the AST
+ * recurse once for each level, so a deep enough program would otherwise overflow the stack. Brackets inside
+ * string and char literals and comments are not counted.
  */
-pub fn parse(filename:&str) -> Result<Vec<ASTNode>, Box::<dyn Error>> {
-    let program_text = get_file_contents(filename)?;
-    let mut ast = vec![];
+fn check_nesting_depth(source:&str, limit:usize) -> Result<(), NestingTooDeep> {
+    let mut depth:usize = 0;
+    let mut chars = source.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => {
+                chars.by_ref().find(|(_, c)| *c == '"');
+            },
 
-    // get the pairs and skip the program node
-    let pairs = IridescentParser::parse(Rule::program, program_text.as_str())?
-                                        .next().unwrap().into_inner();
-    let mut symbol_table = SymbolTable {entries: vec![]};
-    for pair in pairs {
-        match pair.as_rule() {
-            Rule::function_decl => {
-                ast.push(build_ast_from_function(pair, &mut symbol_table));
+            '\'' => {
+                chars.next();
+                chars.next_if(|(_, c)| *c == '\'');
+            },
+
+            '/' if chars.next_if(|(_, c)| *c == '*').is_some() => {
+                while let Some((_, c)) = chars.next() {
+                    if c == '*' && chars.next_if(|(_, c)| *c == '/').is_some() {
+                        break;
+                    }
+                }
+            },
+
+            '(' | '{' => {
+                depth += 1;
+                if depth > limit {
+                    let line_start = source[..index].rfind('\n').map(|start| start + 1).unwrap_or(0);
+                    return Err(NestingTooDeep {
+                        limit,
+                        line: source[..index].matches('\n').count() + 1,
+                        column: source[line_start..index].chars().count() + 1,
+                        source_line: source[line_start..].lines().next().unwrap_or("").to_owned()
+                    });
+                }
             },
 
+            ')' | '}' => depth = depth.saturating_sub(1),
             _ => {}
         }
     }
 
+    Ok(())
+}
+
+
+/**
+ * Returns the position in the source of each function declaration, being its `fn` keyword or the first of the
+ * annotations before it. `fn` is a reserved word and functions cannot be nested, so every `fn` outside a
+ * string, char or comment starts a function, even after one whose braces do not balance.
+ */
+fn find_function_starts(source:&str) -> Vec<usize> {
+    let mut starts = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => {
+                chars.by_ref().find(|(_, c)| *c == '"');
+            },
+
+            '\'' => {
+                chars.next();
+                chars.next_if(|(_, c)| *c == '\'');
+            },
+
+            '/' if chars.next_if(|(_, c)| *c == '*').is_some() => {
+                while let Some((_, c)) = chars.next() {
+                    if c == '*' && chars.next_if(|(_, c)| *c == '/').is_some() {
+                        break;
+                    }
+                }
+            },
+
+            'f' if source[index..].starts_with("fn") => {
+                let follows_word = source[..index].chars().next_back().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+                let ends_word = source[index + 2..].chars().next().is_none_or(|c| !c.is_ascii_alphanumeric() && c != '_');
+                if follows_word || !ends_word {
+                    continue;
+                }
+
+                let mut start = index;
+                while let Some(annotation) = ["@inline", "@pure"].iter().find(|annotation| source[..start].trim_end().ends_with(*annotation)) {
+                    start = source[..start].trim_end().len() - annotation.len();
+                }
+
+                starts.push(start);
+                chars.next();
+            },
+
+            _ => {}
+        }
+    }
+
+    starts
+}
+
+
+/**
+ * Returns the source with everything outside `range` replaced by spaces, keeping line breaks so that errors
+ * in the range are given the same lines and columns as in the whole source.
+ */
+fn mask_source(source:&str, range:Range<usize>) -> String {
+    source.char_indices()
+          .map(|(index, c)| if range.contains(&index) || c == '\n' { c } else { ' ' })
+          .collect()
+}
+
+
+/**
+ * Takes the source code of a program and returns a vector of `ASTNode` structs which represent the AST
+ * subtrees of the top-level nodes in the Iridescent AST, such as function declarations, struct definitions,
+ * and include statements. Brackets and braces may be nested no deeper than `max_nesting`.
+ */
+pub fn parse_program(program_text:&str, max_nesting:usize) -> Result<Vec<ASTNode>, Box::<dyn Error>> {
+    let (ast, errors) = parse_program_with_errors(program_text, max_nesting)?;
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(ast)
+    }
+}
+
+
+/**
+ * Takes the source code of a program and returns the top-level nodes of every function which could be parsed,
+ * along with the errors found in the others, so that every syntax error in the program can be reported at
+ * once. Errors which stop the program being parsed at all, such as brackets nested deeper than `max_nesting`,
+ * are returned on their own.
+ */
+pub fn parse_program_with_errors(program_text:&str, max_nesting:usize) -> Result<PartialProgram, Box::<dyn Error>> {
+    check_nesting_depth(program_text, max_nesting)?;
+    let mut ast = vec![];
+    let errors = parse_source(program_text, 0, &mut SymbolTable {entries: vec![]}, &mut ast);
+    Ok((ast, errors))
+}
+
+
+/**
+ * Takes the source code of each file of a program split across several files, in the order of the file
+ * table, and returns the top-level nodes of all of them together. Each function records the index of the
+ * file it is in, and scopes are numbered across all the files so that they are unique within the program.
+ * Brackets and braces may be nested no deeper than `max_nesting` in any of the files. An error is returned
+ * along with the index of the file it is in.
+ */
+pub fn parse_sources(sources:&[&str], max_nesting:usize) -> Result<Vec<ASTNode>, (usize, Box::<dyn Error>)> {
+    let mut ast = vec![];
+    let mut symbol_table = SymbolTable {entries: vec![]};
+    for (file, source) in sources.iter().enumerate() {
+        check_nesting_depth(source, max_nesting).map_err(|err| (file, Box::new(err) as Box<dyn Error>))?;
+        if let Some(err) = parse_source(source, file, &mut symbol_table, &mut ast).into_iter().next() {
+            return Err((file, err));
+        }
+    }
+
     Ok(ast)
 }
+
+
+/**
+ * Parses the source code of one file, adding its top-level nodes to `ast` and the scopes it uses to
+ * `symbol_table`, and returns the errors found. If the file does not match the grammar, each function in it
+ * is parsed on its own, with the rest of the file blanked out, so that a syntax error in one function does
+ * not hide those in the functions after it and the functions without any are still added.
+ */
+fn parse_source(source:&str, file:usize, symbol_table:&mut SymbolTable, ast:&mut Vec<ASTNode>) -> Vec<Box::<dyn Error>> {
+    if let Ok(mut pairs) = IridescentParser::parse(Rule::program, source) {
+        return build_ast_from_program(pairs.next(), file, symbol_table, ast);
+    }
+
+    let mut bounds = vec![0];
+    bounds.extend(find_function_starts(source));
+    bounds.push(source.len());
+    bounds.dedup();
+
+    let mut errors = vec![];
+    for section in bounds.windows(2) {
+        let section = mask_source(source, section[0]..section[1]);
+        match IridescentParser::parse(Rule::program, &section) {
+            Ok(mut pairs) => errors.extend(build_ast_from_program(pairs.next(), file, symbol_table, ast)),
+            Err(err) => errors.push(Box::new(err) as Box<dyn Error>)
+        }
+    }
+
+    errors
+}
+
+
+/**
+ * Takes the `Pair` representing a program, or part of one, and adds each function in it to `ast`, returning
+ * the errors found. A function with an error is left out and the functions after it are still added.
+ */
+fn build_ast_from_program(program:Option<Pair<Rule>>, file:usize, symbol_table:&mut SymbolTable, ast:&mut Vec<ASTNode>) -> Vec<Box::<dyn Error>> {
+    let Some(program) = program else {
+        return vec![Box::<dyn Error>::from("Could not parse program: it is empty.")];
+    };
+
+    if let Err(err) = validate_identifiers(&program) {
+        return vec![Box::new(err)];
+    }
+
+    let mut errors:Vec<Box<dyn Error>> = vec![];
+    for pair in program.into_inner() {
+        if pair.as_rule() == Rule::function_decl {
+            match build_ast_from_function(pair, symbol_table, file) {
+                Ok(function) => ast.push(function),
+                Err(err) => errors.push(Box::new(err))
+            }
+        }
+    }
+
+    errors
+}
+
+
+/**
+ * Takes a filename, or `-` to read the program from stdin, and returns a vector of `ASTNode` structs which
+ * represent the AST subtrees of the top-level nodes in the Iridescent AST, such as function declarations,
+ * struct definitions, and include statements, along with the errors found in the functions which could not
+ * be parsed, as described for `parse_program_with_errors`.
+ */
+pub fn parse(filename:&str, max_nesting:usize) -> Result<PartialProgram, Box::<dyn Error>> {
+    let program_text = get_file_contents(filename)?;
+    parse_program_with_errors(&program_text, max_nesting)
+}
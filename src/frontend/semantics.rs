@@ -1,7 +1,36 @@
 use super::ast::*;
+use super::trace::Trace;
+use super::call_graph::CallGraph;
 use crate::errors::*;
 
 use std::error::Error;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+
+/**
+ * The prefix used by every label the compiler generates internally, including those in the assembly
+ * libraries. User identifiers may not start with it, and user functions are labelled `fn_<name>`.
+ */
+pub const RESERVED_PREFIX:&str = "__";
+
+
+/**
+ * The identifier of the hidden variable holding the limit of a for loop when it is only evaluated once. It
+ * cannot be written in a program, so it never clashes with a user variable.
+ */
+pub const HOISTED_LIMIT_IDENTIFIER:&str = "<for limit>";
+
+
+/**
+ * The identifiers and types of the hidden variables a for each loop keeps while it runs, which are the string
+ * being iterated over, the index of the current character and the length of the string. Like the hoisted
+ * limit of a for loop, they cannot be written in a program.
+ */
+const FOR_EACH_HIDDEN_VARIABLES:[(&str, Type); 3] = [
+    ("<for each string>", Type::String),
+    ("<for each index>", Type::Integer),
+    ("<for each length>", Type::Integer)
+];
 
 
 /**
@@ -15,129 +44,190 @@ pub struct SymbolTable {
 
 impl SymbolTable {
     /**
-     * Adds a row to the symbol table. Will panic if a duplicate identifier in an overlapping scope 
-     * is found.
+     * Adds a row to the symbol table. Returns a `DuplicateSymbolError` if the row clashes with an existing
+     * one: two functions may never share an identifier, and two variables may not share an identifier in
+     * the same scope. A variable may share an identifier with a function, and no two blocks may share an
+     * identifier, as each is named after the scope the parser gave it.
      */
-    fn add(&mut self, new_row:SymbolTableRow) {
-        let new_identifier = new_row.get_identifier();
+    fn add(&mut self, new_row:SymbolTableRow) -> Result<(), Box<dyn Error>> {
         for row in &self.rows {
-            if row.get_identifier() == new_row.get_identifier()  && 
-                    row.get_parent_identifier() == new_row.get_parent_identifier() {
-                panic!("Duplicate identifier {} detected", new_identifier);
+            if row.get_identifier() != new_row.get_identifier() {
+                continue;
+            }
+
+            let clashes = match (row, &new_row) {
+                (SymbolTableRow::Function {..}, SymbolTableRow::Function {..}) => true,
+                (SymbolTableRow::Variable {..}, SymbolTableRow::Variable {..}) => {
+                    row.get_parent_scope_id() == new_row.get_parent_scope_id()
+                },
+                (SymbolTableRow::ScopeBlock {..}, SymbolTableRow::ScopeBlock {..}) => true,
+
+                // blocks are only named for the symbol table, so a function or variable may share their name
+                _ => false
+            };
+
+            if clashes {
+                return Err(Box::new(DuplicateSymbolError {
+                    identifier: new_row.get_identifier(),
+                    existing_kind: row.get_kind(),
+                    existing_location: row.get_location(),
+                    existing_line: row.get_line(),
+                    duplicate_kind: new_row.get_kind(),
+                    duplicate_location: new_row.get_location(),
+                    duplicate_line: new_row.get_line()
+                }));
             }
         }
 
         self.rows.push(new_row);
+        Ok(())
     }
 
 
     /**
-     * Finds the highest numbered scope ID in the whole symbol table and returns that ID + 1 as the next
-     * ID to be assigned.
+     * Takes an identifier and an array of the scopes containing the symbol starting broad and moving down, and returns
+     * the row for the symbol in the innermost of those scopes, so that a variable is found in preference to a function
+     * of the same name, and a variable in a nested block in preference to one of the same name in a block enclosing it.
+     * The rows are ranked by how deep their scope is rather than by their order in the table, and no two rows may share
+     * an identifier in the same scope, so the same row is always found. Returns an Error if the identifier is not in scope.
      */
-    fn get_next_scope_id(&self) -> usize {
-        let mut max_id:usize = 1;
-        for row in &self.rows {
-            if row.get_scope_id() >= max_id {
-                max_id = row.get_scope_id() + 1;
-            }
-        }
+    fn get_row_in_scope(&self, identifier:&str, scope_history:&[usize]) -> Result<&SymbolTableRow, Box<dyn Error>> {
+        self.rows.iter()
+                 .filter(|row| row.get_identifier() == identifier)
+                 .filter_map(|row| {
+                     scope_history.iter()
+                                  .position(|scope| *scope == row.get_parent_scope_id())
+                                  .map(|depth| (depth, row))
+                 })
+                 .max_by_key(|(depth, _)| *depth)
+                 .map(|(_, row)| row)
+                 .ok_or_else(|| Box::new(SymbolNotFoundError::new(identifier)) as Box<dyn Error>)
+    }
 
-        max_id
+
+    /**
+     * Takes an identifier and an array of the scopes as in get_row_in_scope(), and returns the scope of the symbol if 
+     * the identifier is in scope, and an Error if not.
+     */
+    fn get_identifier_in_scope(&self, identifier:&str, scope_history:&[usize]) -> Result<usize, Box<dyn Error>> {
+        Ok(self.get_row_in_scope(identifier, scope_history)?.get_scope_id())
     }
 
 
     /**
-     * Takes an identifier and an array of the scopes containing the symbol starting broad and moving down, and returns 
-     * the scope of the symbol if the identifier is in scope, and an Error if not.
+     * Takes an identifier of a function and returns a vector of the types of the parameters of that function. Returns
+     * an error if the identifier was not found or was a variable.
      */
-    fn get_identifier_in_scope(&self, identifier:&str, scope_history:&Vec<usize>) -> Result<usize, Box<dyn Error>> {
+    pub fn get_function_param_types(&self, identifier:&String) -> Result<Vec<Type>, Box<dyn Error + Send + Sync>> {
         for row in &self.rows {
-            if row.get_identifier() == identifier && scope_history.contains(&row.get_parent_scope_id()) {
-                return Ok(row.get_scope_id());
+            if let SymbolTableRow::Function {identifier: function_id, parameters, ..} = row {
+                if function_id == identifier {
+                    return Ok(parameters.clone())
+                }
             }
         }
 
-        Err(Box::new(SymbolNotFoundError(identifier.to_owned())))
+        if self.rows.iter().any(|row| &row.get_identifier() == identifier) {
+            return Err(Box::new(IncorrectDatatype))
+        }
+
+        Err(Box::new(SymbolNotFoundError::new(identifier)))
     }
 
 
     /**
-     * Takes an identifier and an array of the scopes as in get_identifier_in_scope(), and returns the type or return type 
-     * of the symbol if the identifier is in scope, and an Error if not.
+     * Finds the return type of the given function name, returns a `SymbolNotFoundError` if the function
+     * identifier is not found.
      */
-    fn get_identifier_type_in_scope(&self, identifier:&str, scope_history:&Vec<usize>) -> Result<Type, Box<dyn Error>> {
+    pub fn get_func_return_type(&self, identifier:&str) -> Result<Type, SymbolNotFoundError> {
         for row in &self.rows {
-            if row.get_identifier() == identifier && scope_history.contains(&row.get_parent_scope_id()) {
-                return Ok(row.get_scope_type());
+            if &row.get_identifier() == identifier {
+                match row {                    
+                    SymbolTableRow::Function {return_type, ..} => {
+                        return Ok(return_type.clone())
+                    },
+
+                    _ => {}
+                }
             }
         }
 
-        Err(Box::new(SymbolNotFoundError(identifier.to_owned())))
+        Err(SymbolNotFoundError::new(identifier))
     }
 
 
     /**
-     * Takes an identifier and an array of the scopes as in get_identifier_in_scope(), and returns the mutability of the 
-     * symbol if the identifier is in scope, and an Error if not.
+     * Takes a scope history as in get_identifier_in_scope() and returns the identifier of the function
+     * whose scope is part of that history, or `None` if the history is only the global scope.
      */
-    fn get_mutability_in_scope(&self, identifier:&str, scope_history:&Vec<usize>) -> Result<Mutability, Box<dyn Error>> {
+    fn get_function_in_scope_history(&self, scope_history:&[usize]) -> Option<String> {
         for row in &self.rows {
-            if row.get_identifier() == identifier && scope_history.contains(&row.get_parent_scope_id()) {
-                return Ok(row.get_mutability());
+            if let SymbolTableRow::Function {identifier, scope, ..} = row {
+                if scope_history.contains(scope) {
+                    return Some(identifier.to_string());
+                }
             }
         }
 
-        Err(Box::new(SymbolNotFoundError(identifier.to_owned())))
+        None
     }
 
 
     /**
-     * Takes an identifier of a function and returns a vector of the types of the parameters of that function. Returns
-     * an error if the identifier was not found or was a variable.
+     * Finds the identifier of the function with the smallest edit distance to the given identifier, as long
+     * as it is close enough to plausibly be a typo.
      */
-    fn get_function_param_types(&self, identifier:&String) -> Result<Vec<Type>, Box<dyn Error>> {
+    fn get_closest_function_identifier(&self, identifier:&str) -> Option<String> {
+        let max_distance = usize::max(2, identifier.len() / 3);
+        let mut closest:Option<(usize, String)> = None;
         for row in &self.rows {
-            if &row.get_identifier() == identifier {
-                match row {
-                    SymbolTableRow::Variable {..} => {
-                        return Err(Box::new(IncorrectDatatype))
-                    },
-
-                    SymbolTableRow::ScopeBlock {..} => {
-                        return Err(Box::new(IncorrectDatatype))
-                    },
-                    
-                    SymbolTableRow::Function {parameters, ..} => {
-                        return Ok(parameters.clone())
-                    }
+            if let SymbolTableRow::Function {identifier: candidate, ..} = row {
+                let distance = get_edit_distance(identifier, candidate);
+                if distance <= max_distance && closest.as_ref().is_none_or(|(best, _)| distance < *best) {
+                    closest = Some((distance, candidate.to_string()));
                 }
             }
         }
 
-        Err(Box::new(SymbolNotFoundError(identifier.to_owned())))
+        closest.map(|(_, candidate)| candidate)
     }
 
 
     /**
-     * Finds the return type of the given function name, returns a `SymbolNotFoundError` if the function
-     * identifier is not found.
+     * Builds the error for a call to a function which does not exist, naming the function the call was
+     * made from and suggesting a similarly named function if there is one.
      */
-    pub fn get_func_return_type(&self, identifier:&str) -> Result<Type, SymbolNotFoundError> {
-        for row in &self.rows {
-            if &row.get_identifier() == identifier {
-                match row {                    
-                    SymbolTableRow::Function {return_type, ..} => {
-                        return Ok(return_type.clone())
-                    },
+    fn get_function_not_found_error(&self, identifier:&str, scope_history:&[usize]) -> SymbolNotFoundError {
+        SymbolNotFoundError {
+            identifier: identifier.to_owned(),
+            function: self.get_function_in_scope_history(scope_history),
+            suggestion: self.get_closest_function_identifier(identifier)
+        }
+    }
+}
 
-                    _ => {}
-                }
-            }
+
+/**
+ * Calculates the Levenshtein distance between two strings, i.e. the number of single character insertions,
+ * deletions, or substitutions needed to turn one into the other.
+ *
+ * ### Examples
+ * `assert_eq!(get_edit_distance("print_lne", "print_line"), 1);`
+ */
+fn get_edit_distance(a:&str, b:&str) -> usize {
+    let b_chars:Vec<char> = b.chars().collect();
+    let mut previous:Vec<usize> = (0..=b_chars.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current.push(usize::min(substitution, usize::min(previous[j + 1], current[j]) + 1));
         }
 
-        Err(SymbolNotFoundError(identifier.to_owned()))
+        previous = current;
     }
+
+    previous[b_chars.len()]
 }
 
 
@@ -149,11 +239,12 @@ impl SymbolTable {
 pub enum SymbolTableRow {
     Variable {
         identifier: String,
-        function_id: String,
         primitive_type: Type,
         mutability: Mutability,
+        storage: Storage,
         parent_scope: usize,
-        parent: Box<SymbolTableRow>
+        parent: Box<SymbolTableRow>,
+        line: Option<usize>
     },
 
     Function {
@@ -161,7 +252,8 @@ pub enum SymbolTableRow {
         return_type: Type,
         parameters: Vec<Type>,
         scope: usize,
-        parent_scope: usize
+        parent_scope: usize,
+        line: usize
     },
 
     ScopeBlock {
@@ -234,27 +326,79 @@ impl SymbolTableRow {
 
 
     /**
-     * Gets the identifier of the symbol table row if this row is a function, or the identifier of the
-     * parent function if the row is a variable.
+     * Returns a description of what kind of symbol the row represents, for use in error messages
+     */
+    fn get_kind(&self) -> String {
+        match self {
+            SymbolTableRow::Function {..} => "function".to_string(),
+            SymbolTableRow::Variable {..} => "variable".to_string(),
+            SymbolTableRow::ScopeBlock {..} => "scope block".to_string(),
+        }
+    }
+
+
+    /**
+     * Returns the line the symbol is defined on, if it is known. Scope blocks and the hidden variables of loops
+     * have none.
+     */
+    fn get_line(&self) -> Option<usize> {
+        match self {
+            SymbolTableRow::Function {line, ..} => Some(*line),
+            SymbolTableRow::Variable {line, ..} => *line,
+            SymbolTableRow::ScopeBlock {..} => None
+        }
+    }
+
+
+    /**
+     * Returns a description of where the symbol is defined, for use in error messages
      */
-    fn get_parent_identifier(&self) -> String {
+    fn get_location(&self) -> String {
         match self {
-            SymbolTableRow::Function {..} => "global".to_string(),
-            SymbolTableRow::Variable {parent, ..} => parent.get_identifier().to_string(),
-            SymbolTableRow::ScopeBlock {parent, ..} => parent.get_identifier().to_string(),
+            SymbolTableRow::Function {..} => "global scope".to_string(),
+            SymbolTableRow::Variable {parent, ..} | SymbolTableRow::ScopeBlock {parent, ..} => {
+                format!("{} {}", parent.get_kind(), parent.get_identifier())
+            }
         }
     }
 }
 
 
+/**
+ * Adds a row to the symbol table for the block with the given scope inside `parent`, such as the body of an
+ * if statement or a loop, and returns it to be the parent of the rows for the block's contents. The block is
+ * named after its parent and the scope the parser gave it, such as `main_7`, which no other block shares.
+ */
+fn add_scope_block(table:&mut SymbolTable, parent:SymbolTableRow, scope:usize) -> Result<SymbolTableRow, Box<dyn Error>> {
+    let new_row = SymbolTableRow::ScopeBlock {
+        identifier: format!("{}_{}", parent.get_identifier(), scope),
+        parent_scope: parent.get_scope_id(),
+        scope,
+        parent: Box::new(parent)
+    };
+
+    table.add(new_row.clone())?;
+    Ok(new_row)
+}
+
+
+/**
+ * Returns the function or block a parameter or variable is declared in. The grammar only allows these inside
+ * a function, so one without a parent is a bug in the compiler.
+ */
+fn get_declaration_parent(parent:Option<SymbolTableRow>, identifier:&str) -> Result<SymbolTableRow, InternalCompilerError> {
+    parent.ok_or_else(|| InternalCompilerError(format!("the variable {} is not declared inside a function", identifier)))
+}
+
+
 /**
  * Takes an `ASTNode` struct and either generates a row for the symbol table, which is passed by
  * reference, or calls itself recursively on each of that row's children to generate additional 
  * rows for them.
  */
-fn generate_sub_symbol_table(subtree:ASTNode, table:&mut SymbolTable, parent:Option<SymbolTableRow>, func_name:&str) {
+fn generate_sub_symbol_table(subtree:ASTNode, table:&mut SymbolTable, parent:Option<SymbolTableRow>) -> Result<(), Box<dyn Error>> {
     match subtree.clone() {
-        ASTNode::Function {return_type, identifier, statements, parameters, scope} => {
+        ASTNode::Function {return_type, identifier, statements, parameters, scope, line, ..} => {
             let param_types = parameters.clone().into_iter().map(|param| {
                 match param {
                     ASTNode::Parameter {param_type, ..} => param_type,
@@ -264,120 +408,330 @@ fn generate_sub_symbol_table(subtree:ASTNode, table:&mut SymbolTable, parent:Opt
 
             let function_row = SymbolTableRow::Function {
                 identifier: identifier.clone(),
-                return_type: return_type,
+                return_type,
                 parameters: param_types,
                 parent_scope: 0,
-                scope: scope
+                scope,
+                line
             };
-            table.add(function_row.clone());
+            table.add(function_row.clone())?;
 
             for param in parameters {
-                generate_sub_symbol_table(param, table, Some(function_row.clone()), &identifier);
+                generate_sub_symbol_table(param, table, Some(function_row.clone()))?;
             }
 
             for statement in statements {
-                generate_sub_symbol_table(statement, table, Some(function_row.clone()), &identifier);
+                generate_sub_symbol_table(statement, table, Some(function_row.clone()))?;
             }
         },
 
-        ASTNode::Parameter {param_type, identifier} => {
+        ASTNode::Parameter {param_type, identifier, line} => {
+            let parent = get_declaration_parent(parent, &identifier)?;
             table.add(
                 SymbolTableRow::Variable {
-                    identifier: identifier,
-                    function_id: func_name.to_string(),
+                    identifier,
                     primitive_type: param_type,
                     mutability: Mutability::Constant,
-                    parent_scope: parent.clone().unwrap().get_scope_id(),
-                    parent: Box::new(parent.expect(&format!("Statement {:?} does not have a parent.", subtree)))
+                    storage: Storage::Frame,
+                    parent_scope: parent.get_scope_id(),
+                    parent: Box::new(parent),
+                    line: Some(line)
                 }
-            )
+            )?;
         }
 
-        ASTNode::VarDeclStatement {var_type, mutability, identifier, ..} => {
+        ASTNode::VarDeclStatement {var_type, mutability, storage, identifier, line, ..} => {
+            let parent = get_declaration_parent(parent, &identifier)?;
             table.add(
                 SymbolTableRow::Variable {
-                    identifier: identifier,
-                    function_id: func_name.to_string(),
+                    identifier,
                     primitive_type: var_type,
-                    mutability: mutability,
-                    parent_scope: parent.clone().unwrap().get_scope_id(),
-                    parent: Box::new(parent.expect(&format!("Statement {:?} does not have a parent.", subtree)))
+                    mutability,
+                    storage,
+                    parent_scope: parent.get_scope_id(),
+                    parent: Box::new(parent),
+                    line: Some(line)
                 }
-            )
+            )?;
         },
 
         ASTNode::IfElifElseStatement {statements} => {
             for statement in statements {
-                generate_sub_symbol_table(statement, table, parent.clone(), func_name);
+                generate_sub_symbol_table(statement, table, parent.clone())?;
             }
         },
 
-        ASTNode::IfStatement {statements, scope, ..} => {
-            let scope_id = table.get_next_scope_id();
-            let parent_struct = parent.clone().unwrap();
-            let new_row = SymbolTableRow::ScopeBlock {
-                identifier: format!("{}_{}", parent_struct.get_identifier(), scope_id),
-                parent_scope: parent_struct.get_scope_id(),
-                scope: scope,
-                parent: Box::new(parent_struct)
-            };
-
-            table.add(new_row.clone());
-
+        ASTNode::IfStatement {statements, scope, ..}
+          | ASTNode::ElseStatement {statements, scope}
+          | ASTNode::IndefLoop {statements, scope}
+          | ASTNode::WhileLoop {statements, scope, ..} => {
+            let new_row = add_scope_block(table, parent.unwrap(), scope)?;
             for statement in statements {
-                generate_sub_symbol_table(statement, table, Some(new_row.clone()), func_name);
+                generate_sub_symbol_table(statement, table, Some(new_row.clone()))?;
             }
         },
 
-        ASTNode::ForLoop {statements, scope, control_identifier, control_type, ..} => {
+        ASTNode::ForLoop {statements, scope, control_identifier, control_type, control_mutability, limit, line, ..} => {
             match control_type {
                 Type::Integer | Type::Long | Type::Byte => {},
                 other => panic!("For loop control variable must be int or long, not {:?}", other)
             }
 
-            // TODO: extract some of this to a separate function as it is repeated  in the IfStatement block
-            let scope_id = table.get_next_scope_id();
-            let parent_struct = parent.clone().unwrap();
-            let new_row = SymbolTableRow::ScopeBlock {
-                identifier: format!("{}_{}", parent_struct.get_identifier(), scope_id),
-                parent_scope: parent_struct.get_scope_id(),
-                scope: scope,
-                parent: Box::new(parent_struct)
-            };
-
-            table.add(new_row.clone());
+            let limit_is_invariant = is_loop_invariant(&limit, &control_identifier, &statements);
 
+            let new_row = add_scope_block(table, parent.unwrap(), scope)?;
             table.add(
                 SymbolTableRow::Variable {
                     identifier: control_identifier,
-                    function_id: func_name.to_owned(),
-                    primitive_type: control_type,
-                    mutability: Mutability::Mutable,
-                    parent_scope: scope_id,
-                    parent: Box::new(new_row.clone())
+                    primitive_type: control_type.clone(),
+                    mutability: control_mutability,
+                    storage: Storage::Frame,
+                    parent_scope: scope,
+                    parent: Box::new(new_row.clone()),
+                    line: Some(line)
+                }
+            )?;
+
+            // a limit which is only evaluated once is kept in a hidden variable, which needs space in the frame
+            if limit_is_invariant {
+                table.add(
+                    SymbolTableRow::Variable {
+                        identifier: HOISTED_LIMIT_IDENTIFIER.to_owned(),
+                        primitive_type: control_type,
+                        mutability: Mutability::Constant,
+                        storage: Storage::Frame,
+                        parent_scope: scope,
+                        parent: Box::new(new_row.clone()),
+                        line: None
+                    }
+                )?;
+            }
+
+            for statement in statements {
+                generate_sub_symbol_table(statement, table, Some(new_row.clone()))?;
+            }
+        },
+
+        ASTNode::ForEachLoop {element_type, element_identifier, statements, scope, line, ..} => {
+            let new_row = add_scope_block(table, parent.unwrap(), scope)?;
+            table.add(
+                SymbolTableRow::Variable {
+                    identifier: element_identifier,
+                    primitive_type: element_type,
+                    mutability: Mutability::Constant,
+                    storage: Storage::Frame,
+                    parent_scope: scope,
+                    parent: Box::new(new_row.clone()),
+                    line: Some(line)
                 }
-            );
+            )?;
+
+            for (identifier, primitive_type) in FOR_EACH_HIDDEN_VARIABLES {
+                table.add(
+                    SymbolTableRow::Variable {
+                        identifier: identifier.to_owned(),
+                        primitive_type,
+                        mutability: Mutability::Constant,
+                        storage: Storage::Frame,
+                        parent_scope: scope,
+                        parent: Box::new(new_row.clone()),
+                        line: None
+                    }
+                )?;
+            }
 
             for statement in statements {
-                generate_sub_symbol_table(statement, table, Some(new_row.clone()), func_name);
+                generate_sub_symbol_table(statement, table, Some(new_row.clone()))?;
             }
         }
 
         _ => {}
     };
+
+    Ok(())
+}
+
+
+/**
+ * Returns the types of operand each operator can be applied to, which are the types the backends can generate
+ * code for.
+ */
+pub fn get_operator_types(operator:&Operator) -> &'static [Type] {
+    match operator {
+        Operator::Addition => &[Type::Byte, Type::Integer, Type::Long, Type::Float, Type::Double, Type::String],
+        Operator::Subtraction
+          | Operator::Multiplication
+          | Operator::Division => &[Type::Byte, Type::Integer, Type::Long, Type::Float, Type::Double],
+        Operator::NegateNumerical => &[Type::Integer, Type::Long, Type::Float, Type::Double],
+        Operator::NegateLogical => &[Type::Boolean],
+        Operator::And
+          | Operator::Or
+          | Operator::XOr
+          | Operator::Complement
+          | Operator::LeftShiftLogical
+          | Operator::RightShiftArithmetic
+          | Operator::RightShiftLogical => &[Type::Byte, Type::Integer, Type::Long]
+    }
+}
+
+
+/**
+ * Returns the types of argument each intrinsic function has a version for.
+ */
+pub fn get_intrinsic_types(intrinsic:&Intrinsic) -> &'static [Type] {
+    match intrinsic {
+        Intrinsic::Pow => &[Type::Integer, Type::Long],
+        Intrinsic::Round | Intrinsic::Floor | Intrinsic::Ceil => &[Type::Float, Type::Double],
+        Intrinsic::Alloc => &[Type::Integer],
+        Intrinsic::Abs | Intrinsic::Min | Intrinsic::Max => &[Type::Integer, Type::Long, Type::Float, Type::Double]
+    }
+}
+
+
+/**
+ * Returns the types of value each comparison can be made between. Values of any type can be compared for
+ * equality, but only numbers can be ordered.
+ */
+pub fn get_comparison_types(operator:&BooleanOperator) -> &'static [Type] {
+    match operator {
+        BooleanOperator::Equal | BooleanOperator::NotEqual => {
+            &[Type::Byte, Type::Integer, Type::Long, Type::Char, Type::Boolean, Type::Float, Type::Double, Type::String]
+        },
+
+        BooleanOperator::Greater
+          | BooleanOperator::GreaterOrEqual
+          | BooleanOperator::Less
+          | BooleanOperator::LessOrEqual => &[Type::Byte, Type::Integer, Type::Long, Type::Float, Type::Double],
+        BooleanOperator::Invert => &[Type::Boolean]
+    }
+}
+
+
+/**
+ * Returns the types a value of the given type can be cast into.
+ */
+pub fn get_cast_types(from:&Type) -> &'static [Type] {
+    match from {
+        Type::Integer => &[Type::Byte, Type::Long, Type::Float, Type::Double, Type::String],
+        Type::Long => &[Type::Integer],
+        Type::Byte => &[Type::Integer, Type::Long, Type::Float, Type::Double, Type::Char, Type::String],
+        Type::Float => &[Type::Integer, Type::Byte, Type::Double],
+        Type::Double => &[Type::Byte, Type::Integer, Type::Float],
+        Type::Char => &[Type::Byte],
+        Type::String => &[Type::Integer, Type::Byte],
+        Type::Boolean | Type::Void => &[]
+    }
+}
+
+
+/**
+ * Returns the types of value which can be printed in the given format. Strings, chars, bytes and ints can be
+ * printed, only bytes and ints can be printed in hex, and floats can only be printed to a number of decimal
+ * places.
+ */
+pub fn get_printable_types(format:&PrintFormat) -> &'static [Type] {
+    match format {
+        PrintFormat::Plain => &[Type::String, Type::Char, Type::Byte, Type::Integer],
+        PrintFormat::Hex => &[Type::Byte, Type::Integer],
+        PrintFormat::Precision(_) => &[Type::Float]
+    }
+}
+
+
+/**
+ * Checks that the given operator can be applied to operands of the given type, so that code generation
+ * never sees a combination it has no code for, such as complementing a float.
+ */
+fn validate_operator_type(operator:&Operator, operand_type:&Type) -> Result<(), Box<dyn Error>> {
+    match get_operator_types(operator).contains(operand_type) {
+        true => Ok(()),
+        false => Err(Box::new(UnsupportedOperatorType {
+            operator: get_operator_symbol(operator),
+            operand_type: operand_type.clone(),
+            line: None
+        }))
+    }
+}
+
+
+/**
+ * Adds the line of the statement being validated to an error about an operator being applied to the wrong
+ * type or to mismatched types, as the expressions it is found in do not record their line. Any other error is returned unchanged.
+ */
+fn locate_operator_error(err:Box<dyn Error>, line:usize) -> Box<dyn Error> {
+    let err = match err.downcast::<UnsupportedOperatorType>() {
+        Ok(mut err) => {
+            err.line.get_or_insert(line);
+            return err;
+        },
+
+        Err(err) => err
+    };
+
+    match err.downcast::<MismatchedOperandTypes>() {
+        Ok(mut err) => {
+            err.line.get_or_insert(line);
+            err
+        },
+
+        Err(err) => err
+    }
+}
+
+
+/**
+ * Returns the row for an identifier in the innermost of the given scopes, as `SymbolTable::get_row_in_scope`
+ * does, and writes the lookup to the trace with the scopes searched and what was found.
+ */
+fn lookup_in_scope<'a>(symbol_table:&'a SymbolTable, identifier:&str, scope_history:&[usize], trace:&Trace) -> Result<&'a SymbolTableRow, Box<dyn Error>> {
+    let row = symbol_table.get_row_in_scope(identifier, scope_history);
+    trace.log(|| {
+        let found = match &row {
+            Ok(SymbolTableRow::Variable {primitive_type, parent_scope, ..}) => format!("found {} variable in scope {}", primitive_type, parent_scope),
+            Ok(SymbolTableRow::Function {return_type, ..}) => format!("found function returning {}", return_type),
+            Ok(SymbolTableRow::ScopeBlock {..}) => String::from("found scope block"),
+            Err(_) => String::from("not found")
+        };
+
+        format!("lookup {} in scopes {:?}: {}", identifier, scope_history, found)
+    });
+
+    row
+}
+
+
+/**
+ * Returns the name of the kind of node given for the trace, along with the identifier it names, declares or
+ * assigns to and its line where it has them.
+ */
+fn describe_node(node:&ASTNode) -> String {
+    match node {
+        ASTNode::Function {identifier, ..} => format!("function {}", identifier),
+        ASTNode::Identifier(identifier) => format!("identifier {}", identifier),
+        ASTNode::FunctionCall {identifier, ..} => format!("call to {}", identifier),
+        ASTNode::VarDeclStatement {identifier, line, ..}
+          | ASTNode::VarAssignStatement {identifier, line, ..} => format!("{} of {} on line {}", get_node_name(node), identifier, line),
+        ASTNode::ExpressionStatement {line, ..}
+          | ASTNode::IfStatement {line, ..}
+          | ASTNode::WhileLoop {line, ..}
+          | ASTNode::ForLoop {line, ..}
+          | ASTNode::ForEachLoop {line, ..} => format!("{} on line {}", get_node_name(node), line),
+        other => get_node_name(other).to_owned()
+    }
 }
 
 
 /**
  * Verifies that the given expression node has a child of the correct type
  */
-fn validate_term_of_type(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<(), Box<dyn Error>> {
+fn validate_term_of_type(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<(), Box<dyn Error>> {
     match node {
         ASTNode::Term { child } => {
             match &**child {
                 ASTNode::Expression {..} => {
-                    match validate_expression_of_type(&*child, &required_type, symbol_table, scope_history) {
+                    match validate_expression_of_type(child, required_type, symbol_table, scope_history, trace) {
                         Ok(_) => {},
+                        Err(err) if err.is::<VoidValueUsed>() || err.is::<UnsupportedOperatorType>() || err.is::<UnsupportedCast>() => return Err(err),
                         Err(_) => {
                             return Err(Box::new(IncorrectDatatype)); 
                         }
@@ -391,22 +745,30 @@ fn validate_term_of_type(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
                 },
 
                 ASTNode::Identifier(identifier) => {
-                    if &symbol_table.get_identifier_type_in_scope(identifier, scope_history)? != required_type {
+                    if &lookup_in_scope(symbol_table, identifier, scope_history, trace)?.get_scope_type() != required_type {
                         return Err(Box::new(IncorrectDatatype));
                     }
                 },
 
-                ASTNode::FunctionCall {identifier, ..} => {
-                    if &symbol_table.get_identifier_type_in_scope(identifier, &vec![0]).unwrap() != required_type {
+                ASTNode::FunctionCall {..} => {
+                    validate_not_void_call(child, symbol_table, "an expression")?;
+                    let return_type = validate_function_call(child, symbol_table, scope_history, trace)?;
+                    if &return_type != required_type {
                         return Err(Box::new(IncorrectDatatype));
                     }
                 },
 
                 ASTNode::TypeCast {into, ..} => {
-                    semantic_validation_subtree(child, symbol_table, scope_history).unwrap();
+                    semantic_validation_subtree(child, symbol_table, scope_history, trace)?;
                     if into != required_type {
                         return Err(Box::new(IncorrectDatatype));
                     }
+                },
+
+                ASTNode::SizeOf(_) | ASTNode::TypeOf(_) => {
+                    if &find_valid_type_of_node(child, symbol_table, scope_history, trace)? != required_type {
+                        return Err(Box::new(IncorrectDatatype));
+                    }
                 }
 
                 _ => panic!("{:?} is not a valid token for semantic analysis of terms.", node)
@@ -425,37 +787,25 @@ fn validate_term_of_type(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
  * semantically valid (i.e. everything is of the same datatype and datatype is valid for the 
  * operation) - otherwise will return an Error.
  */
-fn validate_expression_of_type(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<(), Box<dyn Error>> {
+fn validate_expression_of_type(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<(), Box<dyn Error>> {
     match &node {
         ASTNode::Expression {lhs, rhs, operator} => {
-            validate_term_of_type(lhs, required_type, symbol_table, &scope_history).unwrap();
+            validate_term_of_type(lhs, required_type, symbol_table, scope_history, trace)?;
             match &rhs {
                 None => {},
                 Some(term) => {
-                    validate_term_of_type(term, required_type, symbol_table, &scope_history).unwrap();
+                    validate_term_of_type(term, required_type, symbol_table, scope_history, trace)?;
                 }
             }
 
             // check that operator arg types are valid for operator (e.g. cannot do true - false or "hello" / "world")
             // we already have validated that the args are the "required_type"
-            match operator {
-                None => {},
-                Some(op) => {
-                    match required_type {
-                        Type::Char => panic!("{:?} is not a valid operator for arguments of type {:?}", op, required_type),
-                        
-                        Type::String => {
-                            if op != &Operator::Addition {
-                                panic!("Only the '+' operator (concatenation) can be applied to a string");
-                            }
-                        }
-                        
-                        _ => {}
-                    }
-                } 
+            if let Some(op) = operator {
+                validate_operator_type(op, required_type)?;
             }
         },
 
+        ASTNode::TernaryExpression {..} => validate_ternary_expression(node, symbol_table, scope_history, trace, required_type)?,
         _ => panic!("{:?} is not an expression", node)
     };
 
@@ -465,46 +815,69 @@ fn validate_expression_of_type(node:&ASTNode, required_type:&Type, symbol_table:
 
 /**
  * Checks that an `Expression`, `Term`, `Value`, or `Identifier` AST node is valid according  to 
- * the datatypes of its children and panics if it is not. Otherwise returns the type that the node 
+ * the datatypes of its children and returns an error if it is not. Otherwise returns the type that the node 
  * would have if evaluated or passed to a higher expression or term.
  */
-pub fn find_valid_type_of_node(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<Type, Box<dyn Error>> {
-    match node {
-        ASTNode::Expression {lhs, rhs, ..} => {
-            let lhs_type = find_valid_type_of_node(lhs, symbol_table, scope_history).unwrap();
-            match rhs {
-                None => {},
-                Some(rhs) => {
-                    let rhs_type = find_valid_type_of_node(rhs, symbol_table, scope_history).unwrap();
-                    if (lhs_type == rhs_type && lhs_type != Type::Char) 
-                        || (lhs_type == Type::String && rhs_type == Type::Char)
-                        || (rhs_type == Type::Char && lhs_type == Type::String) {
-                        return Ok(lhs_type);
-                    } else {
-                        panic!("Boolean term validation found term of {:?} and {:?} mismatched datatypes", lhs_type, rhs_type)
-                    }
+pub fn find_valid_type_of_node(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    let found = match node {
+        ASTNode::Expression {lhs, rhs, operator} => {
+            let lhs_type = find_valid_type_of_node(lhs, symbol_table, scope_history, trace)?;
+            if let Some(operator) = operator {
+                validate_operator_type(operator, &lhs_type)?;
+            }
+
+            if let (Some(rhs), Some(operator)) = (rhs, operator) {
+                let rhs_type = find_valid_type_of_node(rhs, symbol_table, scope_history, trace)?;
+                if !((lhs_type == rhs_type && lhs_type != Type::Char) || (lhs_type == Type::String && rhs_type == Type::Char)) {
+                    return Err(Box::new(MismatchedOperandTypes {operator: get_operator_symbol(operator), lhs_type, rhs_type, line: None}));
                 }
             }
 
             Ok(lhs_type)
         },
 
-        ASTNode::Term {child} => find_valid_type_of_node(child, symbol_table, scope_history),
+        ASTNode::Term {child} => find_valid_type_of_node(child, symbol_table, scope_history, trace),
         ASTNode::Value {literal_type, ..} => Ok(literal_type.clone()),
-        ASTNode::Identifier(identifier) => symbol_table.get_identifier_type_in_scope(identifier, scope_history),
+        ASTNode::Identifier(identifier) => lookup_in_scope(symbol_table, identifier, scope_history, trace).map(SymbolTableRow::get_scope_type),
+        ASTNode::FunctionCall {..} => validate_function_call(node, symbol_table, scope_history, trace),
+        ASTNode::TypeCast {into, ..} => {
+            semantic_validation_subtree(node, symbol_table, scope_history, trace)?;
+            Ok(into.clone())
+        },
+
+        // the variable must be in scope even though only its type is used
+        ASTNode::SizeOf(identifier) => {
+            lookup_in_scope(symbol_table, identifier, scope_history, trace)?;
+            Ok(Type::Integer)
+        },
+
+        ASTNode::TypeOf(identifier) => {
+            lookup_in_scope(symbol_table, identifier, scope_history, trace)?;
+            Ok(Type::String)
+        },
+
         unknown => panic!("{:?} is not a valid token in an expression", unknown)
+    };
+
+    // a term only passes on the type of its child, which has already been traced
+    if let (Ok(found_type), false) = (&found, matches!(node, ASTNode::Term {..})) {
+        trace.log(|| format!("type of {} is {}", describe_node(node), found_type));
     }
+
+    found
 }
 
 
-fn check_if_type_numeric(primitive_type:&Type) -> bool {
-    match primitive_type {
-        Type::Integer
-         | Type::Long
-         | Type::Byte
-         | Type::Float
-         | Type::Double => true,
-         _ => return false
+/**
+ * Returns the type of the value given by an expression used as a statement, which is the type of its first
+ * operand as every operand must have the same type.
+ */
+fn find_discarded_value_type(expression:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    match expression {
+        ASTNode::Expression {lhs: first, ..}
+          | ASTNode::Term {child: first}
+          | ASTNode::TernaryExpression {if_true: first, ..} => find_discarded_value_type(first, symbol_table, scope_history, trace),
+        other => find_valid_type_of_node(other, symbol_table, scope_history, trace)
     }
 }
 
@@ -514,30 +887,37 @@ fn check_if_type_numeric(primitive_type:&Type) -> bool {
  * to be used on a pair of integer arguments.
  * 
  * ### Examples
- * `validate_boolean_operator_with_args(&Type::Integer, &Type::Integer, &BooleanOperator::GreaterThan); // does not panic`
+ * `validate_boolean_operator_with_args(&Type::Integer, &Type::Integer, &BooleanOperator::GreaterThan); // Ok(())`
  * 
- * `validate_boolean_operator_with_args(&Type::Integer, &Type::Boolean, &BooleanOperator::Equal); // panics`
+ * `validate_boolean_operator_with_args(&Type::Integer, &Type::Boolean, &BooleanOperator::Equal); // MismatchedOperandTypes`
  */
 fn validate_boolean_operator_with_args(lhs_type:&Type, rhs_type:&Type, operator:&BooleanOperator) -> Result<(), Box<dyn Error>> {
     match operator {
-        // 2 arguments can be any datatype except void
-        BooleanOperator::Equal | BooleanOperator::NotEqual => {
-            if (lhs_type != rhs_type) || lhs_type == &Type::Void {
-                panic!("{:?} and {:?} are not valid datatype arguments for boolean operator {:?}", lhs_type, rhs_type, operator)
+        // 2 arguments of the same type, which must be one the comparison can be made on
+        BooleanOperator::Equal
+          | BooleanOperator::NotEqual
+          | BooleanOperator::Greater
+          | BooleanOperator::GreaterOrEqual
+          | BooleanOperator::Less
+          | BooleanOperator::LessOrEqual => {
+            if lhs_type != rhs_type {
+                return Err(Box::new(MismatchedOperandTypes {
+                    operator: get_boolean_operator_symbol(operator),
+                    lhs_type: lhs_type.clone(),
+                    rhs_type: rhs_type.clone(),
+                    line: None
+                }));
             }
-        },
 
-        // must have 2 numeric arguments
-        BooleanOperator::Greater | BooleanOperator::GreaterOrEqual | BooleanOperator::Less | BooleanOperator::LessOrEqual => {
-            if (lhs_type != rhs_type) || !check_if_type_numeric(lhs_type) {
-                panic!("{:?} and {:?} are not valid datatype arguments for boolean operator {:?}", lhs_type, rhs_type, operator)
+            if !get_comparison_types(operator).contains(lhs_type) {
+                return Err(Box::new(UnsupportedOperatorType {operator: get_boolean_operator_symbol(operator), operand_type: lhs_type.clone(), line: None}));
             }
         },
 
         // 1 boolean argument
         BooleanOperator::Invert => {
             if lhs_type != &Type::Boolean || rhs_type != &Type::Void {
-                panic!("{:?} is not a valid argument for boolean operator {:?}", lhs_type, operator)
+                return Err(Box::new(UnsupportedOperatorType {operator: get_boolean_operator_symbol(operator), operand_type: lhs_type.clone(), line: None}));
             }
         },
     }
@@ -547,57 +927,76 @@ fn validate_boolean_operator_with_args(lhs_type:&Type, rhs_type:&Type, operator:
 
 
 /**
- * Takes an `ASTNode` representing a boolean term and checks that it and its children are valid (e.g. correct 
- * datatypes and returns a boolean)
+ * The type of a boolean term once it has been validated, along with whether it is the result of comparing
+ * two values rather than a value which is a bool in its own right, such as a variable or function call.
  */
-fn validate_boolean_term(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<Type, Box<dyn Error>> {    
-    let lhs_type:Option<Type>;
-    let mut rhs_type:Option<Type> = None;
-    match node {
-        ASTNode::BooleanTerm {lhs, rhs, operator} => {
-            match &**lhs {
-                ASTNode::BooleanTerm {..} => {
-                    lhs_type = Some(validate_boolean_term(lhs, required_type, symbol_table, scope_history).unwrap());
-                },
+struct BooleanTermType {
+    value_type: Type,
+    is_comparison: bool
+}
 
-                ASTNode::Term {..} => {
-                    let term_type = find_valid_type_of_node(lhs, symbol_table, scope_history).unwrap();
-                    validate_term_of_type(lhs, &term_type, symbol_table, scope_history).unwrap();
-                    lhs_type = Some(term_type);
-                },
 
-                unknown => panic!("{:?} is not a valid token in a boolean term", unknown)
-            };
+/**
+ * Checks that a comparison is not applied to the result of another comparison, as in `((a, b)<, c)<`, which
+ * is most likely meant to be `((a, b)<, (b, c)<)&&`. Checking that two comparison results are equal is
+ * allowed, as both sides are bools.
+ */
+fn validate_not_chained(lhs:&BooleanTermType, rhs:&BooleanTermType, operator:&BooleanOperator) -> Result<(), Box<dyn Error>> {
+    let chained = match operator {
+        BooleanOperator::Invert => false,
+        BooleanOperator::Equal | BooleanOperator::NotEqual => {
+            (lhs.is_comparison && rhs.value_type != Type::Boolean) || (rhs.is_comparison && lhs.value_type != Type::Boolean)
+        },
+        _ => lhs.is_comparison || rhs.is_comparison
+    };
 
-            match rhs {
-                Some(rhs) => {
-                    match &**rhs {
-                        ASTNode::BooleanTerm {..} => {
-                            rhs_type = Some(validate_boolean_term(rhs, required_type, symbol_table, scope_history).unwrap());
-                        }
-                        ASTNode::Term {..} => {
-                            let term_type = find_valid_type_of_node(rhs, symbol_table, scope_history).unwrap();
-                            validate_term_of_type(rhs, &term_type, symbol_table, scope_history).unwrap();
-                            rhs_type = Some(term_type);
-                        },
-        
-                        unknown => panic!("{:?} is not a valid token in a boolean term", unknown)
-                    };
-                },
+    match chained {
+        true => Err(Box::new(ChainedComparison {operator: get_boolean_operator_symbol(operator)})),
+        false => Ok(())
+    }
+}
 
-                None => {}
-            }
+
+/**
+ * Takes an `ASTNode` representing a boolean term and checks that it and its children are valid (e.g. correct 
+ * datatypes and returns a boolean). Returns the type of the term and whether it is the result of a comparison.
+ */
+fn validate_boolean_term(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<BooleanTermType, Box<dyn Error>> {    
+    let validate_child = |child:&ASTNode| -> Result<BooleanTermType, Box<dyn Error>> {
+        match child {
+            ASTNode::BooleanTerm {..} => validate_boolean_term(child, symbol_table, scope_history, trace),
+            ASTNode::Term {..} => {
+                validate_not_void_call(child, symbol_table, "a condition")?;
+                let term_type = find_valid_type_of_node(child, symbol_table, scope_history, trace)?;
+                validate_term_of_type(child, &term_type, symbol_table, scope_history, trace)?;
+                Ok(BooleanTermType {value_type: term_type, is_comparison: false})
+            },
+
+            unknown => panic!("{:?} is not a valid token in a boolean term", unknown)
+        }
+    };
+
+    match node {
+        ASTNode::BooleanTerm {lhs, rhs, operator} => {
+            let lhs_type = validate_child(lhs)?;
+            let rhs_type = match rhs {
+                Some(rhs) => validate_child(rhs)?,
+                None => BooleanTermType {value_type: Type::Void, is_comparison: false}
+            };
 
             // if there is an operator, check it is valid for the argument types and return the boolean type as this is a true
             // boolean term, not just leading to a value
             match operator {
                 Some(operator) => {
-                    let lhs_type = lhs_type.unwrap_or(Type::Void);
-                    validate_boolean_operator_with_args(&lhs_type, &rhs_type.unwrap_or(Type::Void), &operator).unwrap();
-                    Ok(Type::Boolean)
+                    validate_not_chained(&lhs_type, &rhs_type, operator)?;
+                    validate_boolean_operator_with_args(&lhs_type.value_type, &rhs_type.value_type, operator)?;
+                    Ok(BooleanTermType {
+                        value_type: Type::Boolean,
+                        is_comparison: operator != &BooleanOperator::Invert || lhs_type.is_comparison
+                    })
                 }
 
-                None => Ok(lhs_type.unwrap_or(Type::Void))
+                None => Ok(lhs_type)
             }
         },
 
@@ -607,34 +1006,61 @@ fn validate_boolean_term(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
 
 
 /**
- * Takes an `ASTNode` representing a boolean expression and checks it and its children are valid (i.e. 
- * correct datatypes).
+ * Checks that the value given to a variable in a declaration or assignment is valid and has the type of the
+ * variable. Comparisons and boolean connectives can only be given to bool variables.
  */
-fn validate_boolean_expr(node:&ASTNode, required_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<Type, Box<dyn Error>> {
-    let lhs_type:Type;
-    let mut rhs_type:Option<Type> = None;
+fn validate_assigned_value(value:&ASTNode, var_type:&Type, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<(), Box<dyn Error>> {
+    match value {
+        ASTNode::Expression {..} => validate_expression_of_type(value, var_type, symbol_table, scope_history, trace),
+        ASTNode::TernaryExpression {..} => validate_ternary_expression(value, symbol_table, scope_history, trace, var_type),
+        ASTNode::BooleanExpression {..} => {
+            match &validate_boolean_expr(value, symbol_table, scope_history, trace)? == var_type {
+                true => Ok(()),
+                false => Err(Box::new(IncorrectDatatype))
+            }
+        },
+
+        ASTNode::InputStatement(input_type, _) => {
+            if input_type != var_type {
+                return Err(Box::new(IncorrectDatatype));
+            }
+
+            semantic_validation_subtree(value, symbol_table, scope_history, trace)
+        },
+        other => panic!("{:?} is not a valid value for a variable", other)
+    }
+}
+
+
+/**
+ * Takes an `ASTNode` representing a boolean expression and checks it and its children are valid (i.e. 
+ * correct datatypes).
+ */
+fn validate_boolean_expr(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    let lhs_type:Type;
+    let mut rhs_type:Option<Type> = None;
     match node {
         ASTNode::BooleanExpression {lhs, rhs, connector, operator} => {
             match &**lhs {
                 ASTNode::BooleanExpression {..} => {
-                    lhs_type = validate_boolean_expr(lhs, required_type, symbol_table, scope_history).unwrap();
+                    lhs_type = validate_boolean_expr(lhs, symbol_table, scope_history, trace)?;
                 },
                 ASTNode::BooleanTerm {..} => {
-                    lhs_type = validate_boolean_term(lhs, required_type, symbol_table, scope_history).unwrap();
+                    lhs_type = validate_boolean_term(lhs, symbol_table, scope_history, trace)?.value_type;
                 },
-                unknown => panic!("{:?} is not a valid argument to a boolean expression", unknown)
+                unknown => return Err(Box::new(InternalCompilerError(format!("a {} is not a valid argument to a boolean expression", get_node_name(unknown)))))
             }
 
             match rhs {
                 Some(rhs) => {
                     match &**rhs {
                         ASTNode::BooleanExpression {..} => {
-                            rhs_type = Some(validate_boolean_expr(rhs, required_type, symbol_table, scope_history).unwrap());
+                            rhs_type = Some(validate_boolean_expr(rhs, symbol_table, scope_history, trace)?);
                         },
                         ASTNode::BooleanTerm {..} => {
-                            rhs_type = Some(validate_boolean_term(rhs, required_type, symbol_table, scope_history).unwrap());
+                            rhs_type = Some(validate_boolean_term(rhs, symbol_table, scope_history, trace)?.value_type);
                         },
-                        unknown => panic!("{:?} is not a valid argument to a boolean expression", unknown)
+                        unknown => return Err(Box::new(InternalCompilerError(format!("a {} is not a valid argument to a boolean expression", get_node_name(unknown)))))
                     };
                 },
 
@@ -643,9 +1069,15 @@ fn validate_boolean_expr(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
 
             // check that if there is a boolean connector, both the arguments are booleans
             match connector {
-                Some(_) => {
-                    if lhs_type != Type::Boolean || rhs_type.clone().unwrap_or(Type::Boolean) != Type::Boolean {
-                        panic!("{:?} and {:?} are not valid arguments for a boolean expression", lhs_type, rhs_type.unwrap_or(Type::Void))
+                Some(connector) => {
+                    for operand_type in [&lhs_type, rhs_type.as_ref().unwrap_or(&Type::Boolean)] {
+                        if operand_type != &Type::Boolean {
+                            return Err(Box::new(UnsupportedOperatorType {
+                                operator: get_boolean_connector_symbol(connector),
+                                operand_type: operand_type.clone(),
+                                line: None
+                            }));
+                        }
                     }
                 }
 
@@ -654,14 +1086,14 @@ fn validate_boolean_expr(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
 
             match operator {
                 Some(operator) => {
-                    validate_boolean_operator_with_args(&lhs_type, &rhs_type.unwrap_or(Type::Void), &operator).unwrap();
+                    validate_boolean_operator_with_args(&lhs_type, &rhs_type.unwrap_or(Type::Void), operator)?;
                 }
 
                 None => {}
             }
         },
 
-        unknown => panic!("{:?} is not a boolean expression", unknown)
+        unknown => return Err(Box::new(InternalCompilerError(format!("a {} is not a boolean expression", get_node_name(unknown)))))
     }
 
     Ok(lhs_type)
@@ -669,82 +1101,128 @@ fn validate_boolean_expr(node:&ASTNode, required_type:&Type, symbol_table:&Symbo
 
 
 /**
- * Returns true if this section of the AST contains a `break` statement, otherwise returns false.
+ * Checks an argument to a call and returns its type. An argument may be a boolean expression such as
+ * `(x, y)>`, which gives a bool, as well as a value, variable or call.
  */
-fn check_if_has_break(node:&ASTNode) -> bool {
-    match node {
-        ASTNode::Break => true,
+fn find_argument_type(arg:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    match arg {
+        ASTNode::BooleanExpression {..} => validate_boolean_expr(arg, symbol_table, scope_history, trace),
+        _ => find_valid_type_of_node(arg, symbol_table, scope_history, trace)
+    }
+}
 
-        ASTNode::IfElifElseStatement {statements, ..} |
-        ASTNode::IfStatement {statements, ..} |
-        ASTNode::ElseStatement {statements, ..} => {
-            for statement in statements {
-                if check_if_has_break(statement) == true {
-                    return true;
-                }
-            }
 
-            false
-        }
+/**
+ * Checks that the function being called exists and that the arguments passed to it match its parameters in
+ * number and type, resolving the return types of any calls nested in the arguments. Returns the return type
+ * of the function called.
+ */
+fn validate_function_call(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    let (identifier, arguments) = match node {
+        ASTNode::FunctionCall {identifier, arguments} => (identifier, arguments),
+        other => panic!("{:?} is not a function call", other)
+    };
+
+    // a user function with the same name as an intrinsic takes precedence over it
+    let return_type = match (symbol_table.get_func_return_type(identifier), get_intrinsic_from_str(identifier)) {
+        (Ok(return_type), _) => return_type,
+        (Err(_), Some(intrinsic)) => return validate_intrinsic_call(&intrinsic, identifier, arguments, symbol_table, scope_history, trace),
+        (Err(_), None) => return Err(Box::new(symbol_table.get_function_not_found_error(identifier, scope_history)))
+    };
+
+    let param_types = symbol_table.get_function_param_types(identifier).map_err(|err| err as Box<dyn Error>)?;
+    if arguments.len() != param_types.len() {
+        return Err(Box::new(IncorrectNumArguments(identifier.to_string())));
+    }
 
-        _ => false
+    for (arg, param_type) in arguments.iter().zip(&param_types) {
+        validate_not_void_call(arg, symbol_table, &format!("an argument to function {}", identifier))?;
+        if &find_argument_type(arg, symbol_table, scope_history, trace)? != param_type {
+            return Err(Box::new(IncorrectDatatype));
+        }
     }
+
+    Ok(return_type)
 }
 
 
 /**
- * Validates that an indefinite loop has a `break` statement somewhere so that it is not infinite
+ * Checks the arguments to a call of an intrinsic function and selects the version of it for their type, which
+ * is also the type it returns apart from `round`, `floor`, and `ceil`, which take a float or double and return
+ * an int, and `alloc`, which takes an int and returns the address of the memory as a long. `abs`, `min`, and
+ * `max` take int, long, float, or double arguments, and `pow` takes int or long arguments. Every argument must
+ * have the same type.
  */
-fn validate_indef_loop_has_break(node:&ASTNode) -> bool {
-    match node {
-        ASTNode::IndefLoop {statements, ..} => {
-            for statement in statements {
-                if check_if_has_break(statement) {
-                    return true;
-                }
-            }
-        },
+fn validate_intrinsic_call(intrinsic:&Intrinsic, identifier:&str, arguments:&[ASTNode], symbol_table:&SymbolTable, 
+            scope_history:&Vec<usize>, trace:&Trace) -> Result<Type, Box<dyn Error>> {
+    let arg_count = match intrinsic {
+        Intrinsic::Abs | Intrinsic::Round | Intrinsic::Floor | Intrinsic::Ceil | Intrinsic::Alloc => 1,
+        Intrinsic::Min | Intrinsic::Max | Intrinsic::Pow => 2
+    };
 
-        unknown => panic!("{:?} is not an indefinite loop node", unknown)
+    if arguments.len() != arg_count {
+        return Err(Box::new(IncorrectNumArguments(identifier.to_string())));
+    }
+
+    let mut arg_types = vec![];
+    for arg in arguments {
+        validate_not_void_call(arg, symbol_table, &format!("an argument to function {}", identifier))?;
+        arg_types.push(find_argument_type(arg, symbol_table, scope_history, trace)?);
+    }
+
+    let arg_type = arg_types[0].clone();
+    if arg_types.iter().any(|other| other != &arg_type) {
+        return Err(Box::new(IncorrectDatatype));
     }
 
-    panic!("Indefinite loop must contain a break statement!");
+    let supported = get_intrinsic_types(intrinsic).contains(&arg_type);
+    match (supported, intrinsic) {
+        (true, Intrinsic::Round | Intrinsic::Floor | Intrinsic::Ceil) => Ok(Type::Integer),
+        (true, Intrinsic::Alloc) => Ok(Type::Long),
+        (true, _) => Ok(arg_type),
+        (false, _) => Err(Box::new(NoIntrinsicOverload {function: identifier.to_string(), argument_type: arg_type}))
+    }
 }
 
 
-fn validate_for_loop_part(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, control_type:&Type) -> Result<(), Box<dyn Error>> {
-    semantic_validation_subtree(node, &symbol_table, &scope_history)?;
+/**
+ * Returns the identifier of the function called if the given node is just a call to a function which returns
+ * void, possibly wrapped in a term or an expression without an operator.
+ */
+fn get_void_call(node:&ASTNode, symbol_table:&SymbolTable) -> Option<String> {
     match node {
-        ASTNode::Expression {..} => {
-            validate_expression_of_type(node, control_type, symbol_table, scope_history).unwrap();
+        ASTNode::Term {child} => get_void_call(child, symbol_table),
+        ASTNode::Expression {lhs, operator: None, rhs: None} => get_void_call(lhs, symbol_table),
+        ASTNode::FunctionCall {identifier, ..} => match symbol_table.get_func_return_type(identifier) {
+            Ok(Type::Void) => Some(identifier.to_string()),
+            _ => None
         },
 
-        ASTNode::Term {..} => {
-            validate_term_of_type(node, control_type, symbol_table, scope_history).unwrap();
-        },
-
-        other => panic!("{:?} is not a valid loop control statement argument", other)
+        _ => None
     }
-
-    Ok(())
 }
 
 
 /**
- * Takes an ASTNode representing a ternary expression and validates that it has the following properties:
- *   - The condition is valid
- *   - The terms for if the conditon is true and false are valid
- *   - The terms for true and false match the required datatype
+ * Returns a `VoidValueUsed` error if the given node is a call to a void function, where `context` describes
+ * where the value was being used for the error message.
  */
-fn validate_ternary_expression(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, required_type:&Type) -> Result<(), Box<dyn Error>> {
-    match node {
-        ASTNode::TernaryExpression {condition, if_true, if_false} => {
-            validate_boolean_expr(condition, &Type::Boolean, symbol_table, scope_history).unwrap();
-            validate_term_of_type(if_true, required_type, symbol_table, scope_history).unwrap();
-            validate_term_of_type(if_false, required_type, symbol_table, scope_history).unwrap();
-        },
+fn validate_not_void_call(node:&ASTNode, symbol_table:&SymbolTable, context:&str) -> Result<(), Box<dyn Error>> {
+    match get_void_call(node, symbol_table) {
+        Some(function) => Err(Box::new(VoidValueUsed {function, context: context.to_string()})),
+        None => Ok(())
+    }
+}
 
-        other => panic!("{:?} is not a terary expression", other)
+
+/**
+ * Takes an `ASTNode` representing the condition of an if statement, while loop, or ternary expression and
+ * checks that it is a valid boolean expression which evaluates to a boolean.
+ */
+fn validate_condition(condition:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<(), Box<dyn Error>> {
+    let condition_type = validate_boolean_expr(condition, symbol_table, scope_history, trace)?;
+    if condition_type != Type::Boolean {
+        return Err(Box::new(NonBooleanCondition(condition_type)));
     }
 
     Ok(())
@@ -752,206 +1230,433 @@ fn validate_ternary_expression(node:&ASTNode, symbol_table:&SymbolTable, scope_h
 
 
 /**
- * Takes an AST node and runs semantic analysis on it to ensure it is valid when the context of the whole program
- * is taken into consideration.
+ * Returns true if this block contains a `break` statement which can run, ending the loop the block belongs
+ * to. Breaks inside nested loops belong to those loops and so are not counted. A branch of an if statement
+ * whose condition is always false given the constants in `constants` can never run, and nor can any branch
+ * after one whose condition is always true, so breaks in them are not counted either. Conditions which
+ * cannot be worked out at compile time are assumed to be able to run.
  */
-fn semantic_validation_subtree(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>) -> Result<(), Box<dyn Error>> {
-    let mut scope_history = scope_history.clone();
-    match node {
-        ASTNode::Function {identifier, statements, return_type, ..} => {
-            let mut has_return = false;
-            for statement in statements {
-                scope_history.push(symbol_table.get_identifier_in_scope(&identifier, &scope_history)?);
-                semantic_validation_subtree(statement, &symbol_table, &scope_history)?;
+fn has_reachable_break(statements:&[ASTNode], constants:&HashMap<String, Literal>) -> bool {
+    let mut constants = constants.clone();
+    for statement in statements {
+        match statement {
+            ASTNode::Break => return true,
+
+            ASTNode::IfElifElseStatement {statements: branches} => {
+                for branch in branches {
+                    match branch {
+                        ASTNode::IfStatement {condition, statements, ..} => match evaluate_constant_condition(condition, &constants) {
+                            Some(false) => continue,
+                            Some(true) => {
+                                if has_reachable_break(statements, &constants) {
+                                    return true;
+                                }
+
+                                break;
+                            },
+                            None => {
+                                if has_reachable_break(statements, &constants) {
+                                    return true;
+                                }
+                            }
+                        },
 
-                match statement.clone() {
-                    ASTNode::ReturnStatement { expression } => {
-                        validate_expression_of_type(&expression, &return_type, symbol_table, &scope_history)?;
-                        has_return = true;
-                    },
+                        ASTNode::ElseStatement {statements, ..} if has_reachable_break(statements, &constants) => return true,
+                        _ => {}
+                    }
+                }
+            },
 
-                    ASTNode::FunctionCall {identifier, arguments} => {
-                        let param_types = symbol_table.get_function_param_types(&identifier)?;
-                        let arg_types:Vec<Type> = arguments.into_iter().map(|param|
-                            match param {
-                                ASTNode::Value {literal_type, ..} => literal_type, 
-                                ASTNode::Identifier(identifier) => symbol_table.get_identifier_type_in_scope(&identifier, &scope_history).unwrap(),
-                                unknown => panic!("{:?} is not a valid parameter in function call {}", unknown, identifier) 
-                            }
-                        ).collect();
+            _ => {}
+        }
 
-                        if arg_types.len() != param_types.len() {
-                            return Err(Box::new(IncorrectNumArguments(identifier)));
-                        }
+        update_constants(statement, &mut constants);
+    }
 
-                        for i in 0..arg_types.len() {
-                            if param_types[i] != arg_types[i] {
-                                return Err(Box::new(IncorrectDatatype));
-                            }
-                        }
-                    }
+    false
+}
 
-                    _ => {}
-                }
+
+/**
+ * Checks that every return statement in a block, including those in nested blocks, has a value if and only
+ * if the function it is in does not have the void return type.
+ */
+fn validate_return_values(statements:&[ASTNode], function:&str, return_type:&Type) -> Result<(), Box<dyn Error>> {
+    for statement in statements {
+        match statement {
+            ASTNode::ReturnStatement {expression} => match (expression, return_type) {
+                (Some(_), Type::Void) => return Err(Box::new(UnexpectedReturnValue(function.to_string()))),
+                (None, Type::Void) | (Some(_), _) => {},
+                (None, _) => return Err(Box::new(MissingReturnValue(function.to_string())))
+            },
+
+            ASTNode::IfElifElseStatement {statements}
+              | ASTNode::IfStatement {statements, ..}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..}
+              | ASTNode::WhileLoop {statements, ..}
+              | ASTNode::ForLoop {statements, ..}
+              | ASTNode::ForEachLoop {statements, ..} => validate_return_values(statements, function, return_type)?,
+
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Walks this section of the AST and adds the identifier of every function called within it to `calls`.
+ */
+pub fn get_called_functions(node:&ASTNode, calls:&mut Vec<String>) {
+    match node {
+        ASTNode::FunctionCall {identifier, arguments} => {
+            calls.push(identifier.to_string());
+            for arg in arguments {
+                get_called_functions(arg, calls);
             }
+        },
 
-            if return_type != &Type::Void && !has_return {
-                return Err(Box::new(BadFunctionReturn(identifier.to_string())));
+        ASTNode::Function {statements, ..}
+          | ASTNode::IfElifElseStatement {statements}
+          | ASTNode::ElseStatement {statements, ..}
+          | ASTNode::IndefLoop {statements, ..}
+          | ASTNode::ForEachLoop {statements, ..} => {
+            for statement in statements {
+                get_called_functions(statement, calls);
             }
         },
 
-        ASTNode::VarDeclStatement {var_type, value, ..} => {
-            match &**value {
-                ASTNode::Expression {..} => validate_expression_of_type(&value, &var_type, symbol_table, &scope_history).unwrap(),
-                ASTNode::TernaryExpression {..} => validate_ternary_expression(&value, symbol_table, &scope_history, &var_type).unwrap(),
-                ASTNode::InputStatement(_) => semantic_validation_subtree(&value, symbol_table, &scope_history).unwrap(),
-                other => panic!("{:?} is not a valid variable declaration expression", other)
+        ASTNode::IfStatement {condition, statements, ..} | ASTNode::WhileLoop {condition, statements, ..} => {
+            get_called_functions(condition, calls);
+            for statement in statements {
+                get_called_functions(statement, calls);
             }
-        }
-        
-        ASTNode::VarAssignStatement {identifier, value} => {
-            if symbol_table.get_mutability_in_scope(&identifier, &scope_history)? != Mutability::Mutable {
-                return Err(Box::new(ImmutableReassignmentError(identifier.to_string())));
+        },
+
+        ASTNode::ForLoop {control_initial, limit, step, statements, ..} => {
+            get_called_functions(control_initial, calls);
+            get_called_functions(limit, calls);
+            get_called_functions(step, calls);
+            for statement in statements {
+                get_called_functions(statement, calls);
             }
+        },
 
-            symbol_table.get_identifier_in_scope(&identifier, &scope_history)?;
-            let var_type = symbol_table.get_identifier_type_in_scope(&identifier, &scope_history).unwrap();
-            validate_expression_of_type(&value, &var_type, symbol_table, &scope_history)?;
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            get_called_functions(lhs, calls);
+            if let Some(rhs) = rhs {
+                get_called_functions(rhs, calls);
+            }
         },
 
-        ASTNode::IfElifElseStatement {statements} => {
-            for statement in statements {
-                match statement {
-                    ASTNode::IfStatement {statements, scope, condition} => {
-                        validate_boolean_expr(condition, &Type::Boolean, symbol_table, &scope_history).unwrap();
-                        for sub_stmt in statements {
-                            scope_history.push( *scope );
-                            semantic_validation_subtree(sub_stmt, symbol_table, &scope_history).unwrap();
-                        }
-                    },
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            get_called_functions(condition, calls);
+            get_called_functions(if_true, calls);
+            get_called_functions(if_false, calls);
+        },
 
-                    ASTNode::ElseStatement {statements, scope} => {
-                        for sub_stmt in statements {
-                            scope_history.push( *scope );
-                            semantic_validation_subtree(sub_stmt, symbol_table, &scope_history).unwrap();
-                        }
-                    }
+        ASTNode::ReturnStatement {expression: Some(child)}
+          | ASTNode::VarDeclStatement {value: child, ..}
+          | ASTNode::VarAssignStatement {value: child, ..}
+          | ASTNode::ExpressionStatement {expression: child, ..}
+          | ASTNode::Term {child}
+          | ASTNode::TypeCast {from: child, ..} => get_called_functions(child, calls),
 
-                    _ => panic!("Invalid block if if, else if, else structure {:?}", statement)
-                }
+        ASTNode::PrintStatement {terms, ..} => {
+            for (term, _) in terms {
+                get_called_functions(term, calls);
             }
         },
 
-        ASTNode::IndefLoop {statements, scope, ..} => {
-            if !validate_indef_loop_has_break(node) {
-                panic!("Indefinite loop must contain a break statement!");
+        _ => {}
+    }
+}
+
+
+/**
+ * Checks that every function marked with the `@inline` annotation is not recursive, as a recursive
+ * function cannot be inlined into its callers.
+ */
+fn validate_inline_functions(root:&[ASTNode]) -> Result<(), Box<dyn Error>> {
+    let call_graph = CallGraph::new(root);
+    let cycles = call_graph.get_cycles();
+    for node in root {
+        if let ASTNode::Function {identifier, inline: true, ..} = node {
+            if cycles.iter().flatten().any(|function| function == identifier) {
+                return Err(Box::new(RecursiveInlineFunction(identifier.to_string())));
             }
+        }
+    }
 
-            for statement in statements {
-                scope_history.push( *scope );
-                semantic_validation_subtree(statement, &symbol_table, &scope_history)?;
+    Ok(())
+}
+
+
+/**
+ * Walks this section of a function and returns why it is not pure, without looking inside the user functions
+ * it calls, whose names are in `functions`. A function is not pure if it prints, reads input, keeps a mutable
+ * static variable, as its result could then depend on earlier calls, or allocates memory.
+ */
+fn find_direct_impurity(node:&ASTNode, functions:&HashSet<String>) -> Option<String> {
+    let find_in_all = |nodes:&[ASTNode]| nodes.iter().find_map(|node| find_direct_impurity(node, functions));
+    match node {
+        ASTNode::PrintStatement {..} => Some("prints".to_owned()),
+        ASTNode::InputStatement(..) => Some("reads input".to_owned()),
+        ASTNode::VarDeclStatement {storage: Storage::Static, mutability: Mutability::Mutable, identifier, ..} => {
+            Some(format!("keeps the mutable static variable {}", identifier))
+        },
+
+        ASTNode::FunctionCall {identifier, arguments} => {
+            if !functions.contains(identifier) && get_intrinsic_from_str(identifier) == Some(Intrinsic::Alloc) {
+                return Some("allocates memory with alloc".to_owned());
             }
+
+            find_in_all(arguments)
         },
 
-        ASTNode::ForLoop {statements, scope, control_type, control_initial, limit, step, ..} => {
-            validate_for_loop_part(control_initial, &symbol_table, &scope_history, control_type).unwrap();
-            validate_for_loop_part(limit, &symbol_table, &scope_history, control_type).unwrap();
-            validate_for_loop_part(step, &symbol_table, &scope_history, control_type).unwrap();
+        ASTNode::Function {statements, ..}
+          | ASTNode::IfElifElseStatement {statements}
+          | ASTNode::ElseStatement {statements, ..}
+          | ASTNode::IndefLoop {statements, ..}
+          | ASTNode::ForEachLoop {statements, ..} => find_in_all(statements),
 
-            for statement in statements {
-                scope_history.push( *scope );
-                semantic_validation_subtree(statement, &symbol_table, &scope_history)?;
-            }
+        ASTNode::IfStatement {condition, statements, ..} | ASTNode::WhileLoop {condition, statements, ..} => {
+            find_direct_impurity(condition, functions).or_else(|| find_in_all(statements))
         },
 
-        ASTNode::WhileLoop {condition, statements, scope} => {
-            validate_boolean_expr(condition, &Type::Boolean, &symbol_table, &scope_history).unwrap();
-            for statement in statements {
-                scope_history.push( *scope );
-                semantic_validation_subtree(statement, &symbol_table, &scope_history)?;
-            }
+        ASTNode::ForLoop {control_initial, limit, step, statements, ..} => {
+            find_direct_impurity(control_initial, functions)
+                .or_else(|| find_direct_impurity(limit, functions))
+                .or_else(|| find_direct_impurity(step, functions))
+                .or_else(|| find_in_all(statements))
         },
 
-        ASTNode::TypeCast {from, into} => {
-            let from_type = match &**from {
-                ASTNode::Identifier(identifier) => symbol_table.get_identifier_type_in_scope(identifier, &scope_history).unwrap(),
-                ASTNode::Value {literal_type, ..} => literal_type.clone(),
-                other => panic!("{:?} is not a valid node for a type cast", other)
-            };
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            find_direct_impurity(lhs, functions).or_else(|| rhs.as_ref().and_then(|rhs| find_direct_impurity(rhs, functions)))
+        },
 
-            match from_type {
-                Type::Integer => match into {
-                    Type::Long | Type::Float | Type::Double | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            find_direct_impurity(condition, functions)
+                .or_else(|| find_direct_impurity(if_true, functions))
+                .or_else(|| find_direct_impurity(if_false, functions))
+        },
 
-                Type::Long => match into {
-                    Type::Integer | Type::Double | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
+        ASTNode::ReturnStatement {expression: Some(child)}
+          | ASTNode::VarDeclStatement {value: child, ..}
+          | ASTNode::VarAssignStatement {value: child, ..}
+          | ASTNode::ExpressionStatement {expression: child, ..}
+          | ASTNode::Term {child}
+          | ASTNode::TypeCast {from: child, ..} => find_direct_impurity(child, functions),
 
-                Type::Byte  => match into {
-                    Type::Integer | Type::Long | Type::Float | Type::Double | Type::Char | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
-                
-                Type::Float => match into {
-                    Type::Integer | Type::Byte | Type::Double | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
-                
-                Type::Double => match into {
-                    Type::Byte | Type::Integer | Type::Long | Type::Float | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
+        _ => None
+    }
+}
 
-                Type::Boolean => match into {
-                    Type::Boolean => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
-                },
 
-                Type::Char => match into {
-                    Type::Byte | Type::String => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
+/**
+ * Checks that every function marked with the `@pure` annotation has no side effects, and that neither does
+ * any function it can call, directly or through other functions. The error gives the chain of calls to the
+ * nearest function which is not pure, such as "calls a, which calls b, which prints". Calls to functions which
+ * are not in `root`, such as those in other files of a program built from a directory, are checked when the
+ * whole program is validated.
+ */
+fn validate_pure_functions(root:&[ASTNode]) -> Result<(), Box<dyn Error>> {
+    let functions:HashMap<&str, &ASTNode> = root.iter().filter_map(|node| match node {
+        ASTNode::Function {identifier, ..} => Some((identifier.as_str(), node)),
+        _ => None
+    }).collect();
+    let names:HashSet<String> = functions.keys().map(|name| name.to_string()).collect();
+
+    for node in root {
+        let identifier = match node {
+            ASTNode::Function {identifier, pure: true, ..} => identifier,
+            _ => continue
+        };
+
+        // searched breadth first, remembering who called each function so the chain can be given
+        let mut callers:HashMap<&str, &str> = HashMap::new();
+        let mut pending = VecDeque::from([identifier.as_str()]);
+        while let Some(current) = pending.pop_front() {
+            if let Some(reason) = find_direct_impurity(functions[current], &names) {
+                let mut chain = vec![reason];
+                let mut callee = current;
+                while let Some(caller) = callers.get(callee) {
+                    chain.push(format!("calls {}, which", callee));
+                    callee = caller;
                 }
 
-                Type::String => match into {
-                    Type::Integer | Type::Long | Type::Byte | Type::Float | Type::Double => {},
-                    _ => panic!("{:?} cannot be cast to {:?}", from_type, into)
+                chain.reverse();
+                return Err(Box::new(ImpureFunction {function: identifier.to_owned(), reason: chain.join(" ")}));
+            }
+
+            let mut calls = vec![];
+            get_called_functions(functions[current], &mut calls);
+            for call in calls {
+                if let Some((name, _)) = functions.get_key_value(call.as_str()) {
+                    if *name != identifier && !callers.contains_key(name) {
+                        callers.insert(name, current);
+                        pending.push_back(name);
+                    }
                 }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 
-                Type::Void => panic!("{:?} cannot be cast to {:?}", from_type, into)
+/**
+ * Walks this section of an expression and adds every variable identifier read within it to `identifiers`.
+ */
+fn get_used_identifiers(node:&ASTNode, identifiers:&mut Vec<String>) {
+    match node {
+        ASTNode::Identifier(identifier) => identifiers.push(identifier.to_string()),
+
+        ASTNode::FunctionCall {arguments, ..} => {
+            for arg in arguments {
+                get_used_identifiers(arg, identifiers);
             }
         },
 
-        ASTNode::PrintStatement {terms} => {
-            for term in terms {
-                match term {
-                    ASTNode::Value {literal_type, ..} => {
-                        if literal_type != &Type::String {
-                            panic!("Only strings can be printed, not {:?}", literal_type);
-                        }
-                    },
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            get_used_identifiers(lhs, identifiers);
+            if let Some(rhs) = rhs {
+                get_used_identifiers(rhs, identifiers);
+            }
+        },
 
-                    ASTNode::Identifier(identifier) => {
-                        let id_type = symbol_table.get_identifier_type_in_scope(identifier, &scope_history).unwrap();
-                        if id_type != Type::String {
-                            panic!("Only strings can be printed, not {:?}", id_type);
-                        }
-                    }
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            get_used_identifiers(condition, identifiers);
+            get_used_identifiers(if_true, identifiers);
+            get_used_identifiers(if_false, identifiers);
+        },
 
-                    other => panic!("{:?} nodes cannot be printed", other)
-                }
+        ASTNode::ReturnStatement {expression: Some(child)}
+          | ASTNode::ExpressionStatement {expression: child, ..}
+          | ASTNode::Term {child}
+          | ASTNode::TypeCast {from: child, ..} => get_used_identifiers(child, identifiers),
+
+        ASTNode::PrintStatement {terms, ..} => {
+            for (term, _) in terms {
+                get_used_identifiers(term, identifiers);
             }
         },
 
-        ASTNode::InputStatement(length) => {
-            if length <= &1 {
-                panic!("Size of input buffer must be at least 2 to allow for 1 character of input plus '\\0'")
+        _ => {}
+    }
+}
+
+
+/**
+ * Checks that every variable read in the given node has already been declared, where `declared` holds
+ * the identifier and scope of every variable whose declaration has been passed so far. Variables which
+ * are not in the symbol table at all are left for the rest of semantic validation to report.
+ */
+fn validate_node_declaration_order(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&[usize], 
+        declared:&[(String, usize)], used_line:Option<usize>) -> Result<(), Box<dyn Error>> {
+    let mut identifiers = vec![];
+    get_used_identifiers(node, &mut identifiers);
+    for identifier in identifiers {
+        if let Ok(SymbolTableRow::Variable {parent_scope, line, ..}) = symbol_table.get_row_in_scope(&identifier, scope_history) {
+            if !declared.contains(&(identifier.to_string(), *parent_scope)) {
+                return Err(Box::new(UseBeforeDeclaration {
+                    identifier: identifier.to_string(),
+                    declared_line: *line,
+                    used_line
+                }));
             }
         }
+    }
 
-        _ => {}
+    Ok(())
+}
+
+
+/**
+ * Walks the statements of a block in order, checking that no variable is read before the statement
+ * declaring it. Variables declared earlier remain usable in nested blocks.
+ */
+fn validate_block_declaration_order(statements:&[ASTNode], symbol_table:&SymbolTable, scope_history:&[usize], 
+        declared:&mut Vec<(String, usize)>) -> Result<(), Box<dyn Error>> {
+    let scope = *scope_history.last().unwrap();
+    for statement in statements {
+        match statement {
+            ASTNode::VarDeclStatement {identifier, value, line, ..} => {
+                validate_node_declaration_order(value, symbol_table, scope_history, declared, Some(*line))?;
+                declared.push((identifier.to_string(), scope));
+            },
+
+            ASTNode::VarAssignStatement {identifier, value, line} => {
+                let target = ASTNode::Identifier(identifier.to_string());
+                validate_node_declaration_order(&target, symbol_table, scope_history, declared, Some(*line))?;
+                validate_node_declaration_order(value, symbol_table, scope_history, declared, Some(*line))?;
+            },
+
+            ASTNode::IfElifElseStatement {statements} => {
+                for block in statements {
+                    match block {
+                        ASTNode::IfStatement {condition, statements, scope, ..} => {
+                            validate_node_declaration_order(condition, symbol_table, scope_history, declared, None)?;
+                            let mut block_history = scope_history.to_vec();
+                            block_history.push(*scope);
+                            validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+                        },
+
+                        ASTNode::ElseStatement {statements, scope} => {
+                            let mut block_history = scope_history.to_vec();
+                            block_history.push(*scope);
+                            validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+                        },
+
+                        _ => {}
+                    }
+                }
+            },
+
+            ASTNode::WhileLoop {condition, statements, scope, ..} => {
+                validate_node_declaration_order(condition, symbol_table, scope_history, declared, None)?;
+                let mut block_history = scope_history.to_vec();
+                block_history.push(*scope);
+                validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+            },
+
+            ASTNode::IndefLoop {statements, scope} => {
+                let mut block_history = scope_history.to_vec();
+                block_history.push(*scope);
+                validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+            },
+
+            ASTNode::ForLoop {control_identifier, control_initial, limit, step, statements, scope, ..} => {
+                validate_node_declaration_order(control_initial, symbol_table, scope_history, declared, None)?;
+                validate_node_declaration_order(limit, symbol_table, scope_history, declared, None)?;
+                validate_node_declaration_order(step, symbol_table, scope_history, declared, None)?;
+
+                let mut block_history = scope_history.to_vec();
+                block_history.push(*scope);
+                declared.push((control_identifier.to_string(), *scope));
+                validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+            },
+
+            ASTNode::ForEachLoop {element_identifier, iterable, statements, scope, line, ..} => {
+                validate_node_declaration_order(iterable, symbol_table, scope_history, declared, Some(*line))?;
+
+                let mut block_history = scope_history.to_vec();
+                block_history.push(*scope);
+                declared.push((element_identifier.to_string(), *scope));
+                validate_block_declaration_order(statements, symbol_table, &block_history, declared)?;
+            },
+
+            other => validate_node_declaration_order(other, symbol_table, scope_history, declared, None)?
+        }
     }
 
     Ok(())
@@ -959,33 +1664,1066 @@ fn semantic_validation_subtree(node:&ASTNode, symbol_table:&SymbolTable, scope_h
 
 
 /**
- * Takes the root node of the AST and runs semantic analysis, checking for:
- *   - undeclared/out of scope variables
- *   - no/incorrect return statements
- *   - reassignment to immutable variable
- *   - operations on non-matching datatypes
- *   - functions with incorrect return types
- *   - incorrect arguments to function calls
- *   - check validity of boolean statements
+ * Checks that no variable in any function is read before it is declared. As the symbol table is built
+ * for a whole function at once, a variable is otherwise in scope for the entire block it is declared in.
  */
-pub fn semantic_validation(root:Vec<ASTNode>, symbol_table:&SymbolTable) -> Result<(), Box<dyn Error>> {
+fn validate_declaration_order(root:&[ASTNode], symbol_table:&SymbolTable) -> Result<(), Box<dyn Error>> {
     for node in root {
-        semantic_validation_subtree(&node, symbol_table, &vec![0])?;
+        if let ASTNode::Function {parameters, statements, scope, ..} = node {
+            let mut declared:Vec<(String, usize)> = parameters.iter().filter_map(|param| match param {
+                ASTNode::Parameter {identifier, ..} => Some((identifier.to_string(), *scope)),
+                _ => None
+            }).collect();
+
+            validate_block_declaration_order(statements, symbol_table, &[0, *scope], &mut declared)?;
+        }
     }
 
     Ok(())
 }
 
 
+/**
+ * Validates that an indefinite loop has a `break` statement which can run so that it is not infinite. Only
+ * conditions made of literals are worked out here, as the constants in scope are not tracked during
+ * validation, so a break behind a condition which is always false because of a constant is instead reported
+ * as a warning by `find_constant_warnings`.
+ */
+fn validate_indef_loop_has_break(node:&ASTNode) -> bool {
+    match node {
+        ASTNode::IndefLoop {statements, ..} => has_reachable_break(statements, &HashMap::new()),
+        unknown => panic!("{:?} is not an indefinite loop node", unknown)
+    }
+}
+
+
+/**
+ * Checks that a part of the header of a for loop, being the initial value, limit or step, is a valid value
+ * of the same type as the control variable. Each can be any expression, including function calls and casts.
+ */
+fn validate_for_loop_part(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace, control_type:&Type) -> Result<(), Box<dyn Error>> {
+    validate_not_void_call(node, symbol_table, "the header of a for loop")?;
+    match node {
+        ASTNode::Expression {..} => validate_expression_of_type(node, control_type, symbol_table, scope_history, trace),
+        ASTNode::TernaryExpression {..} => validate_ternary_expression(node, symbol_table, scope_history, trace, control_type),
+        other => panic!("{:?} is not a valid loop control statement argument", other)
+    }
+}
+
+
+/**
+ * Checks the number of times a repeat loop repeats is an int or a long. A long is converted to an int to be
+ * compared with the hidden control variable.
+ */
+fn validate_repeat_count(count:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace, line:usize) -> Result<(), Box<dyn Error>> {
+    validate_not_void_call(count, symbol_table, "the header of a repeat loop")?;
+    match find_valid_type_of_node(count, symbol_table, scope_history, trace)? {
+        found @ (Type::Integer | Type::Long) => validate_for_loop_part(count, symbol_table, scope_history, trace, &found),
+        found => Err(Box::new(InvalidRepeatCount {found, line}))
+    }
+}
+
+
+/**
+ * Takes an ASTNode representing a ternary expression and validates that it has the following properties:
+ *   - The condition is valid and of type bool
+ *   - The terms for if the conditon is true and false are valid
+ *   - The terms for true and false have the same datatype as each other
+ *   - The terms for true and false match the required datatype
+ */
+fn validate_ternary_expression(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace, required_type:&Type) -> Result<(), Box<dyn Error>> {
+    match node {
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            validate_condition(condition, symbol_table, scope_history, trace)?;
+
+            validate_not_void_call(if_true, symbol_table, "a ternary expression")?;
+            validate_not_void_call(if_false, symbol_table, "a ternary expression")?;
+
+            let true_type = find_valid_type_of_node(if_true, symbol_table, scope_history, trace)?;
+            let false_type = find_valid_type_of_node(if_false, symbol_table, scope_history, trace)?;
+            if true_type != false_type {
+                return Err(Box::new(MismatchedTernaryArms(true_type, false_type)));
+            }
+
+            validate_term_of_type(if_true, required_type, symbol_table, scope_history, trace)?;
+            validate_term_of_type(if_false, required_type, symbol_table, scope_history, trace)?;
+        },
+
+        other => panic!("{:?} is not a terary expression", other)
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Takes an AST node and runs semantic analysis on it to ensure it is valid when the context of the whole program
+ * is taken into consideration.
+ */
+fn semantic_validation_subtree(node:&ASTNode, symbol_table:&SymbolTable, scope_history:&Vec<usize>, trace:&Trace) -> Result<(), Box<dyn Error>> {
+    let _level = trace.enter(|| format!("{} in scopes {:?}", describe_node(node), scope_history));
+    let mut scope_history = scope_history.clone();
+    match node {
+        ASTNode::Function {identifier, statements, return_type, ..} => {
+            validate_return_values(statements, identifier, return_type)?;
+
+            let mut has_return = false;
+            for statement in statements {
+                scope_history.push(lookup_in_scope(symbol_table, identifier, &scope_history, trace)?.get_scope_id());
+                semantic_validation_subtree(statement, symbol_table, &scope_history, trace)?;
+
+                if let ASTNode::ReturnStatement { expression: Some(expression) } = statement.clone() {
+                    validate_not_void_call(&expression, symbol_table, "a return statement")?;
+                    match *expression {
+                        ASTNode::BooleanExpression {..} => {
+                            if &validate_boolean_expr(&expression, symbol_table, &scope_history, trace)? != return_type {
+                                return Err(Box::new(IncorrectDatatype));
+                            }
+                        },
+
+                        _ => validate_expression_of_type(&expression, return_type, symbol_table, &scope_history, trace)?
+                    }
+
+                    has_return = true;
+                }
+            }
+
+            if return_type != &Type::Void && !has_return {
+                return Err(Box::new(BadFunctionReturn(identifier.to_string())));
+            }
+        },
+
+        // a call made as a statement is only worth making if it has side effects, which intrinsics never do apart
+        // from alloc, whose memory could never be used if its address was discarded
+        ASTNode::ExpressionStatement {expression, line, ..} => match &**expression {
+            ASTNode::FunctionCall {identifier, ..} => {
+                validate_function_call(expression, symbol_table, &scope_history, trace)?;
+                if symbol_table.get_func_return_type(identifier).is_err() {
+                    return Err(Box::new(UnusedIntrinsicResult(identifier.to_string())));
+                }
+            },
+
+            _ => {
+                let value_type = find_discarded_value_type(expression, symbol_table, &scope_history, trace)?;
+                validate_expression_of_type(expression, &value_type, symbol_table, &scope_history, trace)
+                    .map_err(|err| locate_operator_error(err, *line))?;
+            }
+        },
+
+        ASTNode::VarDeclStatement {var_type, value, identifier, storage, line, ..} => {
+            validate_not_void_call(value, symbol_table, &format!("the declaration of variable {}", identifier))?;
+            validate_assigned_value(value, var_type, symbol_table, &scope_history, trace).map_err(|err| locate_operator_error(err, *line))?;
+
+            // a static is initialised in the data section before the program runs, so its value must be known
+            if *storage == Storage::Static && evaluate_constant_term(value, &HashMap::new()).is_none() {
+                return Err(Box::new(NonConstantStaticInitialiser {identifier: identifier.to_owned(), line: *line}));
+            }
+        }
+        
+        ASTNode::VarAssignStatement {identifier, value, line} => {
+            let row = lookup_in_scope(symbol_table, identifier, &scope_history, trace)?;
+            if row.get_mutability() != Mutability::Mutable {
+                return Err(Box::new(ImmutableReassignmentError(identifier.to_string())));
+            }
+
+            validate_not_void_call(value, symbol_table, &format!("the assignment to variable {}", identifier))?;
+            let var_type = row.get_scope_type();
+            validate_assigned_value(value, &var_type, symbol_table, &scope_history, trace).map_err(|err| locate_operator_error(err, *line))?;
+        },
+
+        ASTNode::IfElifElseStatement {statements} => {
+            for statement in statements {
+                match statement {
+                    ASTNode::IfStatement {statements, scope, condition, line} => {
+                        validate_condition(condition, symbol_table, &scope_history, trace).map_err(|err| locate_operator_error(err, *line))?;
+                        for sub_stmt in statements {
+                            scope_history.push( *scope );
+                            semantic_validation_subtree(sub_stmt, symbol_table, &scope_history, trace)?;
+                        }
+                    },
+
+                    ASTNode::ElseStatement {statements, scope} => {
+                        for sub_stmt in statements {
+                            scope_history.push( *scope );
+                            semantic_validation_subtree(sub_stmt, symbol_table, &scope_history, trace)?;
+                        }
+                    }
+
+                    _ => panic!("Invalid block if if, else if, else structure {:?}", statement)
+                }
+            }
+        },
+
+        ASTNode::IndefLoop {statements, scope, ..} => {
+            if !validate_indef_loop_has_break(node) {
+                return Err(Box::new(IndefLoopWithoutBreak));
+            }
+
+            for statement in statements {
+                scope_history.push( *scope );
+                semantic_validation_subtree(statement, symbol_table, &scope_history, trace)?;
+            }
+        },
+
+        ASTNode::ForLoop {statements, scope, control_type, control_identifier, control_initial, limit, step, line, ..} => {
+            validate_for_loop_part(control_initial, symbol_table, &scope_history, trace, control_type)?;
+            match is_repeat_control(control_identifier) {
+                true => validate_repeat_count(limit, symbol_table, &scope_history, trace, *line)?,
+                false => validate_for_loop_part(limit, symbol_table, &scope_history, trace, control_type)?
+            }
+            validate_for_loop_part(step, symbol_table, &scope_history, trace, control_type)?;
+
+            for statement in statements {
+                scope_history.push( *scope );
+                semantic_validation_subtree(statement, symbol_table, &scope_history, trace)?;
+            }
+        },
+
+        ASTNode::WhileLoop {condition, statements, scope, ..} => {
+            validate_condition(condition, symbol_table, &scope_history, trace)?;
+            for statement in statements {
+                scope_history.push( *scope );
+                semantic_validation_subtree(statement, symbol_table, &scope_history, trace)?;
+            }
+        },
+
+        // only strings can be iterated over, one char at a time
+        ASTNode::ForEachLoop {element_type, iterable, statements, scope, line, ..} => {
+            let (identifier, iterable_type) = match &**iterable {
+                ASTNode::Identifier(identifier) => (identifier, lookup_in_scope(symbol_table, identifier, &scope_history, trace)?.get_scope_type()),
+                other => panic!("{:?} cannot be iterated over", other)
+            };
+
+            if iterable_type != Type::String {
+                return Err(Box::new(NotIterable {identifier: identifier.to_string(), value_type: iterable_type, line: *line}));
+            }
+
+            if element_type != &Type::Char {
+                return Err(Box::new(MismatchedElementType {element_type: element_type.clone(), iterable_type, line: *line}));
+            }
+
+            for statement in statements {
+                scope_history.push( *scope );
+                semantic_validation_subtree(statement, symbol_table, &scope_history, trace)?;
+            }
+        },
+
+        ASTNode::TypeCast {from, into, line, ..} => {
+            let from_type = match &**from {
+                ASTNode::Identifier(identifier) => lookup_in_scope(symbol_table, identifier, &scope_history, trace)?.get_scope_type(),
+                ASTNode::Value {literal_type, ..} => literal_type.clone(),
+                other => panic!("{:?} is not a valid node for a type cast", other)
+            };
+
+            if !get_cast_types(&from_type).contains(into) {
+                return Err(Box::new(UnsupportedCast {from: from_type, into: into.clone(), line: *line}));
+            }
+        },
+
+        ASTNode::PrintStatement {terms, ..} => {
+            for (term, format) in terms {
+                let term_type = match term {
+                    ASTNode::Value {literal_type, ..} => literal_type.clone(),
+                    ASTNode::Identifier(identifier) => lookup_in_scope(symbol_table, identifier, &scope_history, trace)?.get_scope_type(),
+                    other => panic!("{:?} nodes cannot be printed", other)
+                };
+
+                if !get_printable_types(format).contains(&term_type) {
+                    return Err(Box::new(UnprintableValue {value_type: term_type, format: *format}));
+                }
+            }
+        },
+
+        ASTNode::InputStatement(_, 0) => return Err(Box::new(EmptyInput)),
+
+        _ => {}
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Takes a term and returns its value if it is known at compile time, which is when it is a literal, a
+ * constant variable in `constants` (see `update_constants`), or a numeric cast of either. Strings are never
+ * treated as constant as they are compared by address at runtime.
+ */
+pub fn evaluate_constant_term(node:&ASTNode, constants:&HashMap<String, Literal>) -> Option<Literal> {
+    match node {
+        ASTNode::Value {value: Literal::String(_), ..} => None,
+        ASTNode::Value {value, ..} => Some(value.clone()),
+        ASTNode::Identifier(identifier) => constants.get(identifier).cloned(),
+        ASTNode::Term {child} => evaluate_constant_term(child, constants),
+        ASTNode::TypeCast {from, into, ..} => cast_literal(&evaluate_constant_term(from, constants)?, into),
+        ASTNode::Expression {lhs, operator: None, rhs: None}
+          | ASTNode::BooleanTerm {lhs, operator: None, rhs: None}
+          | ASTNode::BooleanExpression {lhs, operator: None, connector: None, rhs: None} => evaluate_constant_term(lhs, constants),
+        ASTNode::BooleanTerm {..} | ASTNode::BooleanExpression {..} => {
+            evaluate_constant_condition(node, constants).map(Literal::Boolean)
+        },
+
+        _ => None
+    }
+}
+
+
+/**
+ * Converts a numeric literal into the given type the same way a `Cast` instruction would at runtime, returning
+ * `None` for casts which are not between numeric types.
+ */
+fn cast_literal(literal:&Literal, into:&Type) -> Option<Literal> {
+    let result = match (literal, into) {
+        (Literal::Integer(value), Type::Byte) => Literal::Byte(*value as u8),
+        (Literal::Integer(value), Type::Long) => Literal::Long(*value as i64),
+        (Literal::Integer(value), Type::Float) => Literal::Float(*value as f32),
+        (Literal::Integer(value), Type::Double) => Literal::Double(*value as f64),
+        (Literal::Long(value), Type::Integer) => Literal::Integer(*value as i32),
+        (Literal::Long(value), Type::Double) => Literal::Double(*value as f64),
+        (Literal::Byte(value), Type::Integer) => Literal::Integer(*value as i32),
+        (Literal::Byte(value), Type::Long) => Literal::Long(*value as i64),
+        (Literal::Byte(value), Type::Float) => Literal::Float(*value as f32),
+        (Literal::Byte(value), Type::Double) => Literal::Double(*value as f64),
+        (Literal::Float(value), Type::Integer) => Literal::Integer(*value as i32),
+        (Literal::Float(value), Type::Byte) => Literal::Byte(*value as u8),
+        (Literal::Float(value), Type::Double) => Literal::Double(*value as f64),
+        (Literal::Double(value), Type::Integer) => Literal::Integer(*value as i32),
+        (Literal::Double(value), Type::Byte) => Literal::Byte(*value as u8),
+        (Literal::Double(value), Type::Long) => Literal::Long(*value as i64),
+        (Literal::Double(value), Type::Float) => Literal::Float(*value as f32),
+        _ => return None
+    };
+
+    Some(result)
+}
+
+
+/**
+ * Returns the type of the given literal.
+ */
+fn get_literal_type(literal:&Literal) -> Type {
+    match literal {
+        Literal::Byte(_) => Type::Byte,
+        Literal::Integer(_) => Type::Integer,
+        Literal::Long(_) => Type::Long,
+        Literal::Char(_) => Type::Char,
+        Literal::Boolean(_) => Type::Boolean,
+        Literal::Float(_) => Type::Float,
+        Literal::Double(_) => Type::Double,
+        Literal::String(_) => Type::String
+    }
+}
+
+
+/**
+ * Returns the value of a numeric literal as it would be written in a program, with floats widened to doubles
+ * so that any precision lost by a cast is shown.
+ */
+fn format_literal(literal:&Literal) -> String {
+    match literal {
+        Literal::Byte(value) => value.to_string(),
+        Literal::Integer(value) => value.to_string(),
+        Literal::Long(value) => value.to_string(),
+        Literal::Float(value) => (*value as f64).to_string(),
+        Literal::Double(value) => value.to_string(),
+        other => format!("{:?}", other)
+    }
+}
+
+
+/**
+ * Applies a boolean operator to two literals of the same type, returning `None` if they cannot be compared.
+ */
+fn compare_literals(lhs:&Literal, rhs:&Literal, operator:&BooleanOperator) -> Option<bool> {
+    let ordering = match (lhs, rhs) {
+        (Literal::Byte(lhs), Literal::Byte(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Integer(lhs), Literal::Integer(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Long(lhs), Literal::Long(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Char(lhs), Literal::Char(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Boolean(lhs), Literal::Boolean(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Float(lhs), Literal::Float(rhs)) => lhs.partial_cmp(rhs),
+        (Literal::Double(lhs), Literal::Double(rhs)) => lhs.partial_cmp(rhs),
+        _ => None
+    }?;
+
+    match operator {
+        BooleanOperator::Equal => Some(ordering.is_eq()),
+        BooleanOperator::NotEqual => Some(ordering.is_ne()),
+        BooleanOperator::Greater => Some(ordering.is_gt()),
+        BooleanOperator::GreaterOrEqual => Some(ordering.is_ge()),
+        BooleanOperator::Less => Some(ordering.is_lt()),
+        BooleanOperator::LessOrEqual => Some(ordering.is_le()),
+        BooleanOperator::Invert => None
+    }
+}
+
+
+/**
+ * Takes a boolean expression or term and returns its value if it can be worked out at compile time from
+ * literals and the constant variables in `constants`, or `None` if it depends on anything else. Both sides of
+ * a connector must be constant, as the right hand side is always evaluated at runtime.
+ * 
+ * ### Examples
+ * `(1, 2)>` evaluates to `Some(false)`, and `(x, 2)>` evaluates to `None` unless `x` is a constant.
+ */
+pub fn evaluate_constant_condition(node:&ASTNode, constants:&HashMap<String, Literal>) -> Option<bool> {
+    let get_bool = |node:&ASTNode| match evaluate_constant_term(node, constants)? {
+        Literal::Boolean(value) => Some(value),
+        _ => None
+    };
+
+    match node {
+        ASTNode::BooleanExpression {lhs, rhs: Some(rhs), connector: Some(connector), ..} => {
+            let (lhs, rhs) = (get_bool(lhs)?, get_bool(rhs)?);
+            Some(match connector {
+                BooleanConnector::And => lhs && rhs,
+                BooleanConnector::Or => lhs || rhs,
+                BooleanConnector::XOr => lhs ^ rhs
+            })
+        },
+
+        ASTNode::BooleanTerm {lhs, operator, rhs} | ASTNode::BooleanExpression {lhs, operator, rhs, connector: None} => {
+            match (operator, rhs) {
+                (None, None) => get_bool(lhs),
+                (Some(BooleanOperator::Invert), None) => get_bool(lhs).map(|value| !value),
+                (Some(operator), Some(rhs)) => {
+                    let lhs = evaluate_constant_term(lhs, constants)?;
+                    compare_literals(&lhs, &evaluate_constant_term(rhs, constants)?, operator)
+                },
+
+                _ => None
+            }
+        },
+
+        _ => get_bool(node)
+    }
+}
+
+
+/**
+ * Updates the map of constant variables in scope after the given statement. A constant declared with a value
+ * known at compile time is added, and any other declaration removes a constant it shadows.
+ */
+pub fn update_constants(statement:&ASTNode, constants:&mut HashMap<String, Literal>) {
+    if let ASTNode::VarDeclStatement {identifier, mutability, value, ..} = statement {
+        match (mutability, evaluate_constant_term(value, constants)) {
+            (Mutability::Constant, Some(literal)) => constants.insert(identifier.to_owned(), literal),
+            _ => constants.remove(identifier)
+        };
+    }
+}
+
+
+/**
+ * Returns a copy of the constants in scope without the given identifier, for the body of a block which
+ * declares a variable of that name shadowing it.
+ */
+pub fn shadow_constant(constants:&HashMap<String, Literal>, identifier:&str) -> HashMap<String, Literal> {
+    let mut constants = constants.clone();
+    constants.remove(identifier);
+    constants
+}
+
+
+/**
+ * Adds a warning to `warnings` if the given condition is always true or always false.
+ */
+fn check_constant_condition(condition:&ASTNode, construct:&'static str, line:usize, constants:&HashMap<String, Literal>, 
+            warnings:&mut Vec<Warning>) {
+    match evaluate_constant_condition(condition, constants) {
+        Some(true) => warnings.push(Warning::ConditionAlwaysTrue {construct, line}),
+        Some(false) => warnings.push(Warning::ConditionAlwaysFalse {construct, line}),
+        None => {}
+    }
+}
+
+
+/**
+ * Adds a warning to `warnings` for each else if branch of an if statement which can never run, either because the
+ * condition of an earlier branch is always true, or because its condition is the same as that of an earlier
+ * branch. Conditions which call a function or read input may give a different value the second time, so
+ * they are not compared.
+ */
+fn check_branch_conditions(branches:&[ASTNode], constants:&HashMap<String, Literal>, warnings:&mut Vec<Warning>) {
+    let mut always_true_line = None;
+    let mut earlier_conditions:Vec<(&ASTNode, usize)> = vec![];
+    for branch in branches {
+        let (condition, line) = match branch {
+            ASTNode::IfStatement {condition, line, ..} => (condition, *line),
+            _ => continue
+        };
+
+        let duplicate_line = earlier_conditions.iter()
+                                               .find(|(earlier, _)| !has_side_effects(condition) && is_same_expression(earlier, condition))
+                                               .map(|(_, earlier_line)| *earlier_line);
+
+        match (always_true_line, duplicate_line) {
+            (Some(earlier_line), _) => warnings.push(Warning::UnreachableBranch {line, earlier_line}),
+            (None, Some(earlier_line)) => warnings.push(Warning::DuplicateCondition {line, earlier_line}),
+            (None, None) => {}
+        }
+
+        if always_true_line.is_none() && evaluate_constant_condition(condition, constants) == Some(true) {
+            always_true_line = Some(line);
+        }
+
+        earlier_conditions.push((condition, line));
+    }
+}
+
+
+/**
+ * Walks this section of a statement and adds a warning to `warnings` for every cast of a value known at compile
+ * time which changes that value, such as casting a long which does not fit into an int.
+ */
+fn check_constant_casts(node:&ASTNode, constants:&HashMap<String, Literal>, warnings:&mut Vec<Warning>) {
+    match node {
+        ASTNode::TypeCast {from, into, line, ..} => {
+            check_constant_casts(from, constants, warnings);
+            let before = match evaluate_constant_term(from, constants) {
+                Some(before) => before,
+                None => return
+            };
+
+            if let Some(after) = cast_literal(&before, into) {
+                if cast_literal(&after, &get_literal_type(&before)).as_ref() != Some(&before) {
+                    warnings.push(Warning::CastTruncatesValue {
+                        into: into.to_string(),
+                        before: format_literal(&before),
+                        after: format_literal(&after),
+                        line: *line
+                    });
+                }
+            }
+        },
+
+        ASTNode::FunctionCall {arguments, ..} => {
+            for arg in arguments {
+                check_constant_casts(arg, constants, warnings);
+            }
+        },
+
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            check_constant_casts(lhs, constants, warnings);
+            if let Some(rhs) = rhs {
+                check_constant_casts(rhs, constants, warnings);
+            }
+        },
+
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            check_constant_casts(condition, constants, warnings);
+            check_constant_casts(if_true, constants, warnings);
+            check_constant_casts(if_false, constants, warnings);
+        },
+
+        ASTNode::ReturnStatement {expression: Some(child)}
+          | ASTNode::Term {child}
+          | ASTNode::VarDeclStatement {value: child, ..}
+          | ASTNode::VarAssignStatement {value: child, ..}
+          | ASTNode::ExpressionStatement {expression: child, ..} => check_constant_casts(child, constants, warnings),
+
+        _ => {}
+    }
+}
+
+
+/**
+ * Checks a block of statements for if statements and ternary expressions whose conditions are known at
+ * compile time and for casts which change a value known at compile time, adding a warning for each to
+ * `warnings`. Each nested block gets its own copy of the constants so that declarations inside it do not leak
+ * out.
+ */
+fn find_constant_warnings_in_block(statements:&[ASTNode], constants:&HashMap<String, Literal>, warnings:&mut Vec<Warning>) {
+    let mut constants = constants.clone();
+    for statement in statements {
+        check_constant_casts(statement, &constants, warnings);
+        match statement {
+            ASTNode::VarDeclStatement {value, line, ..}
+              | ASTNode::VarAssignStatement {value, line, ..}
+              | ASTNode::ExpressionStatement {expression: value, line, ..} => {
+                if let ASTNode::TernaryExpression {condition, ..} = &**value {
+                    check_constant_condition(condition, get_node_name(value), *line, &constants, warnings);
+                }
+            },
+
+            ASTNode::IfElifElseStatement {statements} => {
+                check_branch_conditions(statements, &constants, warnings);
+                for branch in statements {
+                    match branch {
+                        ASTNode::IfStatement {condition, statements, line, ..} => {
+                            check_constant_casts(condition, &constants, warnings);
+                            check_constant_condition(condition, get_node_name(branch), *line, &constants, warnings);
+                            find_constant_warnings_in_block(statements, &constants, warnings);
+                        },
+
+                        ASTNode::ElseStatement {statements, ..} => find_constant_warnings_in_block(statements, &constants, warnings),
+                        _ => {}
+                    }
+                }
+            },
+
+            ASTNode::WhileLoop {condition, statements, ..} => {
+                check_constant_casts(condition, &constants, warnings);
+                find_constant_warnings_in_block(statements, &constants, warnings);
+            },
+
+            ASTNode::IndefLoop {statements, ..} => {
+                if !has_reachable_break(statements, &constants) {
+                    if let Some((line, _)) = get_line_range(statements) {
+                        warnings.push(Warning::LoopNeverBreaks {line});
+                    }
+                }
+
+                find_constant_warnings_in_block(statements, &constants, warnings);
+            },
+
+            ASTNode::ForLoop {statements, ..} => find_constant_warnings_in_block(statements, &constants, warnings),
+
+            ASTNode::ForEachLoop {element_identifier, statements, ..} => {
+                find_constant_warnings_in_block(statements, &shadow_constant(&constants, element_identifier), warnings);
+            },
+
+            _ => {}
+        }
+
+        update_constants(statement, &mut constants);
+    }
+}
+
+
+/**
+ * Takes the root of the AST and returns a warning for each if statement or ternary expression whose
+ * condition is always true or always false, for each else if branch which can never run, for each cast which
+ * changes a value known at compile time, and for each indefinite loop whose breaks can never run because of a
+ * constant, as these are usually mistakes.
+ */
+pub fn find_constant_warnings(root:&[ASTNode]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for node in root {
+        if let ASTNode::Function {statements, ..} = node {
+            find_constant_warnings_in_block(statements, &HashMap::new(), &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+
+/**
+ * Walks a block of statements and adds a warning to `warnings` for every while or for loop with no statements
+ * in its body, including loops nested in other blocks.
+ */
+fn find_empty_loops_in_block(statements:&[ASTNode], warnings:&mut Vec<Warning>) {
+    for statement in statements {
+        match statement {
+            ASTNode::WhileLoop {statements, line, ..}
+              | ASTNode::ForLoop {statements, line, ..}
+              | ASTNode::ForEachLoop {statements, line, ..} => {
+                if statements.is_empty() {
+                    warnings.push(Warning::EmptyLoopBody {construct: get_node_name(statement), line: *line});
+                }
+
+                find_empty_loops_in_block(statements, warnings);
+            },
+
+            ASTNode::IfElifElseStatement {statements}
+              | ASTNode::IfStatement {statements, ..}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..} => find_empty_loops_in_block(statements, warnings),
+
+            _ => {}
+        }
+    }
+}
+
+
+/**
+ * Takes the root of the AST and returns a warning for each while or for loop with an empty body, as a while
+ * loop with an empty body never ends if its condition is true, and a for loop with one does nothing.
+ */
+pub fn find_empty_loops(root:&[ASTNode]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for node in root {
+        if let ASTNode::Function {statements, ..} = node {
+            find_empty_loops_in_block(statements, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+
+/**
+ * Walks a block of statements and adds a warning to `warnings` for every expression used as a statement which
+ * has no side effects, including those in nested blocks.
+ */
+fn find_unused_expressions_in_block(statements:&[ASTNode], warnings:&mut Vec<Warning>) {
+    for statement in statements {
+        match statement {
+            ASTNode::ExpressionStatement {expression, line, ..} if !has_side_effects(expression) => {
+                warnings.push(Warning::UnusedExpression {line: *line});
+            },
+
+            ASTNode::IfElifElseStatement {statements}
+              | ASTNode::IfStatement {statements, ..}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..}
+              | ASTNode::WhileLoop {statements, ..}
+              | ASTNode::ForLoop {statements, ..}
+              | ASTNode::ForEachLoop {statements, ..} => find_unused_expressions_in_block(statements, warnings),
+
+            _ => {}
+        }
+    }
+}
+
+
+/**
+ * Takes the root of the AST and returns a warning for each expression used as a statement which calls no
+ * functions and reads no input, as its value is thrown away without it having done anything.
+ */
+pub fn find_unused_expressions(root:&[ASTNode]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for node in root {
+        if let ASTNode::Function {statements, ..} = node {
+            find_unused_expressions_in_block(statements, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+
+/**
+ * Walks a block of statements in the order they are written, keeping in `stored` the line each variable
+ * holding a string input was read on, along with the line of the first input read since, which overwrites
+ * it when every input shares one buffer. Adds a warning to `warnings` the first time such a variable is read
+ * after it has been overwritten. A variable given any other value is no longer tracked.
+ */
+fn find_overwritten_inputs_in_block(statements:&[ASTNode], stored:&mut HashMap<String, (usize, Option<usize>)>, warnings:&mut Vec<Warning>) {
+    for statement in statements {
+        // the value of a statement is worked out before it is stored, so its reads come before its input
+        let mut identifiers = vec![];
+        match statement {
+            ASTNode::VarDeclStatement {value, ..} | ASTNode::VarAssignStatement {value, ..} => get_used_identifiers(value, &mut identifiers),
+            ASTNode::IfStatement {condition, ..} | ASTNode::WhileLoop {condition, ..} => get_used_identifiers(condition, &mut identifiers),
+            ASTNode::ForLoop {control_initial, limit, step, ..} => {
+                for part in [control_initial, limit, step] {
+                    get_used_identifiers(part, &mut identifiers);
+                }
+            },
+            ASTNode::ForEachLoop {iterable, ..} => get_used_identifiers(iterable, &mut identifiers),
+            other => get_used_identifiers(other, &mut identifiers)
+        }
+
+        for identifier in identifiers {
+            if let Some((earlier_line, Some(line))) = stored.get(&identifier).copied() {
+                warnings.push(Warning::InputOverwritten {identifier: identifier.clone(), line, earlier_line});
+                stored.remove(&identifier);
+            }
+        }
+
+        match statement {
+            ASTNode::VarDeclStatement {identifier, value, line, ..} | ASTNode::VarAssignStatement {identifier, value, line} => {
+                if !matches!(**value, ASTNode::InputStatement(Type::String, _)) {
+                    stored.remove(identifier);
+                    continue;
+                }
+
+                for (_, overwritten_on) in stored.values_mut() {
+                    overwritten_on.get_or_insert(*line);
+                }
+
+                stored.insert(identifier.to_owned(), (*line, None));
+            },
+
+            ASTNode::IfElifElseStatement {statements}
+              | ASTNode::IfStatement {statements, ..}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..}
+              | ASTNode::WhileLoop {statements, ..}
+              | ASTNode::ForLoop {statements, ..}
+              | ASTNode::ForEachLoop {statements, ..} => find_overwritten_inputs_in_block(statements, stored, warnings),
+
+            _ => {}
+        }
+    }
+}
+
+
+/**
+ * Takes the root of the AST and returns a warning for each variable holding a string input which is read
+ * after a later input in the same function, as the generated code reads every input into one shared buffer
+ * unless `--distinct-input-buffers` is given, so the later input replaces the string the variable points to.
+ */
+pub fn find_overwritten_inputs(root:&[ASTNode]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for node in root {
+        if let ASTNode::Function {statements, ..} = node {
+            find_overwritten_inputs_in_block(statements, &mut HashMap::new(), &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+
+/**
+ * Adds a warning to `warnings` if a variable declared on the given line has the same identifier as one in an
+ * enclosing block, then adds it to the innermost block in `scopes`. Each block maps the identifiers declared in
+ * it so far to the line they were declared on, or `None` for a parameter.
+ */
+fn declare_in_scopes(identifier:&str, line:usize, scopes:&mut [HashMap<String, Option<usize>>], warnings:&mut Vec<Warning>) {
+    let (innermost, outer) = scopes.split_last_mut().expect("a block is always in a function");
+    if let Some(outer_line) = outer.iter().rev().find_map(|scope| scope.get(identifier)) {
+        warnings.push(Warning::ShadowsVariable {identifier: identifier.to_owned(), line, outer_line: *outer_line});
+    }
+
+    innermost.insert(identifier.to_owned(), Some(line));
+}
+
+
+/**
+ * Walks a block of statements and adds a warning to `warnings` for every variable, for loop control variable
+ * or for each element declared with the same identifier as a variable or parameter in an enclosing block,
+ * which it hides until the end of its own block.
+ */
+fn find_shadowed_variables_in_block(statements:&[ASTNode], scopes:&mut Vec<HashMap<String, Option<usize>>>, warnings:&mut Vec<Warning>) {
+    for statement in statements {
+        match statement {
+            ASTNode::VarDeclStatement {identifier, line, ..} => declare_in_scopes(identifier, *line, scopes, warnings),
+            ASTNode::IfElifElseStatement {statements} => find_shadowed_variables_in_block(statements, scopes, warnings),
+            ASTNode::IfStatement {statements, ..}
+              | ASTNode::ElseStatement {statements, ..}
+              | ASTNode::IndefLoop {statements, ..}
+              | ASTNode::WhileLoop {statements, ..} => {
+                scopes.push(HashMap::new());
+                find_shadowed_variables_in_block(statements, scopes, warnings);
+                scopes.pop();
+            },
+
+            ASTNode::ForLoop {control_identifier: identifier, statements, line, ..}
+              | ASTNode::ForEachLoop {element_identifier: identifier, statements, line, ..} => {
+                scopes.push(HashMap::new());
+                declare_in_scopes(identifier, *line, scopes, warnings);
+                find_shadowed_variables_in_block(statements, scopes, warnings);
+                scopes.pop();
+            },
+
+            _ => {}
+        }
+    }
+}
+
+
+/**
+ * Takes the root of the AST and returns a warning for each variable which hides a variable or parameter of
+ * the same name in an enclosing block. The innermost declaration is always the one used, so this is allowed,
+ * but the outer one cannot be read until the end of the block, which is easy to miss.
+ */
+pub fn find_shadowed_variables(root:&[ASTNode]) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for node in root {
+        if let ASTNode::Function {parameters, statements, ..} = node {
+            let parameters = parameters.iter().filter_map(|parameter| match parameter {
+                ASTNode::Parameter {identifier, ..} => Some((identifier.to_owned(), None)),
+                _ => None
+            }).collect();
+
+            find_shadowed_variables_in_block(statements, &mut vec![parameters], &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+
+/**
+ * Checks that no function or variable identifier starts with the prefix reserved for labels generated by
+ * the compiler.
+ */
+fn validate_reserved_identifiers(symbol_table:&SymbolTable) -> Result<(), Box<dyn Error>> {
+    for row in &symbol_table.rows {
+        match row {
+            SymbolTableRow::Function {identifier, ..} | SymbolTableRow::Variable {identifier, ..} => {
+                if identifier.starts_with(RESERVED_PREFIX) {
+                    return Err(Box::new(ReservedIdentifier {identifier: identifier.to_string(), line: None}));
+                }
+            },
+
+            SymbolTableRow::ScopeBlock {..} => {}
+        }
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Fills in the resolved types of a node which has passed semantic validation and of every node below it, so
+ * that code generation can read the types which were checked rather than working them out again. These are
+ * the type of the value an expression statement discards, the type a cast converts from, the type of the limit
+ * of a for loop, and the type of each term of a print statement.
+ */
+fn resolve_types(node:&mut ASTNode, symbol_table:&SymbolTable, scope_history:&[usize]) -> Result<(), Box<dyn Error>> {
+    // the types were already traced as they were checked
+    let trace = &Trace::disabled();
+    let mut scope_history = scope_history.to_vec();
+    match node {
+        ASTNode::Function {identifier, statements, ..} => {
+            scope_history.push(symbol_table.get_identifier_in_scope(identifier, &scope_history)?);
+            for statement in statements {
+                resolve_types(statement, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::ExpressionStatement {expression, resolved_type, ..} => {
+            *resolved_type = Some(find_discarded_value_type(expression, symbol_table, &scope_history, trace)?);
+            resolve_types(expression, symbol_table, &scope_history)?;
+        },
+
+        ASTNode::TypeCast {from, resolved_type, ..} => {
+            *resolved_type = Some(find_valid_type_of_node(from, symbol_table, &scope_history, trace)?);
+        },
+
+        ASTNode::PrintStatement {terms, resolved_types} => {
+            let term_types = terms.iter().map(|(term, _)| find_valid_type_of_node(term, symbol_table, &scope_history, trace));
+            *resolved_types = Some(term_types.collect::<Result<_, _>>()?);
+        },
+
+        // the header of a for loop is in the enclosing scope, as it is checked before the loop's scope is entered
+        ASTNode::ForLoop {control_initial, limit, step, statements, scope, resolved_type, ..} => {
+            *resolved_type = Some(find_valid_type_of_node(limit, symbol_table, &scope_history, trace)?);
+            for part in [control_initial, limit, step] {
+                resolve_types(part, symbol_table, &scope_history)?;
+            }
+
+            scope_history.push(*scope);
+            for statement in statements {
+                resolve_types(statement, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::IfStatement {condition, statements, scope, ..}
+          | ASTNode::WhileLoop {condition, statements, scope, ..} => {
+            resolve_types(condition, symbol_table, &scope_history)?;
+            scope_history.push(*scope);
+            for statement in statements {
+                resolve_types(statement, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::ElseStatement {statements, scope}
+          | ASTNode::IndefLoop {statements, scope}
+          | ASTNode::ForEachLoop {statements, scope, ..} => {
+            scope_history.push(*scope);
+            for statement in statements {
+                resolve_types(statement, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::IfElifElseStatement {statements} => {
+            for statement in statements {
+                resolve_types(statement, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::FunctionCall {arguments, ..} => {
+            for argument in arguments {
+                resolve_types(argument, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::ReturnStatement {expression: Some(child)}
+          | ASTNode::VarDeclStatement {value: child, ..}
+          | ASTNode::VarAssignStatement {value: child, ..}
+          | ASTNode::Term {child} => resolve_types(child, symbol_table, &scope_history)?,
+
+        ASTNode::Expression {lhs, rhs, ..}
+          | ASTNode::BooleanTerm {lhs, rhs, ..}
+          | ASTNode::BooleanExpression {lhs, rhs, ..} => {
+            resolve_types(lhs, symbol_table, &scope_history)?;
+            if let Some(rhs) = rhs {
+                resolve_types(rhs, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            for part in [condition, if_true, if_false] {
+                resolve_types(part, symbol_table, &scope_history)?;
+            }
+        },
+
+        ASTNode::ReturnStatement {expression: None}
+          | ASTNode::Parameter {..}
+          | ASTNode::Value {..}
+          | ASTNode::InputStatement(..)
+          | ASTNode::SizeOf(_)
+          | ASTNode::TypeOf(_)
+          | ASTNode::Identifier(_)
+          | ASTNode::Break
+          | ASTNode::Continue => {}
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Takes the root node of the AST and runs semantic analysis, checking for:
+ *   - undeclared/out of scope variables
+ *   - variables used before they are declared
+ *   - no/incorrect return statements
+ *   - reassignment to immutable variable
+ *   - operations on non-matching datatypes
+ *   - functions with incorrect return types
+ *   - incorrect arguments to function calls
+ *   - check validity of boolean statements
+ *   - functions marked `@inline` which are recursive
+ *   - functions marked `@pure` which have side effects, or call a function which does
+ *   - identifiers starting with the reserved prefix
+ *
+ * Returns the AST decorated with the types resolved for it (see `resolve_types`), which is the AST code must
+ * be generated from.
+ */
+pub fn semantic_validation(root:Vec<ASTNode>, symbol_table:&SymbolTable) -> Result<Vec<ASTNode>, Box<dyn Error>> {
+    semantic_validation_with_trace(root, symbol_table, &Trace::disabled())
+}
+
+
+/**
+ * Runs semantic analysis as `semantic_validation` does, writing the scopes in effect for each statement, the
+ * lookups of each identifier and the type found for each node to `trace` as the AST is walked, for
+ * `--trace-semantics`.
+ */
+pub fn semantic_validation_with_trace(root:Vec<ASTNode>, symbol_table:&SymbolTable, trace:&Trace) -> Result<Vec<ASTNode>, Box<dyn Error>> {
+    validate_reserved_identifiers(symbol_table)?;
+    validate_inline_functions(&root)?;
+    validate_pure_functions(&root)?;
+    validate_declaration_order(&root, symbol_table)?;
+    for node in &root {
+        semantic_validation_subtree(node, symbol_table, &vec![0], trace)?;
+    }
+
+    let mut root = root;
+    for node in &mut root {
+        resolve_types(node, symbol_table, &[0])?;
+    }
+
+    Ok(root)
+}
+
+
 /**
  * Called to generate an entire symbol table for all functions and variables in a program. Takes the root
- * `Vec<ASTNode>` of the program.
+ * `Vec<ASTNode>` of the program, and returns a `DuplicateSymbolError` if any symbols clash.
  */
-pub fn generate_symbol_table(root:Vec<ASTNode>) -> SymbolTable {
+pub fn generate_symbol_table(root:Vec<ASTNode>) -> Result<SymbolTable, Box<dyn Error>> {
     let mut table = SymbolTable { rows: vec![] };
     for node in root {
-        generate_sub_symbol_table(node, &mut table, None, "global");
+        generate_sub_symbol_table(node, &mut table, None)?;
     }
 
-    table
+    Ok(table)
 }
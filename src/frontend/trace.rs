@@ -0,0 +1,70 @@
+use std::cell::{Cell, RefCell};
+
+
+/**
+ * Collects the trace of semantic validation printed with `--trace-semantics`, which follows the walk over the
+ * AST, with each line indented by how deep in the walk it was written. A disabled trace keeps nothing, and
+ * the text of a line is only built when the trace is enabled, so validating without the flag only costs a
+ * check of whether it is.
+ */
+#[derive(Debug, Default)]
+pub struct Trace {
+    lines: Option<RefCell<Vec<String>>>,
+    depth: Cell<usize>
+}
+
+impl Trace {
+    pub fn new(enabled:bool) -> Trace {
+        Trace {lines: enabled.then(|| RefCell::new(vec![])), depth: Cell::new(0)}
+    }
+
+
+    pub fn disabled() -> Trace {
+        Trace::default()
+    }
+
+
+    /**
+     * Adds the line given by `line` to the trace at the current depth, only building it if the trace is
+     * enabled.
+     */
+    pub fn log<F:FnOnce() -> String>(&self, line:F) {
+        if let Some(lines) = &self.lines {
+            lines.borrow_mut().push(format!("{}{}", "  ".repeat(self.depth.get()), line()));
+        }
+    }
+
+
+    /**
+     * Adds the line given by `line` to the trace, and indents every line added after it by one more level
+     * until the returned `TraceLevel` is dropped.
+     */
+    pub fn enter<F:FnOnce() -> String>(&self, line:F) -> TraceLevel<'_> {
+        self.log(line);
+        self.depth.set(self.depth.get() + 1);
+        TraceLevel {trace: self}
+    }
+
+
+    /**
+     * Returns the lines of the trace so far, each already indented, which is nothing if it is disabled.
+     */
+    pub fn get_lines(&self) -> Vec<String> {
+        self.lines.as_ref().map_or(vec![], |lines| lines.borrow().clone())
+    }
+}
+
+
+/**
+ * A level of the trace entered with `Trace::enter`, which returns the trace to the level it was at before
+ * when dropped.
+ */
+pub struct TraceLevel<'a> {
+    trace: &'a Trace
+}
+
+impl Drop for TraceLevel<'_> {
+    fn drop(&mut self) {
+        self.trace.depth.set(self.trace.depth.get() - 1);
+    }
+}
@@ -1,37 +1,931 @@
 mod frontend;
 mod backend;
+mod conformance;
+mod diagnostics;
+mod doctor;
 mod errors;
+mod optimisation;
+mod project;
+mod size_report;
+mod stack_report;
+mod timing;
+#[cfg(test)]
+mod testing;
 
 extern crate pest;
 #[macro_use]
 extern crate pest_derive;
-use std::env;
+use std::{env, fs, io, panic, process, thread};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-fn main() {
-    let cmd_args:Vec<String> = env::args().collect();
+use backend::{Backend, OverflowMode};
+use backend::mips::CodegenOptions;
+use diagnostics::{Diagnostic, MessageFormat};
+use size_report::SizeReport;
+use stack_report::StackReport;
+use timing::PhaseTimings;
+use frontend::trace::Trace;
 
-    let filename = &cmd_args[1];
-    let output_name = format!("{}.asm", &cmd_args[2]);
-    if !filename.ends_with(".iri") {
-        panic!("Input filename must have the .iri file extension");
+
+/**
+ * How often the input file is checked for changes in watch mode.
+ */
+const WATCH_POLL_INTERVAL:Duration = Duration::from_millis(250);
+
+/**
+ * How long the input file must go without changing before it is recompiled in watch mode, so that an editor
+ * writing a file in several steps only triggers one compilation.
+ */
+const WATCH_DEBOUNCE:Duration = Duration::from_millis(100);
+
+/**
+ * The size of the stack the compiler runs on. The passes over the AST recurse once for each level of nesting,
+ * and need more than the main thread has to reach the nesting limit in an unoptimised build.
+ */
+const COMPILER_STACK_SIZE:usize = 64 * 1024 * 1024;
+
+
+/**
+ * Describes a flag which can be given after the target flag, with the name of the value which follows it if
+ * it takes one. `--help` is made from these, and `parse_options` can only read flags which are listed here,
+ * so the help cannot leave one out. Any other flag is rejected by `check_flags`.
+ */
+struct CliOption {
+    name: &'static str,
+    value: Option<&'static str>,
+    description: &'static str
+}
+
+/**
+ * Every flag which can be given after the target flag, in the order they are listed by `--help`.
+ */
+const CLI_OPTIONS:[CliOption; 32] = [
+    CliOption {name: "-O1", value: None, description: "remove branches which can never be taken"},
+    CliOption {name: "-O2", value: None, description: "do everything -O1 does, and also remove unreachable functions and traps, fold calls to pure functions and constants, and build jump tables"},
+    CliOption {name: "--create-dirs", value: None, description: "create any missing directories of the output filename"},
+    CliOption {name: "--force", value: None, description: "compile input files without the .iri or .irid extension without a warning"},
+    CliOption {name: "--checked", value: None, description: "check every index into a string is in range at runtime"},
+    CliOption {name: "--overflow", value: Some("trap|wrap"), description: "stop the program when int arithmetic overflows, or wrap around, which is the default"},
+    CliOption {name: "--distinct-input-buffers", value: None, description: "give each input in the program its own buffer"},
+    CliOption {name: "--no-packing", value: None, description: "give each variable a whole word of its frame"},
+    CliOption {name: "--frame-checks", value: None, description: "guard the frame of each function with a canary checked before it returns"},
+    CliOption {name: "--debug-info", value: None, description: "describe the frame at the start of each function in the MIPS"},
+    CliOption {name: "--debug", value: None, description: "run the program in a debugger which reads commands from stdin, with -run"},
+    CliOption {name: "--jump-table-density", value: Some("N"), description: "build a jump table with -O2 when at least N percent of its entries are cases"},
+    CliOption {name: "--watch", value: None, description: "compile the program again each time the input file is saved"},
+    CliOption {name: "--time", value: None, description: "print how long each phase of compilation took"},
+    CliOption {name: "--verbose", value: None, description: "print the symbol table and the optimised intermediate code"},
+    CliOption {name: "--trace-semantics", value: None, description: "print a trace of the scopes, lookups and types of semantic validation"},
+    CliOption {name: "--report-sizes", value: None, description: "print the size of the code of each function and of the data section"},
+    CliOption {name: "--size-budget", value: Some("N"), description: "warn if the code and data take up more than N bytes, with -mips"},
+    CliOption {name: "--size-budget-hard", value: None, description: "make going over the size budget an error"},
+    CliOption {name: "--report-stack", value: None, description: "print how much of the stack each function and the deepest chain of calls use, and warn about recursion"},
+    CliOption {name: "--stack-size", value: Some("N"), description: "set aside N bytes in the data section for the stack and start the program on it, with -mips"},
+    CliOption {name: "--max-nesting", value: Some("N"), description: "allow brackets and braces to be nested N deep rather than 512"},
+    CliOption {name: "--max-steps", value: Some("N"), description: "stop the program after N intermediate instructions, with -run"},
+    CliOption {name: "--max-call-depth", value: Some("N"), description: "stop the program when more than N calls are in progress, with -run"},
+    CliOption {name: "--max-heap", value: Some("N"), description: "stop the program when it allocates more than N bytes, with -run"},
+    CliOption {name: "--emit-callgraph", value: Some("<path>"), description: "write the call graph of the program to the path in the dot language"},
+    CliOption {name: "--emit-ir", value: Some("<path>"), description: "write the intermediate code given to the backend to the path"},
+    CliOption {name: "--emit-ir-debug", value: Some("<path>"), description: "write the intermediate code to the path as the compiler's own types"},
+    CliOption {name: "--message-format", value: Some("human|json"), description: "print errors and warnings as text, which is the default, or as JSON"},
+    CliOption {name: "--help", value: None, description: "print this help and exit, in place of the input filename"},
+    CliOption {name: "--version", value: None, description: "print the version of the compiler and exit, in place of the input filename"},
+    CliOption {name: "--list-targets", value: None, description: "print the target flags compiled in and exit, in place of the input filename"}
+];
+
+
+/**
+ * The options given on the command line which change how a program is compiled.
+ */
+struct CompileOptions {
+    create_dirs: bool,
+    prune_branches: bool,
+    remove_unreachable_traps: bool,
+    remove_unreachable_functions: bool,
+    fold_pure_calls: bool,
+    propagate_constants: bool,
+    jump_tables: bool,
+    jump_table_density: usize,
+    checked: bool,
+    overflow: OverflowMode,
+    distinct_input_buffers: bool,
+    no_packing: bool,
+    frame_checks: bool,
+    debug_info: bool,
+    debug: bool,
+    time: bool,
+    verbose: bool,
+    trace_semantics: bool,
+    report_sizes: bool,
+    size_budget: Option<u64>,
+    size_budget_hard: bool,
+    report_stack: bool,
+    stack_size: Option<u64>,
+    max_nesting: usize,
+    max_steps: Option<u64>,
+    max_call_depth: Option<usize>,
+    max_heap: Option<usize>,
+    emit_callgraph: Option<String>,
+    emit_ir: Option<String>,
+    emit_ir_debug: Option<String>,
+    message_format: MessageFormat
+}
+
+
+/**
+ * Returns true if the output name given on the command line means the assembly should be written to stdout
+ * rather than a file, which is the case for `-` and `--stdout`.
+ */
+fn is_stdout(output_name:&str) -> bool {
+    output_name == "-" || output_name == "--stdout"
+}
+
+
+/**
+ * Takes the output name given on the command line and returns the path of the file to write, adding the
+ * extension of the backend, such as `.asm`, unless it is already there. If `create_dirs` is true, any missing
+ * parent directories are created. Returns an error if the output path is the input file.
+ */
+fn prepare_output_path(input_name:&str, output_name:&str, extension:&str, create_dirs:bool) -> Result<String, Box<dyn Error>> {
+    let output_path = match output_name.ends_with(&format!(".{}", extension)) {
+        true => output_name.to_owned(),
+        false => format!("{}.{}", output_name, extension)
+    };
+
+    if let Ok(output) = fs::canonicalize(&output_path) {
+        if fs::canonicalize(input_name).is_ok_and(|input| input == output) {
+            return Err(Box::new(errors::OutputOverwritesInput(output_path)));
+        }
     }
 
-    println!("Compiling {} into {}", filename, &cmd_args[2]);
-    let ast = frontend::parser::parse(filename).unwrap();
+    if create_dirs {
+        if let Some(parent) = Path::new(&output_path).parent() {
+            fs::create_dir_all(parent).map_err(|err| errors::OutputFileError::new(&output_path, &err))?;
+        }
+    }
+
+    Ok(output_path)
+}
+
+
+/**
+ * Generates the code for the program with the given backend and writes it to the file at the given path,
+ * creating or truncating it. Any I/O error is reported as a problem with the output file. Returns the number
+ * of target instructions generated for each function.
+ */
+fn write_output_file(backend:&dyn Backend, instructions:Vec<frontend::intermediate_gen::IntermediateInstr>, output_path:&str,
+                     symbol_table:&frontend::semantics::SymbolTable) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+    let file = OpenOptions::new().write(true)
+                                 .truncate(true)
+                                 .create(true)
+                                 .open(output_path)
+                                 .map_err(|err| errors::OutputFileError::new(output_path, &err))?;
+
+    // only I/O errors are to do with the output file, anything else is a problem with the code generated
+    let mut writer = BufWriter::new(file);
+    backend.generate(instructions, symbol_table, &mut writer).map_err(|err| match err.downcast::<io::Error>() {
+        Ok(err) => Box::new(errors::OutputFileError::new(output_path, &err)),
+        Err(err) => err
+    })
+}
+
+
+/**
+ * Writes the intermediate code to the file at the given path, one instruction per line, in the textual format
+ * given by its `Display`, or in its `Debug` format if `debug` is true.
+ */
+fn write_ir_file(instructions:&[frontend::intermediate_gen::IntermediateInstr], path:&str, debug:bool) -> Result<(), Box<dyn Error>> {
+    let lines:Vec<String> = instructions.iter().map(|instr| match debug {
+        true => format!("{:?}", instr),
+        false => instr.to_string()
+    }).collect();
+
+    fs::write(path, format!("{}\n", lines.join("\n").trim_start())).map_err(|err| errors::OutputFileError::new(path, &err))?;
+    Ok(())
+}
+
+
+/**
+ * Compiles the program in the file `filename`, or stdin if it is `-`, for the given target flag, writing any
+ * output to `output_name`, or stdout if it is `-` or `--stdout`. Everything else the compiler prints goes to
+ * stderr, so the assembly can be piped into another program. Nothing is kept between calls, so the same file
+ * can be compiled again after it changes. Returns the exit code of the program if the target is `-run`, which
+ * runs it with the interpreter. The time taken by each phase is recorded in `timings`, including the phase
+ * which failed if there is an error.
+ */
+fn compile_file(filename:&str, output_name:&str, target:&str, options:&CompileOptions, timings:&mut PhaseTimings) -> Result<Option<i32>, Box<dyn Error>> {
+    eprintln!("Compiling {} into {}", filename, output_name);
+    let (ast, parse_errors) = timings.time("parsing", || frontend::parser::parse(filename, options.max_nesting))?;
+    check_parse_errors(parse_errors, filename, options)?;
     // println!("{:#?}\n\n\n", ast);
-    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone());
-    println!("{:#?}", symbol_table);
-    frontend::semantics::semantic_validation(ast.clone(), &symbol_table).unwrap();
-    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table);
+    let symbol_table = timings.time("symbol table generation", || frontend::semantics::generate_symbol_table(ast.clone()))?;
+    if options.verbose {
+        eprintln!("{:#?}", symbol_table);
+    }
+
+    let trace = Trace::new(options.trace_semantics);
+    let validated = timings.time("semantic validation", || -> Result<_, Box<dyn Error>> {
+        let ast = frontend::semantics::semantic_validation_with_trace(ast, &symbol_table, &trace)?;
+        let mut warnings = frontend::semantics::find_constant_warnings(&ast);
+        warnings.extend(frontend::semantics::find_empty_loops(&ast));
+        warnings.extend(frontend::semantics::find_unused_expressions(&ast));
+        warnings.extend(frontend::semantics::find_shadowed_variables(&ast));
+        warnings.extend(frontend::call_graph::CallGraph::new(&ast).find_unreachable_functions(&ast));
+        if !options.distinct_input_buffers {
+            warnings.extend(frontend::semantics::find_overwritten_inputs(&ast));
+        }
+
+        Ok((ast, warnings))
+    });
+
+    // the trace is most useful when validation fails, so it is printed before any error is returned
+    for line in trace.get_lines() {
+        eprintln!("{}", line);
+    }
+
+    let (ast, warnings) = validated?;
+
+    for warning in warnings {
+        eprintln!("{}", options.message_format.render(&Diagnostic::from_warning(&warning, filename)));
+    }
+
+    generate_output(ast, symbol_table, filename, output_name, target, options, timings)
+}
+
+
+/**
+ * Returns the error found while parsing a program if there was only one. If there were more, each is printed
+ * so that they can all be fixed at once, and an error giving how many there were is returned.
+ */
+fn check_parse_errors(parse_errors:Vec<Box<dyn Error>>, filename:&str, options:&CompileOptions) -> Result<(), Box<dyn Error>> {
+    if parse_errors.len() <= 1 {
+        return match parse_errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(())
+        };
+    }
+
+    for err in &parse_errors {
+        eprintln!("{}", options.message_format.render(&Diagnostic::from_error(err.as_ref(), filename)));
+    }
+
+    Err(Box::new(errors::SyntaxErrors {count: parse_errors.len()}))
+}
 
-    for instr in &instructions {
-        println!("{}", instr);
+
+/**
+ * Compiles every `.iri` file in `directory` and its subdirectories into one program, writing the assembly
+ * to `output_name`, or stdout if it is `-` or `--stdout`. Functions in any file can call those in the
+ * others, and exactly one of the files must declare `main`. Errors found in a single file are reported with
+ * its path.
+ */
+fn compile_directory(directory:&str, output_name:&str, options:&CompileOptions, timings:&mut PhaseTimings) -> Result<Option<i32>, Box<dyn Error>> {
+    eprintln!("Compiling {} into {}", directory, output_name);
+    let (ast, files) = timings.time("parsing", || project::parse_directory(directory, options.max_nesting))?;
+    let symbol_table = timings.time("symbol table generation", || project::generate_symbol_table(&ast, &files, directory))?;
+    if options.verbose {
+        eprintln!("{:#?}", symbol_table);
+    }
+
+    let trace = Trace::new(options.trace_semantics);
+    let validated = timings.time("semantic validation", || -> Result<_, Box<dyn Error>> {
+        let ast = project::semantic_validation(&ast, &symbol_table, &files, &trace)?;
+        let warnings = project::find_warnings(&ast, &files, options.distinct_input_buffers);
+        Ok((ast, warnings))
+    });
+
+    // the trace is most useful when validation fails, so it is printed before any error is returned
+    for line in trace.get_lines() {
+        eprintln!("{}", line);
+    }
+
+    let (ast, warnings) = validated?;
+
+    for (path, warning) in warnings {
+        eprintln!("{}", options.message_format.render(&Diagnostic::from_warning(&warning, path)));
     }
 
-    match &*cmd_args[3] {
-        "-mips" => backend::mips::generate_mips(instructions, &output_name, &symbol_table).unwrap(),
+    generate_output(ast, symbol_table, directory, output_name, "-mips", options, timings)
+}
+
+
+/**
+ * Optimises a program which has passed semantic validation and generates the output for the given target
+ * flag, as described for `compile_file`. `input_name` is the file or directory the program was read from.
+ * The call graph is written before any optimisation, so it shows every function in the program, while the
+ * intermediate code for `--emit-ir` is written after it, exactly as it is given to the backend. The stack
+ * report of `--report-stack` is also made from the call graph before optimisation, so calls which are later
+ * folded away are still counted.
+ */
+fn generate_output(ast:Vec<frontend::ast::ASTNode>, symbol_table:frontend::semantics::SymbolTable, input_name:&str, output_name:&str, target:&str,
+                   options:&CompileOptions, timings:&mut PhaseTimings) -> Result<Option<i32>, Box<dyn Error>> {
+    if let Some(path) = &options.emit_callgraph {
+        let dot = frontend::call_graph::CallGraph::new(&ast).to_dot();
+        fs::write(path, dot).map_err(|err| errors::OutputFileError::new(path, &err))?;
+    }
+
+    let call_graph = options.report_stack.then(|| frontend::call_graph::CallGraph::new(&ast));
+
+    let ast = match options.remove_unreachable_functions {
+        true => timings.time_pass("AST optimisation", "unreachable function removal", || optimisation::dead_functions::remove_unreachable_functions(ast)),
+        false => ast
+    };
+
+    let ast = timings.time_pass("AST optimisation", "cast folding", || optimisation::cast_folding::fold_constant_casts(ast));
+    let ast = match options.prune_branches {
+        true => timings.time_pass("AST optimisation", "branch pruning", || optimisation::branch_pruning::prune_constant_branches(ast)),
+        false => ast
+    };
+
+    let pure_functions = optimisation::pure_calls::find_pure_functions(&ast);
+    let (instructions, frame_variables) = timings.time("intermediate generation", || {
+        frontend::intermediate_gen::generate_program_intermediate_with_variables(ast, &symbol_table)
+    })?;
+    let instructions = match options.fold_pure_calls {
+        true => timings.time_pass("optimisation", "pure call folding", || optimisation::pure_calls::fold_pure_calls(instructions, &pure_functions, &symbol_table, options.overflow)),
+        false => instructions
+    };
+
+    let instructions = match options.propagate_constants {
+        true => timings.time_pass("optimisation", "constant propagation", || optimisation::constant_propagation::propagate_constants(instructions, options.overflow)),
+        false => instructions
+    };
+
+    let instructions = optimisation::optimise(instructions, options.overflow, timings);
+    let instructions = match options.jump_tables {
+        true => timings.time_pass("optimisation", "jump table building", || optimisation::jump_tables::build_jump_tables(instructions, options.jump_table_density)),
+        false => instructions
+    };
+
+    let instructions = match options.remove_unreachable_traps {
+        true => timings.time_pass("optimisation", "unreachable trap removal", || optimisation::traps::remove_unreachable_traps(instructions)),
+        false => instructions
+    };
+
+    let instructions = match options.checked {
+        true => frontend::intermediate_gen::add_bounds_checks(instructions),
+        false => instructions
+    };
+
+    if options.verbose {
+        for instr in &instructions {
+            eprintln!("{}", instr);
+        }
+    }
+
+    if let Some(path) = &options.emit_ir {
+        write_ir_file(&instructions, path, false)?;
+    }
+
+    if let Some(path) = &options.emit_ir_debug {
+        write_ir_file(&instructions, path, true)?;
+    }
+
+    // the size of the code is only known once it has been generated for MIPS
+    if options.size_budget.is_some() && target != "-mips" {
+        return Err(Box::new(errors::InvalidOption(String::from("--size-budget can only be used with the -mips target"))));
+    }
+
+    let mut sizes = (options.report_sizes || options.size_budget.is_some()).then(|| SizeReport::new(&instructions, &symbol_table, !options.no_packing));
+    let codegen = CodegenOptions {
+        overflow: options.overflow,
+        distinct_input_buffers: options.distinct_input_buffers,
+        no_packing: options.no_packing,
+        frame_checks: options.frame_checks,
+        stack_size: options.stack_size,
+        debug_info: options.debug_info.then_some(&frame_variables[..])
+    };
+    if let Some(call_graph) = &call_graph {
+        let stack = StackReport::new(call_graph, &symbol_table, &codegen)?;
+        match options.message_format {
+            MessageFormat::Human => eprintln!("{}", stack),
+            MessageFormat::Json => eprintln!("{}", stack.to_json())
+        }
+
+        for cycle in stack.get_cycles() {
+            eprintln!("{}", options.message_format.render(&Diagnostic::from_file_warning("unbounded-stack", cycle.get_warning(), input_name)));
+        }
+    }
+
+    if let (Some(sizes), "-mips") = (&mut sizes, target) {
+        sizes.add_data_sizes(backend::mips::get_data_sizes(&instructions, &codegen)?);
+    }
+    let exit_code = match target {
+        "-run" => {
+            let mut interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_overflow(options.overflow);
+            if let Some(max_steps) = options.max_steps {
+                interpreter = interpreter.with_max_steps(max_steps);
+            }
+
+            if let Some(max_call_depth) = options.max_call_depth {
+                interpreter = interpreter.with_max_call_depth(max_call_depth);
+            }
+
+            if let Some(max_heap) = options.max_heap {
+                interpreter = interpreter.with_max_heap(max_heap);
+            }
+
+            match options.debug {
+                true => {
+                    let mut debugger = backend::debugger::Debugger::new(interpreter, &instructions, &frame_variables, options.message_format);
+                    Some(debugger.run(&mut io::stdin().lock(), &mut io::stdout(), &mut io::stderr())?)
+                },
+                false => Some(timings.time("backend", || interpreter.run(&mut io::stdin().lock(), &mut io::stdout()))?)
+            }
+        },
         "-ird" => panic!("Iridium architecture compilation is not yet supported"),
         "-x64" => panic!("The x86-64 architecture compilation is not yet supported"),
-        option => panic!("{} is not a valid target code flag", option)
+        option => {
+            let backend = option.strip_prefix('-').and_then(|name| backend::find_backend(name, &codegen))
+                                                  .unwrap_or_else(|| panic!("{} is not a valid target code flag", option));
+
+            let counts = match is_stdout(output_name) {
+                true => {
+                    let mut writer = BufWriter::new(io::stdout().lock());
+                    timings.time("backend", || backend.generate(instructions, &symbol_table, &mut writer))?
+                },
+                false => {
+                    let output_path = prepare_output_path(input_name, output_name, backend.get_extension(), options.create_dirs)?;
+                    timings.time("backend", || write_output_file(backend.as_ref(), instructions, &output_path, &symbol_table))?
+                }
+            };
+
+            if let Some(sizes) = &mut sizes {
+                sizes.add_mips_counts(&counts);
+            }
+
+            None
+        }
+    };
+
+    if let Some(sizes) = sizes {
+        match options.message_format {
+            MessageFormat::Human => eprintln!("{}", sizes),
+            MessageFormat::Json => eprintln!("{}", sizes.to_json())
+        }
+
+        if let (Some(budget), Some(total)) = (options.size_budget, sizes.get_total_bytes()) {
+            if total > budget {
+                let err = errors::SizeBudgetExceeded {total, budget};
+                match options.size_budget_hard {
+                    true => return Err(Box::new(err)),
+                    false => eprintln!("{}", options.message_format.render(&Diagnostic::from_file_warning("size-budget-exceeded", err.to_string(), input_name)))
+                }
+            }
+        }
+    }
+
+    Ok(exit_code)
+}
+
+
+/**
+ * Returns the time the file at the given path was last modified, or `None` if it cannot be read, such as
+ * while an editor is replacing it.
+ */
+fn get_modified_time(path:&str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+
+/**
+ * Formats the current time of day in UTC as `hh:mm:ss` for the summaries printed in watch mode.
+ */
+fn get_timestamp() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0) % 86400;
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+
+/**
+ * Runs `compile`, catching any panic from inside the compiler, and prints the time taken by each phase if
+ * `--time` was given. The timings are printed whether or not compilation succeeded.
+ */
+fn compile_and_time<F>(options:&CompileOptions, compile:F) -> thread::Result<Result<Option<i32>, Box<dyn Error>>>
+where F: FnOnce(&mut PhaseTimings) -> Result<Option<i32>, Box<dyn Error>> {
+    let mut timings = PhaseTimings::new();
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| compile(&mut timings)));
+    if options.time {
+        eprintln!("{}", timings);
+    }
+
+    result
+}
+
+
+/**
+ * Compiles the file and prints a one line summary of whether it succeeded. Errors, including panics from
+ * inside the compiler, are reported rather than ending the process so that watch mode keeps running.
+ */
+fn compile_and_report(filename:&str, output_name:&str, target:&str, options:&CompileOptions) {
+    match compile_and_time(options, |timings| compile_file(filename, output_name, target, options, timings)) {
+        Ok(Ok(Some(exit_code))) => println!("[{}] Ran {}, which exited with code {}", get_timestamp(), filename, exit_code),
+        Ok(Ok(None)) => println!("[{}] Compiled {} successfully", get_timestamp(), filename),
+        Ok(Err(err)) => {
+            eprintln!("{}", options.message_format.render(&Diagnostic::from_error(err.as_ref(), filename)));
+            eprintln!("[{}] Failed to compile {}", get_timestamp(), filename);
+        },
+        Err(_) => eprintln!("[{}] Failed to compile {}: the compiler panicked", get_timestamp(), filename)
+    }
+}
+
+
+/**
+ * Polls the modification time of the file until it differs from `last_modified`, then keeps polling until it
+ * has stopped changing for `WATCH_DEBOUNCE`, returning the time it settled on. `poll` is given how long to
+ * wait before it reads the time, and gives `None` while the file cannot be read, such as while an editor is
+ * replacing it, which is never taken as settled.
+ */
+fn wait_for_change(last_modified:Option<SystemTime>, mut poll:impl FnMut(Duration) -> Option<SystemTime>) -> Option<SystemTime> {
+    loop {
+        let mut modified = poll(WATCH_POLL_INTERVAL);
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+
+        loop {
+            let settled = poll(WATCH_DEBOUNCE);
+            if settled.is_some() && settled == modified {
+                return settled;
+            }
+
+            modified = settled;
+        }
+    }
+}
+
+
+/**
+ * Compiles the file, then polls it for changes to its modification time and compiles it again after each
+ * change, never returning. A change is only acted on once the file has stopped changing for `WATCH_DEBOUNCE`.
+ */
+fn watch(filename:&str, output_name:&str, target:&str, options:&CompileOptions) -> ! {
+    let mut last_modified = get_modified_time(filename);
+    compile_and_report(filename, output_name, target, options);
+    println!("Watching {} for changes", filename);
+
+    loop {
+        last_modified = wait_for_change(last_modified, |interval| {
+            thread::sleep(interval);
+            get_modified_time(filename)
+        });
+
+        compile_and_report(filename, output_name, target, options);
+    }
+}
+
+
+/**
+ * Returns the entry of `CLI_OPTIONS` for the given flag. Panics if it has none, as then `--help` would not
+ * describe a flag the compiler reads.
+ */
+fn get_cli_option(name:&str) -> &'static CliOption {
+    CLI_OPTIONS.iter().find(|option| option.name == name).unwrap_or_else(|| panic!("{} is not listed in CLI_OPTIONS", name))
+}
+
+
+/**
+ * Returns true if the given flag, which must be listed in `CLI_OPTIONS`, is among the flags.
+ */
+fn has_flag(flags:&[String], name:&str) -> bool {
+    get_cli_option(name);
+    flags.iter().any(|arg| arg == name)
+}
+
+
+/**
+ * Returns the position among the flags of the given flag, which must be listed in `CLI_OPTIONS`, so that the
+ * value after it can be read.
+ */
+fn find_flag(flags:&[String], name:&str) -> Option<usize> {
+    get_cli_option(name);
+    flags.iter().position(|arg| arg == name)
+}
+
+
+/**
+ * Returns the text printed by `--help`, made from the backends compiled in and `CLI_OPTIONS`.
+ */
+fn get_help_text() -> String {
+    let name = env!("CARGO_PKG_NAME");
+    let mut lines = vec![
+        format!("Usage: {} <input filename> <output filename> <target flag> [flags]", name),
+        format!("       {} build <directory> -o <output filename> [flags]", name),
+        format!("       {} doctor", name),
+        format!("       {} gen-conformance <directory>", name),
+        String::new(),
+        String::from("Targets:")
+    ];
+
+    let mut targets:Vec<(String, &str)> = backend::get_backends(&CodegenOptions::default()).iter()
+                                                                                          .map(|backend| (format!("-{}", backend.get_name()), backend.get_description()))
+                                                                                          .collect();
+    targets.push((String::from("-run"), "run the program with the intermediate code interpreter"));
+    let width = targets.iter().map(|(flag, _)| flag.len()).max().unwrap_or(0);
+    lines.extend(targets.iter().map(|(flag, description)| format!("  {:width$}  {}", flag, description, width = width)));
+
+    lines.push(String::new());
+    lines.push(String::from("Flags:"));
+    let usages:Vec<String> = CLI_OPTIONS.iter().map(|option| match option.value {
+        Some(value) => format!("{} {}", option.name, value),
+        None => option.name.to_owned()
+    }).collect();
+
+    let width = usages.iter().map(|usage| usage.len()).max().unwrap_or(0);
+    lines.extend(usages.iter().zip(&CLI_OPTIONS).map(|(usage, option)| format!("  {:width$}  {}", usage, option.description, width = width)));
+    lines.join("\n")
+}
+
+
+/**
+ * Handles `--help`, `--version` and `--list-targets` given in place of the input filename, printing what they
+ * ask for and exiting. `--list-targets` prints the name, file extension and description of each backend
+ * compiled in, one per line.
+ */
+fn print_info(flag:&str) -> ! {
+    match flag {
+        "--help" => println!("{}", get_help_text()),
+        "--version" => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        _ => {
+            for backend in backend::get_backends(&CodegenOptions::default()) {
+                println!("-{}\t.{}\t{}", backend.get_name(), backend.get_extension(), backend.get_description());
+            }
+        }
+    }
+
+    process::exit(0);
+}
+
+
+/**
+ * Takes the flags given on the command line after the input and output names and returns the options they
+ * set.
+ */
+fn parse_options(flags:&[String]) -> CompileOptions {
+    CompileOptions {
+        create_dirs: has_flag(flags, "--create-dirs"),
+        prune_branches: has_flag(flags, "-O1") || has_flag(flags, "-O2"),
+        remove_unreachable_traps: has_flag(flags, "-O2"),
+        remove_unreachable_functions: has_flag(flags, "-O2"),
+        fold_pure_calls: has_flag(flags, "-O2"),
+        propagate_constants: has_flag(flags, "-O2"),
+        jump_tables: has_flag(flags, "-O2"),
+        jump_table_density: match find_flag(flags, "--jump-table-density") {
+            Some(position) => {
+                let density = flags.get(position + 1).expect("--jump-table-density must be followed by a percentage");
+                match density.parse() {
+                    Ok(density @ 1..=100) => density,
+                    _ => panic!("--jump-table-density must be followed by a percentage from 1 to 100")
+                }
+            },
+            None => optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY
+        },
+        checked: has_flag(flags, "--checked"),
+        overflow: match find_flag(flags, "--overflow") {
+            Some(position) => {
+                let name = flags.get(position + 1).expect("--overflow must be followed by trap or wrap");
+                OverflowMode::from_name(name).unwrap()
+            },
+            None => OverflowMode::Wrap
+        },
+        distinct_input_buffers: has_flag(flags, "--distinct-input-buffers"),
+        no_packing: has_flag(flags, "--no-packing"),
+        frame_checks: has_flag(flags, "--frame-checks"),
+        debug_info: has_flag(flags, "--debug-info"),
+        debug: has_flag(flags, "--debug"),
+        time: has_flag(flags, "--time"),
+        verbose: has_flag(flags, "--verbose"),
+        trace_semantics: has_flag(flags, "--trace-semantics"),
+        report_sizes: has_flag(flags, "--report-sizes"),
+        size_budget: find_flag(flags, "--size-budget").map(|position| {
+            let budget = flags.get(position + 1).expect("--size-budget must be followed by a number of bytes");
+            budget.parse().expect("--size-budget must be followed by a number of bytes")
+        }),
+        size_budget_hard: has_flag(flags, "--size-budget-hard"),
+        report_stack: has_flag(flags, "--report-stack"),
+        stack_size: find_flag(flags, "--stack-size").map(|position| {
+            let size = flags.get(position + 1).expect("--stack-size must be followed by a number of bytes");
+            match size.parse() {
+                Ok(size) if size > 0 && size % 8 == 0 => size,
+                _ => panic!("--stack-size must be followed by a number of bytes which is a multiple of 8")
+            }
+        }),
+        max_nesting: match find_flag(flags, "--max-nesting") {
+            Some(position) => {
+                let limit = flags.get(position + 1).expect("--max-nesting must be followed by a number");
+                limit.parse().expect("--max-nesting must be followed by a number")
+            },
+            None => frontend::parser::DEFAULT_NESTING_LIMIT
+        },
+        max_steps: find_flag(flags, "--max-steps").map(|position| {
+            let limit = flags.get(position + 1).expect("--max-steps must be followed by a number");
+            limit.parse().expect("--max-steps must be followed by a number")
+        }),
+        max_call_depth: find_flag(flags, "--max-call-depth").map(|position| {
+            let limit = flags.get(position + 1).expect("--max-call-depth must be followed by a number");
+            limit.parse().expect("--max-call-depth must be followed by a number")
+        }),
+        max_heap: find_flag(flags, "--max-heap").map(|position| {
+            let limit = flags.get(position + 1).expect("--max-heap must be followed by a number of bytes");
+            limit.parse().expect("--max-heap must be followed by a number of bytes")
+        }),
+        emit_callgraph: find_flag(flags, "--emit-callgraph").map(|position| {
+            flags.get(position + 1).expect("--emit-callgraph must be followed by the path to write the graph to").to_owned()
+        }),
+        emit_ir: find_flag(flags, "--emit-ir").map(|position| {
+            flags.get(position + 1).expect("--emit-ir must be followed by the path to write the intermediate code to").to_owned()
+        }),
+        emit_ir_debug: find_flag(flags, "--emit-ir-debug").map(|position| {
+            flags.get(position + 1).expect("--emit-ir-debug must be followed by the path to write the intermediate code to").to_owned()
+        }),
+        message_format: match find_flag(flags, "--message-format") {
+            Some(position) => {
+                let name = flags.get(position + 1).expect("--message-format must be followed by human or json");
+                MessageFormat::from_name(name).unwrap()
+            },
+            None => MessageFormat::Human
+        }
+    }
+}
+
+
+/**
+ * Compiles every `.iri` file in a directory into one program for `build <directory> -o <output filename>`,
+ * which can be followed by the same flags as compiling a single file, and exits.
+ */
+fn build(args:&[String]) -> ! {
+    let directory = args.first().expect("build must be given a directory to compile");
+    if args.get(1).map(String::as_str) != Some("-o") {
+        panic!("build must be given the output filename with -o, such as build <directory> -o <output filename>");
+    }
+
+    let output_name = args.get(2).expect("-o must be followed by the output filename");
+    let options = parse_options(&args[3..]);
+    check_flags(&args[3..], &options, directory);
+    errors::install_internal_error_hook();
+    match compile_and_time(&options, |timings| compile_directory(directory, output_name, &options, timings)) {
+        Ok(Ok(_)) => process::exit(0),
+        Ok(Err(err)) => {
+            eprintln!("{}", options.message_format.render(&Diagnostic::from_error(err.as_ref(), directory)));
+            process::exit(1);
+        },
+        Err(panic) => panic::resume_unwind(panic)
+    }
+}
+
+
+/**
+ * Checks *target_code.json* and the runtime libraries for `doctor` without compiling a program, printing the
+ * operations each architecture has code for and any problems found, and exits with 1 if there were any.
+ */
+fn doctor() -> ! {
+    let report = doctor::run_doctor();
+    for line in &report.features {
+        println!("{}", line);
+    }
+
+    for problem in &report.problems {
+        eprintln!("error: {}", problem);
+    }
+
+    match report.problems.is_empty() {
+        true => {
+            println!("No problems found");
+            process::exit(0);
+        },
+        false => {
+            eprintln!("Found {} problems", report.problems.len());
+            process::exit(1);
+        }
+    }
+}
+
+
+/**
+ * Writes the conformance programs for `gen-conformance <directory>` into the directory and exits, so that they
+ * can be run through each backend and compared with the output the language says they should give.
+ */
+fn gen_conformance(args:&[String]) -> ! {
+    let directory = args.first().expect("gen-conformance must be given the directory to write the programs into");
+    match conformance::write_conformance_programs(Path::new(directory)) {
+        Ok(count) => {
+            println!("Wrote {} conformance programs to {}", count, directory);
+            process::exit(0);
+        },
+        Err(err) => {
+            eprintln!("error: could not write the conformance programs to {}: {}", directory, err);
+            process::exit(1);
+        }
+    }
+}
+
+
+/**
+ * Checks the input file can be read before anything else is done, exiting with an error if it cannot. Warns
+ * if it does not have the extension of a source file unless `force` is true, as it may have been given by
+ * mistake, but compiles it anyway so that generated files with other names can be compiled.
+ */
+fn check_input_file(filename:&str, options:&CompileOptions, force:bool) {
+    if let Err(err) = fs::File::open(filename) {
+        let err = errors::InputFileError::new(filename, &err);
+        eprintln!("{}", options.message_format.render(&Diagnostic::from_error(&err, filename)));
+        process::exit(1);
+    }
+
+    if !force && !project::is_source_file(Path::new(filename)) {
+        let message = format!("{} does not have the .iri or .irid file extension, so it may not be a program. Pass --force to hide this warning.", filename);
+        eprintln!("{}", options.message_format.render(&Diagnostic::from_file_warning("unexpected-extension", message, filename)));
+    }
+}
+
+
+/**
+ * Checks every flag is listed in `CLI_OPTIONS` before anything is compiled, exiting with an error naming the
+ * first which is not, as a misspelt flag would otherwise be ignored. The value after a flag which takes one is
+ * skipped, so that it is not taken for a flag.
+ */
+fn check_flags(flags:&[String], options:&CompileOptions, input_name:&str) {
+    let mut remaining = flags.iter();
+    while let Some(flag) = remaining.next() {
+        match CLI_OPTIONS.iter().find(|option| option.name == flag) {
+            Some(CliOption {value: Some(_), ..}) => {
+                remaining.next();
+            },
+
+            Some(_) => {},
+            None => {
+                let err = errors::InvalidOption(format!("Unknown flag {}, run with --help to list the flags which can be given", flag));
+                eprintln!("{}", options.message_format.render(&Diagnostic::from_error(&err, input_name)));
+                process::exit(1);
+            }
+        }
+    }
+}
+
+
+/**
+ * Compiles the program given on the command line, exiting with the exit code of the program if it was run.
+ */
+fn run() {
+    let cmd_args:Vec<String> = env::args().collect();
+    if cmd_args.get(1).map(String::as_str) == Some("build") {
+        build(&cmd_args[2..]);
+    }
+
+    if cmd_args.get(1).map(String::as_str) == Some("doctor") {
+        doctor();
+    }
+
+    if cmd_args.get(1).map(String::as_str) == Some("gen-conformance") {
+        gen_conformance(&cmd_args[2..]);
+    }
+
+    if let Some(flag @ ("--help" | "--version" | "--list-targets")) = cmd_args.get(1).map(String::as_str) {
+        print_info(flag);
+    }
+
+    if cmd_args.len() < 4 {
+        eprintln!("{}", get_help_text());
+        process::exit(1);
+    }
+
+    let filename = &cmd_args[1];
+    let options = parse_options(cmd_args.get(4..).unwrap_or_default());
+    check_flags(&cmd_args[4..], &options, filename);
+    if filename != "-" {
+        check_input_file(filename, &options, has_flag(&cmd_args[4..], "--force"));
+    }
+
+    errors::install_internal_error_hook();
+    if has_flag(&cmd_args[4..], "--watch") {
+        if filename == "-" {
+            panic!("Cannot watch a program read from stdin");
+        }
+
+        watch(filename, &cmd_args[2], &cmd_args[3], &options);
+    }
+
+    match compile_and_time(&options, |timings| compile_file(filename, &cmd_args[2], &cmd_args[3], &options, timings)) {
+        Ok(Ok(Some(exit_code))) => process::exit(exit_code),
+        Ok(Ok(None)) => {},
+        Ok(Err(err)) => {
+            eprintln!("{}", options.message_format.render(&Diagnostic::from_error(err.as_ref(), filename)));
+            process::exit(1);
+        },
+        Err(panic) => panic::resume_unwind(panic)
+    }
+}
+
+
+fn main() {
+    let compiler = thread::Builder::new().stack_size(COMPILER_STACK_SIZE).spawn(run).expect("Could not start the compiler thread");
+    if let Err(panic) = compiler.join() {
+        panic::resume_unwind(panic);
     }
 }
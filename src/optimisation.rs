@@ -0,0 +1,26 @@
+pub mod branch_pruning;
+pub mod cast_folding;
+pub mod constant_propagation;
+pub mod control_flow;
+pub mod dead_functions;
+pub mod jump_tables;
+pub mod jumps;
+pub mod liveness;
+pub mod pure_calls;
+pub mod traps;
+
+use crate::backend::OverflowMode;
+use crate::frontend::intermediate_gen::IntermediateInstr;
+use crate::timing::PhaseTimings;
+
+
+/**
+ * Takes the intermediate code of the whole program and runs every optimisation pass over it, returning
+ * the optimised intermediate code. The time taken by each pass is recorded in `timings`. Passes may remove
+ * code with no side effects, but never remove or reorder calls, input, output or stores, so operands are
+ * still evaluated from left to right, and never remove arithmetic which can trap with the given overflow mode.
+ */
+pub fn optimise(instructions:Vec<IntermediateInstr>, overflow:OverflowMode, timings:&mut PhaseTimings) -> Vec<IntermediateInstr> {
+    let instructions = timings.time_pass("optimisation", "dead store elimination", || liveness::eliminate_dead_stores(instructions, overflow));
+    timings.time_pass("optimisation", "redundant jump removal", || jumps::remove_redundant_jumps(instructions))
+}
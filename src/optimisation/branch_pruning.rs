@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::frontend::ast::{ASTNode, Literal};
+use crate::frontend::semantics::{evaluate_constant_condition, shadow_constant, update_constants};
+
+
+/**
+ * Takes a chain of if, else if, and else branches and removes every branch whose condition is always false.
+ * The first branch whose condition is always true becomes an else branch, and every branch after it is
+ * removed as it can never be reached. Returns `None` if no branches are left.
+ */
+fn prune_if_structure(branches:&[ASTNode], constants:&HashMap<String, Literal>) -> Option<ASTNode> {
+    let mut pruned = vec![];
+    for branch in branches {
+        match branch {
+            ASTNode::IfStatement {condition, statements, scope, line} => {
+                let statements = prune_block(statements, constants);
+                match evaluate_constant_condition(condition, constants) {
+                    Some(false) => {},
+                    Some(true) => {
+                        pruned.push(ASTNode::ElseStatement {statements, scope: *scope});
+                        break;
+                    },
+
+                    None => pruned.push(ASTNode::IfStatement {condition: condition.clone(), statements, scope: *scope, line: *line})
+                }
+            },
+
+            ASTNode::ElseStatement {statements, scope} => {
+                pruned.push(ASTNode::ElseStatement {statements: prune_block(statements, constants), scope: *scope});
+            },
+
+            other => pruned.push(other.clone())
+        }
+    }
+
+    match pruned.is_empty() {
+        true => None,
+        false => Some(ASTNode::IfElifElseStatement {statements: pruned})
+    }
+}
+
+
+/**
+ * Takes the value assigned to a variable or used as a statement and, if it is a ternary expression with a
+ * condition known at compile time, returns the arm which is always chosen as an expression. Any other value is
+ * returned unchanged.
+ */
+fn prune_ternary(value:&ASTNode, constants:&HashMap<String, Literal>) -> ASTNode {
+    match value {
+        ASTNode::TernaryExpression {condition, if_true, if_false} => {
+            let arm = match evaluate_constant_condition(condition, constants) {
+                Some(true) => if_true,
+                Some(false) => if_false,
+                None => return value.clone()
+            };
+
+            ASTNode::Expression {lhs: arm.clone(), operator: None, rhs: None}
+        },
+
+        other => other.clone()
+    }
+}
+
+
+/**
+ * Prunes every branch in a block of statements which can never be taken, recursing into nested blocks.
+ */
+fn prune_block(statements:&[ASTNode], constants:&HashMap<String, Literal>) -> Vec<ASTNode> {
+    let mut constants = constants.clone();
+    let mut pruned = vec![];
+    for statement in statements {
+        let statement = match statement {
+            ASTNode::IfElifElseStatement {statements} => match prune_if_structure(statements, &constants) {
+                Some(structure) => structure,
+                None => continue
+            },
+
+            ASTNode::VarDeclStatement {var_type, mutability, storage, identifier, value, line} => ASTNode::VarDeclStatement {
+                var_type: var_type.clone(),
+                mutability: mutability.clone(),
+                storage: *storage,
+                identifier: identifier.clone(),
+                value: Box::new(prune_ternary(value, &constants)),
+                line: *line
+            },
+
+            ASTNode::VarAssignStatement {identifier, value, line} => ASTNode::VarAssignStatement {
+                identifier: identifier.clone(),
+                value: Box::new(prune_ternary(value, &constants)),
+                line: *line
+            },
+
+            ASTNode::ExpressionStatement {expression, line, resolved_type} => ASTNode::ExpressionStatement {
+                expression: Box::new(prune_ternary(expression, &constants)),
+                line: *line,
+                resolved_type: resolved_type.clone()
+            },
+
+            ASTNode::IndefLoop {statements, scope} => ASTNode::IndefLoop {
+                statements: prune_block(statements, &constants),
+                scope: *scope
+            },
+
+            ASTNode::WhileLoop {condition, statements, scope, line} => ASTNode::WhileLoop {
+                condition: condition.clone(),
+                statements: prune_block(statements, &constants),
+                scope: *scope,
+                line: *line
+            },
+
+            ASTNode::ForLoop {control_type, control_mutability, control_identifier, control_initial, limit, step, statements, scope, line, resolved_type} => ASTNode::ForLoop {
+                control_type: control_type.clone(),
+                control_mutability: control_mutability.clone(),
+                control_identifier: control_identifier.clone(),
+                control_initial: control_initial.clone(),
+                limit: limit.clone(),
+                step: step.clone(),
+                statements: prune_block(statements, &constants),
+                scope: *scope,
+                line: *line,
+                resolved_type: resolved_type.clone()
+            },
+
+            ASTNode::ForEachLoop {element_type, element_identifier, iterable, statements, scope, line} => ASTNode::ForEachLoop {
+                element_type: element_type.clone(),
+                element_identifier: element_identifier.clone(),
+                iterable: iterable.clone(),
+                statements: prune_block(statements, &shadow_constant(&constants, element_identifier)),
+                scope: *scope,
+                line: *line
+            },
+
+            other => other.clone()
+        };
+
+        update_constants(&statement, &mut constants);
+        pruned.push(statement);
+    }
+
+    pruned
+}
+
+
+/**
+ * Takes the AST of the whole program and removes the branches of if statements and ternary expressions
+ * which can never be taken because their conditions are known at compile time. Run at `-O1` and above.
+ */
+pub fn prune_constant_branches(root:Vec<ASTNode>) -> Vec<ASTNode> {
+    root.into_iter().map(|node| match node {
+        ASTNode::Function {return_type, identifier, parameters, statements, scope, inline, pure, file, line} => ASTNode::Function {
+            statements: prune_block(&statements, &HashMap::new()),
+            return_type, identifier, parameters, scope, inline, pure, file, line
+        },
+
+        other => other
+    }).collect()
+}
@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::frontend::ast::{get_intrinsic_from_str, ASTNode, Intrinsic, Literal, Type};
+use crate::frontend::semantics::{evaluate_constant_term, shadow_constant, update_constants};
+
+
+/**
+ * Rounds a float or double literal into an int in the same way as the given rounding intrinsic would at
+ * runtime, returning `None` if the intrinsic does not round or the literal is not a float or double. Halfway
+ * values are rounded to the even int, as the MIPS round instructions do.
+ */
+fn round_literal(intrinsic:&Intrinsic, literal:&Literal) -> Option<Literal> {
+    let value = match literal {
+        Literal::Float(value) => *value as f64,
+        Literal::Double(value) => *value,
+        _ => return None
+    };
+
+    let rounded = match intrinsic {
+        Intrinsic::Round => value.round_ties_even(),
+        Intrinsic::Floor => value.floor(),
+        Intrinsic::Ceil => value.ceil(),
+        _ => return None
+    };
+
+    Some(Literal::Integer(rounded as i32))
+}
+
+
+/**
+ * Replaces every cast of a value known at compile time in this section of a statement with the value it casts
+ * to, so that no conversion code is generated for it, and does the same for calls to `round`, `floor`, and
+ * `ceil` unless `functions`, the names of the functions declared in the program, shows they are not the
+ * intrinsics. Any other node is rebuilt with its children folded.
+ */
+fn fold_node(node:&ASTNode, constants:&HashMap<String, Literal>, functions:&HashSet<String>) -> ASTNode {
+    let fold = |child:&ASTNode| Box::new(fold_node(child, constants, functions));
+    match node {
+        ASTNode::TypeCast {from, into, line, resolved_type} => {
+            match evaluate_constant_term(node, constants) {
+                Some(value) => ASTNode::Value {literal_type: into.clone(), value},
+                None => ASTNode::TypeCast {from: fold(from), into: into.clone(), line: *line, resolved_type: resolved_type.clone()}
+            }
+        },
+
+        ASTNode::Term {child} => ASTNode::Term {child: fold(child)},
+
+        ASTNode::Expression {lhs, operator, rhs} => ASTNode::Expression {
+            lhs: fold(lhs),
+            operator: operator.clone(),
+            rhs: rhs.as_deref().map(fold)
+        },
+
+        ASTNode::BooleanTerm {lhs, operator, rhs} => ASTNode::BooleanTerm {
+            lhs: fold(lhs),
+            operator: operator.clone(),
+            rhs: rhs.as_deref().map(fold)
+        },
+
+        ASTNode::BooleanExpression {lhs, operator, connector, rhs} => ASTNode::BooleanExpression {
+            lhs: fold(lhs),
+            operator: operator.clone(),
+            connector: connector.clone(),
+            rhs: rhs.as_deref().map(fold)
+        },
+
+        ASTNode::TernaryExpression {condition, if_true, if_false} => ASTNode::TernaryExpression {
+            condition: fold(condition),
+            if_true: fold(if_true),
+            if_false: fold(if_false)
+        },
+
+        ASTNode::FunctionCall {identifier, arguments} => {
+            let arguments:Vec<ASTNode> = arguments.iter().map(|arg| fold_node(arg, constants, functions)).collect();
+            let rounded = match (get_intrinsic_from_str(identifier), arguments.as_slice()) {
+                (Some(intrinsic), [argument]) if !functions.contains(identifier) => {
+                    evaluate_constant_term(argument, constants).and_then(|value| round_literal(&intrinsic, &value))
+                },
+
+                _ => None
+            };
+
+            match rounded {
+                Some(value) => ASTNode::Value {literal_type: Type::Integer, value},
+                None => ASTNode::FunctionCall {identifier: identifier.clone(), arguments}
+            }
+        },
+
+        other => other.clone()
+    }
+}
+
+
+/**
+ * Folds the casts of values known at compile time in a block of statements, recursing into nested blocks.
+ */
+fn fold_block(statements:&[ASTNode], constants:&HashMap<String, Literal>, functions:&HashSet<String>) -> Vec<ASTNode> {
+    let mut constants = constants.clone();
+    let mut folded = vec![];
+    for statement in statements {
+        let statement = match statement {
+            ASTNode::VarDeclStatement {var_type, mutability, storage, identifier, value, line} => ASTNode::VarDeclStatement {
+                var_type: var_type.clone(),
+                mutability: mutability.clone(),
+                storage: *storage,
+                identifier: identifier.clone(),
+                value: Box::new(fold_node(value, &constants, functions)),
+                line: *line
+            },
+
+            ASTNode::VarAssignStatement {identifier, value, line} => ASTNode::VarAssignStatement {
+                identifier: identifier.clone(),
+                value: Box::new(fold_node(value, &constants, functions)),
+                line: *line
+            },
+
+            ASTNode::ReturnStatement {expression} => ASTNode::ReturnStatement {
+                expression: expression.as_ref().map(|expression| Box::new(fold_node(expression, &constants, functions)))
+            },
+
+            ASTNode::ExpressionStatement {expression, line, resolved_type} => ASTNode::ExpressionStatement {
+                expression: Box::new(fold_node(expression, &constants, functions)),
+                line: *line,
+                resolved_type: resolved_type.clone()
+            },
+
+            ASTNode::IfElifElseStatement {statements} => ASTNode::IfElifElseStatement {
+                statements: statements.iter().map(|branch| match branch {
+                    ASTNode::IfStatement {condition, statements, scope, line} => ASTNode::IfStatement {
+                        condition: Box::new(fold_node(condition, &constants, functions)),
+                        statements: fold_block(statements, &constants, functions),
+                        scope: *scope,
+                        line: *line
+                    },
+
+                    ASTNode::ElseStatement {statements, scope} => ASTNode::ElseStatement {
+                        statements: fold_block(statements, &constants, functions),
+                        scope: *scope
+                    },
+
+                    other => other.clone()
+                }).collect()
+            },
+
+            ASTNode::IndefLoop {statements, scope} => ASTNode::IndefLoop {
+                statements: fold_block(statements, &constants, functions),
+                scope: *scope
+            },
+
+            ASTNode::WhileLoop {condition, statements, scope, line} => ASTNode::WhileLoop {
+                condition: Box::new(fold_node(condition, &constants, functions)),
+                statements: fold_block(statements, &constants, functions),
+                scope: *scope,
+                line: *line
+            },
+
+            ASTNode::ForLoop {control_type, control_mutability, control_identifier, control_initial, limit, step, statements, scope, line, resolved_type} => ASTNode::ForLoop {
+                control_type: control_type.clone(),
+                control_mutability: control_mutability.clone(),
+                control_identifier: control_identifier.clone(),
+                control_initial: control_initial.clone(),
+                limit: limit.clone(),
+                step: step.clone(),
+                statements: fold_block(statements, &constants, functions),
+                scope: *scope,
+                line: *line,
+                resolved_type: resolved_type.clone()
+            },
+
+            ASTNode::ForEachLoop {element_type, element_identifier, iterable, statements, scope, line} => ASTNode::ForEachLoop {
+                element_type: element_type.clone(),
+                element_identifier: element_identifier.clone(),
+                iterable: iterable.clone(),
+                statements: fold_block(statements, &shadow_constant(&constants, element_identifier), functions),
+                scope: *scope,
+                line: *line
+            },
+
+            other => other.clone()
+        };
+
+        update_constants(&statement, &mut constants);
+        folded.push(statement);
+    }
+
+    folded
+}
+
+
+/**
+ * Takes the AST of the whole program and replaces each cast of a literal or constant with the value it casts
+ * to, so the conversion is done at compile time, along with each call to a rounding intrinsic on one. Casts
+ * and calls on values only known at runtime are left alone.
+ */
+pub fn fold_constant_casts(root:Vec<ASTNode>) -> Vec<ASTNode> {
+    let functions:HashSet<String> = root.iter().filter_map(|node| match node {
+        ASTNode::Function {identifier, ..} => Some(identifier.clone()),
+        _ => None
+    }).collect();
+
+    root.into_iter().map(|node| match node {
+        ASTNode::Function {return_type, identifier, parameters, statements, scope, inline, pure, file, line} => ASTNode::Function {
+            statements: fold_block(&statements, &HashMap::new(), &functions),
+            return_type, identifier, parameters, scope, inline, pure, file, line
+        },
+
+        other => other
+    }).collect()
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use crate::backend::OverflowMode;
+use crate::backend::interpreter::evaluate_operation;
+use crate::frontend::ast::Type;
+use crate::frontend::intermediate_gen::{Argument, IntermediateInstr};
+use super::control_flow::ControlFlowGraph;
+use super::pure_calls::get_value_argument;
+
+
+/**
+ * The value of each local variable known to hold a constant at a point in a function, by its id.
+ */
+type Constants = HashMap<usize, Argument>;
+
+
+/**
+ * Returns the type of the value given by a `Push` of the argument.
+ */
+fn get_argument_type(argument:&Argument) -> Type {
+    match argument {
+        Argument::Byte(_) => Type::Byte,
+        Argument::Integer(_) => Type::Integer,
+        Argument::Long(_) => Type::Long,
+        Argument::Boolean(_) => Type::Boolean,
+        Argument::Char(_) => Type::Char,
+        Argument::Float(_) => Type::Float,
+        Argument::Double(_) => Type::Double,
+        Argument::String(_) => Type::String
+    }
+}
+
+
+/**
+ * Updates the constants known after the instruction at `index` in a block starting at `block_start`. A store
+ * of a value pushed by the instruction just before it makes its variable constant, and any other store makes
+ * it unknown. Strings are never constant, as they are compared by address and so a load of one cannot be
+ * replaced by a literal.
+ */
+fn update_constants(instructions:&[IntermediateInstr], index:usize, block_start:usize, constants:&mut Constants) {
+    if let IntermediateInstr::Store(_, id) = &instructions[index] {
+        match index.checked_sub(1).filter(|previous| *previous >= block_start).map(|previous| &instructions[previous]) {
+            Some(IntermediateInstr::Push(push_type, argument)) if push_type != &Type::String => {
+                constants.insert(*id, argument.clone());
+            },
+            _ => {
+                constants.remove(id);
+            }
+        }
+    }
+}
+
+
+/**
+ * Runs forward data-flow analysis over the control flow graph until it reaches a fixed point, and returns the
+ * constants known on entry to each basic block. A variable is only constant on entry to a block if it holds
+ * the same constant at the end of every block which can reach it, so a variable assigned before a loop and
+ * again inside it is not constant within the loop. Blocks which cannot be reached are given `None`.
+ */
+fn get_constants_in(instructions:&[IntermediateInstr], cfg:&ControlFlowGraph) -> Vec<Option<Constants>> {
+    let mut predecessors:Vec<Vec<usize>> = vec![vec![]; cfg.blocks.len()];
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        for successor in &block.successors {
+            predecessors[*successor].push(index);
+        }
+    }
+
+    let mut constants_in:Vec<Option<Constants>> = vec![None; cfg.blocks.len()];
+    let mut constants_out:Vec<Option<Constants>> = vec![None; cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            // the first block of a function starts knowing nothing, and any other block knows what every block
+            // reached so far which leads to it agrees on
+            let new_in = match predecessors[index].is_empty() {
+                true => Some(Constants::new()),
+                false => predecessors[index].iter().filter_map(|predecessor| constants_out[*predecessor].as_ref()).fold(None, |known, out| match known {
+                    None => Some(out.clone()),
+                    Some(mut known) => {
+                        known.retain(|id, value| out.get(id) == Some(value));
+                        Some(known)
+                    }
+                })
+            };
+
+            let new_out = new_in.clone().map(|mut constants| {
+                for index in block.start..block.end {
+                    update_constants(instructions, index, block.start, &mut constants);
+                }
+
+                constants
+            });
+
+            if new_in != constants_in[index] || new_out != constants_out[index] {
+                constants_in[index] = new_in;
+                constants_out[index] = new_out;
+                changed = true;
+            }
+        }
+    }
+
+    constants_in
+}
+
+
+/**
+ * Replaces each `Load` of a variable known to hold a constant with a `Push` of the constant. Returns true if
+ * anything was replaced.
+ */
+fn replace_constant_loads(instructions:&mut [IntermediateInstr]) -> bool {
+    let cfg = ControlFlowGraph::build(instructions);
+    let constants_in = get_constants_in(instructions, &cfg);
+
+    let mut changed = false;
+    for (block, constants) in cfg.blocks.iter().zip(constants_in) {
+        let mut constants = match constants {
+            Some(constants) => constants,
+            None => continue
+        };
+
+        for index in block.start..block.end {
+            if let IntermediateInstr::Load(load_type, id) = &instructions[index] {
+                if let Some(argument) = constants.get(id) {
+                    instructions[index] = IntermediateInstr::Push(load_type.clone(), argument.clone());
+                    changed = true;
+                }
+            }
+
+            update_constants(instructions, index, block.start, &mut constants);
+        }
+    }
+
+    changed
+}
+
+
+/**
+ * Replaces each operator, comparison and intrinsic whose operands are all pushed just before it with a `Push`
+ * of the value it gives, worked out as the interpreter would, so `push.i32 5`, `push.i32 2`, `mul` becomes
+ * `push.i32 10`. Operations which would trap with the given overflow mode, give a float which is not finite,
+ * or work on strings are kept. Returns the folded instructions and whether anything was folded.
+ */
+fn fold_constant_operations(instructions:Vec<IntermediateInstr>, overflow:OverflowMode) -> (Vec<IntermediateInstr>, bool) {
+    let mut folded:Vec<IntermediateInstr> = Vec::with_capacity(instructions.len());
+    let mut changed = false;
+    for instr in instructions {
+        let operand_count = match instr {
+            IntermediateInstr::NumNeg | IntermediateInstr::Complement | IntermediateInstr::LogicNeg | IntermediateInstr::Abs
+              | IntermediateInstr::Round | IntermediateInstr::Floor | IntermediateInstr::Ceil => 1,
+            _ => 2
+        };
+
+        if let Some(start) = folded.len().checked_sub(operand_count) {
+            let operands:Option<Vec<Argument>> = folded[start..].iter().map(|operand| match operand {
+                IntermediateInstr::Push(push_type, argument) if push_type != &Type::String => Some(argument.clone()),
+                _ => None
+            }).collect();
+
+            let result = operands.and_then(|operands| evaluate_operation(&instr, &operands, overflow)).and_then(get_value_argument);
+            let finite = match &result {
+                Some(Argument::Float(value)) => value.is_finite(),
+                Some(Argument::Double(value)) => value.is_finite(),
+                _ => true
+            };
+
+            if let Some(argument) = result.filter(|_| finite) {
+                folded.truncate(start);
+                folded.push(IntermediateInstr::Push(get_argument_type(&argument), argument));
+                changed = true;
+                continue;
+            }
+        }
+
+        folded.push(instr);
+    }
+
+    (folded, changed)
+}
+
+
+/**
+ * Follows constants from the statements which assign them to the statements which use them. Each load of a
+ * local variable which holds the same constant on every path reaching it, such as `x` in
+ * `let int x = 5; let int y = (x, 2)*;`, is replaced with a push of the constant, and operations on constants
+ * are then worked out, giving `push.i32 10` for `y`. This repeats until nothing more is known, as working out
+ * one variable can make the next constant. The stores left unused are removed by dead store elimination.
+ * Static variables and parameters are never followed. Run at `-O2`.
+ */
+pub fn propagate_constants(instructions:Vec<IntermediateInstr>, overflow:OverflowMode) -> Vec<IntermediateInstr> {
+    let mut instructions = instructions;
+    loop {
+        let replaced = replace_constant_loads(&mut instructions);
+        let (folded, changed) = fold_constant_operations(instructions, overflow);
+        instructions = folded;
+        if !replaced && !changed {
+            return instructions;
+        }
+    }
+}
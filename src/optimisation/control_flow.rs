@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::frontend::intermediate_gen::IntermediateInstr;
+
+
+/**
+ * Represents a straight-line run of intermediate instructions which can only be entered at the first
+ * instruction and only left after the last, along with the indexes of the blocks control may pass to.
+ */
+#[derive(Debug)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<usize>
+}
+
+
+/**
+ * Represents the control flow graph of a program's intermediate code. Blocks never span more than one
+ * function, so the graph is a set of disconnected per-function graphs.
+ */
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>
+}
+
+impl ControlFlowGraph {
+    /**
+     * Takes a slice of intermediate instructions and splits them into basic blocks, then links each block
+     * to the blocks it can jump or fall through to.
+     */
+    pub fn build(instructions:&[IntermediateInstr]) -> ControlFlowGraph {
+        let mut blocks:Vec<BasicBlock> = vec![];
+        let mut label_blocks:HashMap<String, usize> = HashMap::new();
+
+        // find the leaders, i.e. the instructions which start a new block
+        let mut start = 0;
+        for (index, instr) in instructions.iter().enumerate() {
+            match instr {
+                IntermediateInstr::Label(label) => {
+                    if index > start {
+                        blocks.push(BasicBlock {start, end: index, successors: vec![]});
+                        start = index;
+                    }
+
+                    label_blocks.insert(label.to_owned(), blocks.len());
+                },
+
+                IntermediateInstr::FuncStart(_) if index > start => {
+                    blocks.push(BasicBlock {start, end: index, successors: vec![]});
+                    start = index;
+                },
+
+                IntermediateInstr::Jump(_)
+                  | IntermediateInstr::JumpZero(_)
+                  | IntermediateInstr::TableSwitch(..)
+                  | IntermediateInstr::Return(_)
+                  | IntermediateInstr::Trap(_)
+                  | IntermediateInstr::FuncEnd(_) => {
+                    blocks.push(BasicBlock {start, end: index + 1, successors: vec![]});
+                    start = index + 1;
+                },
+
+                _ => {}
+            }
+        }
+
+        if start < instructions.len() {
+            blocks.push(BasicBlock {start, end: instructions.len(), successors: vec![]});
+        }
+
+        // link each block to its successors according to the last instruction in the block
+        let block_count = blocks.len();
+        for (index, block) in blocks.iter_mut().enumerate() {
+            let fallthrough = if index + 1 < block_count { vec![index + 1] } else { vec![] };
+            block.successors = match &instructions[block.end - 1] {
+                IntermediateInstr::Jump(label) => vec![label_blocks[label]],
+                IntermediateInstr::JumpZero(label) => {
+                    let mut successors = fallthrough;
+                    successors.push(label_blocks[label]);
+                    successors
+                },
+
+                IntermediateInstr::TableSwitch(_, labels, default) => {
+                    let mut successors:Vec<usize> = labels.iter().chain([default]).map(|label| label_blocks[label]).collect();
+                    successors.sort_unstable();
+                    successors.dedup();
+                    successors
+                },
+
+                IntermediateInstr::Return(_) | IntermediateInstr::Trap(_) | IntermediateInstr::FuncEnd(_) => vec![],
+                _ => fallthrough
+            };
+        }
+
+        ControlFlowGraph { blocks }
+    }
+}
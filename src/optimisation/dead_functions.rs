@@ -0,0 +1,21 @@
+use crate::frontend::ast::ASTNode;
+use crate::frontend::call_graph::CallGraph;
+
+
+/**
+ * Takes the AST of the whole program and removes every function which `main` never calls, either directly or
+ * through other functions, so that no code is generated for it. Programs without `main` are left as they
+ * are. Run at `-O2`.
+ */
+pub fn remove_unreachable_functions(root:Vec<ASTNode>) -> Vec<ASTNode> {
+    let call_graph = CallGraph::new(&root);
+    let reachable:Vec<String> = match call_graph.get_reachable() {
+        Some(reachable) => reachable.into_iter().map(str::to_owned).collect(),
+        None => return root
+    };
+
+    root.into_iter().filter(|node| match node {
+        ASTNode::Function {identifier, ..} => reachable.contains(identifier),
+        _ => true
+    }).collect()
+}
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::frontend::ast::Type;
+use crate::frontend::intermediate_gen::{Argument, IntermediateInstr};
+
+
+/**
+ * The fewest cases an if statement must compare against for a jump table to be built for it, as with fewer
+ * the comparisons are no slower than the bounds check and load a table needs.
+ */
+const MIN_JUMP_TABLE_CASES:usize = 4;
+
+/**
+ * The percentage of the values from the lowest case to the highest which must have a case of their own for a
+ * jump table to be built, unless another is given with `--jump-table-density`.
+ */
+pub const DEFAULT_JUMP_TABLE_DENSITY:usize = 50;
+
+
+/**
+ * Represents an if statement whose branches each compare the same int or char variable against a literal with
+ * `==`, such as `if (c, 'a')== { ... } else if (c, 'b')== { ... }`. Each case holds its value, the index of
+ * the first instruction of its condition, and the label its condition jumps to when it is false. The default
+ * is the label the last condition jumps to, where the else branch starts if there is one.
+ */
+struct CaseChain {
+    read: IntermediateInstr,
+    cases: Vec<(i64, usize, String)>,
+    default: String
+}
+
+
+/**
+ * Returns the type and location of the variable read by a `Load` or `LoadParam`, so two reads can be
+ * compared, or `None` for any other instruction.
+ */
+fn get_read_location(instr:&IntermediateInstr) -> Option<(bool, &Type, usize)> {
+    match instr {
+        IntermediateInstr::Load(var_type, slot) => Some((false, var_type, *slot)),
+        IntermediateInstr::LoadParam(var_type, index) => Some((true, var_type, *index)),
+        _ => None
+    }
+}
+
+
+/**
+ * Takes the index of the first instruction of the condition of an if or else if branch and, if the condition
+ * compares an int or char variable against a literal of the same type with `==`, returns the instruction
+ * reading the variable, the value of the literal, and the label jumped to when they are not equal. The literal
+ * may be on either side.
+ */
+fn match_case(instructions:&[IntermediateInstr], index:usize) -> Option<(&IntermediateInstr, i64, &str)> {
+    let (read, literal, label) = match instructions.get(index..index + 4)? {
+        [IntermediateInstr::Push(_, literal), read, IntermediateInstr::Equal, IntermediateInstr::JumpZero(label)]
+          | [read, IntermediateInstr::Push(_, literal), IntermediateInstr::Equal, IntermediateInstr::JumpZero(label)] => (read, literal, label),
+        _ => return None
+    };
+
+    let value = match (get_read_location(read)?, literal) {
+        ((_, Type::Integer, _), Argument::Integer(value)) => *value as i64,
+        ((_, Type::Char, _), Argument::Char(value)) => *value as i64,
+        _ => return None
+    };
+
+    Some((read, value, label))
+}
+
+
+/**
+ * Finds the chain of branches whose conditions each compare the same variable against a literal, starting
+ * with the condition at the given index. Each condition after the first must directly follow the label the
+ * one before it jumps to, and the branch before it must end with a jump, so that no branch runs on into the
+ * next condition. The jump at the end of the last branch may have been removed as redundant. Returns `None`
+ * if there are fewer than `MIN_JUMP_TABLE_CASES` cases.
+ */
+fn find_case_chain(instructions:&[IntermediateInstr], start:usize) -> Option<CaseChain> {
+    let (read, _, _) = match_case(instructions, start)?;
+    let location = get_read_location(read);
+
+    let mut cases = vec![];
+    let mut index = start;
+    while let Some((next_read, value, label)) = match_case(instructions, index) {
+        if get_read_location(next_read) != location {
+            break;
+        }
+
+        let label_index = instructions[index + 4..].iter()
+                                                   .take_while(|instr| !matches!(instr, IntermediateInstr::FuncEnd(_)))
+                                                   .position(|instr| matches!(instr, IntermediateInstr::Label(other) if other == label))
+                                                   .map(|position| index + 4 + position);
+        let label_index = match label_index {
+            Some(label_index) => label_index,
+            None => break
+        };
+
+        cases.push((value, index, label.to_owned()));
+        if !matches!(instructions[label_index - 1], IntermediateInstr::Jump(_)) {
+            break;
+        }
+
+        index = label_index + 1;
+    }
+
+    if cases.len() < MIN_JUMP_TABLE_CASES {
+        return None;
+    }
+
+    let default = cases.last().unwrap().2.clone();
+    Some(CaseChain {read: read.clone(), cases, default})
+}
+
+
+/**
+ * Returns the label the body of the case whose condition jumps to the given label starts at in a jump table.
+ */
+fn get_case_label(else_label:&str) -> String {
+    format!("{}_match", else_label)
+}
+
+
+/**
+ * Takes a chain of cases and returns the lowest value and the label to jump to for each value from it to the
+ * highest, or `None` if fewer than `density` percent of those values have a case. When two cases have the
+ * same value the first is taken, as the later one could never run.
+ */
+fn build_table(chain:&CaseChain, density:usize) -> Option<(i32, Vec<String>)> {
+    let mut values:BTreeMap<i64, String> = BTreeMap::new();
+    for (value, _, else_label) in &chain.cases {
+        values.entry(*value).or_insert_with(|| get_case_label(else_label));
+    }
+
+    let (low, high) = (*values.keys().next()?, *values.keys().next_back()?);
+    let range = (high - low + 1) as usize;
+    if values.len() * 100 < density * range {
+        return None;
+    }
+
+    let labels = (low..=high).map(|value| values.get(&value).cloned().unwrap_or_else(|| chain.default.clone())).collect();
+    Some((i32::try_from(low).ok()?, labels))
+}
+
+
+/**
+ * Replaces each if statement which compares one int or char variable against at least `MIN_JUMP_TABLE_CASES`
+ * literals, such as a chain of `if (c, 'a')== { ... } else if (c, 'b')== { ... }`, with a `TableSwitch`
+ * which jumps straight to the branch for the value of the variable through a table of labels, instead of
+ * comparing it against each literal in turn. The variable is read once, before the table, and values with no
+ * case go to the else branch, or past the if statement if it has none. A table is only built if at least
+ * `density` percent of the values from the lowest case to the highest have a case, as otherwise most of the
+ * table would be wasted, and chains which are too sparse are left as comparisons. Run at `-O2`.
+ */
+pub fn build_jump_tables(instructions:Vec<IntermediateInstr>, density:usize) -> Vec<IntermediateInstr> {
+    // the instructions which replace the condition starting at each index, which all take 4 instructions
+    let mut replacements:HashMap<usize, Vec<IntermediateInstr>> = HashMap::new();
+    for index in 0..instructions.len() {
+        if replacements.contains_key(&index) {
+            continue;
+        }
+
+        let chain = match find_case_chain(&instructions, index) {
+            Some(chain) => chain,
+            None => continue
+        };
+
+        let (low, labels) = match build_table(&chain, density) {
+            Some(table) => table,
+            None => continue
+        };
+
+        for (case, (_, condition, else_label)) in chain.cases.iter().enumerate() {
+            let mut replacement = match case {
+                0 => vec![chain.read.clone(), IntermediateInstr::TableSwitch(low, labels.clone(), chain.default.clone())],
+                _ => vec![]
+            };
+
+            replacement.push(IntermediateInstr::Label(get_case_label(else_label)));
+            replacements.insert(*condition, replacement);
+        }
+    }
+
+    let mut switched = Vec::with_capacity(instructions.len());
+    let mut index = 0;
+    while index < instructions.len() {
+        match replacements.remove(&index) {
+            Some(replacement) => {
+                switched.extend(replacement);
+                index += 4;
+            },
+            None => {
+                switched.push(instructions[index].clone());
+                index += 1;
+            }
+        }
+    }
+
+    switched
+}
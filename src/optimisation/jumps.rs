@@ -0,0 +1,34 @@
+use crate::frontend::intermediate_gen::IntermediateInstr;
+
+
+/**
+ * Returns true if the jump at the given index goes to one of the labels directly after it, in which case
+ * the jump does nothing as execution would reach the label anyway.
+ */
+fn jumps_to_next_label(instructions:&[IntermediateInstr], index:usize) -> bool {
+    let target = match &instructions[index] {
+        IntermediateInstr::Jump(target) => target,
+        _ => return false
+    };
+
+    instructions[index + 1..].iter()
+                             .map_while(|instr| match instr {
+                                 IntermediateInstr::Label(label) => Some(label),
+                                 _ => None
+                             })
+                             .any(|label| label == target)
+}
+
+
+/**
+ * Removes every unconditional `Jump` to a label which directly follows it, such as those generated at the
+ * end of an empty if statement body.
+ */
+pub fn remove_redundant_jumps(instructions:Vec<IntermediateInstr>) -> Vec<IntermediateInstr> {
+    let redundant:Vec<bool> = (0..instructions.len()).map(|index| jumps_to_next_label(&instructions, index)).collect();
+    instructions.into_iter()
+                .zip(redundant)
+                .filter(|(_, redundant)| !redundant)
+                .map(|(instr, _)| instr)
+                .collect()
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+
+use crate::backend::OverflowMode;
+use crate::frontend::intermediate_gen::IntermediateInstr;
+use super::control_flow::ControlFlowGraph;
+
+
+/**
+ * Returns the number of values the given instruction pops off and pushes onto the evaluation stack, or
+ * `None` if the instruction has side effects, affects control flow or can fail at runtime, in which case it
+ * cannot be removed as part of the computation feeding a dead store. Only instructions which cannot fail
+ * are removed: division can divide by zero and indexing a string can go out of range, so both are kept even
+ * when their result is never used, along with `pow`. With `--overflow trap` int arithmetic can stop the
+ * program too, so it is kept as well, and whether a program traps does not depend on whether the result of
+ * the arithmetic is used.
+ */
+fn get_pure_stack_effect(instr:&IntermediateInstr, overflow:OverflowMode) -> Option<(usize, usize)> {
+    let can_overflow = matches!(instr, IntermediateInstr::Add | IntermediateInstr::Sub | IntermediateInstr::Mult | IntermediateInstr::NumNeg);
+    if can_overflow && overflow == OverflowMode::Trap {
+        return None;
+    }
+
+    match instr {
+        IntermediateInstr::Push(..)
+          | IntermediateInstr::Load(..)
+          | IntermediateInstr::LoadStatic(..)
+          | IntermediateInstr::LoadParam(..) => Some((0, 1)),
+
+        IntermediateInstr::NumNeg
+          | IntermediateInstr::Complement
+          | IntermediateInstr::LogicNeg
+          | IntermediateInstr::Abs
+          | IntermediateInstr::Round
+          | IntermediateInstr::Floor
+          | IntermediateInstr::Ceil
+          | IntermediateInstr::StrLen
+          | IntermediateInstr::Cast(..) => Some((1, 1)),
+
+        IntermediateInstr::Add
+          | IntermediateInstr::Sub
+          | IntermediateInstr::Mult
+          | IntermediateInstr::BitwiseAnd
+          | IntermediateInstr::BitwiseOr
+          | IntermediateInstr::BitwiseXor
+          | IntermediateInstr::LogicAnd
+          | IntermediateInstr::LogicOr
+          | IntermediateInstr::LogicXor
+          | IntermediateInstr::LeftShiftLogical
+          | IntermediateInstr::RightShiftArithmetic
+          | IntermediateInstr::RightShiftLogical
+          | IntermediateInstr::GreaterThan
+          | IntermediateInstr::LessThan
+          | IntermediateInstr::GreaterEqual
+          | IntermediateInstr::LessEqual
+          | IntermediateInstr::Equal
+          | IntermediateInstr::NotEqual
+          | IntermediateInstr::Min
+          | IntermediateInstr::Max => Some((2, 1)),
+
+        // calls, I/O, stores, anything affecting control flow and anything which can fail must be kept
+        _ => None
+    }
+}
+
+
+/**
+ * Takes the index of a `Store` instruction and walks backwards from it to find the first instruction of
+ * the side-effect free computation producing the stored value. Returns `None` if the value is produced
+ * by anything with side effects (such as a `Call` or `In`) or the computation crosses a block boundary.
+ */
+fn find_feeding_computation(instructions:&[IntermediateInstr], store_index:usize, block_start:usize, overflow:OverflowMode) -> Option<usize> {
+    let mut needed:usize = 1;
+    let mut index = store_index;
+    while index > block_start {
+        index -= 1;
+        let (pops, pushes) = get_pure_stack_effect(&instructions[index], overflow)?;
+        needed = needed - pushes + pops;
+        if needed == 0 {
+            return Some(index);
+        }
+    }
+
+    None
+}
+
+
+/**
+ * Runs backward liveness analysis over the control flow graph until it reaches a fixed point, and returns
+ * the set of variable ids which are live on exit from each basic block.
+ */
+fn get_live_out_sets(instructions:&[IntermediateInstr], cfg:&ControlFlowGraph) -> Vec<HashSet<usize>> {
+    // the variables read in a block before being written (uses) and the variables written (defs)
+    let mut uses:Vec<HashSet<usize>> = vec![];
+    let mut defs:Vec<HashSet<usize>> = vec![];
+    for block in &cfg.blocks {
+        let mut block_uses = HashSet::new();
+        let mut block_defs = HashSet::new();
+        for instr in &instructions[block.start..block.end] {
+            match instr {
+                IntermediateInstr::Load(_, id) if !block_defs.contains(id) => {
+                    block_uses.insert(*id);
+                },
+
+                IntermediateInstr::Store(_, id) => {
+                    block_defs.insert(*id);
+                },
+
+                _ => {}
+            }
+        }
+
+        uses.push(block_uses);
+        defs.push(block_defs);
+    }
+
+    let mut live_in:Vec<HashSet<usize>> = vec![HashSet::new(); cfg.blocks.len()];
+    let mut live_out:Vec<HashSet<usize>> = vec![HashSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..cfg.blocks.len()).rev() {
+            let mut new_out = HashSet::new();
+            for successor in &cfg.blocks[index].successors {
+                new_out.extend(live_in[*successor].iter().copied());
+            }
+
+            let mut new_in:HashSet<usize> = new_out.difference(&defs[index]).copied().collect();
+            new_in.extend(uses[index].iter().copied());
+
+            if new_in != live_in[index] || new_out != live_out[index] {
+                live_in[index] = new_in;
+                live_out[index] = new_out;
+                changed = true;
+            }
+        }
+    }
+
+    live_out
+}
+
+
+/**
+ * Finds every `Store` whose variable is never loaded afterwards on any path and marks it for removal,
+ * along with the side-effect free computation feeding it. Returns true if anything was marked.
+ */
+fn mark_dead_stores(instructions:&[IntermediateInstr], removed:&mut [bool], overflow:OverflowMode) -> bool {
+    let cfg = ControlFlowGraph::build(instructions);
+    let live_out = get_live_out_sets(instructions, &cfg);
+
+    let mut changed = false;
+    for (block, block_live_out) in cfg.blocks.iter().zip(live_out) {
+        let mut live = block_live_out;
+        for index in (block.start..block.end).rev() {
+            if removed[index] {
+                continue;
+            }
+
+            match &instructions[index] {
+                IntermediateInstr::Load(_, id) => {
+                    live.insert(*id);
+                },
+
+                IntermediateInstr::Store(_, id) => {
+                    if live.contains(id) {
+                        live.remove(id);
+                        continue;
+                    }
+
+                    // the store is dead, so remove it along with the code computing its value if that can
+                    // be done without losing a side effect, otherwise conservatively keep it
+                    if let Some(start) = find_feeding_computation(instructions, index, block.start, overflow) {
+                        for flag in removed.iter_mut().take(index + 1).skip(start) {
+                            *flag = true;
+                        }
+
+                        changed = true;
+                    }
+                },
+
+                _ => {}
+            }
+        }
+    }
+
+    changed
+}
+
+
+/**
+ * Removes `Store` instructions whose target variable is dead (never loaded afterwards on any path through
+ * the control flow graph), along with the computation feeding them when it is side-effect free. Repeats
+ * until no more stores can be removed, as removing a computation can remove the last use of a variable.
+ * Arithmetic which can overflow is only removed if overflow wraps rather than trapping.
+ */
+pub fn eliminate_dead_stores(instructions:Vec<IntermediateInstr>, overflow:OverflowMode) -> Vec<IntermediateInstr> {
+    let mut instructions = instructions;
+    loop {
+        let mut removed = vec![false; instructions.len()];
+        if !mark_dead_stores(&instructions, &mut removed, overflow) {
+            return instructions;
+        }
+
+        instructions = instructions.into_iter()
+                                   .zip(removed)
+                                   .filter(|(_, removed)| !removed)
+                                   .map(|(instr, _)| instr)
+                                   .collect();
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use crate::backend::OverflowMode;
+use crate::backend::interpreter::{Interpreter, Value};
+use crate::frontend::ast::{ASTNode, Type};
+use crate::frontend::intermediate_gen::{Argument, IntermediateInstr};
+use crate::frontend::semantics::SymbolTable;
+
+
+/**
+ * The most instructions a call to a pure function may take to be worked out at compile time. Calls which take
+ * longer, or never return, are left to run as normal.
+ */
+const PURE_CALL_STEP_LIMIT:u64 = 100_000;
+
+/**
+ * The most calls which may be in progress at once while working out a call to a pure function.
+ */
+const PURE_CALL_DEPTH_LIMIT:usize = 1000;
+
+
+/**
+ * Returns the names of the functions marked with the `@pure` annotation, which semantic analysis has checked
+ * have no side effects.
+ */
+pub fn find_pure_functions(root:&[ASTNode]) -> HashSet<String> {
+    root.iter().filter_map(|node| match node {
+        ASTNode::Function {identifier, pure: true, ..} => Some(identifier.to_owned()),
+        _ => None
+    }).collect()
+}
+
+
+/**
+ * Converts a value returned by a call into the argument of the `Push` which replaces it, or `None` for a
+ * string, as strings are compared by address and so a call returning one cannot be replaced by a literal.
+ */
+pub fn get_value_argument(value:Value) -> Option<Argument> {
+    match value {
+        Value::Byte(value) => Some(Argument::Byte(value)),
+        Value::Integer(value) => Some(Argument::Integer(value)),
+        Value::Long(value) => Some(Argument::Long(value)),
+        Value::Char(value) => Some(Argument::Char(value)),
+        Value::Boolean(value) => Some(Argument::Boolean(value)),
+        Value::Float(value) => Some(Argument::Float(value)),
+        Value::Double(value) => Some(Argument::Double(value)),
+        Value::String(_) => None
+    }
+}
+
+
+/**
+ * Replaces every call to a pure function whose arguments are all literals with a `Push` of the value it
+ * returns, worked out by running the call in the interpreter. A pure function always returns the same value
+ * for the same arguments, so repeated calls such as `square(4)` all become `push.i32 16`. Calls which trap,
+ * including on an int overflowing with the given overflow mode, take too long or involve strings are kept.
+ * Run at `-O2`.
+ */
+pub fn fold_pure_calls(instructions:Vec<IntermediateInstr>, pure_functions:&HashSet<String>, symbol_table:&SymbolTable, overflow:OverflowMode)
+        -> Vec<IntermediateInstr> {
+    if pure_functions.is_empty() {
+        return instructions;
+    }
+
+    let mut interpreter = Interpreter::new(&instructions, symbol_table)
+        .with_max_steps(PURE_CALL_STEP_LIMIT)
+        .with_max_call_depth(PURE_CALL_DEPTH_LIMIT)
+        .with_overflow(overflow);
+
+    let mut folded:Vec<IntermediateInstr> = Vec::with_capacity(instructions.len());
+    for instr in &instructions {
+        if let IntermediateInstr::Call(function, return_type, argument_count) = instr {
+            let foldable = pure_functions.contains(function) && !matches!(return_type, Type::Void | Type::String);
+            if let Some(start) = folded.len().checked_sub(*argument_count).filter(|_| foldable) {
+                // the arguments are the values pushed just before the call, so they are only known if each
+                // of the instructions before it is a push
+                let arguments:Option<Vec<Argument>> = folded[start..].iter().map(|instr| match instr {
+                    IntermediateInstr::Push(push_type, argument) if push_type != &Type::String => Some(argument.clone()),
+                    _ => None
+                }).collect();
+
+                let result = arguments.and_then(|arguments| interpreter.evaluate_call(function, &arguments).ok().flatten());
+                if let Some(argument) = result.and_then(get_value_argument) {
+                    folded.truncate(start);
+                    folded.push(IntermediateInstr::Push(return_type.clone(), argument));
+                    continue;
+                }
+            }
+        }
+
+        folded.push(instr.clone());
+    }
+
+    folded
+}
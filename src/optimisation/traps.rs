@@ -0,0 +1,48 @@
+use crate::frontend::intermediate_gen::IntermediateInstr;
+use super::control_flow::ControlFlowGraph;
+
+
+/**
+ * Returns whether each basic block of the control flow graph can be reached from the start of the function
+ * it is in. A block is a function start if its first instruction is a `FuncStart`.
+ */
+fn find_reachable_blocks(instructions:&[IntermediateInstr], cfg:&ControlFlowGraph) -> Vec<bool> {
+    let mut reachable = vec![false; cfg.blocks.len()];
+    let mut pending:Vec<usize> = (0..cfg.blocks.len()).filter(|index| {
+        matches!(instructions[cfg.blocks[*index].start], IntermediateInstr::FuncStart(_))
+    }).collect();
+
+    while let Some(index) = pending.pop() {
+        if reachable[index] {
+            continue;
+        }
+
+        reachable[index] = true;
+        pending.extend(cfg.blocks[index].successors.iter().copied());
+    }
+
+    reachable
+}
+
+
+/**
+ * Removes every `Trap` which control can never reach, such as the one at the end of a function whose last
+ * statement is a return, so that no code is generated for it. Traps which may be reached are kept.
+ */
+pub fn remove_unreachable_traps(instructions:Vec<IntermediateInstr>) -> Vec<IntermediateInstr> {
+    let cfg = ControlFlowGraph::build(&instructions);
+    let reachable = find_reachable_blocks(&instructions, &cfg);
+
+    let mut removed = vec![false; instructions.len()];
+    for (block, reachable) in cfg.blocks.iter().zip(reachable) {
+        if !reachable && matches!(instructions[block.end - 1], IntermediateInstr::Trap(_)) {
+            removed[block.end - 1] = true;
+        }
+    }
+
+    instructions.into_iter()
+                .zip(removed)
+                .filter(|(_, removed)| !removed)
+                .map(|(instr, _)| instr)
+                .collect()
+}
@@ -0,0 +1,207 @@
+use std::error::Error;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::errors::{DuplicateSymbolError, InFile, MissingMainFunction, NoSourceFiles, Warning};
+use crate::frontend::{ast::ASTNode, call_graph::CallGraph, parser, semantics};
+use crate::frontend::semantics::SymbolTable;
+use crate::frontend::trace::Trace;
+
+
+/**
+ * The paths of the files a program built from a directory is made up of. Each function in the AST records
+ * the index of its file in this table, so that errors can say which file they are in.
+ */
+#[derive(Debug)]
+pub struct FileTable {
+    paths: Vec<String>
+}
+
+impl FileTable {
+    /**
+     * Returns the path of the file with the given index.
+     */
+    pub fn get_path(&self, file:usize) -> &str {
+        &self.paths[file]
+    }
+
+
+    /**
+     * Returns the number of files in the program.
+     */
+    pub fn get_file_count(&self) -> usize {
+        self.paths.len()
+    }
+
+
+    /**
+     * Takes an error found in the file with the given index and returns it with the path of that file.
+     */
+    fn in_file(&self, file:usize, error:Box<dyn Error>) -> Box<dyn Error> {
+        Box::new(InFile {path: self.get_path(file).to_owned(), error})
+    }
+}
+
+
+/**
+ * Returns the index of the file the given top-level node is in, or `None` if it does not record one.
+ */
+fn get_file(node:&ASTNode) -> Option<usize> {
+    match node {
+        ASTNode::Function {file, ..} => Some(*file),
+        _ => None
+    }
+}
+
+
+/**
+ * Returns the top-level nodes of the AST which are in the file with the given index.
+ */
+fn get_nodes_in_file(ast:&[ASTNode], file:usize) -> Vec<ASTNode> {
+    ast.iter().filter(|node| get_file(node) == Some(file)).cloned().collect()
+}
+
+
+/**
+ * The file extensions of Iridescent source files, where `.irid` is accepted as an alias of `.iri`.
+ */
+pub const SOURCE_EXTENSIONS:[&str; 2] = ["iri", "irid"];
+
+
+/**
+ * Returns true if the path has one of the extensions of Iridescent source files.
+ */
+pub fn is_source_file(path:&Path) -> bool {
+    path.extension().is_some_and(|extension| SOURCE_EXTENSIONS.iter().any(|source| extension == *source))
+}
+
+
+/**
+ * Adds the path of every `.iri` or `.irid` file in the directory and its subdirectories to `paths`.
+ */
+fn find_source_files(directory:&Path, paths:&mut Vec<String>) -> Result<(), Box<dyn Error>> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_source_files(&path, paths)?;
+        } else if is_source_file(&path) {
+            paths.push(path.to_str().ok_or("source file path is not valid unicode")?.to_owned());
+        }
+    }
+
+    Ok(())
+}
+
+
+/**
+ * Parses every `.iri` file in the directory and its subdirectories as parts of one program, and returns the
+ * AST of the whole program along with the table of files it came from. The files are sorted by path so the
+ * program is always built in the same order. Brackets and braces may be nested no deeper than `max_nesting`
+ * in any of the files.
+ */
+pub fn parse_directory(directory:&str, max_nesting:usize) -> Result<(Vec<ASTNode>, FileTable), Box<dyn Error>> {
+    let mut paths = vec![];
+    find_source_files(Path::new(directory), &mut paths)?;
+    if paths.is_empty() {
+        return Err(Box::new(NoSourceFiles(directory.to_owned())));
+    }
+
+    paths.sort();
+    let files = FileTable {paths};
+    let sources = files.paths.iter().map(fs::read_to_string).collect::<Result<Vec<String>, _>>()?;
+    let sources:Vec<&str> = sources.iter().map(|source| source.as_str()).collect();
+    let ast = parser::parse_sources(&sources, max_nesting).map_err(|(file, err)| files.in_file(file, err))?;
+
+    Ok((ast, files))
+}
+
+
+/**
+ * Checks that no two files declare a function with the same name, and that one of them declares `main`.
+ * Functions declared twice in the same file are left to the symbol table to report.
+ */
+fn validate_functions(ast:&[ASTNode], files:&FileTable, directory:&str) -> Result<(), Box<dyn Error>> {
+    let mut declared_in:HashMap<&str, (usize, usize)> = HashMap::new();
+    for node in ast {
+        if let ASTNode::Function {identifier, file, line, ..} = node {
+            match declared_in.get(identifier.as_str()) {
+                Some((existing, existing_line)) if existing != file => return Err(files.in_file(*file, Box::new(DuplicateSymbolError {
+                    identifier: identifier.to_string(),
+                    existing_kind: "function".to_owned(),
+                    existing_location: files.get_path(*existing).to_owned(),
+                    existing_line: Some(*existing_line),
+                    duplicate_kind: "function".to_owned(),
+                    duplicate_location: files.get_path(*file).to_owned(),
+                    duplicate_line: Some(*line)
+                }))),
+
+                Some(_) => {},
+                None => {
+                    declared_in.insert(identifier, (*file, *line));
+                }
+            }
+        }
+    }
+
+    match declared_in.contains_key("main") {
+        true => Ok(()),
+        false => Err(Box::new(MissingMainFunction(directory.to_owned())))
+    }
+}
+
+
+/**
+ * Generates the symbol table for a program built from a directory. Each file is checked on its own first so
+ * that an error can be reported with the path of the file it is in.
+ */
+pub fn generate_symbol_table(ast:&[ASTNode], files:&FileTable, directory:&str) -> Result<SymbolTable, Box<dyn Error>> {
+    validate_functions(ast, files, directory)?;
+    for file in 0..files.get_file_count() {
+        semantics::generate_symbol_table(get_nodes_in_file(ast, file)).map_err(|err| files.in_file(file, err))?;
+    }
+
+    semantics::generate_symbol_table(ast.to_vec())
+}
+
+
+/**
+ * Validates a program built from a directory against the symbol table of the whole program. Each file is
+ * validated on its own first so that an error can be reported with the path of the file it is in, and then
+ * the whole program is validated to find problems which span files, such as inline functions which call each
+ * other. Returns the whole program decorated with its resolved types, as `semantics::semantic_validation` does.
+ * Only the validation of the whole program is written to `trace`, so each statement is traced once.
+ */
+pub fn semantic_validation(ast:&[ASTNode], symbol_table:&SymbolTable, files:&FileTable, trace:&Trace) -> Result<Vec<ASTNode>, Box<dyn Error>> {
+    for file in 0..files.get_file_count() {
+        semantics::semantic_validation(get_nodes_in_file(ast, file), symbol_table).map_err(|err| files.in_file(file, err))?;
+    }
+
+    semantics::semantic_validation_with_trace(ast.to_vec(), symbol_table, trace)
+}
+
+
+/**
+ * Returns the warnings for each file of a program built from a directory, along with the path of the file
+ * each one is in. Functions are only reported as never called if no file calls them, and inputs are only
+ * reported as overwritten if `distinct_input_buffers` is false.
+ */
+pub fn find_warnings<'a>(ast:&[ASTNode], files:&'a FileTable, distinct_input_buffers:bool) -> Vec<(&'a str, Warning)> {
+    let call_graph = CallGraph::new(ast);
+    let mut warnings = vec![];
+    for file in 0..files.get_file_count() {
+        let nodes = get_nodes_in_file(ast, file);
+        let mut file_warnings = semantics::find_constant_warnings(&nodes);
+        file_warnings.extend(semantics::find_empty_loops(&nodes));
+        file_warnings.extend(semantics::find_unused_expressions(&nodes));
+        file_warnings.extend(semantics::find_shadowed_variables(&nodes));
+        file_warnings.extend(call_graph.find_unreachable_functions(&nodes));
+        if !distinct_input_buffers {
+            file_warnings.extend(semantics::find_overwritten_inputs(&nodes));
+        }
+
+        warnings.extend(file_warnings.into_iter().map(|warning| (files.get_path(file), warning)));
+    }
+
+    warnings
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::json;
+
+use crate::backend::mips::{get_frame_size, DataSizes};
+use crate::frontend::intermediate_gen::IntermediateInstr;
+use crate::frontend::semantics::SymbolTable;
+
+
+/**
+ * Represents how large a single function is at each stage of compilation. The number of MIPS instructions is
+ * only known if the program was compiled to MIPS.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct FunctionSize {
+    pub name: String,
+    pub intermediate_instrs: usize,
+    pub mips_instrs: Option<usize>,
+    pub frame_size: u64
+}
+
+
+/**
+ * Records the size of every function in a program, in the order they appear in the intermediate code, for
+ * `--report-sizes` and `--size-budget`, along with the size of each kind of entry in the data section if the
+ * program was compiled to MIPS.
+ */
+#[derive(Debug)]
+pub struct SizeReport {
+    functions: Vec<FunctionSize>,
+    data: Option<DataSizes>
+}
+
+impl SizeReport {
+    /**
+     * Counts the intermediate instructions between the start and end of each function, and finds the size of
+     * each function's frame, with its bytes, chars and bools packed into a byte each if `packed` is true.
+     */
+    pub fn new(instructions:&[IntermediateInstr], symbol_table:&SymbolTable, packed:bool) -> SizeReport {
+        let mut functions:Vec<FunctionSize> = vec![];
+        for instr in instructions {
+            match instr {
+                IntermediateInstr::FuncStart(name) => functions.push(FunctionSize {
+                    name: name.to_owned(),
+                    intermediate_instrs: 0,
+                    mips_instrs: None,
+                    frame_size: get_frame_size(name, symbol_table, packed)
+                }),
+
+                IntermediateInstr::FuncEnd(_) => {},
+                _ => {
+                    if let Some(function) = functions.last_mut() {
+                        function.intermediate_instrs += 1;
+                    }
+                }
+            }
+        }
+
+        SizeReport {functions, data: None}
+    }
+
+
+    /**
+     * Records the number of MIPS instructions generated for each function, as returned by the backend.
+     */
+    pub fn add_mips_counts(&mut self, counts:&HashMap<String, usize>) {
+        for function in &mut self.functions {
+            function.mips_instrs = Some(*counts.get(&function.name).unwrap_or(&0));
+        }
+    }
+
+
+    /**
+     * Records the size of each kind of entry in the data section, as found by the backend.
+     */
+    pub fn add_data_sizes(&mut self, data:DataSizes) {
+        self.data = Some(data);
+    }
+
+
+    /**
+     * Returns the number of bytes taken up by the code of every function and the data section together, with
+     * 4 bytes for each MIPS instruction. The code of the runtime library is not counted. Returns `None` if the
+     * program was not compiled to MIPS.
+     */
+    pub fn get_total_bytes(&self) -> Option<u64> {
+        let (_, mips, _) = self.get_totals();
+        Some(get_text_bytes(mips?) + self.data?.get_total())
+    }
+
+
+    /**
+     * Returns the totals of the intermediate instructions, MIPS instructions and frame sizes of every function.
+     */
+    fn get_totals(&self) -> (usize, Option<usize>, u64) {
+        (
+            self.functions.iter().map(|function| function.intermediate_instrs).sum(),
+            self.functions.iter().map(|function| function.mips_instrs).sum(),
+            self.functions.iter().map(|function| function.frame_size).sum()
+        )
+    }
+
+
+    /**
+     * Returns the report as a JSON object on a single line, with a `functions` array holding the name,
+     * `intermediate`, `mips`, `text_bytes` and `frame_size` of each function, and a `total` object holding the
+     * sums of the same fields. A `data` object holds the bytes of each kind of entry in the data section and
+     * their `total`. `mips`, `text_bytes` and `data` are null if the program was not compiled to MIPS.
+     */
+    pub fn to_json(&self) -> String {
+        let functions:Vec<serde_json::Value> = self.functions.iter().map(|function| json!({
+            "name": function.name,
+            "intermediate": function.intermediate_instrs,
+            "mips": function.mips_instrs,
+            "text_bytes": function.mips_instrs.map(get_text_bytes),
+            "frame_size": function.frame_size
+        })).collect();
+
+        let data = self.data.map(|data| {
+            let mut object:serde_json::Map<String, serde_json::Value> = data.get_categories().iter()
+                .map(|(name, size)| (name.replace(' ', "_"), json!(size)))
+                .collect();
+            object.insert(String::from("total"), json!(data.get_total()));
+            object
+        });
+
+        let (intermediate, mips, frame_size) = self.get_totals();
+        json!({
+            "functions": functions,
+            "total": {"intermediate": intermediate, "mips": mips, "text_bytes": mips.map(get_text_bytes), "frame_size": frame_size},
+            "data": data
+        }).to_string()
+    }
+}
+
+
+/**
+ * Returns the number of bytes the given number of MIPS instructions take up, which is 4 for each.
+ */
+fn get_text_bytes(mips:usize) -> u64 {
+    4 * mips as u64
+}
+
+
+/**
+ * Formats a row of the table, showing a dash for the number of MIPS instructions and the bytes they take up
+ * if they are not known.
+ */
+fn format_row(name:&str, intermediate:usize, mips:Option<usize>, frame_size:u64) -> String {
+    let (mips, text_bytes) = match mips {
+        Some(mips) => (mips.to_string(), get_text_bytes(mips).to_string()),
+        None => (String::from("-"), String::from("-"))
+    };
+
+    format!("{:<28}{:>14}{:>14}{:>12}{:>12}", name, intermediate, mips, text_bytes, frame_size)
+}
+
+
+impl fmt::Display for SizeReport {
+    /**
+     * Formats the report as a table with a row for each function and the totals at the bottom. The text and
+     * frame sizes are in bytes. If the size of the data section is known, it follows in a second table with a
+     * row for each kind of entry, and then the size of the whole program.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<28}{:>14}{:>14}{:>12}{:>12}", "Function", "Intermediate", "MIPS", "Text", "Frame")?;
+        for function in &self.functions {
+            writeln!(f, "{}", format_row(&function.name, function.intermediate_instrs, function.mips_instrs, function.frame_size))?;
+        }
+
+        let (intermediate, mips, frame_size) = self.get_totals();
+        write!(f, "{}", format_row("total", intermediate, mips, frame_size))?;
+
+        if let (Some(data), Some(total)) = (self.data, self.get_total_bytes()) {
+            write!(f, "\n\n{:<28}{:>12}", "Data", "Bytes")?;
+            for (name, size) in data.get_categories() {
+                write!(f, "\n{:<28}{:>12}", name, size)?;
+            }
+
+            write!(f, "\n{:<28}{:>12}", "total", data.get_total())?;
+            write!(f, "\n\n{:<28}{:>12}", "text and data", total)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use serde_json::json;
+
+use crate::backend::mips::{get_call_size, CodegenOptions};
+use crate::frontend::call_graph::CallGraph;
+use crate::frontend::semantics::SymbolTable;
+
+
+/**
+ * Represents how much of the stack a single function uses: `call_size` for each call to it, and `deepest` for
+ * the call to it along with the deepest chain of calls it can make.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct FunctionStack {
+    pub name: String,
+    pub call_size: u64,
+    pub deepest: u64
+}
+
+
+/**
+ * Represents a cycle of recursive functions, along with the bytes of the stack taken up by going around the
+ * cycle once, calling each of its functions.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecursiveCycle {
+    pub functions: Vec<String>,
+    pub iteration_size: u64
+}
+
+impl RecursiveCycle {
+    /**
+     * Returns the message of the warning that the stack used by the cycle has no bound.
+     */
+    pub fn get_warning(&self) -> String {
+        format!("recursion through {} takes up {} bytes of the stack each time around, so how much of the stack the program uses has no bound",
+                self.functions.join(", "), self.iteration_size)
+    }
+}
+
+
+/**
+ * Records how much of the stack the MIPS generated for a program uses, for `--report-stack`, worked out from
+ * the call graph and the size of each call. A recursive cycle is counted once, as if each of its functions
+ * were called once on the way through it, so the depths are those of paths which never call a function
+ * already in progress. Each cycle `main` can reach is listed along with what going around it again costs.
+ */
+#[derive(Debug)]
+pub struct StackReport {
+    functions: Vec<FunctionStack>,
+    deepest_path: Vec<String>,
+    cycles: Vec<RecursiveCycle>
+}
+
+impl StackReport {
+    /**
+     * Finds the size of each call to each function in the call graph with the given options, and the
+     * deepest chain of calls from each function.
+     */
+    pub fn new(call_graph:&CallGraph, symbol_table:&SymbolTable, options:&CodegenOptions) -> Result<StackReport, Box<dyn Error>> {
+        let mut call_sizes:HashMap<&str, u64> = HashMap::new();
+        for function in call_graph.get_functions() {
+            call_sizes.insert(function, get_call_size(function, symbol_table, options)?);
+        }
+
+        let cycles = call_graph.get_cycles();
+        let mut deepest_paths:HashMap<&str, (u64, Vec<&str>)> = HashMap::new();
+        let functions = call_graph.get_functions().iter().map(|function| FunctionStack {
+            name: function.to_owned(),
+            call_size: call_sizes[function.as_str()],
+            deepest: find_deepest_path(function, call_graph, &cycles, &call_sizes, &mut deepest_paths).0
+        }).collect();
+
+        let deepest_path = match deepest_paths.get("main") {
+            Some((_, path)) => path.iter().map(|function| function.to_string()).collect(),
+            None => vec![]
+        };
+
+        let reachable = call_graph.get_reachable();
+        let cycles = cycles.iter()
+            .filter(|cycle| reachable.as_ref().is_none_or(|reachable| reachable.contains(cycle[0])))
+            .map(|cycle| RecursiveCycle {
+                functions: cycle.iter().map(|function| function.to_string()).collect(),
+                iteration_size: cycle.iter().map(|function| call_sizes[function]).sum()
+            }).collect();
+
+        Ok(StackReport {functions, deepest_path, cycles})
+    }
+
+
+    /**
+     * Returns the recursive cycles `main` can reach, each of which can use as much of the stack as it likes.
+     */
+    pub fn get_cycles(&self) -> &[RecursiveCycle] {
+        &self.cycles
+    }
+
+
+    /**
+     * Returns the most bytes of the stack the program can use without going around a recursive cycle more
+     * than once, or `None` if it has no `main` function.
+     */
+    pub fn get_max_depth(&self) -> Option<u64> {
+        self.functions.iter().find(|function| function.name == "main").map(|function| function.deepest)
+    }
+
+
+    /**
+     * Returns the report as a JSON object on a single line, with a `functions` array holding the `name`,
+     * `call_size` and `deepest` bytes of each function, the `deepest_path` from `main` as an array of function
+     * names and its size as `max_depth`, and a `cycles` array holding the `functions` and `iteration_size` of
+     * each recursive cycle.
+     */
+    pub fn to_json(&self) -> String {
+        let functions:Vec<serde_json::Value> = self.functions.iter().map(|function| json!({
+            "name": function.name,
+            "call_size": function.call_size,
+            "deepest": function.deepest
+        })).collect();
+
+        let cycles:Vec<serde_json::Value> = self.cycles.iter().map(|cycle| json!({
+            "functions": cycle.functions,
+            "iteration_size": cycle.iteration_size
+        })).collect();
+
+        json!({
+            "functions": functions,
+            "deepest_path": self.deepest_path,
+            "max_depth": self.get_max_depth(),
+            "cycles": cycles
+        }).to_string()
+    }
+}
+
+
+/**
+ * Returns the bytes of the stack taken up by calling the given function and then the deepest chain of calls
+ * it can make, along with the functions on that chain. Every function of a recursive cycle is counted once,
+ * and the chain carries on from whichever of them calls deepest out of the cycle. The results are kept in
+ * `deepest_paths`, so each function is only looked at once.
+ */
+fn find_deepest_path<'a>(function:&'a str, call_graph:&'a CallGraph, cycles:&[Vec<&'a str>], call_sizes:&HashMap<&str, u64>,
+                         deepest_paths:&mut HashMap<&'a str, (u64, Vec<&'a str>)>) -> (u64, Vec<&'a str>) {
+    if let Some(deepest) = deepest_paths.get(function) {
+        return deepest.clone();
+    }
+
+    let members:Vec<&str> = match cycles.iter().find(|cycle| cycle.contains(&function)) {
+        Some(cycle) => std::iter::once(function).chain(cycle.iter().copied().filter(|member| *member != function)).collect(),
+        None => vec![function]
+    };
+
+    let mut deepest:(u64, Vec<&str>) = (0, vec![]);
+    for callee in members.iter().flat_map(|member| call_graph.get_calls(member)) {
+        if !members.contains(&callee.as_str()) {
+            let path = find_deepest_path(callee, call_graph, cycles, call_sizes, deepest_paths);
+            if path.0 > deepest.0 {
+                deepest = path;
+            }
+        }
+    }
+
+    let size = members.iter().map(|member| call_sizes[member]).sum::<u64>() + deepest.0;
+    let path:Vec<&str> = members.into_iter().chain(deepest.1).collect();
+    deepest_paths.insert(function, (size, path.clone()));
+    (size, path)
+}
+
+
+impl fmt::Display for StackReport {
+    /**
+     * Formats the report as a table with a row for each function giving the bytes of each call to it and of
+     * the deepest chain of calls from it, followed by the deepest chain from `main` and a line for each
+     * recursive cycle.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<28}{:>12}{:>12}", "Function", "Call", "Deepest")?;
+        for function in &self.functions {
+            write!(f, "\n{:<28}{:>12}{:>12}", function.name, function.call_size, function.deepest)?;
+        }
+
+        if let Some(max_depth) = self.get_max_depth() {
+            write!(f, "\n\ndeepest path: {} ({} bytes)", self.deepest_path.join(" -> "), max_depth)?;
+        }
+
+        for cycle in &self.cycles {
+            write!(f, "\nrecursive cycle: {} ({} bytes each time around)", cycle.functions.join(" -> "), cycle.iteration_size)?;
+        }
+
+        Ok(())
+    }
+}
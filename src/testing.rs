@@ -0,0 +1,3127 @@
+use std::{env, fs, panic};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::{errors, frontend, backend, conformance, doctor, optimisation, project};
+use crate::backend::interpreter::Value;
+use crate::backend::mips::MipsInstr;
+use crate::diagnostics::{Diagnostic, MessageFormat};
+use crate::frontend::ast::{PrintFormat, Type};
+use crate::frontend::intermediate_gen::{Argument, IntermediateInstr};
+use crate::frontend::parser::DEFAULT_NESTING_LIMIT;
+use crate::frontend::semantics::SymbolTable;
+use crate::frontend::trace::Trace;
+use crate::size_report::SizeReport;
+use crate::stack_report::StackReport;
+use crate::timing::PhaseTimings;
+use crate::errors::{Warning, MissingReturnValue, UnexpectedReturnValue, NoIntrinsicOverload, UnusedIntrinsicResult, 
+    IncorrectNumArguments, UnsupportedOperatorType, ChainedComparison, IncorrectDatatype, UnprintableValue, ReservedIdentifier,
+    ImmutableReassignmentError, MissingTargetTemplate, NestingTooDeep, NotIterable, MismatchedElementType, RuntimeError,
+    InvalidOperandType, NonConstantStaticInitialiser, ImpureFunction, IndefLoopWithoutBreak, NonBooleanCondition, SymbolNotFoundError,
+    MismatchedTernaryArms, RecursiveInlineFunction, UseBeforeDeclaration, VoidValueUsed,
+    MismatchedOperandTypes, InternalCompilerError};
+
+
+/**
+ * The directory containing the programs run by the differential testing harness.
+ */
+const PROGRAMS_DIR:&str = "tests/programs";
+
+/**
+ * The directory containing malformed programs which the parser must reject with an error.
+ */
+const MALFORMED_DIR:&str = "tests/malformed";
+
+/**
+ * The directory containing programs whose intermediate code is compared against a checked-in `.ir` file.
+ */
+const IR_SNAPSHOTS_DIR:&str = "tests/ir_snapshots";
+
+/**
+ * Setting this environment variable to `1` when running the tests writes the current intermediate code of
+ * each program in `IR_SNAPSHOTS_DIR` to its snapshot instead of comparing against it.
+ */
+const BLESS_VAR:&str = "IRIDESCENT_BLESS";
+
+
+/**
+ * Represents the behaviour a test program declares it has in its leading comment block.
+ */
+#[derive(PartialEq, Eq, Debug)]
+struct Expectation {
+    exit_code: i32,
+    output: String
+}
+
+
+/**
+ * Represents a MIPS simulator found on the host which the generated assembly can be run under.
+ */
+enum Simulator {
+    Spim,
+    Mars(String)
+}
+
+
+/**
+ * Takes the source of a test program and parses the expected exit code and output from the block comment
+ * it starts with. The first line of the comment must be `exit: <code>`, optionally followed by an
+ * `input: <line>` line, and then `output:`, after which every line is expected to be printed followed by
+ * a newline.
+ */
+fn parse_expectation(source:&str) -> Result<Expectation, String> {
+    let source = source.trim_start();
+    if !source.starts_with("/*") {
+        return Err("program does not start with a comment block".to_owned());
+    }
+
+    let end = source.find("*/").ok_or("comment block is never closed")?;
+    let mut lines = source[2..end].lines().skip_while(|line| line.trim().is_empty());
+
+    let exit_line = lines.next().ok_or("comment block is empty")?;
+    let exit_code = match exit_line.trim().strip_prefix("exit:") {
+        Some(code) => code.trim().parse().map_err(|_| format!("invalid exit code '{}'", code.trim()))?,
+        None => return Err(format!("expected 'exit:' but found '{}'", exit_line))
+    };
+
+    let mut lines = lines.skip_while(|line| line.trim().starts_with("input:"));
+    match lines.next() {
+        Some(line) if line.trim() == "output:" => {},
+        Some(line) => return Err(format!("expected 'output:' but found '{}'", line)),
+        None => return Err("comment block has no 'output:' section".to_owned())
+    }
+
+    let output = lines.map(|line| format!("{}\n", line)).collect();
+    Ok(Expectation {exit_code, output})
+}
+
+
+/**
+ * Takes the source of a test program and returns the line of input given to it by the `input:` line of its
+ * leading comment block, followed by a newline as if typed by a user. Programs without one get no input.
+ */
+fn parse_input(source:&str) -> String {
+    let comment = match source.trim_start().strip_prefix("/*").and_then(|rest| rest.split("*/").next()) {
+        Some(comment) => comment,
+        None => return String::new()
+    };
+
+    comment.lines()
+           .find_map(|line| line.trim().strip_prefix("input:"))
+           .map(|input| format!("{}\n", input.trim()))
+           .unwrap_or_default()
+}
+
+
+/**
+ * Compiles the program at the given path into optimised intermediate code in the same way as the
+ * compiler binary, returning it along with the program's symbol table. Branches which can never be taken
+ * are pruned if `prune_branches` is true, as with `-O1`.
+ */
+fn compile(path:&Path, prune_branches:bool) -> Result<(Vec<IntermediateInstr>, SymbolTable), Box<dyn Error>> {
+    let ast = frontend::parser::parse_program(&fs::read_to_string(path)?, DEFAULT_NESTING_LIMIT)?;
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+    let ast = optimisation::cast_folding::fold_constant_casts(ast);
+    let ast = match prune_branches {
+        true => optimisation::branch_pruning::prune_constant_branches(ast),
+        false => ast
+    };
+
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    Ok((optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new()), symbol_table))
+}
+
+
+/**
+ * Parses and validates the given source in the same way as the compiler binary, returning the validated
+ * AST.
+ */
+fn validate_source(source:&str) -> Result<Vec<frontend::ast::ASTNode>, Box<dyn Error>> {
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+    frontend::semantics::semantic_validation(ast, &symbol_table)
+}
+
+
+/**
+ * Compiles the program at the given path into intermediate code with every optimisation `-O2` turns on, in
+ * the same order as the compiler binary, returning it along with the program's symbol table.
+ */
+fn compile_at_o2(path:&Path) -> Result<(Vec<IntermediateInstr>, SymbolTable), Box<dyn Error>> {
+    let ast = frontend::parser::parse_program(&fs::read_to_string(path)?, DEFAULT_NESTING_LIMIT)?;
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+    let ast = optimisation::dead_functions::remove_unreachable_functions(ast);
+    let ast = optimisation::cast_folding::fold_constant_casts(ast);
+    let ast = optimisation::branch_pruning::prune_constant_branches(ast);
+
+    let pure_functions = optimisation::pure_calls::find_pure_functions(&ast);
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let instructions = optimisation::pure_calls::fold_pure_calls(instructions, &pure_functions, &symbol_table, backend::OverflowMode::Wrap);
+    let instructions = optimisation::constant_propagation::propagate_constants(instructions, backend::OverflowMode::Wrap);
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+    let instructions = optimisation::jump_tables::build_jump_tables(instructions, optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY);
+    Ok((optimisation::traps::remove_unreachable_traps(instructions), symbol_table))
+}
+
+
+/**
+ * Runs the intermediate code under the interpreter with the given input and returns its exit code and output.
+ */
+fn run_interpreter(instructions:&[IntermediateInstr], symbol_table:&SymbolTable, input:&str) -> Result<Expectation, Box<dyn Error>> {
+    let mut output:Vec<u8> = vec![];
+    let mut interpreter = backend::interpreter::Interpreter::new(instructions, symbol_table);
+    let exit_code = interpreter.run(&mut input.as_bytes(), &mut output)?;
+    Ok(Expectation {exit_code, output: String::from_utf8(output)?})
+}
+
+
+/**
+ * Looks for a MIPS simulator on the host, preferring `spim` and falling back to the MARS jar given by the
+ * `MARS_JAR` environment variable. Returns `None` if neither is available.
+ */
+fn find_simulator() -> Option<Simulator> {
+    if Command::new("spim").arg("-version").output().is_ok() {
+        return Some(Simulator::Spim);
+    }
+
+    let jar = env::var("MARS_JAR").ok()?;
+    match Command::new("java").arg("-version").output() {
+        Ok(_) if Path::new(&jar).exists() => Some(Simulator::Mars(jar)),
+        _ => None
+    }
+}
+
+
+/**
+ * Writes the assembly to a temporary file, runs it under the given simulator with the given input on stdin,
+ * and returns everything it printed to stdout.
+ */
+fn run_mips(assembly:&str, name:&str, simulator:&Simulator, input:&str) -> Result<String, Box<dyn Error>> {
+    let asm_path = env::temp_dir().join(format!("iridescent_{}_{}.asm", std::process::id(), name));
+    fs::write(&asm_path, assembly)?;
+
+    let mut command = match simulator {
+        Simulator::Spim => Command::new("spim"),
+        Simulator::Mars(_) => Command::new("java")
+    };
+
+    match simulator {
+        Simulator::Spim => command.arg("-quiet").arg("-file").arg(&asm_path),
+        Simulator::Mars(jar) => command.arg("-jar").arg(jar).arg("nc").arg(&asm_path)
+    };
+
+    let output = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().and_then(|mut child| {
+        child.stdin.take().unwrap().write_all(input.as_bytes())?;
+        child.wait_with_output()
+    });
+
+    fs::remove_file(&asm_path)?;
+    Ok(String::from_utf8(output?.stdout)?)
+}
+
+
+/**
+ * Generates the intermediate code for the program at the given path without any optimisation, so that it
+ * shows exactly what is generated for each construct, and returns it as text with one instruction per line.
+ */
+fn generate_ir_text(path:&Path) -> Result<String, Box<dyn Error>> {
+    let ast = frontend::parser::parse_program(&fs::read_to_string(path)?, DEFAULT_NESTING_LIMIT)?;
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    Ok(format_ir(&instructions))
+}
+
+
+/**
+ * Returns the intermediate code as text with one instruction per line, in the same layout as the snapshots.
+ */
+fn format_ir(instructions:&[IntermediateInstr]) -> String {
+    let lines:Vec<String> = instructions.iter().map(|instr| instr.to_string()).collect();
+    format!("{}\n", lines.join("\n").trim_start())
+}
+
+
+/**
+ * Compares the generated intermediate code against the expected code line by line, returning a description
+ * of the first line which differs, or `None` if they are the same.
+ */
+fn diff_ir(expected:&str, actual:&str) -> Option<String> {
+    let expected_lines:Vec<&str> = expected.lines().collect();
+    let actual_lines:Vec<&str> = actual.lines().collect();
+    (0..expected_lines.len().max(actual_lines.len())).find_map(|index| {
+        let expected_line = expected_lines.get(index).copied().unwrap_or("<end of snapshot>");
+        let actual_line = actual_lines.get(index).copied().unwrap_or("<end of output>");
+        (expected_line != actual_line).then(|| format!("line {}: expected `{}`, generated `{}`", index + 1, expected_line, actual_line))
+    })
+}
+
+
+/**
+ * Returns the paths of every .iri program in the given directory of the crate, sorted by name.
+ */
+fn get_test_programs(dir:&str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let mut programs:Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+                                                     .map(|entry| entry.unwrap().path())
+                                                     .filter(|path| path.extension().is_some_and(|ext| ext == "iri"))
+                                                     .collect();
+
+    programs.sort();
+    programs
+}
+
+
+/**
+ * Returns the floating point registers, along with whether they are written, used by a line of MIPS. Double
+ * precision operands cover both registers of the even/odd pair they name.
+ */
+fn get_float_register_uses(line:&str) -> Vec<(usize, bool)> {
+    let line = line.split('#').next().unwrap().trim();
+    let (opcode, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands:Vec<&str> = operands.split(',').map(|operand| operand.trim()).collect();
+
+    // the width of each operand, where conversions give the width of their destination then their source
+    let widths:Vec<usize> = match opcode.split('.').collect::<Vec<&str>>()[..] {
+        ["cvt", into, from] => vec![if into == "d" {2} else {1}, if from == "d" {2} else {1}],
+        ["l" | "s", "d"] => vec![2],
+        [_, .., "d"] => vec![2; operands.len()],
+        _ => vec![1; operands.len()]
+    };
+
+    // stores and comparisons only read their first operand, while conditional moves read and then write it
+    let is_conditional_move = opcode.starts_with("movt") || opcode.starts_with("movf");
+    let written_operand = match opcode {
+        "swc1" | "s.s" | "s.d" | "mfc1" => None,
+        "mtc1" => Some(1),
+        _ if opcode.starts_with("c.") || is_conditional_move => None,
+        _ => Some(0)
+    };
+
+    let mut uses = vec![];
+    for (index, operand) in operands.iter().enumerate() {
+        let register = match operand.strip_prefix("$f").and_then(|number| number.parse::<usize>().ok()) {
+            Some(register) => register,
+            None => continue
+        };
+
+        let width = *widths.get(index).or(widths.last()).unwrap_or(&1);
+        for offset in 0..width {
+            uses.push((register + offset, written_operand == Some(index)));
+            if is_conditional_move && index == 0 {
+                uses.push((register + offset, true));
+            }
+        }
+    }
+
+    uses
+}
+
+
+/**
+ * Takes generated MIPS and returns the first line which reads a floating point register not written earlier
+ * in the code for the same intermediate instruction. Values on the evaluation stack are kept in memory, so a
+ * register read before it is written holds whatever an earlier instruction or a called function left in it.
+ */
+fn find_float_register_read_before_write(assembly:&str) -> Option<String> {
+    for block in assembly.split("\n\n") {
+        let mut written:Vec<usize> = vec![];
+        for line in block.lines() {
+            for (register, is_write) in get_float_register_uses(line) {
+                match is_write {
+                    true => written.push(register),
+                    false if !written.contains(&register) => return Some(line.trim().to_owned()),
+                    false => {}
+                }
+            }
+        }
+    }
+
+    None
+}
+
+
+/**
+ * Takes generated MIPS and returns the first label which a jump, branch or address load refers to but which
+ * is never defined, as the simulator would refuse to assemble it. Registers, offsets and numbers are not
+ * labels, so are skipped.
+ */
+fn find_undefined_label(assembly:&str) -> Option<String> {
+    let code:Vec<&str> = assembly.lines().map(|line| line.split('#').next().unwrap_or_default().trim()).collect();
+    let defined:BTreeSet<&str> = code.iter().filter_map(|line| line.split_once(':')).map(|(label, _)| label).collect();
+    code.iter().filter_map(|line| {
+        let (op, args) = line.split_once(char::is_whitespace)?;
+        if !matches!(op, "j" | "jal" | "la" | "l.s" | "l.d") && !op.starts_with('b') {
+            return None;
+        }
+
+        let target = args.rsplit(',').next()?.trim();
+        match target.starts_with(['$', '-']) || target.contains('(') || target.starts_with(|c:char| c.is_ascii_digit()) {
+            true => None,
+            false => Some(target)
+        }
+    }).find(|target| !defined.contains(target)).map(str::to_owned)
+}
+
+
+/**
+ * Looks for a MIPS simulator for the differential tests, warning that only the generated assembly will be
+ * checked if there is none. On CI, shown by the `CI` environment variable, a missing simulator fails the
+ * test instead, so that the MIPS side is never skipped without anyone noticing.
+ */
+fn find_harness_simulator() -> Option<Simulator> {
+    let simulator = find_simulator();
+    if simulator.is_none() {
+        assert!(env::var_os("CI").is_none(), "No MIPS simulator found on CI, install spim or set MARS_JAR to the MARS jar");
+        eprintln!("No MIPS simulator found, only checking the generated assembly and the interpreter against the expected output");
+    }
+
+    simulator
+}
+
+
+/**
+ * Checks a single test program, returning a description of the first way in which it did not behave as
+ * declared or the backends disagreed.
+ */
+fn check_program(path:&Path, simulator:&Option<Simulator>) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let expected = parse_expectation(&source)?;
+    let input = parse_input(&source);
+    let (pruned_instructions, pruned_symbol_table) = compile(path, true).map_err(|err| format!("failed to compile at -O1: {}", err))?;
+    let pruned = run_interpreter(&pruned_instructions, &pruned_symbol_table, &input).map_err(|err| format!("interpreter failed at -O1: {}", err))?;
+    if pruned != expected {
+        return Err(format!("interpreter gave {:?} at -O1 but expected {:?}", pruned, expected));
+    }
+
+    let (optimised_instructions, optimised_symbol_table) = compile_at_o2(path).map_err(|err| format!("failed to compile at -O2: {}", err))?;
+    let optimised = run_interpreter(&optimised_instructions, &optimised_symbol_table, &input).map_err(|err| format!("interpreter failed at -O2: {}", err))?;
+    if optimised != expected {
+        return Err(format!("interpreter gave {:?} at -O2 but expected {:?}", optimised, expected));
+    }
+
+    let optimised_assembly = backend::mips::generate_mips_to_string(optimised_instructions, &optimised_symbol_table)
+        .map_err(|err| format!("failed to generate MIPS at -O2: {}", err))?;
+
+    if let Some(line) = find_float_register_read_before_write(&optimised_assembly) {
+        return Err(format!("MIPS at -O2 reads a float register which is not saved across instructions: {}", line));
+    }
+
+    if let Some(label) = find_undefined_label(&optimised_assembly) {
+        return Err(format!("MIPS at -O2 refers to the undefined label {}", label));
+    }
+
+    let (instructions, symbol_table) = compile(path, false).map_err(|err| format!("failed to compile: {}", err))?;
+    let interpreted = run_interpreter(&instructions, &symbol_table, &input).map_err(|err| format!("interpreter failed: {}", err))?;
+    if interpreted != expected {
+        return Err(format!("interpreter gave {:?} but expected {:?}", interpreted, expected));
+    }
+
+    let text = format_ir(&instructions);
+    let parsed = frontend::intermediate_gen::parse_intermediate(&text).map_err(|err| format!("intermediate code could not be read back: {}", err))?;
+    if let Some(diff) = diff_ir(&text, &format_ir(&parsed)) {
+        return Err(format!("intermediate code changed when read back, {}", diff));
+    }
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table)
+        .map_err(|err| format!("failed to generate MIPS: {}", err))?;
+
+    if let Some(line) = find_float_register_read_before_write(&assembly) {
+        return Err(format!("MIPS reads a float register which is not saved across instructions: {}", line));
+    }
+
+    if let Some(label) = find_undefined_label(&assembly) {
+        return Err(format!("MIPS refers to the undefined label {}", label));
+    }
+
+    if let Some(simulator) = simulator {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let mips_output = run_mips(&assembly, &name, simulator, &input).map_err(|err| format!("simulator failed: {}", err))?;
+        if mips_output != expected.output {
+            return Err(format!("MIPS printed {:?} but the interpreter printed {:?}", mips_output, expected.output));
+        }
+
+        let optimised_output = run_mips(&optimised_assembly, &name, simulator, &input).map_err(|err| format!("simulator failed at -O2: {}", err))?;
+        if optimised_output != expected.output {
+            return Err(format!("MIPS printed {:?} at -O2 but the interpreter printed {:?}", optimised_output, expected.output));
+        }
+    }
+
+    Ok(())
+}
+
+
+#[test]
+fn backends_agree_on_test_programs() {
+    let simulator = find_harness_simulator();
+    let programs = get_test_programs(PROGRAMS_DIR);
+    assert!(programs.len() >= 10, "Expected at least 10 test programs in {}", PROGRAMS_DIR);
+
+    let failures:Vec<String> = programs.iter()
+                                       .filter_map(|path| {
+                                           check_program(path, &simulator).err()
+                                                                          .map(|err| format!("{}: {}", path.display(), err))
+                                       })
+                                       .collect();
+
+    assert!(failures.is_empty(), "{} test programs failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+
+#[test]
+fn undefined_labels_in_generated_mips_are_found() {
+    let assembly = ".data:\n\t__asciiz_1: .asciiz \"a\"\n\n.text:\nfn_main: # start subroutine\n\tla $a0, __asciiz_1\n\tbeqz $t0, __l_1\n\tlw $t0, -4($fp)\n\tjal fn_main\n\tjr $ra";
+    assert_eq!(find_undefined_label(assembly), Some(String::from("__l_1")));
+    assert_eq!(find_undefined_label(&assembly.replace("__l_1", "fn_main")), None);
+}
+
+
+#[test]
+fn backends_follow_the_language_on_conformance_programs() {
+    let simulator = find_harness_simulator();
+    let dir = env::temp_dir().join(format!("iridescent_conformance_{}", std::process::id()));
+    let count = conformance::write_conformance_programs(&dir).unwrap();
+    assert!(count >= 80, "Expected a conformance program for each operator and type, but only {} were written", count);
+
+    let mut programs:Vec<PathBuf> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    programs.sort();
+    assert_eq!(programs.len(), count);
+
+    let failures:Vec<String> = programs.iter()
+                                       .filter_map(|path| {
+                                           check_program(path, &simulator).err()
+                                                                          .map(|err| format!("{}: {}", path.display(), err))
+                                       })
+                                       .collect();
+
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(failures.is_empty(), "{} conformance programs failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+
+#[test]
+fn conformance_results_follow_the_language() {
+    assert_eq!(conformance::get_literal(&Value::Integer(i32::MIN)), "((2147483647)-, 1)-");
+    assert_eq!(conformance::get_literal(&Value::Float(-1.0)), "(1.0)-");
+    assert_eq!(conformance::get_literal(&Value::Double(0.5)), "0.5d");
+
+    assert_eq!(conformance::evaluate_binary("+", &Value::Byte(255), &Value::Byte(1)), Some(Value::Byte(0)));
+    assert_eq!(conformance::evaluate_binary("/", &Value::Integer(i32::MIN), &Value::Integer(-1)), Some(Value::Integer(i32::MIN)));
+    assert_eq!(conformance::evaluate_binary(">>", &Value::Integer(-1), &Value::Integer(31)), Some(Value::Integer(1)));
+    assert_eq!(conformance::evaluate_binary("/", &Value::Long(1), &Value::Long(0)), None);
+    assert_eq!(conformance::evaluate_unary("-", &Value::Long(i64::MIN)), Some(Value::Long(i64::MIN)));
+    assert_eq!(conformance::evaluate_comparison("!=", &Value::Float(f32::NAN), &Value::Float(f32::NAN)), Some(true));
+    assert_eq!(conformance::evaluate_comparison("<", &Value::Byte(1), &Value::Byte(255)), Some(true));
+}
+
+
+#[test]
+fn expectation_is_parsed_from_leading_comment() {
+    let source = "/*\nexit: 3\noutput:\nfirst\nsecond\n*/\nfn void main() {}";
+    assert_eq!(parse_expectation(source), Ok(Expectation {exit_code: 3, output: "first\nsecond\n".to_owned()}));
+    assert!(parse_expectation("fn void main() {}").is_err());
+    assert!(parse_expectation("/*\noutput:\n*/").is_err());
+
+    let source = "/*\nexit: 0\ninput: quit\noutput:\nbye\n*/\nfn void main() {}";
+    assert_eq!(parse_expectation(source), Ok(Expectation {exit_code: 0, output: "bye\n".to_owned()}));
+    assert_eq!(parse_input(source), "quit\n");
+    assert_eq!(parse_input("/*\nexit: 0\noutput:\n*/"), "");
+}
+
+
+#[test]
+fn parser_rejects_malformed_programs() {
+    let programs = get_test_programs(MALFORMED_DIR);
+    assert!(!programs.is_empty(), "Expected malformed programs in {}", MALFORMED_DIR);
+
+    let failures:Vec<String> = programs.iter()
+                                       .filter_map(|path| {
+                                           let source = fs::read_to_string(path).unwrap();
+                                           match panic::catch_unwind(|| frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).is_err()) {
+                                               Ok(true) => None,
+                                               Ok(false) => Some(format!("{}: parsed without an error", path.display())),
+                                               Err(_) => Some(format!("{}: parser panicked", path.display()))
+                                           }
+                                       })
+                                       .collect();
+
+    assert!(failures.is_empty(), "{} malformed programs were not rejected:\n{}", failures.len(), failures.join("\n"));
+}
+
+
+#[test]
+fn intermediate_code_matches_snapshots() {
+    let programs = get_test_programs(IR_SNAPSHOTS_DIR);
+    assert!(!programs.is_empty(), "Expected programs in {}", IR_SNAPSHOTS_DIR);
+
+    let bless = env::var(BLESS_VAR).is_ok_and(|value| value == "1");
+    let failures:Vec<String> = programs.iter()
+                                       .filter_map(|path| {
+                                           let actual = match generate_ir_text(path) {
+                                               Ok(actual) => actual,
+                                               Err(err) => return Some(format!("{}: failed to compile: {}", path.display(), err))
+                                           };
+
+                                           let snapshot = path.with_extension("ir");
+                                           if bless {
+                                               fs::write(&snapshot, &actual).unwrap();
+                                               return None;
+                                           }
+
+                                           let expected = match fs::read_to_string(&snapshot) {
+                                               Ok(expected) => expected,
+                                               Err(_) => return Some(format!("{}: missing snapshot {}", path.display(), snapshot.display()))
+                                           };
+
+                                           // the snapshot must also read back into the same instructions
+                                           match frontend::intermediate_gen::parse_intermediate(&expected) {
+                                               Ok(parsed) => diff_ir(&expected, &actual).or_else(|| diff_ir(&expected, &format_ir(&parsed)))
+                                                                                        .map(|diff| format!("{}: {}", path.display(), diff)),
+                                               Err(err) => Some(format!("{}: {}", snapshot.display(), err))
+                                           }
+                                       })
+                                       .collect();
+
+    assert!(failures.is_empty(), "{} programs did not match their IR snapshots, run with {}=1 to update them:\n{}",
+            failures.len(), BLESS_VAR, failures.join("\n"));
+}
+
+
+#[test]
+fn mips_assembly_can_be_generated_into_a_string() {
+    let source = "fn int add(int a, int b) {\n    return (a, b)+;\n}\n\nfn void main() {\n    let int sum = add(2, 3);\n    print << \"sum: \" << sum;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+
+    // the data section comes first, then each function, with the program halting at the end
+    let (data, text) = assembly.split_once("\n.text:\n").unwrap_or_else(|| panic!("no text section in:\n{}", assembly));
+    assert!(data.starts_with(".data:\n"), "unexpected assembly:\n{}", assembly);
+    assert!(data.contains(".asciiz \"sum: \""), "unexpected data section:\n{}", data);
+    assert!(text.find("\nfn_main:").unwrap() < text.find("\nfn_add:").unwrap(), "unexpected text section:\n{}", text);
+    assert!(text.contains("\tjal fn_add"), "unexpected text section:\n{}", text);
+    assert!(text.trim_end().ends_with("__end:\n\tli $v0, 10 # halt syscall\n\tsyscall"), "unexpected text section:\n{}", text);
+}
+
+
+#[test]
+fn mips_constants_are_grouped_by_directive_in_a_stable_order() {
+    let source = "fn void main() {\n    print << \"first\";\n    let char c = 'x';\n    let double d = 2.5d;\n    let float f = 1.5;\n    print << \"second\" << c << f:2;\n    let char e = 'y';\n    let float g = 3.25;\n    let double h = (d, 0.5d)+;\n    let float k = float(h);\n    print << e << g:2 << k:2;\n}";
+    let generate = || {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap()
+    };
+
+    // floats, doubles, chars and strings each come together whatever order they are used in, and within each
+    // group in the order they are first used, so compiling the same program again gives the same data section
+    let assembly = generate();
+    let data = assembly.split_once("\n.text:").unwrap().0;
+    assert_eq!(data.lines().collect::<Vec<&str>>(), vec![
+        ".data:",
+        "\t.align 2",
+        "\t__float_3fc00000: .float 1.5",
+        "\t__float_40500000: .float 3.25",
+        "\t.align 3",
+        "\t__double_4004000000000000: .double 2.5",
+        "\t__double_3fe0000000000000: .double 0.5",
+        "\t__byte_78: .byte 'x'",
+        "\t__byte_79: .byte 'y'",
+        "\t__asciiz_89d7ed7f996f1d41: .asciiz \"first\"",
+        "\t__asciiz_a49985ef4cee20bd: .asciiz \"second\""
+    ]);
+
+    assert!(generate() == assembly, "compiling the same program twice gave different assembly");
+}
+
+
+#[test]
+fn functions_are_laid_out_with_main_first() {
+    let path = Path::new("tests/layout/function_order.iri");
+    let (instructions, symbol_table) = compile(path, false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+
+    // only the contents, banners and labels are compared, so changes to the code of each function do not matter
+    let lines:Vec<&str> = assembly.lines()
+                                  .filter(|line| line.starts_with("# ====") || line.starts_with("#   ") || line.starts_with("fn_") || line.starts_with("__end"))
+                                  .collect();
+    let actual = format!("{}\n", lines.join("\n"));
+
+    let layout = path.with_extension("layout");
+    if env::var(BLESS_VAR).is_ok_and(|value| value == "1") {
+        fs::write(&layout, &actual).unwrap();
+    }
+
+    let expected = fs::read_to_string(&layout).unwrap();
+    assert_eq!(diff_ir(&expected, &actual), None, "run with {}=1 to update {}", BLESS_VAR, layout.display());
+
+    // the program starts in main without jumping there, and a library nothing calls is left out
+    assert!(!assembly.contains("j fn_main"));
+    assert!(!assembly.contains("__strcmp:"));
+}
+
+#[test]
+fn constant_conditions_are_reported() {
+    let source = "fn void main() {\n    let int limit = 10;\n    if (limit, 10)== {\n        print << \"equal\\n\";\n    }\n\n    let mut int count = 0;\n    if (count, 1)> {\n        print << \"more\\n\";\n    }\n\n    let int chosen = (1, 2)> ? 3 : 4;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    assert_eq!(frontend::semantics::find_constant_warnings(&ast), vec![
+        Warning::ConditionAlwaysTrue {construct: "if statement", line: 3},
+        Warning::ConditionAlwaysFalse {construct: "ternary expression", line: 12}
+    ]);
+}
+
+
+#[test]
+fn else_if_branches_which_can_never_run_are_reported() {
+    let source = "fn void main() {\n    let int limit = 10;\n    let mut int count = 0;\n    if (limit, 5)> {\n        count = 1;\n    } else if (count, 1)> {\n        count = 2;\n    }\n\n    if (int(count), 3)== {\n        count = 3;\n    } else if (count, 4)== {\n        count = 4;\n    } else if (int(count), 3)== {\n        count = 5;\n    } else {\n        count = 6;\n    }\n\n    if (next(), 1)== {\n        count = 7;\n    } else if (next(), 1)== {\n        count = 8;\n    }\n}\nfn int next() {\n    return 1;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let warnings = frontend::semantics::find_constant_warnings(&ast);
+    assert_eq!(warnings, vec![
+        Warning::UnreachableBranch {line: 6, earlier_line: 4},
+        Warning::ConditionAlwaysTrue {construct: "if statement", line: 4},
+        Warning::DuplicateCondition {line: 14, earlier_line: 10}
+    ]);
+
+    assert_eq!(warnings[0].to_string(), "The branch on line 6 can never run, as the condition of the branch on line 4 is always true.");
+    assert_eq!(warnings[2].to_string(), "The condition of the branch on line 14 is the same as that of the branch on line 10, so it can never run.");
+}
+
+
+#[test]
+fn truncating_constant_casts_are_reported() {
+    let source = "fn void main() {\n    let long big = 5000000000l;\n    let int truncated = int(big);\n    let int fits = int(7l);\n    let byte wrapped = byte(300);\n    let float rounded = float(0.1d);\n    let float exact = float(0.5d);\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    assert_eq!(frontend::semantics::find_constant_warnings(&ast), vec![
+        Warning::CastTruncatesValue {into: "int".to_string(), before: "5000000000".to_string(), after: "705032704".to_string(), line: 3},
+        Warning::CastTruncatesValue {into: "byte".to_string(), before: "300".to_string(), after: "44".to_string(), line: 5},
+        Warning::CastTruncatesValue {into: "float".to_string(), before: "0.1".to_string(), after: "0.10000000149011612".to_string(), line: 6}
+    ]);
+
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let folded = optimisation::cast_folding::fold_constant_casts(ast);
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(folded, &symbol_table).unwrap();
+    assert!(!instructions.iter().any(|instr| matches!(instr, IntermediateInstr::Cast(..))));
+}
+
+
+#[test]
+fn return_values_must_match_return_type() {
+    assert!(validate_source("fn void main() {\n    while (1, 2)< {\n        return;\n    }\n}").is_ok());
+
+    let err = validate_source("fn int f() {\n    if (1, 2)< {\n        return;\n    }\n    return 1;\n}\nfn void main() {}").unwrap_err();
+    assert!(err.downcast_ref::<MissingReturnValue>().is_some(), "unexpected error: {}", err);
+
+    let err = validate_source("fn void main() {\n    return 1;\n}").unwrap_err();
+    assert!(err.downcast_ref::<UnexpectedReturnValue>().is_some(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn indefinite_loops_need_a_break_which_can_run() {
+    assert!(validate_source("fn void main() {\n    let mut int x = 0;\n    loop {\n        x = (x, 1)+;\n        if (x, 5)> {\n            break;\n        }\n    }\n}").is_ok());
+    assert!(validate_source("fn void main() {\n    loop {\n        if (1, 2)< {\n            break;\n        }\n    }\n}").is_ok());
+
+    // a break in an inner loop only ends that loop, and a break behind a condition which is always false never runs
+    for source in ["fn void main() {\n    loop {\n        loop {\n            break;\n        }\n    }\n}",
+                   "fn void main() {\n    loop {\n        while true {\n            break;\n        }\n    }\n}",
+                   "fn void main() {\n    loop {\n        if false {\n            break;\n        }\n    }\n}",
+                   "fn void main() {\n    loop {\n        if true {\n            print << \"a\";\n        } else {\n            break;\n        }\n    }\n}"] {
+        let err = validate_source(source).unwrap_err();
+        assert!(err.is::<IndefLoopWithoutBreak>(), "unexpected error for {}: {}", source, err);
+    }
+
+    // a condition which is always false because of a constant is only known once constants are followed
+    let source = "fn void main() {\n    let int limit = 3;\n    loop {\n        if (limit, 5)> {\n            break;\n        }\n    }\n}";
+    assert!(validate_source(source).is_ok());
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let warnings = frontend::semantics::find_constant_warnings(&ast);
+    assert!(warnings.contains(&Warning::LoopNeverBreaks {line: 4}), "missing warning in {:?}", warnings);
+}
+
+
+#[test]
+fn intrinsic_calls_are_type_checked() {
+    assert!(validate_source("fn void main() {\n    let double x = max(1.5d, 2.5d);\n    let long y = pow(2l, 3l);\n}").is_ok());
+    assert!(validate_source("fn int abs(int x) {\n    return 0;\n}\nfn void main() {\n    abs(3);\n}").is_ok());
+
+    let err = validate_source("fn void main() {\n    let float x = pow(1.5, 2.0);\n}").unwrap_err();
+    assert!(err.downcast_ref::<NoIntrinsicOverload>().is_some(), "unexpected error: {}", err);
+
+    let err = validate_source("fn void main() {\n    let int x = min(1);\n}").unwrap_err();
+    assert!(err.downcast_ref::<IncorrectNumArguments>().is_some(), "unexpected error: {}", err);
+
+    let err = validate_source("fn void main() {\n    abs(3);\n}").unwrap_err();
+    assert!(err.downcast_ref::<UnusedIntrinsicResult>().is_some(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn boolean_expressions_can_be_passed_to_bool_parameters() {
+    let validate = |source:&str| -> Result<_, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        Ok(frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table)?)
+    };
+
+    let log_if = "fn void log_if(bool cond, string msg) {\n    if cond {\n        print << msg;\n    }\n}\n";
+    let instructions = validate(&format!("{}fn void main() {{\n    let int x = 3;\n    log_if((x, 2)>, \"big\");\n}}", log_if)).unwrap();
+    let call = instructions.iter().position(|instr| matches!(instr, IntermediateInstr::Call(name, ..) if name == "log_if")).unwrap();
+    let arguments:Vec<String> = instructions[call - 4..call].iter().map(|instr| instr.to_string().trim().to_owned()).collect();
+    assert_eq!(arguments, vec!["load.i32 %0", "push.i32 2", "gt", "push.str \"big\""]);
+
+    // the expression must still give the type of the parameter
+    for argument in ["(x, 2)+", "x"] {
+        let err = validate(&format!("{}fn void main() {{\n    let int x = 3;\n    log_if({}, \"big\");\n}}", log_if, argument)).unwrap_err();
+        assert!(err.is::<IncorrectDatatype>(), "unexpected error for {}: {}", argument, err);
+    }
+
+    let err = validate("fn void main() {\n    let int x = abs((1, 2)>);\n}").unwrap_err();
+    assert!(err.downcast_ref::<NoIntrinsicOverload>().is_some(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn rounding_intrinsics_return_ints_and_fold_on_literals() {
+    let compile_source = |source:&str| -> Result<Vec<IntermediateInstr>, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let folded = optimisation::cast_folding::fold_constant_casts(ast);
+        Ok(frontend::intermediate_gen::generate_program_intermediate(folded, &symbol_table)?)
+    };
+
+    let err = compile_source("fn void main() {\n    let float x = round(1.5);\n}").unwrap_err();
+    assert!(err.downcast_ref::<IncorrectDatatype>().is_some(), "unexpected error: {}", err);
+
+    let err = compile_source("fn void main() {\n    let int x = floor(3);\n}").unwrap_err();
+    assert!(err.downcast_ref::<NoIntrinsicOverload>().is_some(), "unexpected error: {}", err);
+
+    // calls on literals and constants are evaluated at compile time, halfway values going to the even int
+    let instructions = compile_source("fn void main() {\n    let double half = 2.5d;\n    let int a = round(half);\n    let int b = floor(1.5);\n    let int c = ceil(1.5);\n}").unwrap();
+    let pushed:Vec<String> = instructions.iter().filter_map(|instr| match instr {
+        IntermediateInstr::Push(_, value) => Some(value.to_string()),
+        _ => None
+    }).collect();
+    assert_eq!(pushed, vec!["2.5d", "2", "1", "2"]);
+    assert!(!instructions.iter().any(|instr| matches!(instr, IntermediateInstr::Round | IntermediateInstr::Floor | IntermediateInstr::Ceil)));
+
+    // a function declared with the same name is called instead
+    let instructions = compile_source("fn int round(float x) {\n    return 7;\n}\nfn void main() {\n    let int a = round(1.5);\n}").unwrap();
+    assert!(instructions.iter().any(|instr| matches!(instr, IntermediateInstr::Call(name, ..) if name == "round")));
+}
+
+
+/**
+ * Generates the intermediate code for the given source and returns its labels and jumps in order, with each
+ * label renamed to `L<n>` by the order in which it first appears so the result does not depend on the
+ * global label counter.
+ */
+fn get_control_flow(source:&str) -> Vec<String> {
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+
+    let mut names:Vec<String> = vec![];
+    let mut rename = |label:&str| -> String {
+        let position = names.iter().position(|name| name == label).unwrap_or_else(|| {
+            names.push(label.to_string());
+            names.len() - 1
+        });
+        format!("L{}", position)
+    };
+
+    frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap().iter().filter_map(|instr| match instr {
+        IntermediateInstr::JumpZero(label) => Some(format!("JumpZero {}", rename(label))),
+        IntermediateInstr::Jump(label) => Some(format!("Jump {}", rename(label))),
+        IntermediateInstr::Label(label) => Some(format!("Label {}", rename(label))),
+        _ => None
+    }).collect()
+}
+
+
+#[test]
+fn if_structures_jump_to_their_own_end() {
+    let sequential = "fn void choose(int x) {\n    if (x, 1)== {\n        print << \"a\";\n    }\n    else {\n        print << \"b\";\n    }\n\n    if (x, 2)== {\n        print << \"c\";\n    }\n    else {\n        print << \"d\";\n    }\n}\nfn void main() {}";
+    assert_eq!(get_control_flow(sequential), vec![
+        "JumpZero L0", "Jump L1", "Label L0", "Label L1",
+        "JumpZero L2", "Jump L3", "Label L2", "Label L3"
+    ]);
+
+    let nested_in_else = "fn void choose(int x) {\n    if (x, 1)== {\n        print << \"a\";\n    }\n    else {\n        if (x, 2)== {\n            print << \"b\";\n        }\n        else {\n            print << \"c\";\n        }\n    }\n}\nfn void main() {}";
+    assert_eq!(get_control_flow(nested_in_else), vec![
+        "JumpZero L0", "Jump L1", "Label L0",
+        "JumpZero L2", "Jump L3", "Label L2", "Label L3",
+        "Label L1"
+    ]);
+
+    let nested_in_if = "fn void choose(int x) {\n    if (x, 1)> {\n        if (x, 2)== {\n            print << \"a\";\n        }\n        else {\n            print << \"b\";\n        }\n        print << \"c\";\n    }\n    else {\n        print << \"d\";\n    }\n}\nfn void main() {}";
+    assert_eq!(get_control_flow(nested_in_if), vec![
+        "JumpZero L0",
+        "JumpZero L1", "Jump L2", "Label L1", "Label L2",
+        "Jump L3", "Label L0", "Label L3"
+    ]);
+}
+
+
+#[test]
+fn operators_are_checked_against_operand_types() {
+    assert!(validate_source("fn void main() {\n    let float x = (2.5)-;\n    let double y = (2.5d)-;\n    let long z = ((7l)~, 2l)<<;\n}").is_ok());
+
+    for source in [
+        "fn void main() {\n    let float x = (2.5)~;\n}",
+        "fn void main() {\n    let double x = (2.5d, 1.0d)>>;\n}",
+        "fn void main() {\n    let float x = ((1.5)~, 2.0)+;\n}",
+        "fn void main() {\n    let string x = (\"a\", \"b\")-;\n}",
+        "fn void main() {\n    if ((1.5, 2.0)&, 1.0)> {\n        print << \"never\";\n    }\n}",
+        "fn void main() {\n    let string x = (\"a\", \"b\")*;\n}",
+        "fn void main() {\n    let string x = (\"a\", \"b\")/;\n}",
+        "fn void main() {\n    let bool x = (true, false)&;\n}",
+        "fn void main() {\n    let bool x = (true, false)|;\n}",
+        "fn void main() {\n    let bool x = (true, false)^;\n}",
+        "fn void main() {\n    let bool x = (true)~;\n}",
+        "fn void main() {\n    let bool x = (true)-;\n}",
+        "fn void main() {\n    let bool x = (true, false)+;\n}",
+        "fn void main() {\n    let int x = (3)!;\n}",
+        "fn void main() {\n    let string x = (\"a\")!;\n}",
+        "fn void main() {\n    let string x = (\"a\", \"b\")<<;\n}"
+    ] {
+        let err = validate_source(source).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedOperatorType>().is_some(), "unexpected error for {}: {}", source, err);
+    }
+
+    // the error gives the line of the statement the operator is in, including statements nested in an if
+    let err = validate_source("fn void main() {\n    if true {\n        let mut bool x = true;\n        x = (x, false)&;\n    }\n}").unwrap_err();
+    assert_eq!(err.to_string(), "The & operator cannot be applied to values of type bool on line 4.");
+}
+
+
+#[test]
+fn parameters_are_indexed_within_their_function() {
+    let (instructions, _) = compile(Path::new("tests/programs/parameters.iri"), false).unwrap();
+    let indexes:Vec<usize> = instructions.iter().filter_map(|instr| match instr {
+        IntermediateInstr::LoadParam(_, index) => Some(*index),
+        _ => None
+    }).collect();
+
+    assert_eq!(indexes, vec![0, 1, 2, 0, 1, 0, 1]);
+}
+
+
+#[test]
+fn calls_carry_their_return_type_and_argument_count() {
+    let (instructions, _) = compile(Path::new("tests/programs/parameters.iri"), false).unwrap();
+    let calls:Vec<(String, Type, usize)> = instructions.into_iter().filter_map(|instr| match instr {
+        IntermediateInstr::Call(name, return_type, argument_count) => Some((name, return_type, argument_count)),
+        _ => None
+    }).collect();
+
+    assert_eq!(calls, vec![
+        ("add_three".to_owned(), Type::Integer, 3),
+        ("subtract".to_owned(), Type::Integer, 2),
+        ("combine".to_owned(), Type::Integer, 2)
+    ]);
+}
+
+
+#[test]
+fn directories_are_built_into_one_program() {
+    let directory = "tests/projects/split_program";
+    let (ast, files) = project::parse_directory(directory, DEFAULT_NESTING_LIMIT).unwrap();
+    assert_eq!(files.get_file_count(), 3);
+    assert_eq!(files.get_path(0), "tests/projects/split_program/main.iri");
+
+    // scopes are numbered across the files rather than starting again in each one
+    let scopes:Vec<usize> = ast.iter().filter_map(|node| match node {
+        frontend::ast::ASTNode::Function {scope, ..} => Some(*scope),
+        _ => None
+    }).collect();
+    assert_eq!(scopes, vec![1, 2, 3, 4]);
+
+    let symbol_table = project::generate_symbol_table(&ast, &files, directory).unwrap();
+    let ast = project::semantic_validation(&ast, &symbol_table, &files, &Trace::disabled()).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "total = 25\n");
+}
+
+
+#[test]
+fn constants_shared_by_files_are_stored_once() {
+    let directory = "tests/projects/shared_constants";
+    let (ast, files) = project::parse_directory(directory, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = project::generate_symbol_table(&ast, &files, directory).unwrap();
+    let ast = project::semantic_validation(&ast, &symbol_table, &files, &Trace::disabled()).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "3 63\n");
+
+    // labels are made from the bits of the constant, so both files use the same one
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert_eq!(assembly.matches(".float 3.14").count(), 1);
+    assert!(assembly.contains("\n\t__float_4048f5c3: .float 3.14"));
+    assert_eq!(assembly.matches("lwc1 $f4, __float_4048f5c3").count(), 2);
+}
+
+
+#[test]
+fn input_has_its_line_ending_stripped() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/echo_compare.iri"), false).unwrap();
+    let run = |input:&str| run_interpreter(&instructions, &symbol_table, input).unwrap().output;
+
+    assert_eq!(run("quit\n"), "you typed quit\ngoodbye\n");
+    assert_eq!(run("quit\r\n"), "you typed quit\ngoodbye\n");
+    assert_eq!(run("sam\n"), "you typed sam\nhello\n");
+    assert_eq!(run("quitting\n"), "you typed quitting\nhello\n");
+}
+
+
+#[test]
+fn chars_are_read_one_at_a_time() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/char_menu.iri"), false).unwrap();
+    assert!(instructions.iter().any(|instr| instr.to_string() == "    in.char"));
+    let run = |input:&str| run_interpreter(&instructions, &symbol_table, input).unwrap().output;
+
+    // line endings are read like any other char
+    assert_eq!(run("+q"), "total 1\nbye\n");
+    assert_eq!(run("+\nq"), "total 1\nunknown option \n\nbye\n");
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("li $v0, 12 # get char input"));
+
+    let run_source = |source:&str, input:&str| -> Result<String, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        Ok(run_interpreter(&instructions, &symbol_table, input)?.output)
+    };
+
+    // running out of input gives '\0' rather than waiting for more
+    let source = "fn void main() {\n    let char first = input char;\n    let char second = input char;\n    print << first << second;\n}";
+    assert_eq!(run_source(source, "ab").unwrap(), "ab");
+    assert_eq!(run_source(source, "a").unwrap(), "a\0");
+    assert_eq!(run_source(source, "").unwrap(), "\0\0");
+
+    // the value read is a char, so it cannot be given to a variable of any other type
+    let err = run_source("fn void main() {\n    let string s = input char;\n}", "").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>(), "unexpected error: {}", err);
+    let err = run_source("fn void main() {\n    let char c = input 4;\n}", "").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn printed_string_literals_skip_the_stack() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/string_concat.iri"), false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+
+    // the literals assigned to variables are still pushed, while only the joined string is printed from the stack
+    assert_eq!(assembly.matches("# push string ptr").count(), 2);
+    assert_eq!(assembly.matches("# print string literal").count(), 1);
+    assert_eq!(assembly.matches("# print string\n").count(), 1);
+}
+
+
+#[test]
+fn mips_instructions_are_rendered_consistently() {
+    assert_eq!(MipsInstr::op("add", &["$t0", "$t2", "$t0"]).render(), "\tadd $t0, $t2, $t0");
+    assert_eq!(MipsInstr::op("li", &["$t4", "5"]).comment("push int").render(), "\tli $t4, 5 # push int");
+    assert_eq!(MipsInstr::op("syscall", &[]).render(), "\tsyscall");
+    assert_eq!(MipsInstr::Label(String::from("while_0")).render(), "while_0:");
+    assert_eq!(MipsInstr::Label(String::from("while_0")).comment("ignored").render(), "while_0:");
+    assert_eq!(MipsInstr::Blank.render(), "");
+
+    // the instructions built in Rust must match those still written as templates
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/arithmetic.iri"), false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("\n\tli $t4, 10 # push int\n\tsw $t4, 0($sp)\n\tsubi $sp, $sp, 4\n"));
+    assert!(assembly.contains("\taddi $sp, $sp, 4 # multiply int\n\tlw $t0, 0($sp)\n"));
+    assert!(assembly.ends_with("\n\n__end:\n\tli $v0, 10 # halt syscall\n\tsyscall"));
+}
+
+
+#[test]
+fn timings_cover_every_phase() {
+    let options = crate::CompileOptions {create_dirs: true, prune_branches: true, remove_unreachable_traps: true, remove_unreachable_functions: true, fold_pure_calls: true, propagate_constants: true, jump_tables: true, jump_table_density: optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY, checked: true, overflow: backend::OverflowMode::Trap, distinct_input_buffers: false, no_packing: false, frame_checks: false, debug_info: false, debug: false, time: true, verbose: false, trace_semantics: false, report_sizes: false, size_budget: None, size_budget_hard: false, report_stack: false, stack_size: None, max_nesting: DEFAULT_NESTING_LIMIT, max_steps: None, max_call_depth: None, max_heap: None, emit_callgraph: None, emit_ir: None, emit_ir_debug: None, message_format: MessageFormat::Human};
+    let output = env::temp_dir().join(format!("iridescent_{}_timings", std::process::id()));
+    let output = output.to_str().unwrap();
+
+    let mut timings = PhaseTimings::new();
+    crate::compile_file("tests/programs/elif_chain.iri", output, "-mips", &options, &mut timings).unwrap();
+    fs::remove_file(format!("{}.asm", output)).unwrap();
+
+    let table = timings.to_string();
+    for phase in ["parsing", "symbol table generation", "semantic validation", "AST optimisation", "cast folding",
+                  "branch pruning", "unreachable function removal", "intermediate generation", "optimisation", "dead store elimination",
+                  "redundant jump removal", "pure call folding", "constant propagation", "jump table building", "unreachable trap removal", "backend", "total"] {
+        assert!(table.contains(phase), "Timings are missing {}:\n{}", phase, table);
+    }
+
+    // the phases up to and including the one which failed are still timed
+    let malformed = &get_test_programs(MALFORMED_DIR)[0];
+    let mut timings = PhaseTimings::new();
+    assert!(crate::compile_file(malformed.to_str().unwrap(), output, "-mips", &options, &mut timings).is_err());
+    assert!(timings.to_string().contains("parsing"));
+    assert!(!timings.to_string().contains("symbol table generation"));
+}
+
+
+#[test]
+fn parse_errors_show_the_offending_source() {
+    let error = frontend::parser::parse_program("fn void main() {\n    let byte b = 300b;\n}", DEFAULT_NESTING_LIMIT).unwrap_err();
+    let diagnostic = Diagnostic::from_error(error.as_ref(), "test.iri");
+    assert_eq!((diagnostic.line, diagnostic.column, diagnostic.end), (Some(2), Some(18), Some((2, 22))));
+
+    let rendered = MessageFormat::Human.render(&diagnostic);
+    let lines:Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines, vec![
+        "Error: Could not parse value on line 2: 300b is out of range for type byte.",
+        " 2 |     let byte b = 300b;",
+        "   |                  ^^^^"
+    ]);
+
+    // JSON output stays on one line
+    assert_eq!(MessageFormat::Json.render(&diagnostic).lines().count(), 1);
+}
+
+
+#[test]
+fn diagnostics_are_emitted_as_json() {
+    let path = "tests/malformed/if_missing_condition.iri";
+    let (_, errors) = frontend::parser::parse(path, DEFAULT_NESTING_LIMIT).unwrap();
+    let diagnostic = Diagnostic::from_error(errors[0].as_ref(), path);
+    let json:serde_json::Value = serde_json::from_str(&MessageFormat::Json.render(&diagnostic)).unwrap();
+    assert_eq!(json["severity"], "error");
+    assert_eq!(json["code"], "syntax-error");
+    assert_eq!(json["file"], path);
+    assert_eq!(json["line"], 2);
+    assert_eq!(json["column"], 8);
+    assert!(json["message"].as_str().unwrap().contains("expected"));
+    assert!(json.get("end").is_none());
+    assert_eq!(MessageFormat::Human.render(&diagnostic), format!("Error: {} on line 2, column 8.", diagnostic.message));
+
+    let source = "fn void main() {\n    let int x = 3;\n    let int y = z;\n}";
+    let error = validate_source(source).unwrap_err();
+    let json:serde_json::Value = serde_json::from_str(&Diagnostic::from_error(error.as_ref(), "unknown.iri").to_json()).unwrap();
+    assert_eq!(json["code"], "symbol-not-found");
+    assert_eq!(json["message"], "Could not find symbol z in this scope.");
+    assert!(json["line"].is_null());
+
+    let warning = Warning::ConditionAlwaysTrue {construct: "if statement", line: 4};
+    let diagnostic = Diagnostic::from_warning(&warning, "warned.iri");
+    let json:serde_json::Value = serde_json::from_str(&diagnostic.to_json()).unwrap();
+    assert_eq!(json["severity"], "warning");
+    assert_eq!(json["code"], "condition-always-true");
+    assert_eq!(json["line"], 4);
+    assert_eq!(json["message"], warning.to_string());
+    assert_eq!(MessageFormat::Human.render(&diagnostic), format!("Warning: {}", warning));
+}
+
+
+#[test]
+fn chained_comparisons_are_rejected() {
+    let validate = |condition:&str| validate_source(&format!("fn void check(int a, int b, int c, int d, bool flag) {{\n    if {} {{\n        print << \"yes\";\n    }}\n}}\n\nfn void main() {{}}", condition));
+
+    for condition in ["((a, b)<, (b, c)<)&&", "((a, b)<, (c, d)<)==", "((a, b)<, flag)!=", "((a, b)>=)!", "(flag, true)=="] {
+        assert!(validate(condition).is_ok(), "{} was rejected", condition);
+    }
+
+    for condition in ["((a, b)<, c)<", "(a, (b, c)<)>=", "(((a, b)<)!, c)<=", "((a, b)<, c)==", "(d, (a, b)==)!="] {
+        let err = validate(condition).unwrap_err();
+        assert!(err.downcast_ref::<ChainedComparison>().is_some(), "unexpected error for {}: {}", condition, err);
+    }
+}
+
+
+#[test]
+fn casts_can_be_compared() {
+    let (instructions, _) = compile(Path::new("tests/programs/cast_comparisons.iri"), false).unwrap();
+    let cast = instructions.iter().position(|instr| matches!(instr, IntermediateInstr::Cast(Type::Integer, Type::Long))).unwrap();
+    let comparison:Vec<String> = instructions[cast - 1..cast + 3].iter().map(|instr| format!("{:?}", instr)).collect();
+
+    // the operand is loaded and cast before the other operand is loaded and the two are compared
+    assert_eq!(comparison, vec!["Load(Integer, 0)", "Cast(Integer, Long)", "Load(Long, 1)", "LessThan"]);
+
+    let source = "fn void main() {\n    let long limit = 10l;\n    if (long(missing), limit)< {\n        print << \"less\";\n    }\n}";
+    let err = validate_source(source).unwrap_err();
+    assert!(err.to_string().contains("missing"), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn boolean_expressions_can_be_stored() {
+    assert!(validate_source("fn void main() {\n    let int x = 3;\n    let bool big = (x, 2)>;\n}").is_ok());
+    assert!(validate_source("fn void main() {\n    let int x = 3;\n    let int big = (x, 2)>;\n}").unwrap_err().is::<IncorrectDatatype>());
+    assert!(validate_source("fn void main() {\n    let mut int x = 3;\n    x = ((x, 2)>, (x, 5)<)&&;\n}").unwrap_err().is::<IncorrectDatatype>());
+
+    // the comparison is made before its result is stored
+    let (instructions, _) = compile(Path::new("tests/programs/boolean_variables.iri"), false).unwrap();
+    let store = instructions.iter().position(|instr| matches!(instr, IntermediateInstr::Store(Type::Boolean, _))).unwrap();
+    assert!(matches!(instructions[store - 1], IntermediateInstr::LogicAnd));
+    assert!(matches!(instructions[store - 2], IntermediateInstr::LessThan));
+}
+
+
+#[test]
+fn float_operands_survive_function_calls() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/float_call_operands.iri"), false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert_eq!(find_float_register_read_before_write(&assembly), None);
+
+    // an operand left in a register while a function is called would be read without being reloaded
+    let clobbered = "\tlwc1 $f2, -4($fp)\n\n\tjal fn_get_factor\n\n\tlwc1 $f0, 0($sp)\n\tmul.s $f0, $f2, $f0";
+    assert_eq!(find_float_register_read_before_write(clobbered), Some("mul.s $f0, $f2, $f0".to_owned()));
+    assert_eq!(find_float_register_read_before_write("\tl.d $f4, __double_0\n\tswc1 $f5, -4($sp)"), None);
+    assert_eq!(find_float_register_read_before_write("\tcvt.d.w $f2, $f0"), Some("cvt.d.w $f2, $f0".to_owned()));
+}
+
+
+#[test]
+fn sizes_are_reported_per_function() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/functions.iri"), false).unwrap();
+    let mut sizes = SizeReport::new(&instructions, &symbol_table, true);
+    let mut assembly:Vec<u8> = vec![];
+    let counts = backend::mips::generate_mips(instructions, &mut assembly, &symbol_table, &backend::mips::CodegenOptions::default()).unwrap();
+    sizes.add_mips_counts(&counts);
+
+    let report:serde_json::Value = serde_json::from_str(&sizes.to_json()).unwrap();
+    let names:Vec<&str> = report["functions"].as_array().unwrap().iter().map(|function| function["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["add", "triple", "main"]);
+    assert_eq!(report["functions"][0]["frame_size"], 16);
+    assert_eq!(report["total"]["intermediate"], 41);
+
+    // main is generated first, so every instruction from its label to the next function belongs to it
+    let assembly = String::from_utf8(assembly).unwrap();
+    let main_code = &assembly[assembly.find("fn_main:").unwrap()..assembly.find("fn_add:").unwrap()];
+    let main_instrs = MipsInstr::Raw(main_code.to_owned()).count_instructions();
+    assert_eq!(report["functions"][2]["mips"], main_instrs);
+    assert!(sizes.to_string().lines().last().unwrap().starts_with("total"));
+}
+
+
+#[test]
+fn only_printable_values_can_be_printed() {
+    let validate = |term:&str| validate_source(&format!("fn void check(int i, byte b, char c, long l, bool flag, float f) {{\n    print << {};\n}}\n\nfn void main() {{}}", term));
+
+    for term in ["\"text\"", "i", "b", "c", "'x'", "255b", "i:hex", "b:hex", "255:hex", "f:2", "f:0", "1.5:6"] {
+        assert!(validate(term).is_ok(), "{} was rejected", term);
+    }
+
+    for term in ["l", "flag", "f", "c:hex", "l:hex", "\"text\":hex", "i:2", "f:hex"] {
+        let err = validate(term).unwrap_err();
+        assert!(err.downcast_ref::<UnprintableValue>().is_some(), "unexpected error for {}: {}", term, err);
+    }
+
+    assert_eq!(validate("f").unwrap_err().to_string(), "Floats must be printed to a number of decimal places, such as x:2.");
+    assert!(validate("f:7").unwrap_err().to_string().contains("a float can be printed to at most 6 decimal places, not 7"));
+
+    // hex is printed the same way whatever the size of the value
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/print_formats.iri"), false).unwrap();
+    let output = run_interpreter(&instructions, &symbol_table, "").unwrap().output;
+    assert!(output.lines().nth(1).unwrap().starts_with("0xff 0x0"));
+}
+
+
+#[test]
+fn reserved_words_cannot_be_identifiers() {
+    let parse = |source:&str| frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap_err();
+
+    for source in ["fn int int() {\n    return 1;\n}", "fn void main() {\n    let int return = 1;\n}",
+                   "fn void show(string print) {}", "fn void main() {\n    for int step = 0 until 10 {}\n}"] {
+        let err = parse(source);
+        let err = err.downcast_ref::<ReservedIdentifier>().unwrap_or_else(|| panic!("unexpected error for {}: {}", source, err));
+        assert!(err.line.is_some());
+    }
+
+    let err = parse("fn void main() {\n\n    let mut int loop = 1;\n}");
+    assert_eq!(Diagnostic::from_error(err.as_ref(), "test.iri").line, Some(3));
+
+    // identifiers which only start with a reserved word are fine
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int integer = 1;\n    let string printed = \"\";\n}", DEFAULT_NESTING_LIMIT).is_ok());
+}
+
+
+#[test]
+fn for_loop_headers_are_validated() {
+    let validate = |header:&str| validate_source(&format!("fn void nothing() {{}}\n\nfn long big() {{\n    return 1l;\n}}\n\nfn void main() {{\n    let long l = 2l;\n    for int i = {} {{}}\n}}", header));
+
+    assert!(validate("int(l) until (int(l), 3)+ step (l, 1l)> ? 1 : 2").is_ok());
+    assert!(validate("0 until big()").unwrap_err().downcast_ref::<IncorrectDatatype>().is_some());
+    assert!(validate("0 until 3 step nothing()").unwrap_err().to_string().contains("header of a for loop"));
+}
+
+
+#[test]
+fn invariant_for_loop_limits_are_evaluated_once() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/for_loop_limits.iri"), false).unwrap();
+
+    // the first limit is computed once before the loop, while the second is loaded again every iteration
+    // because the loop assigns to it, and the hoisted limit is given space in the frame which the second loop
+    // then reuses
+    let multiplications = instructions.iter().filter(|instr| matches!(instr, IntermediateInstr::Mult)).count();
+    assert_eq!(multiplications, 1);
+    assert_eq!(backend::mips::get_frame_size("main", &symbol_table, true), 16);
+}
+
+
+#[test]
+fn sibling_blocks_share_frame_slots() {
+    let (_, symbol_table) = compile(Path::new("tests/programs/block_scopes.iri"), false).unwrap();
+
+    // the parameter and total take two slots, and the if and else bodies declare four ints each, which can
+    // never be live at the same time so they only need four more slots between them
+    assert_eq!(backend::mips::get_frame_size("branches", &symbol_table, true), 24);
+
+    // the long declared in the loop takes two slots, so it needs more than the while and else bodies
+    assert_eq!(backend::mips::get_frame_size("main", &symbol_table, true), 24);
+}
+
+
+#[test]
+fn functions_which_fall_off_the_end_are_trapped() {
+    // semantic validation would reject pick for not returning, so it is only run on the other functions to reach
+    // the trap at runtime, which is enough as pick has no types to resolve
+    let source = "fn int pick(int x) {\n    if (x, 0)> {\n        return 1;\n    }\n}\n\nfn int one() {\n    return 1;\n}\n\nfn void main() {\n    let int a = one();\n    let int b = pick(a);\n    let int c = pick(0);\n    print << \"unreachable\\n\";\n}";
+    let mut ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let pick = ast.remove(0);
+    let mut ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    ast.insert(0, pick);
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+
+    let message = "error: function pick returned without a value\n";
+    let result = run_interpreter(&instructions, &symbol_table, "").unwrap();
+    assert_eq!(result, Expectation {exit_code: 1, output: message.to_owned()});
+
+    // only the trap which can be reached is kept at -O2
+    let instructions = optimisation::traps::remove_unreachable_traps(instructions);
+    let traps:Vec<String> = instructions.iter().filter(|instr| matches!(instr, IntermediateInstr::Trap(_))).map(|instr| instr.to_string()).collect();
+    assert_eq!(traps, vec!["    trap \"error: function pick returned without a value\\\\n\""]);
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("\taddi $a0, $zero, 1 # exit with code 1\n\taddi $v0, $zero, 17\n\tsyscall"));
+    if let Some(simulator) = find_simulator() {
+        assert_eq!(run_mips(&assembly, "trap", &simulator, "").unwrap(), message);
+    }
+}
+
+
+#[test]
+fn for_loop_control_variables_are_constant_unless_marked_mutable() {
+    let validate = |header:&str| validate_source(&format!("fn void main() {{\n    for {} i = 0 until 5 {{\n        if (i, 2)== {{\n            i = 4;\n        }}\n    }}\n}}", header));
+
+    assert!(validate("int").unwrap_err().downcast_ref::<ImmutableReassignmentError>().is_some());
+    assert!(validate("mut int").is_ok());
+}
+
+
+#[test]
+fn empty_blocks_are_compiled() {
+    let source = "fn void main() {\n    let mut int x = 0;\n    while (x, 3)< {}\n    for int i = 0 until 3 {\n        loop {\n            break;\n        }\n    }\n    if (x, 0)== {}\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    assert_eq!(frontend::semantics::find_empty_loops(&ast), vec![Warning::EmptyLoopBody {construct: "while loop", line: 3}]);
+
+    // the jump past the else branches of the empty if is removed, as it goes to the label straight after it
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let instructions = optimisation::jumps::remove_redundant_jumps(instructions);
+    for (index, instr) in instructions.iter().enumerate() {
+        if let (IntermediateInstr::Jump(target), Some(IntermediateInstr::Label(label))) = (instr, instructions.get(index + 1)) {
+            assert_ne!(target, label);
+        }
+    }
+}
+
+
+#[test]
+fn bytes_are_kept_in_whole_words_on_the_stack() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/byte_reload.iri"), false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions.clone(), &symbol_table).unwrap();
+
+    // values on the stack are always whole words, and bytes in a frame are zero extended when they are loaded
+    for line in assembly.lines().filter(|line| line.contains("($sp)") || line.contains("($fp)")) {
+        let opcode = line.split_whitespace().next().unwrap();
+        assert!(!["lb", "lbu", "sb"].contains(&opcode) || (line.contains("($fp)") && opcode != "lb"), "{} reads or writes a single byte", line.trim());
+    }
+
+    assert!(assembly.contains("\tandi $t0, $t0, 0xFF\n"), "casting an int to a byte does not mask it");
+
+    // without packing, every variable in the frame takes up a whole word
+    let mut assembly:Vec<u8> = vec![];
+    let options = backend::mips::CodegenOptions {no_packing: true, ..Default::default()};
+    backend::mips::generate_mips(instructions, &mut assembly, &symbol_table, &options).unwrap();
+    for line in String::from_utf8(assembly).unwrap().lines().filter(|line| line.contains("($sp)") || line.contains("($fp)")) {
+        let opcode = line.split_whitespace().next().unwrap();
+        assert!(!["lb", "lbu", "sb"].contains(&opcode), "{} reads or writes a single byte", line.trim());
+    }
+}
+
+
+#[test]
+fn doubles_are_aligned() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/double_frame.iri"), false).unwrap();
+
+    // three ints and a double need 20 bytes, which is rounded up to keep the frame a multiple of 8
+    assert_eq!(backend::mips::get_frame_size("main", &symbol_table, true), 24);
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("\n\t.align 3\n\t__double_400e000000000000: .double 3.75"));
+
+    // doubles on the evaluation stack are moved a word at a time, as it is only aligned to 4 bytes
+    for line in assembly.lines().filter(|line| line.contains("($sp)")) {
+        let opcode = line.split_whitespace().next().unwrap();
+        assert!(!["l.d", "s.d", "ldc1", "sdc1"].contains(&opcode), "{} needs the stack to be aligned to 8 bytes", line.trim());
+    }
+}
+
+
+#[test]
+fn bytes_chars_and_bools_are_packed_into_the_frame() {
+    let bools:String = (0..8).map(|index| format!("    let bool b{} = true;\n", index)).collect();
+    let source = format!("fn void flags() {{\n{}}}\n\nfn void main() {{\n    let int n = 1;\n    let char c = 'a';\n    let long l = 2l;\n    let byte b = 3b;\n    print << c;\n    print << b;\n    flags();\n}}", bools);
+    let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+
+    // eight bools take a byte each rather than a word each
+    assert_eq!(backend::mips::get_frame_size("flags", &symbol_table, true), 8);
+    assert_eq!(backend::mips::get_frame_size("flags", &symbol_table, false), 32);
+
+    // the int and the long keep their words, with the char and byte packed after them and the frame padded to 8
+    assert_eq!(backend::mips::get_frame_size("main", &symbol_table, true), 16);
+    assert_eq!(backend::mips::get_frame_size("main", &symbol_table, false), 24);
+
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    for expected in ["\tsw $t0, -4($fp)", "\tsw $t1, -8($fp)", "\tsb $t0, -13($fp)", "\tsb $t0, -14($fp)", "\tlbu $t0, -13($fp)", "\tlbu $t0, -14($fp)"] {
+        assert!(assembly.contains(&format!("{}\n", expected)) || assembly.contains(&format!("{} #", expected)), "missing {} in\n{}", expected, assembly);
+    }
+}
+
+
+#[test]
+fn debug_info_gives_the_offset_of_each_variable() {
+    let source = "fn int add(int a, int b) {\n    let int total = (a, b)+;\n    return total;\n}\n\nfn void main() {\n    let bool flag = true;\n    if flag {\n        let long big = 2l;\n    } else {\n        let int big = 3;\n    }\n    repeat 2 {\n        let int y = add(1, 2);\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let (instructions, variables) = frontend::intermediate_gen::generate_program_intermediate_with_variables(ast, &symbol_table).unwrap();
+
+    // the hidden control variable of the repeat loop is left out, as it has no name in the source
+    let names:Vec<Vec<&str>> = variables.iter().map(|function| function.variables.iter().map(|variable| variable.identifier.as_str()).collect()).collect();
+    assert_eq!(names, vec![vec!["a", "b", "total"], vec!["flag", "big", "big", "y"]]);
+    assert_eq!(variables[1].lines, Some((7, 14)));
+
+    let mut assembly:Vec<u8> = vec![];
+    let options = backend::mips::CodegenOptions {debug_info: Some(&variables), ..Default::default()};
+    let counts = backend::mips::generate_mips(instructions.clone(), &mut assembly, &symbol_table, &options).unwrap();
+    let assembly = String::from_utf8(assembly).unwrap();
+    assert!(assembly.contains("# frame of add, from line 2 to line 2\n#   a (int) at -4($fp), parameter\n#   b (int) at -8($fp), parameter\n"), "{}", assembly);
+    assert!(assembly.contains("\n.eqv add_a_offset, -4\n.eqv add_b_offset, -8\n.eqv add_total_offset, -12\nfn_add:"), "{}", assembly);
+
+    // a name used twice gets a second constant, and packed variables are given the offset of their byte
+    assert!(assembly.contains("#   flag (bool) at -13($fp), declared on line 7\n"), "{}", assembly);
+    assert!(assembly.contains("\n.eqv main_big_offset, -8\n.eqv main_big_2_offset, -4\n"), "{}", assembly);
+
+    // the information is only comments and directives, so the code itself is the same as without it
+    let mut plain:Vec<u8> = vec![];
+    let plain_counts = backend::mips::generate_mips(instructions, &mut plain, &symbol_table, &backend::mips::CodegenOptions::default()).unwrap();
+    assert!(!String::from_utf8(plain).unwrap().contains(".eqv"));
+    assert_eq!(counts, plain_counts);
+}
+
+
+#[test]
+fn types_are_displayed_by_their_target_code_keys() {
+    let source = fs::read_to_string("src/backend/target_code.json").unwrap();
+    let json:serde_json::Value = serde_json::from_str(&source).unwrap();
+    let instructions = json["mips"].as_object().unwrap();
+
+    // every entry which has a version for each type must be keyed by the names types are displayed by
+    for (instr, code) in instructions {
+        if let Some(versions) = code.as_object() {
+            for key in versions.keys() {
+                let key_type = frontend::ast::get_type_from_string(key);
+                assert!(key_type.is_some(), "{} has a version for {}, which is not a type", instr, key);
+                assert_eq!(&key_type.unwrap().to_string(), key);
+            }
+        }
+    }
+
+    let types = [Type::Byte, Type::Integer, Type::Long, Type::Char, Type::Boolean, Type::Float, Type::Double, Type::String];
+    for instr in ["pop", "return", "load_param", "call", "test_equal"] {
+        for value_type in &types {
+            assert!(!json["mips"][instr][value_type.to_string()].is_null(), "{} has no version for {}", instr, value_type);
+        }
+    }
+
+    assert!(!json["mips"]["call"][Type::Void.to_string()].is_null());
+}
+
+
+#[test]
+fn expression_statements_discard_their_values() {
+    let compile_source = |source:&str| -> Result<(Vec<Warning>, Vec<IntermediateInstr>), Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let warnings = frontend::semantics::find_unused_expressions(&ast);
+        Ok((warnings, frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table)?))
+    };
+
+    let tick = "fn int tick(int n) {\n    return n;\n}\n";
+    let (warnings, instructions) = compile_source(&format!("{}fn void main() {{\n    tick(1);\n    (tick(2), 3)+;\n}}", tick)).unwrap();
+    assert!(warnings.is_empty());
+
+    // each statement leaves nothing on the stack, so both end with a pop of the int they gave
+    let pops:Vec<String> = instructions.iter()
+                                       .filter(|instr| matches!(instr, IntermediateInstr::Pop(_)))
+                                       .map(|instr| instr.to_string().trim().to_owned())
+                                       .collect();
+    assert_eq!(pops, vec!["pop.i32", "pop.i32"]);
+
+    let (warnings, _) = compile_source("fn void main() {\n    let int x = 2;\n    (x, 1)+;\n    if true {\n        5;\n    }\n}").unwrap();
+    assert_eq!(warnings, vec![Warning::UnusedExpression {line: 3}, Warning::UnusedExpression {line: 5}]);
+
+    let err = compile_source("fn void main() {\n    (1, 2.5)+;\n}").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>());
+}
+
+
+#[test]
+fn every_supported_operation_has_target_code() {
+    let programs = doctor::get_coverage_programs();
+
+    // every combination the frontend accepts must have code, so a gap is found here rather than by a user
+    let mut gaps = vec![];
+    for source in &programs {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap_or_else(|err| panic!("{}\n{}", err, source));
+
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        if let Err(err) = backend::mips::generate_mips_to_string(instructions, &symbol_table) {
+            match err.downcast_ref::<MissingTargetTemplate>() {
+                Some(missing) => gaps.push(missing.to_string()),
+                None => panic!("{}\n{}", err, source)
+            }
+        }
+    }
+
+    gaps.dedup();
+    assert!(gaps.is_empty(), "{}", gaps.join("\n"));
+
+    // casts without code are rejected before code generation
+    let err = validate_source("fn void main() {\n    let long big = 5l;\n    let string s = string(big);\n}").unwrap_err();
+    assert_eq!(err.to_string(), "The cast on line 3 cannot convert values of type long to string.");
+}
+
+
+#[test]
+fn doctor_finds_no_problems_with_the_shipped_target_code() {
+    let report = doctor::run_doctor();
+    assert!(report.problems.is_empty(), "{}", report.problems.join("\n"));
+
+    let emitted = doctor::find_emitted_templates().unwrap();
+    for key in [["mips", "add", "float"], ["mips", "table_switch", "-"], ["mips", "integer_overflow", "-"], ["mips", "check_index", "-"], ["mips", "end_func_checked", "-"]] {
+        let key:Vec<String> = key.iter().filter(|name| **name != "-").map(|name| name.to_string()).collect();
+        assert!(emitted.contains(&key), "{:?} was not emitted", key);
+    }
+
+    assert_eq!(report.features.first().unwrap(), &format!("mips: {0} of the {0} templates the backend can emit", emitted.len()));
+    assert!(report.features.contains(&String::from("    out_precision          float")), "{}", report.features.join("\n"));
+}
+
+
+#[test]
+fn doctor_reports_broken_target_code() {
+    let mut table:serde_json::Value = serde_json::from_str(&fs::read_to_string(backend::mips::TARGET_CODE_PATH).unwrap()).unwrap();
+    let emitted = doctor::find_emitted_templates().unwrap();
+
+    table["mips"]["add"].as_object_mut().unwrap().remove("float");
+    table["mips"]["push"]["byte"] = serde_json::Value::Null;
+    table["mips"]["cast"]["int"]["long"] = serde_json::json!(["\tnop", 5]);
+    table["mips"].as_object_mut().unwrap().remove("str_len");
+
+    assert_eq!(doctor::check_target_code(&table, &emitted), vec![
+        "The mips code for cast int long is [\"\\tnop\",5], not a line of code or a list of lines",
+        "The mips code for push byte is null, not a line of code or a list of lines",
+        "target_code.json has no mips code for add float, which the backend can emit",
+        "target_code.json has no mips code for push byte, which the backend can emit",
+        "target_code.json has no mips code for str_len, which the backend can emit"
+    ]);
+
+    let matrix = doctor::get_feature_matrix(&table, &emitted);
+    assert_eq!(matrix[0], format!("mips: {} of the {} templates the backend can emit", emitted.len() - 3, emitted.len()));
+
+    assert_eq!(doctor::check_target_code(&serde_json::json!(["mips"]), &BTreeSet::new()),
+               vec!["target_code.json does not hold an object with an entry for each architecture"]);
+}
+
+
+#[test]
+fn doctor_reports_broken_runtime_libraries() {
+    let table = serde_json::json!({
+        "mips": {
+            "length": {"string": ["\tjal __length # find the length", "\tj __end"]},
+            "missing": "\tjal __missing",
+            "epilogue": "\tj __fn_{}_epilogue"
+        },
+        "ird": {
+            "length": "jal __elsewhere"
+        }
+    });
+
+    let lines = |code:&str| code.lines().map(String::from).collect::<Vec<String>>();
+    let libraries = [
+        ("length_mips", lines("# finds the length of a string\n__length:\n    li $v0, 0 # none\n\n__length_loop:\n    jr $ra")),
+        ("broken_mips", lines("__length:\n    add $t0,, $t1\n    not an instruction!\nindented label:\n    .word 0"))
+    ];
+
+    assert_eq!(doctor::check_libraries(&table, &libraries), vec![
+        "broken_mips.asm defines __length on line 1, which is already defined by length_mips",
+        "Line 2 of broken_mips.asm is not a label or an instruction: add $t0,, $t1",
+        "Line 3 of broken_mips.asm is not a label or an instruction: not an instruction!",
+        "Line 4 of broken_mips.asm is not a label or an instruction: indented label:",
+        "The mips code for missing refers to __missing, which no runtime library defines"
+    ]);
+
+    assert_eq!(doctor::get_feature_matrix(&table, &BTreeSet::new()), vec![
+        "ird: 0 of the 0 templates the backend can emit",
+        "    length  -",
+        "mips: 0 of the 0 templates the backend can emit",
+        "    epilogue  -",
+        "    length    string",
+        "    missing   -"
+    ]);
+}
+
+
+#[test]
+fn locals_are_addressed_from_the_frame_pointer() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/deep_expression_locals.iri"), false).unwrap();
+    let frame_size = backend::mips::get_frame_size("main", &symbol_table, true) as i64;
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+
+    let get_offset = |line:&str, register:&str| -> Option<i64> {
+        let operand = line.split_whitespace().find(|operand| operand.ends_with(register))?;
+        operand.trim_end_matches(register).parse().ok()
+    };
+
+    // the evaluation stack is only ever used at its top, however many values have been pushed onto it
+    for line in assembly.lines() {
+        if let Some(offset) = get_offset(line, "($sp)") {
+            assert!([-4, 0, 4].contains(&offset), "{} is not at the top of the stack", line.trim());
+        }
+
+        if let Some(offset) = get_offset(line, "($fp)").filter(|offset| *offset < 0) {
+            assert!(-offset <= frame_size, "{} is outside the frame of main", line.trim());
+        }
+    }
+}
+
+
+#[test]
+fn deeply_nested_expressions_are_rejected_before_parsing() {
+    let get_source = |depth:usize| format!("fn void main() {{\n    let mut int x = {}1{};\n    print << x;\n}}", "(".repeat(depth), ", 1)*".repeat(depth));
+
+    let err = frontend::parser::parse_program(&get_source(10_000), DEFAULT_NESTING_LIMIT).unwrap_err();
+    let nesting = err.downcast_ref::<NestingTooDeep>().unwrap_or_else(|| panic!("unexpected error: {}", err));
+    assert_eq!((nesting.line, nesting.column, nesting.limit), (2, 20 + DEFAULT_NESTING_LIMIT, DEFAULT_NESTING_LIMIT));
+
+    let diagnostic = Diagnostic::from_error(err.as_ref(), "deep.iri");
+    assert_eq!((diagnostic.code, diagnostic.line, diagnostic.column), ("nesting-too-deep", Some(2), Some(20 + DEFAULT_NESTING_LIMIT)));
+
+    // brackets in literals and comments are not counted, and the limit can be changed
+    let quoted = format!("fn void main() {{\n    /* {} */\n    print << \"{}\" << '(';\n}}", "(".repeat(1000), "{".repeat(1000));
+    assert!(frontend::parser::parse_program(&quoted, DEFAULT_NESTING_LIMIT).is_ok());
+    assert!(frontend::parser::parse_program(&get_source(10), 10).unwrap_err().is::<NestingTooDeep>());
+    assert!(frontend::parser::parse_program(&get_source(10), 11).is_ok());
+
+    // a program nested as deep as the limit allows gets through every phase, with main's braces as one level,
+    // given the same stack as the main thread of the compiler has rather than the smaller one tests run on
+    let path = env::temp_dir().join(format!("iridescent_nesting_{}.iri", std::process::id()));
+    fs::write(&path, get_source(DEFAULT_NESTING_LIMIT - 1)).unwrap();
+    let compiler = std::thread::Builder::new().stack_size(8 * 1024 * 1024).spawn(move || -> Result<_, String> {
+        let (instructions, symbol_table) = compile(&path, true).map_err(|err| err.to_string())?;
+        fs::remove_file(&path).map_err(|err| err.to_string())?;
+        let result = run_interpreter(&instructions, &symbol_table, "").map_err(|err| err.to_string())?;
+        backend::mips::generate_mips_to_string(instructions, &symbol_table).map_err(|err| err.to_string())?;
+        Ok(result.output)
+    }).unwrap();
+
+    assert_eq!(compiler.join().unwrap(), Ok("1".to_owned()));
+}
+
+
+#[test]
+fn for_each_loops_iterate_over_the_chars_of_strings() {
+    let run_source = |source:&str| -> Result<String, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let ast = optimisation::branch_pruning::prune_constant_branches(ast);
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        Ok(run_interpreter(&instructions, &symbol_table, "")?.output)
+    };
+
+    // the string is read once, and the element shadows a constant of the same name without being folded
+    let source = "fn void main() {\n    let char c = 'b';\n    let mut string s = \"abc\";\n    for char c in s {\n        s = \"x\";\n        if (c, 'b')== {\n            continue;\n        }\n        print << c;\n    }\n    print << s;\n}";
+    assert_eq!(run_source(source).unwrap(), "acx");
+
+    let err = run_source("fn void main() {\n    let int n = 4;\n    for char c in n {\n        print << c;\n    }\n}").unwrap_err();
+    assert!(err.downcast_ref::<NotIterable>().is_some(), "unexpected error: {}", err);
+
+    let err = run_source("fn void main() {\n    let string s = \"abc\";\n    for int c in s {\n        print << c;\n    }\n}").unwrap_err();
+    assert!(err.downcast_ref::<MismatchedElementType>().is_some(), "unexpected error: {}", err);
+
+    let err = run_source("fn void main() {\n    let string s = \"abc\";\n    for char c in s {\n        c = 'x';\n    }\n}").unwrap_err();
+    assert!(err.downcast_ref::<ImmutableReassignmentError>().is_some(), "unexpected error: {}", err);
+}
+
+
+/**
+ * Returns the intermediate code of a `main` which prints the char at each of the given indexes of "abc". No
+ * program can index a string past its end yet, so the code is built by hand.
+ */
+fn get_string_index_code(indexes:&[i32]) -> Vec<IntermediateInstr> {
+    let mut instructions = vec![IntermediateInstr::FuncStart("main".to_owned())];
+    for index in indexes {
+        instructions.extend([
+            IntermediateInstr::Push(Type::String, Argument::String("abc".to_owned())),
+            IntermediateInstr::Push(Type::Integer, Argument::Integer(*index)),
+            IntermediateInstr::StrIndex,
+            IntermediateInstr::Out(Type::Char, PrintFormat::Plain)
+        ]);
+    }
+
+    instructions.push(IntermediateInstr::FuncEnd("main".to_owned()));
+    instructions
+}
+
+
+#[test]
+fn checked_string_indexes_stop_the_program_when_out_of_range() {
+    let symbol_table = frontend::semantics::generate_symbol_table(frontend::parser::parse_program("fn void main() {}", DEFAULT_NESTING_LIMIT).unwrap()).unwrap();
+    let run_checked = |indexes:&[i32]| {
+        let instructions = frontend::intermediate_gen::add_bounds_checks(get_string_index_code(indexes));
+        run_interpreter(&instructions, &symbol_table, "").unwrap()
+    };
+
+    assert_eq!(run_checked(&[0, 2]), Expectation {exit_code: 0, output: "ac".to_owned()});
+    assert_eq!(run_checked(&[1, 3, 0]), Expectation {exit_code: 1, output: "bIndex 3 is out of range for a string of length 3 in function main\n".to_owned()});
+    assert_eq!(run_checked(&[-1]), Expectation {exit_code: 1, output: "Index -1 is out of range for a string of length 3 in function main\n".to_owned()});
+
+    // without the flag nothing is checked, so the interpreter only finds the problem as it indexes the string
+    let unchecked = get_string_index_code(&[0, 3]);
+    assert!(!unchecked.iter().any(|instr| matches!(instr, IntermediateInstr::CheckIndex(_))));
+    assert!(run_interpreter(&unchecked, &symbol_table, "").is_err());
+
+    let unchecked_assembly = backend::mips::generate_mips_to_string(unchecked, &symbol_table).unwrap();
+    assert!(!unchecked_assembly.contains("__index_out_of_range"));
+
+    // every check jumps to the same handler, whose message is stored once
+    let checked = frontend::intermediate_gen::add_bounds_checks(get_string_index_code(&[1, 3, 0]));
+    let assembly = backend::mips::generate_mips_to_string(checked, &symbol_table).unwrap();
+    assert_eq!(assembly.matches("__index_out_of_range:").count(), 1);
+    assert_eq!(assembly.matches("beq $t0, $zero, __index_out_of_range").count(), 3);
+    assert_eq!(assembly.matches(" in function ").count(), 1);
+
+    if let Some(simulator) = find_simulator() {
+        let output = run_mips(&assembly, "checked_index", &simulator, "").unwrap();
+        assert!(output.ends_with("bIndex 3 is out of range for a string of length 3 in function main\n"), "unexpected output: {:?}", output);
+    }
+}
+
+
+#[test]
+fn heap_library_is_only_added_to_programs_which_allocate() {
+    let get_assembly = |path:&str| {
+        let (instructions, symbol_table) = compile(Path::new(path), false).unwrap();
+        backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap()
+    };
+
+    assert!(!get_assembly("tests/programs/arithmetic.iri").contains("__alloc"));
+    assert_eq!(get_assembly("tests/programs/heap_blocks.iri").matches("\n__alloc:").count(), 1);
+
+    let err = validate_source("fn void main() {\n    alloc(8);\n}").unwrap_err();
+    assert!(err.is::<UnusedIntrinsicResult>());
+}
+
+
+#[test]
+fn functions_main_never_calls_are_reported_and_removed_at_o2() {
+    let source = "fn int count(int n) {\n    if (n, 0)> {\n        let int rest = (n, 1)-;\n        let int counted = count(rest);\n        return (counted, 1)+;\n    }\n\n    return 0;\n}\nfn int twice(int n) {\n    return (n, 2)*;\n}\nfn void main() {\n    let int x = twice(count(3));\n    print << x;\n}\nfn int ping(int n) {\n    return pong(n);\n}\nfn int pong(int n) {\n    if (n, 0)> {\n        let int rest = (n, 1)-;\n        return ping(rest);\n    }\n\n    return abs(n);\n}\nfn int unused() {\n    return pong(1);\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let call_graph = frontend::call_graph::CallGraph::new(&ast);
+
+    // calls in any position are found, while intrinsics are left out
+    assert_eq!(call_graph.to_dot(), "digraph calls {\n    \"count\" [color=red];\n    \"twice\";\n    \"main\";\n    \"ping\" [color=red, style=dashed];\n    \"pong\" [color=red, style=dashed];\n    \"unused\" [style=dashed];\n    \"count\" -> \"count\" [color=red];\n    \"main\" -> \"twice\";\n    \"main\" -> \"count\";\n    \"ping\" -> \"pong\" [color=red];\n    \"pong\" -> \"ping\" [color=red];\n    \"unused\" -> \"pong\";\n}\n");
+
+    let warnings = call_graph.find_unreachable_functions(&ast);
+    let names:Vec<String> = warnings.iter().map(|warning| match warning {
+        Warning::UnreachableFunction {identifier} => identifier.to_owned(),
+        other => panic!("unexpected warning {:?}", other)
+    }).collect();
+    assert_eq!(names, ["ping", "pong", "unused"]);
+    assert_eq!(warnings[2].to_string(), "The function unused is never called by main or any function main calls, so it is never run.");
+    assert_eq!(Diagnostic::from_warning(&warnings[2], "unused.iri").line, None);
+
+    // the program behaves the same without the functions, which are left out of the assembly
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let ast = optimisation::dead_functions::remove_unreachable_functions(ast);
+    assert_eq!(ast.len(), 3);
+
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "6");
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("fn_count:") && !assembly.contains("fn_ping:") && !assembly.contains("fn_unused:"));
+
+    // a program without main is left alone, as there is nothing to say what is called
+    let ast = frontend::parser::parse_program("fn int helper() {\n    return 1;\n}", DEFAULT_NESTING_LIMIT).unwrap();
+    assert!(frontend::call_graph::CallGraph::new(&ast).find_unreachable_functions(&ast).is_empty());
+    assert_eq!(optimisation::dead_functions::remove_unreachable_functions(ast).len(), 1);
+}
+
+
+#[test]
+fn interpreter_stops_programs_which_go_over_their_limits() {
+    let get_instructions = |source:&str| {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        (instructions, symbol_table)
+    };
+
+    // a loop whose break can never run is stopped, keeping what it printed before then
+    let (instructions, symbol_table) = get_instructions("fn void main() {\n    let mut int n = 0;\n    loop {\n        print << \"x\";\n        n = (n, 1)+;\n        if (n, 0)< {\n            break;\n        }\n    }\n}");
+    let mut output = vec![];
+    let mut interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_max_steps(10_000);
+    let err = interpreter.run(&mut "".as_bytes(), &mut output).unwrap_err();
+    assert_eq!(err.to_string(), "The program was stopped as it went over its limit of 10000 steps.");
+    assert!(!output.is_empty() && output.iter().all(|byte| *byte == b'x'));
+
+    // recursion without end is stopped once too many calls are in progress
+    let (instructions, symbol_table) = get_instructions("fn int forever(int n) {\n    let int m = (n, 1)+;\n    return forever(m);\n}\n\nfn void main() {\n    let int n = forever(0);\n}");
+    let mut interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_max_call_depth(100);
+    let err = interpreter.run(&mut "".as_bytes(), &mut vec![]).unwrap_err();
+    assert!(matches!(err, RuntimeError::BudgetExceeded(_)), "unexpected error: {}", err);
+
+    // blocks are counted after rounding, so two blocks of 5 bytes fill 16 bytes of heap
+    let source = "fn void main() {\n    let long first = alloc(5);\n    let long second = alloc(5);\n    print << \"done\";\n}";
+    let (instructions, symbol_table) = get_instructions(source);
+    let mut output = vec![];
+    let mut interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_max_heap(16);
+    assert_eq!(interpreter.run(&mut "".as_bytes(), &mut output).unwrap(), 0);
+    assert_eq!(output, b"done");
+
+    let mut interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_max_heap(15);
+    let err = interpreter.run(&mut "".as_bytes(), &mut vec![]).unwrap_err();
+    assert_eq!(err.to_string(), "The program was stopped as it went over its limit of 15 bytes of heap.");
+}
+
+
+#[test]
+fn instructions_on_operands_of_the_wrong_type_are_rejected_by_the_mips_backend() {
+    let ast = frontend::parser::parse_program("fn void f(int a) {}\n\nfn void main() {}", DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast).unwrap();
+    let push = |push_type:Type, value:Argument, count:usize| vec![IntermediateInstr::Push(push_type, value); count];
+    let text = || Argument::String("a".to_owned());
+
+    // semantic validation never lets these through, so each is an internal error naming the instruction and type.
+    // Equal and NotEqual are left out, as they take every type a value on the stack can have
+    let cases:Vec<(&str, Type, Vec<IntermediateInstr>)> = vec![
+        ("Store", Type::Void, vec![IntermediateInstr::Store(Type::Void, 0)]),
+        ("Load", Type::Void, vec![IntermediateInstr::Load(Type::Void, 0)]),
+        ("LoadParam", Type::Void, vec![IntermediateInstr::LoadParam(Type::Void, 0)]),
+        ("Pop", Type::Void, vec![IntermediateInstr::Pop(Type::Void)]),
+        ("Add", Type::Boolean, [push(Type::Boolean, Argument::Boolean(true), 2), vec![IntermediateInstr::Add]].concat()),
+        ("Sub", Type::String, [push(Type::String, text(), 2), vec![IntermediateInstr::Sub]].concat()),
+        ("Mult", Type::String, [push(Type::String, text(), 2), vec![IntermediateInstr::Mult]].concat()),
+        ("Div", Type::String, [push(Type::String, text(), 2), vec![IntermediateInstr::Div]].concat()),
+        ("BitwiseAnd", Type::Float, [push(Type::Float, Argument::Float(1.5), 2), vec![IntermediateInstr::BitwiseAnd]].concat()),
+        ("BitwiseOr", Type::Float, [push(Type::Float, Argument::Float(1.5), 2), vec![IntermediateInstr::BitwiseOr]].concat()),
+        ("BitwiseXor", Type::Float, [push(Type::Float, Argument::Float(1.5), 2), vec![IntermediateInstr::BitwiseXor]].concat()),
+        ("NumNeg", Type::String, [push(Type::String, text(), 1), vec![IntermediateInstr::NumNeg]].concat()),
+        ("Complement", Type::Double, [push(Type::Double, Argument::Double(1.5), 1), vec![IntermediateInstr::Complement]].concat()),
+        ("LogicNeg", Type::String, [push(Type::String, text(), 1), vec![IntermediateInstr::LogicNeg]].concat()),
+        ("LeftShiftLogical", Type::Char, [push(Type::Char, Argument::Char('a'), 2), vec![IntermediateInstr::LeftShiftLogical]].concat()),
+        ("RightShiftLogical", Type::Char, [push(Type::Char, Argument::Char('a'), 2), vec![IntermediateInstr::RightShiftLogical]].concat()),
+        ("RightShiftArithmetic", Type::Char, [push(Type::Char, Argument::Char('a'), 2), vec![IntermediateInstr::RightShiftArithmetic]].concat()),
+        ("GreaterThan", Type::String, [push(Type::String, text(), 2), vec![IntermediateInstr::GreaterThan]].concat()),
+        ("GreaterEqual", Type::String, [push(Type::String, text(), 2), vec![IntermediateInstr::GreaterEqual]].concat()),
+        ("LessThan", Type::Boolean, [push(Type::Boolean, Argument::Boolean(true), 2), vec![IntermediateInstr::LessThan]].concat()),
+        ("LessEqual", Type::Boolean, [push(Type::Boolean, Argument::Boolean(true), 2), vec![IntermediateInstr::LessEqual]].concat()),
+        ("JumpZero", Type::String, [push(Type::String, text(), 1), vec![IntermediateInstr::JumpZero("skip".to_owned())]].concat()),
+        ("Out", Type::Float, [push(Type::Float, Argument::Float(1.5), 1), vec![IntermediateInstr::Out(Type::Float, PrintFormat::Plain)]].concat())
+    ];
+
+    for (instruction, operand_type, body) in cases {
+        let function = match instruction {
+            "LoadParam" => "f",
+            _ => "main"
+        };
+
+        let code = [vec![IntermediateInstr::FuncStart(function.to_owned())], body, vec![IntermediateInstr::FuncEnd(function.to_owned())]].concat();
+        let err = backend::mips::generate_mips_to_string(code, &symbol_table).unwrap_err();
+        let err = err.downcast_ref::<InvalidOperandType>().unwrap_or_else(|| panic!("unexpected error for {}: {}", instruction, err));
+        assert_eq!((err.instruction.as_str(), &err.operand_type), (instruction, &operand_type));
+    }
+}
+
+
+#[test]
+fn functions_generated_on_many_threads_match_one_thread() {
+    // every function has constants of its own along with some shared with the others, so the data section
+    // has to be the same whichever thread uses each constant first
+    let mut source = String::new();
+    for index in 0..500 {
+        source += &format!("fn float f{}(int n) {{\n    print << \"f{}\" << \"\\n\";\n    let float x = {}.5;\n    return (x, 1.5)+;\n}}\n\n", index, index % 50, index);
+    }
+
+    source += "fn void main() {\n    let float a = f7(1);\n    let float b = f499(2);\n}\n";
+    let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+
+    let generate = |threads:usize| {
+        let mut assembly:Vec<u8> = vec![];
+        let counts = backend::mips::generate_mips_with_threads(instructions.clone(), &mut assembly, &symbol_table, &backend::mips::CodegenOptions::default(), threads).unwrap();
+        (String::from_utf8(assembly).unwrap(), counts)
+    };
+
+    let (single_assembly, single_counts) = generate(1);
+    assert_eq!(single_counts.len(), 501);
+    for threads in [2, 7, 64, 1000] {
+        let (assembly, counts) = generate(threads);
+        assert!(assembly == single_assembly, "the assembly generated on {} threads differs from that on 1", threads);
+        assert_eq!(counts, single_counts);
+    }
+
+    // an error in a function generated on another thread is returned as it is
+    let broken = vec![IntermediateInstr::FuncStart("main".to_owned()), IntermediateInstr::Pop(Type::Void), IntermediateInstr::FuncEnd("main".to_owned())];
+    let err = backend::mips::generate_mips_with_threads(broken, &mut vec![], &symbol_table, &backend::mips::CodegenOptions::default(), 4).unwrap_err();
+    assert!(err.is::<InvalidOperandType>(), "unexpected error: {}", err);
+
+    // and stops the other threads rather than leaving them waiting for the functions before it to be written
+    let mut broken = instructions.clone();
+    let start = broken.iter().position(|instr| matches!(instr, IntermediateInstr::FuncStart(name) if name == "f250")).unwrap();
+    broken.insert(start + 1, IntermediateInstr::Pop(Type::Void));
+    let err = backend::mips::generate_mips_with_threads(broken, &mut vec![], &symbol_table, &backend::mips::CodegenOptions::default(), 7).unwrap_err();
+    assert!(err.is::<InvalidOperandType>(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn sizeof_and_typeof_are_replaced_by_literals() {
+    let get_ir = |source:&str| -> Result<Vec<String>, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        Ok(instructions.iter().map(|instr| instr.to_string().trim().to_owned()).collect())
+    };
+
+    // types are sized by the parser and variables once their type is known, with nothing loaded at runtime
+    let source = "fn int width(double d) {\n    let int size = sizeof(d);\n    return size;\n}\n\nfn void main() {\n    let bool flag = true;\n    let int a = sizeof(long);\n    let int b = sizeof(flag);\n    let string name = typeof(flag);\n    let string line = input sizeof(double);\n}";
+    let ir = get_ir(source).unwrap();
+    assert_eq!(&ir[3..5], ["push.i32 8", "store.i32 %2"]);
+    assert!(ir.iter().any(|instr| instr == "in.str 8"));
+    assert!(ir.windows(2).any(|pair| pair == ["push.i32 4", "store.i32 %1"]), "{:?}", ir);
+    assert!(ir.iter().any(|instr| instr == "push.str \"bool\""));
+    assert!(!ir.iter().any(|instr| instr.starts_with("load.f64") || instr.starts_with("load.bool")), "unexpected load in {:?}", ir);
+
+    // the results are an int and a string, and the variable must be in scope
+    let err = get_ir("fn void main() {\n    let int n = 1;\n    let string s = sizeof(n);\n}").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>(), "unexpected error: {}", err);
+    let err = get_ir("fn void main() {\n    let int n = 1;\n    let int t = typeof(n);\n}").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>(), "unexpected error: {}", err);
+    assert!(get_ir("fn void main() {\n    let int n = sizeof(m);\n}").is_err());
+
+    // void has no size, and an input's length cannot depend on a variable
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int n = sizeof(void);\n}", DEFAULT_NESTING_LIMIT).is_err());
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int n = 1;\n    let string s = input sizeof(n);\n}", DEFAULT_NESTING_LIMIT).is_err());
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int sizeof = 1;\n}", DEFAULT_NESTING_LIMIT).unwrap_err().is::<ReservedIdentifier>());
+}
+
+
+#[test]
+fn static_variables_keep_their_value_between_calls() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/static_counter.iri"), false).unwrap();
+    assert!(instructions.iter().any(|instr| instr.to_string() == "    static.i32 __static_count_calls_calls 0"));
+    assert!(instructions.iter().any(|instr| instr.to_string() == "    store_static.i64 __static_add_to_total_total"));
+
+    // statics live in the data section, so they take no space in the frame
+    assert_eq!(backend::mips::get_frame_size("count_calls", &symbol_table, true), 0);
+    assert_eq!(backend::mips::get_frame_size("average", &symbol_table, true), 8);
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("__static_count_calls_calls: .word 0\n"));
+    assert!(assembly.contains("__static_average_sum: .double 0\n"));
+    assert!(assembly.contains("sw $t0, __static_toggle_state"));
+
+    let get_ir = |source:&str| -> Result<Vec<String>, Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        Ok(instructions.iter().map(|instr| instr.to_string().trim().to_owned()).collect())
+    };
+
+    // a long's first word is its upper half, and statics of the same name in different blocks are kept apart
+    let source = "fn void main() {\n    let static long big = 4294967298l;\n    if true {\n        let static mut int n = 1;\n    }\n    if false {\n        let static mut int n = 2;\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("__static_main_big: .word 1, 2\n"));
+    assert!(assembly.contains("__static_main_n: .word 1\n"));
+    assert!(assembly.contains("__static_main_n_2: .word 2\n"));
+
+    // the value is set before the program runs, so it must be known at compile time
+    let err = get_ir("fn void main() {\n    let int n = 1;\n    let static int m = n;\n}").unwrap_err();
+    assert!(err.is::<NonConstantStaticInitialiser>(), "unexpected error: {}", err);
+    let err = get_ir("fn void main() {\n    let static string s = \"text\";\n}").unwrap_err();
+    assert!(err.is::<NonConstantStaticInitialiser>(), "unexpected error: {}", err);
+    assert!(get_ir("fn void main() {\n    let static mut float f = float(3);\n}").is_ok());
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int static = 1;\n}", DEFAULT_NESTING_LIMIT).unwrap_err().is::<ReservedIdentifier>());
+}
+
+
+#[test]
+fn pure_calls_with_literal_arguments_are_folded() {
+    let compile_source = |source:&str| -> Result<(Vec<IntermediateInstr>, SymbolTable), Box<dyn Error>> {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT)?;
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone())?;
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table)?;
+        let pure_functions = optimisation::pure_calls::find_pure_functions(&ast);
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        Ok((optimisation::pure_calls::fold_pure_calls(instructions, &pure_functions, &symbol_table, backend::OverflowMode::Wrap), symbol_table))
+    };
+
+    // both calls become the literal they return, and calls with a variable argument are still made
+    let source = "@pure @inline fn int square(int n) {\n    return (n, n)*;\n}\n\nfn void main() {\n    let int a = square(4);\n    let int b = square(4);\n    let int c = square(a);\n    let int total = ((a, b)+, c)+;\n    print << total;\n}";
+    let (instructions, symbol_table) = compile_source(source).unwrap();
+    let main:Vec<String> = instructions.iter().skip_while(|instr| !matches!(instr, IntermediateInstr::FuncStart(name) if name == "main"))
+        .map(|instr| instr.to_string().trim().to_owned()).collect();
+    assert_eq!(&main[1..5], ["push.i32 16", "store.i32 %0", "push.i32 16", "store.i32 %1"]);
+    assert_eq!(main.iter().filter(|instr| instr.starts_with("call square")).count(), 1);
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "288");
+
+    // calls which trap or never end, and functions not marked pure, are left alone
+    let source = "@pure fn int divide(int n) {\n    return (10, n)/;\n}\n\n@pure fn int spin(int n) {\n    loop {\n        if (n, 0)< {\n            break;\n        }\n    }\n    return n;\n}\n\nfn int twice(int n) {\n    return (n, 2)*;\n}\n\nfn void main() {\n    let int a = divide(5);\n    let int b = twice(3);\n    let int c = spin(1);\n    let int d = divide(0);\n}";
+    let (instructions, _) = compile_source(source).unwrap();
+    let calls:Vec<String> = instructions.iter().filter(|instr| matches!(instr, IntermediateInstr::Call(..))).map(|instr| instr.to_string().trim().to_owned()).collect();
+    assert_eq!(calls, ["call twice/1 -> i32", "call spin/1 -> i32", "call divide/1 -> i32"]);
+
+    // a pure function cannot have side effects, or call anything which does
+    let get_error = |source:&str| compile_source(source).unwrap_err().to_string();
+    assert_eq!(get_error("@pure fn int f(int n) {\n    print << n;\n    return n;\n}\n\nfn void main() {}"), "Function f is marked @pure but prints.");
+    assert_eq!(
+        get_error("@pure fn int f(int n) {\n    return g(n);\n}\n\nfn int g(int n) {\n    return h(n);\n}\n\nfn int h(int n) {\n    let string s = input 4;\n    return n;\n}\n\nfn void main() {}"),
+        "Function f is marked @pure but calls g, which calls h, which reads input."
+    );
+    assert_eq!(
+        get_error("@pure fn int f() {\n    let static mut int calls = 0;\n    return calls;\n}\n\nfn void main() {}"),
+        "Function f is marked @pure but keeps the mutable static variable calls."
+    );
+    assert!(compile_source("@pure fn int f() {\n    let int block = alloc(8);\n    return block;\n}\n\nfn void main() {}").unwrap_err().is::<ImpureFunction>());
+
+    // recursion, constant statics and intrinsics other than alloc are all allowed in a pure function
+    let source = "@pure fn int fact(int n) {\n    let static int base = 1;\n    if (n, 1)<= {\n        return base;\n    }\n    let int m = (n, 1)-;\n    let int rest = fact(m);\n    let int product = (n, rest)*;\n    return abs(product);\n}\n\nfn void main() {\n    let int f = fact(5);\n    print << f;\n}";
+    let (instructions, _) = compile_source(source).unwrap();
+    assert!(instructions.iter().any(|instr| instr.to_string() == "    push.i32 120"));
+}
+
+
+#[test]
+fn int_overflow_wraps_or_traps_as_chosen() {
+    let source = "fn int increment(int n) {\n    return (n, 1)+;\n}\n\nfn void main() {\n    let int n = increment(2147483647);\n    print << n;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let run = |overflow:backend::OverflowMode| {
+        let mut output:Vec<u8> = vec![];
+        let exit_code = backend::interpreter::Interpreter::new(&instructions, &symbol_table).with_overflow(overflow).run(&mut "".as_bytes(), &mut output).unwrap();
+        Expectation {exit_code, output: String::from_utf8(output).unwrap()}
+    };
+
+    assert_eq!(run(backend::OverflowMode::Wrap), Expectation {exit_code: 0, output: "-2147483648".to_owned()});
+    assert_eq!(run(backend::OverflowMode::Trap), Expectation {exit_code: 1, output: "integer overflow in function increment\n".to_owned()});
+
+    // wrapping never uses the instructions which raise an exception on overflow, and has no handler
+    let wrap = backend::mips::generate_mips_to_string(instructions.clone(), &symbol_table).unwrap();
+    assert!(wrap.contains("\taddu $t0, $t2, $t0"));
+    assert!(!wrap.contains("__integer_overflow"));
+
+    // trapping checks each operation and jumps to one handler, with only functions which do arithmetic named
+    let mut buffer:Vec<u8> = vec![];
+    let options = backend::mips::CodegenOptions {overflow: backend::OverflowMode::Trap, ..Default::default()};
+    backend::mips::generate_mips(instructions, &mut buffer, &symbol_table, &options).unwrap();
+    let trap = String::from_utf8(buffer).unwrap();
+    assert_eq!(trap.matches("__integer_overflow:").count(), 1);
+    assert_eq!(trap.matches("bltz $t3, __integer_overflow").count(), 1);
+    assert!(trap.contains(".asciiz \"increment\""));
+    assert!(!trap.contains(".asciiz \"main\""));
+
+    if let Some(simulator) = find_simulator() {
+        assert_eq!(run_mips(&trap, "overflow_trap", &simulator, "").unwrap(), "integer overflow in function increment\n");
+        assert_eq!(run_mips(&wrap, "overflow_wrap", &simulator, "").unwrap(), "-2147483648");
+    }
+}
+
+
+#[test]
+fn frame_checks_stop_functions_whose_frame_was_overwritten() {
+    let source = "fn void corrupt() {\n    let int x = 1;\n    print << x;\n}\n\nfn void main() {\n    corrupt();\n    print << 2;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let generate = |instructions:Vec<IntermediateInstr>, frame_checks:bool| {
+        let mut buffer:Vec<u8> = vec![];
+        let options = backend::mips::CodegenOptions {frame_checks, ..Default::default()};
+        backend::mips::generate_mips(instructions, &mut buffer, &symbol_table, &options).unwrap();
+        String::from_utf8(buffer).unwrap()
+    };
+
+    // no program can write past the top of its frame yet, so `corrupt` is made to pop two words it never
+    // pushed, leaving the stack pointer on the canary for the next push to overwrite
+    let start = instructions.iter().position(|instr| matches!(instr, IntermediateInstr::FuncStart(name) if name == "corrupt")).unwrap();
+    let mut corrupted = instructions.clone();
+    corrupted.splice(start + 1..start + 1, [
+        IntermediateInstr::Store(Type::Integer, 0),
+        IntermediateInstr::Store(Type::Integer, 0),
+        IntermediateInstr::Push(Type::Integer, Argument::Integer(0))
+    ]);
+
+    // without the flag there is no canary, and the variables start just below the saved frame pointer
+    let unchecked = generate(instructions.clone(), false);
+    assert!(!unchecked.contains("frame canary"));
+    assert!(!unchecked.contains("__stack_frame_corrupted"));
+    assert!(unchecked.contains("sw $t0, -4($fp)"));
+
+    // every function but main writes and checks its canary, jumping to one handler, and its variables move down
+    let checked = generate(instructions, true);
+    assert_eq!(checked.matches("write frame canary").count(), 1);
+    assert_eq!(checked.matches("bne $t0, $t1, __stack_frame_corrupted").count(), 1);
+    assert_eq!(checked.matches("__stack_frame_corrupted:").count(), 1);
+    assert!(checked.contains("sw $t0, -8($fp)"));
+    assert!(checked.contains(".asciiz \"corrupt\""));
+    assert!(!checked.contains(".asciiz \"main\""));
+
+    if let Some(simulator) = find_simulator() {
+        assert_eq!(run_mips(&checked, "frame_checks", &simulator, "").unwrap(), "12");
+        assert_eq!(run_mips(&generate(corrupted, true), "frame_checks_corrupted", &simulator, "").unwrap(), "1stack frame corrupted in corrupt\n");
+    }
+}
+
+
+#[test]
+fn inputs_share_one_buffer_unless_asked_not_to() {
+    let source = "fn void main() {\n    let string first = input 4;\n    let string second = input 16;\n    print << first;\n    let mut int i = 0;\n    while (i, 3)< {\n        let string line = input 8;\n        print << line;\n        i = (i, 1)+;\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let warnings = frontend::semantics::find_overwritten_inputs(&ast);
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    let generate = |distinct_input_buffers:bool| {
+        let mut buffer:Vec<u8> = vec![];
+        let options = backend::mips::CodegenOptions {distinct_input_buffers, ..Default::default()};
+        backend::mips::generate_mips(instructions.clone(), &mut buffer, &symbol_table, &options).unwrap();
+        String::from_utf8(buffer).unwrap()
+    };
+
+    // one buffer big enough for the longest input is used by all three, including the one in the loop
+    let shared = generate(false);
+    assert!(shared.contains("\t__input_buffer: .space 18"));
+    assert_eq!(shared.matches(".space").count(), 1);
+    assert_eq!(shared.matches("la $a1, __input_buffer\n").count(), 3);
+
+    let distinct = generate(true);
+    for (index, size) in [6, 18, 10].iter().enumerate() {
+        assert!(distinct.contains(&format!("\t__input_buffer_{}: .space {}\n", index, size)), "missing buffer {}", index);
+    }
+
+    // only the first input is read after another has replaced it, as the one in the loop is printed straight away
+    assert_eq!(warnings, vec![Warning::InputOverwritten {identifier: "first".to_owned(), line: 3, earlier_line: 2}]);
+}
+
+
+#[test]
+fn repeat_loops_count_with_a_hidden_control_variable() {
+    // nested loops each have their own control variable, named so that no variable in the program can clash
+    let ast = validate_source("fn void main() {\n    repeat 2 {\n        repeat 3 {\n            print << \"*\";\n        }\n    }\n}").unwrap();
+    let (outer, inner) = match &ast[0] {
+        frontend::ast::ASTNode::Function {statements, ..} => match &statements[0] {
+            frontend::ast::ASTNode::ForLoop {control_identifier: outer, statements, ..} => match &statements[0] {
+                frontend::ast::ASTNode::ForLoop {control_identifier: inner, ..} => (outer.to_owned(), inner.to_owned()),
+                other => panic!("expected a for loop, found {:?}", other)
+            },
+            other => panic!("expected a for loop, found {:?}", other)
+        },
+        other => panic!("expected a function, found {:?}", other)
+    };
+
+    assert!(outer.starts_with('$') && inner.starts_with('$') && outer != inner, "{} and {}", outer, inner);
+
+    // the count can be an int or a long, with a long converted to an int before each comparison
+    let err = validate_source("fn void main() {\n    let float n = 2.0;\n    repeat n {\n    }\n}").unwrap_err();
+    assert_eq!(err.to_string(), "The number of times the repeat loop on line 3 repeats must be an int or long, not float.");
+    assert_eq!(Diagnostic::from_error(err.as_ref(), "repeat.iri").code, "invalid-repeat-count");
+
+    let ast = validate_source("fn void main() {\n    let long n = 2l;\n    repeat n {\n        print << \"*\";\n    }\n}").unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    assert!(instructions.iter().any(|instr| matches!(instr, IntermediateInstr::Cast(Type::Long, Type::Integer))));
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "**");
+
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int repeat = 1;\n}", DEFAULT_NESTING_LIMIT).unwrap_err().is::<ReservedIdentifier>());
+    assert_eq!(frontend::semantics::find_empty_loops(&validate_source("fn void main() {\n    repeat 2 {\n    }\n}").unwrap()),
+               vec![Warning::EmptyLoopBody {construct: "repeat loop", line: 2}]);
+}
+
+
+#[test]
+fn intermediate_code_reads_back_from_its_text() {
+    let instructions = vec![
+        IntermediateInstr::FuncStart("main".to_owned()),
+        IntermediateInstr::Push(Type::Byte, Argument::Byte(200)),
+        IntermediateInstr::Push(Type::Long, Argument::Long(-5)),
+        IntermediateInstr::Push(Type::Float, Argument::Float(f32::INFINITY)),
+        IntermediateInstr::Push(Type::Double, Argument::Double(-0.5)),
+        IntermediateInstr::Push(Type::Char, Argument::Char('\'')),
+        IntermediateInstr::Push(Type::Char, Argument::Char('\u{7f}')),
+        IntermediateInstr::Push(Type::String, Argument::String("say \"hi\"\tthen\\n".to_owned())),
+        IntermediateInstr::DeclareStatic(Type::Boolean, "__static_main_seen".to_owned(), Argument::Boolean(false)),
+        IntermediateInstr::Cast(Type::Long, Type::Integer),
+        IntermediateInstr::Out(Type::Integer, PrintFormat::Hex),
+        IntermediateInstr::In(Type::Char, 1),
+        IntermediateInstr::In(Type::String, 16),
+        IntermediateInstr::Call("fib".to_owned(), Type::Integer, 1),
+        IntermediateInstr::Label("__l_4".to_owned()),
+        IntermediateInstr::JumpZero("__l_4".to_owned()),
+        IntermediateInstr::Store(Type::Double, 3),
+        IntermediateInstr::RightShiftLogical,
+        IntermediateInstr::Trap("error: out of range".to_owned()),
+        IntermediateInstr::FuncEnd("main".to_owned())
+    ];
+
+    let text = format_ir(&instructions);
+    assert!(text.contains("\n    push.u8 200b\n    push.i64 -5l\n    push.f32 inf\n    push.f64 -0.5d\n"), "{}", text);
+    assert!(text.contains("\n    call fib/1 -> i32\n\n__l_4:\n    jz __l_4\n    store.f64 %3\n    srl\n"), "{}", text);
+
+    let parsed = frontend::intermediate_gen::parse_intermediate(&text).unwrap();
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", instructions));
+
+    // the line given is the line of the text, counting the blank lines between functions
+    let err = frontend::intermediate_gen::parse_intermediate("func_start main\n\n    push.i32 5\n    store.i32 0\nfunc_end main").unwrap_err();
+    assert_eq!(err.to_string(), "Could not read the intermediate code `store.i32 0` on line 4: store expects a local such as %0.");
+    for line in ["push.int 5", "push.i32", "jmp", "call fib -> i32", "bogus", "add 1", "trap \"unterminated"] {
+        assert!(line.parse::<IntermediateInstr>().is_err(), "{} was read as an instruction", line);
+    }
+}
+
+
+#[test]
+fn operands_and_arguments_are_evaluated_left_to_right() {
+    let source = "@pure fn int square(int n) {\n    return (n, n)*;\n}\n\nfn int a() {\n    print << \"a\";\n    return 1;\n}\n\nfn int b() {\n    print << \"b\";\n    return 2;\n}\n\nfn int pick(int x, int y, int z) {\n    return x;\n}\n\nfn void main() {\n    let int unused = ((a(), square(3))+, b())-;\n    let int picked = pick(b(), square(2), a());\n    print << picked;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let pure_functions = optimisation::pure_calls::find_pure_functions(&ast);
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+
+    let calls = |instructions:&[IntermediateInstr]| -> Vec<String> {
+        instructions.iter().skip_while(|instr| !matches!(instr, IntermediateInstr::FuncStart(name) if name == "main"))
+            .filter_map(|instr| match instr {
+                IntermediateInstr::Call(function, ..) => Some(function.to_owned()),
+                _ => None
+            }).collect()
+    };
+    assert_eq!(calls(&instructions), ["a", "square", "b", "b", "square", "a", "pick"]);
+
+    // folding the pure calls and removing the dead store must keep the calls with side effects in order
+    let instructions = optimisation::pure_calls::fold_pure_calls(instructions, &pure_functions, &symbol_table, backend::OverflowMode::Wrap);
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+    assert_eq!(calls(&instructions), ["a", "b", "b", "a", "pick"]);
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "abba2");
+}
+
+
+#[test]
+fn constants_are_propagated_across_statements() {
+    let compile_source = |source:&str, overflow:backend::OverflowMode| -> (Vec<IntermediateInstr>, SymbolTable) {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        (optimisation::constant_propagation::propagate_constants(instructions, overflow), symbol_table)
+    };
+
+    let get_body = |instructions:&[IntermediateInstr]| -> Vec<String> {
+        instructions.iter().filter(|instr| !matches!(instr, IntermediateInstr::FuncStart(_) | IntermediateInstr::FuncEnd(_)))
+                           .map(|instr| instr.to_string().trim().to_owned())
+                           .collect()
+    };
+
+    let (instructions, _) = compile_source("fn void main() {\n    let int x = 5;\n    let int y = (x, 2)*;\n}", backend::OverflowMode::Wrap);
+    assert_eq!(get_body(&instructions), ["push.i32 5", "store.i32 %0", "push.i32 10", "store.i32 %1"]);
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+    assert_eq!(get_body(&instructions), Vec::<String>::new());
+
+    // each variable worked out can make the next one constant, and the loads left are only those of y
+    let (instructions, symbol_table) = compile_source("fn void main() {\n    let int x = 5;\n    let int y = (x, 2)*;\n    let bool big = (y, 8)>;\n    let mut int z = (y)-;\n    if big {\n        z = (z, x)+;\n    }\n    print << z << \" \" << y;\n}", backend::OverflowMode::Wrap);
+    let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+    assert_eq!(&get_body(&instructions)[..5], ["push.i32 -10", "store.i32 %2", "push.bool true", "jz __l_2", "push.i32 -5"]);
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "-5 10");
+
+    // a variable assigned before a loop and again inside it is not constant within the loop, though one only
+    // assigned before it is
+    let source = "fn void main() {\n    let int factor = 2;\n    let mut int total = 1;\n    let mut int i = 0;\n    while (i, 3)< {\n        total = (total, factor)*;\n        i = (i, 1)+;\n    }\n    print << total;\n}";
+    let (instructions, symbol_table) = compile_source(source, backend::OverflowMode::Wrap);
+    let body = get_body(&instructions);
+    assert!(!body.contains(&String::from("load.i32 %0")), "factor was not propagated:\n{}", body.join("\n"));
+    for id in [1, 2] {
+        assert!(body.contains(&format!("load.i32 %{}", id)), "%{} was propagated into the loop:\n{}", id, body.join("\n"));
+    }
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "8");
+
+    // where the branches of an if statement disagree the variable is not constant after it
+    let source = "fn void main() {\n    let char c = input char;\n    let mut int x = 1;\n    if (c, 'a')== {\n        x = 2;\n    }\n    print << x;\n}";
+    let (instructions, symbol_table) = compile_source(source, backend::OverflowMode::Wrap);
+    assert!(get_body(&instructions).contains(&String::from("load.i32 %0")));
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "a").unwrap().output, "2");
+
+    // operations which would trap are left to trap when the program runs
+    let source = "fn void main() {\n    let int zero = 0;\n    let int big = 2147483647;\n    let int a = (big, 1)+;\n    let int b = (1, zero)/;\n}";
+    let (instructions, _) = compile_source(source, backend::OverflowMode::Trap);
+    let body = get_body(&instructions);
+    assert!(body.contains(&String::from("add")) && body.contains(&String::from("div")), "{}", body.join("\n"));
+    let (instructions, _) = compile_source(source, backend::OverflowMode::Wrap);
+    let body = get_body(&instructions);
+    assert!(body.contains(&String::from("push.i32 -2147483648")) && body.contains(&String::from("div")), "{}", body.join("\n"));
+
+    // every test program still behaves the same
+    for path in get_test_programs(PROGRAMS_DIR) {
+        let source = fs::read_to_string(&path).unwrap();
+        let (instructions, symbol_table) = compile(&path, true).unwrap();
+        let instructions = optimisation::constant_propagation::propagate_constants(instructions, backend::OverflowMode::Wrap);
+        let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+        let result = run_interpreter(&instructions, &symbol_table, &parse_input(&source)).unwrap();
+        assert_eq!(result, parse_expectation(&source).unwrap(), "{} changed with constants propagated", path.display());
+    }
+}
+
+
+#[test]
+fn dense_if_chains_become_jump_tables() {
+    let compile_source = |source:&str, density:usize| -> (Vec<IntermediateInstr>, SymbolTable) {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+        let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+        let instructions = optimisation::optimise(instructions, backend::OverflowMode::Wrap, &mut PhaseTimings::new());
+        (optimisation::jump_tables::build_jump_tables(instructions, density), symbol_table)
+    };
+
+    let get_tables = |instructions:&[IntermediateInstr]| -> Vec<(i32, usize)> {
+        instructions.iter().filter_map(|instr| match instr {
+            IntermediateInstr::TableSwitch(low, labels, _) => Some((*low, labels.len())),
+            _ => None
+        }).collect()
+    };
+
+    // a 20 case chain over 3 to 22 gives a table with an entry for each value, and misses take the else branch
+    let branches:Vec<String> = (0..20).map(|case| format!("if (n, {})== {{\n        return {};\n    }}", case + 3, case * case)).collect();
+    let source = format!("fn int pick(int n) {{\n    {} else {{\n        print << \"miss \";\n    }}\n    return (1)-;\n}}\n\nfn void main() {{\n    let int a = pick(3);\n    let int b = pick(12);\n    let int c = pick(22);\n    let int d = pick(2);\n    let int e = pick(23);\n    print << a << \" \" << b << \" \" << c << \" \" << d << \" \" << e;\n}}", branches.join(" else "));
+    let (instructions, symbol_table) = compile_source(&source, optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY);
+    assert_eq!(get_tables(&instructions), [(3, 20)]);
+    assert!(!instructions.iter().any(|instr| matches!(instr, IntermediateInstr::JumpZero(_))));
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "miss miss 0 81 361 -1 -1");
+
+    let text = format_ir(&instructions);
+    let parsed = frontend::intermediate_gen::parse_intermediate(&text).unwrap();
+    assert_eq!(diff_ir(&text, &format_ir(&parsed)), None);
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("__table_0: .word "), "missing jump table in:\n{}", assembly);
+    assert!(assembly.contains("jr $t0"));
+
+    // chars written with escapes can be cases, and a sparse chain is left as comparisons unless the density is lowered
+    let source = "fn int classify(char c) {\n    if (c, '\\x41')== {\n        return 1;\n    } else if (c, 'B')== {\n        return 2;\n    } else if ('D', c)== {\n        return 3;\n    } else if (c, 'E')== {\n        return 4;\n    }\n    return 0;\n}\n\nfn int spread(int n) {\n    if (n, 1)== {\n        return 1;\n    } else if (n, 10)== {\n        return 2;\n    } else if (n, 20)== {\n        return 3;\n    } else if (n, 30)== {\n        return 4;\n    }\n    return 0;\n}\n\nfn void main() {\n    let char newline = '\\n';\n    let int a = classify('A');\n    let int b = classify('C');\n    let int c = classify('\\x44');\n    let int d = spread(20);\n    let int e = spread(2);\n    print << a << b << c << d << e << newline;\n}";
+    let (instructions, symbol_table) = compile_source(source, optimisation::jump_tables::DEFAULT_JUMP_TABLE_DENSITY);
+    assert_eq!(get_tables(&instructions), [(65, 5)]);
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "10330\n");
+
+    let (instructions, symbol_table) = compile_source(source, 10);
+    assert_eq!(get_tables(&instructions), [(65, 5), (1, 30)]);
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "10330\n");
+}
+
+
+#[test]
+fn char_literals_can_be_escaped() {
+    let get_char = |literal:&str| -> Result<char, String> {
+        let source = format!("fn void main() {{\n    let char c = {};\n}}", literal);
+        let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).map_err(|err| err.to_string())?;
+        match &ast[0] {
+            frontend::ast::ASTNode::Function {statements, ..} => match &statements[0] {
+                frontend::ast::ASTNode::VarDeclStatement {value, ..} => match frontend::semantics::evaluate_constant_term(value, &HashMap::new()) {
+                    Some(frontend::ast::Literal::Char(value)) => Ok(value),
+                    other => Err(format!("{:?} is not a char", other))
+                },
+                other => Err(format!("{:?} is not a declaration", other))
+            },
+            other => Err(format!("{:?} is not a function", other))
+        }
+    };
+
+    for (literal, expected) in [("'a'", 'a'), ("' '", ' '), ("'\\n'", '\n'), ("'\\t'", '\t'), ("'\\0'", '\0'), ("'\\''", '\''),
+                                ("'\\\\'", '\\'), ("'\"'", '"'), ("'\\x41'", 'A'), ("'\\x7e'", '~')] {
+        assert_eq!(get_char(literal), Ok(expected), "{} was read wrongly", literal);
+    }
+
+    assert!(get_char("'\\q'").unwrap_err().contains("unknown escape in char literal '\\q'"));
+    assert!(get_char("'ab'").is_err());
+    assert!(get_char("'\\x4'").is_err());
+}
+
+
+
+#[test]
+fn long_else_if_chains_give_each_branch_its_own_scope() {
+    // the chain is inside a loop, whose block is given its scope by the parser after the blocks within it
+    let mut source = String::from("fn void main() {\n    let int n = 7;\n    loop {\n        if (n, 0)== {\n            let int value = 0;\n            print << value;\n        }");
+    for branch in 1..20 {
+        source += &format!(" else if (n, {0})== {{\n            let int value = {0};\n            print << value;\n        }}", branch);
+    }
+    source += "\n        break;\n    }\n}";
+
+    let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let blocks:Vec<(String, usize)> = symbol_table.rows.iter().filter_map(|row| match row {
+        frontend::semantics::SymbolTableRow::ScopeBlock {identifier, scope, ..} => Some((identifier.to_owned(), *scope)),
+        _ => None
+    }).collect();
+
+    let identifiers:BTreeSet<&String> = blocks.iter().map(|(identifier, _)| identifier).collect();
+    let scopes:BTreeSet<usize> = blocks.iter().map(|(_, scope)| *scope).collect();
+    assert_eq!((blocks.len(), identifiers.len(), scopes.len()), (21, 21, 21), "{:?}", blocks);
+
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap(), Expectation {exit_code: 0, output: "7".to_owned()});
+}
+
+
+#[test]
+fn shadowed_variables_are_reported() {
+    let source = "fn void show(int x) {\n    loop {\n        let int x = 8;\n        break;\n    }\n}\n\nfn void main() {\n    let int y = 1;\n    if (y, 0)> {\n        let int y = 2;\n    }\n    let int z = 3;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let warnings = frontend::semantics::find_shadowed_variables(&ast);
+    assert_eq!(warnings, vec![
+        Warning::ShadowsVariable {identifier: "x".to_owned(), line: 3, outer_line: None},
+        Warning::ShadowsVariable {identifier: "y".to_owned(), line: 11, outer_line: Some(9)}
+    ]);
+
+    assert!(warnings[0].to_string().contains("hides the parameter of the same name"));
+    assert!(warnings[1].to_string().contains("hides the variable of the same name declared on line 9"));
+}
+
+
+#[test]
+fn code_is_generated_from_the_types_semantic_analysis_resolved() {
+    let source = "fn long next(long n) {\n    return (n, 1l)+;\n}\n\nfn void main() {\n    let long big = 3l;\n    next(big);\n    let int small = int(big);\n    repeat big {\n        print << small;\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let decorated = frontend::semantics::semantic_validation(ast.clone(), &symbol_table).unwrap();
+
+    let statements = match &decorated[1] {
+        frontend::ast::ASTNode::Function {statements, ..} => statements,
+        other => panic!("{:?} is not a function", other)
+    };
+
+    assert!(matches!(&statements[1], frontend::ast::ASTNode::ExpressionStatement {resolved_type: Some(Type::Long), ..}));
+    match &statements[2] {
+        frontend::ast::ASTNode::VarDeclStatement {value, ..} => assert!(format!("{:?}", value).contains("resolved_type: Some(Long)")),
+        other => panic!("{:?} is not a declaration", other)
+    }
+
+    match &statements[3] {
+        frontend::ast::ASTNode::ForLoop {resolved_type: Some(Type::Long), statements, ..} => {
+            assert!(matches!(&statements[0], frontend::ast::ASTNode::PrintStatement {resolved_types: Some(types), ..} if types == &vec![Type::Integer]));
+        },
+        other => panic!("{:?} is not a repeat loop over a long", other)
+    }
+
+    let instructions:Vec<String> = frontend::intermediate_gen::generate_program_intermediate(decorated, &symbol_table).unwrap().iter().map(|instr| instr.to_string()).collect();
+    for expected in ["    pop.i64", "    cast i64 -> i32", "    out.i32"] {
+        assert!(instructions.iter().any(|instr| instr == expected), "missing {} in {:?}", expected, instructions);
+    }
+
+    // code generation relies on the types being resolved rather than working them out again
+    let undecorated = panic::catch_unwind(|| frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap()).unwrap_err();
+    let message = undecorated.downcast_ref::<String>().unwrap();
+    assert!(message.contains("was not resolved by semantic analysis"), "{}", message);
+}
+
+
+#[test]
+fn missing_variables_and_functions_are_internal_errors() {
+    let source = "fn void main() {\n    let mut int total = 1;\n    let int step_size = 2;\n    total = step_size;\n    for int i = 0 until 3 {\n        total = (total, i)+;\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let decorated = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    assert!(frontend::intermediate_gen::generate_program_intermediate(decorated.clone(), &symbol_table).is_ok());
+
+    // semantic analysis would reject a use of an undeclared variable, so the declaration is removed from the
+    // validated AST to leave the assignment reading a variable the memory map does not have
+    let mut crafted = decorated;
+    match &mut crafted[0] {
+        frontend::ast::ASTNode::Function {statements, ..} => {
+            statements.remove(1);
+        },
+        other => panic!("{:?} is not a function", other)
+    }
+
+    let err = frontend::intermediate_gen::generate_program_intermediate(crafted, &symbol_table).unwrap_err();
+    assert_eq!(err.to_string(), "Internal compiler error: the variable step_size of function main is not in the memory map, which only holds \
+        [total] for that function. This is a bug in the compiler.");
+
+    // likewise semantic analysis would reject a call to a function which does not exist, so it is skipped
+    let ast = frontend::parser::parse_program("fn void main() {\n    missing();\n}", DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let err = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap_err();
+    assert_eq!(err.to_string(), "Internal compiler error: the function missing is called but was not found by semantic analysis. \
+        This is a bug in the compiler.");
+}
+
+
+#[test]
+fn floats_are_printed_to_a_number_of_decimal_places() {
+    let source = "fn void main() {\n    let float pi = 3.14159;\n    print << pi:2;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "3.14");
+
+    // the precision is kept in the intermediate code and passed to the library routine
+    let out = IntermediateInstr::Out(Type::Float, PrintFormat::Precision(2));
+    assert_eq!(out.to_string(), "    out.f32 2");
+    assert_eq!(format_ir(&frontend::intermediate_gen::parse_intermediate(&out.to_string()).unwrap()), "out.f32 2\n");
+
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("\tli $a0, 2\n\tsw $ra, 4($sp)\n\tjal __print_float\n"));
+    assert!(assembly.contains("__print_float:") && !assembly.contains("__print_hex:"));
+    if let Some(simulator) = find_simulator() {
+        assert_eq!(run_mips(&assembly, "float_precision", &simulator, "").unwrap(), "3.14");
+    }
+}
+
+
+#[test]
+fn semantic_trace_records_each_lookup() {
+    let source = "fn void main() {\n    let int x = 5;\n    let int z = (x, 1)+;\n    if (z, 5)> {\n        y = z;\n    }\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let trace = Trace::new(true);
+    assert!(frontend::semantics::semantic_validation_with_trace(ast, &symbol_table, &trace).is_err());
+
+    // each lookup is indented under the statement it was made for, ending with the one which failed
+    let lines = trace.get_lines();
+    assert_eq!(lines[0], "function main in scopes [0]");
+    assert!(lines.contains(&String::from("  variable declaration of z on line 3 in scopes [0, 1, 1]")));
+    assert!(lines.contains(&String::from("    lookup x in scopes [0, 1, 1]: found int variable in scope 1")));
+    assert!(lines.contains(&String::from("    type of identifier z is int")));
+    assert_eq!(lines.last().unwrap(), "      lookup y in scopes [0, 1, 1, 1, 2]: not found");
+
+    // nothing is kept without --trace-semantics
+    let disabled = Trace::disabled();
+    disabled.log(|| panic!("the line of a disabled trace should never be built"));
+    assert!(disabled.get_lines().is_empty());
+}
+
+
+#[test]
+fn data_section_sizes_are_counted_by_kind() {
+    let instructions = vec![
+        IntermediateInstr::FuncStart(String::from("main")),
+        IntermediateInstr::DeclareStatic(Type::Long, String::from("__static_total"), Argument::Long(1)),
+        IntermediateInstr::DeclareStatic(Type::Double, String::from("__static_ratio"), Argument::Double(0.5)),
+        IntermediateInstr::Push(Type::String, Argument::String(String::from("hi\\n"))),
+        IntermediateInstr::Push(Type::String, Argument::String(String::from("hi\\n"))),
+        IntermediateInstr::Push(Type::String, Argument::String(String::from("bye"))),
+        IntermediateInstr::Push(Type::Char, Argument::Char('x')),
+        IntermediateInstr::Push(Type::Float, Argument::Float(1.5)),
+        IntermediateInstr::Push(Type::Float, Argument::Float(2.5)),
+        IntermediateInstr::Push(Type::Double, Argument::Double(1.5)),
+        IntermediateInstr::In(Type::String, 10),
+        IntermediateInstr::In(Type::String, 20),
+        IntermediateInstr::FuncEnd(String::from("main"))
+    ];
+
+    // an identical string is stored once, and an escape is a single byte
+    let sizes = backend::mips::get_data_sizes(&instructions, &backend::mips::CodegenOptions::default()).unwrap();
+    assert_eq!(sizes, backend::mips::DataSizes {strings: 8, chars: 1, floats: 8, doubles: 8, statics: 16, jump_tables: 0, input_buffers: 22, stack: 0});
+    assert_eq!(sizes.get_total(), 63);
+
+    // every input has its own buffer when they are distinct
+    let options = backend::mips::CodegenOptions {distinct_input_buffers: true, ..Default::default()};
+    assert_eq!(backend::mips::get_data_sizes(&instructions, &options).unwrap().input_buffers, 34);
+}
+
+
+#[test]
+fn size_budget_counts_code_and_data() {
+    let source = "fn void main() {\n    print << \"hello\\n\";\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+
+    let options = backend::mips::CodegenOptions::default();
+    let mut sizes = SizeReport::new(&instructions, &symbol_table, true);
+    assert_eq!(sizes.get_total_bytes(), None);
+
+    sizes.add_data_sizes(backend::mips::get_data_sizes(&instructions, &options).unwrap());
+    let counts = backend::mips::generate_mips(instructions, &mut vec![], &symbol_table, &options).unwrap();
+    sizes.add_mips_counts(&counts);
+
+    // the string takes 7 bytes with its terminator, and each instruction of main takes 4
+    let report:serde_json::Value = serde_json::from_str(&sizes.to_json()).unwrap();
+    assert_eq!(report["data"]["strings"], 7);
+    assert_eq!(report["data"]["total"], 7);
+    assert_eq!(report["functions"][0]["text_bytes"], 4 * counts["main"]);
+    assert_eq!(sizes.get_total_bytes(), Some(4 * counts["main"] as u64 + 7));
+    assert!(sizes.to_string().lines().last().unwrap().starts_with("text and data"));
+}
+
+
+#[test]
+fn lists_can_span_lines_and_end_with_a_comma() {
+    let parse = |source:&str| frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let expected = parse("fn int add(int a, int b) {\n    return (a, b)+;\n}\n\nfn void main() {\n    let int x = add(1, 2);\n}");
+
+    // the lines are kept the same so that only the layout within them differs
+    let trailing = parse("fn int add(int a, int b,) {\n    return (a, b)+;\n}\n\nfn void main() {\n    let int x = add(1, 2,);\n}");
+    assert_eq!(trailing, expected);
+
+    let split = parse("fn int add(\n    int a,\n    int b,\n)\n{\n    return (a, b)+;\n}\n\nfn void main()\n{\n    let int x = add(\n        1,\n        2\n    );\n}");
+    let names = |ast:&[frontend::ast::ASTNode]| ast.iter().map(|node| match node {
+        frontend::ast::ASTNode::Function {identifier, parameters, ..} => (identifier.clone(), parameters.len()),
+        other => panic!("{:?} is not a function", other)
+    }).collect::<Vec<_>>();
+    assert_eq!(names(&split), vec![(String::from("add"), 2), (String::from("main"), 0)]);
+
+    // a comma is only allowed after an element
+    assert!(frontend::parser::parse_program("fn void main() {\n    let int x = add(,);\n}", DEFAULT_NESTING_LIMIT).is_err());
+    assert!(frontend::parser::parse_program("fn int add(int a,, int b) {\n    return a;\n}", DEFAULT_NESTING_LIMIT).is_err());
+}
+
+
+#[test]
+fn parsing_continues_after_a_function_with_a_syntax_error() {
+    let source = "fn int broken(int a) {\n    let int b = (a, 1)+\n    return b;\n}\n\n@inline\nfn int valid(int a) {\n    return (a, 2)*;\n}\n\nfn void main() {\n    let string s = \"fn {\";\n    print << valid(2;\n}\n";
+    let (ast, errors) = frontend::parser::parse_program_with_errors(source, DEFAULT_NESTING_LIMIT).unwrap();
+    assert_eq!(errors.len(), 2, "Unexpected errors: {:?}", errors);
+    assert!(errors.iter().all(|err| err.is::<pest::error::Error<frontend::parser::Rule>>()));
+
+    // each error is given the line it is on in the whole file
+    let lines:Vec<usize> = errors.iter().map(|err| Diagnostic::from_error(err.as_ref(), "recovery.iri").line.unwrap()).collect();
+    assert_eq!(lines, vec![2, 13]);
+
+    match &ast[..] {
+        [frontend::ast::ASTNode::Function {identifier, inline, ..}] => assert_eq!((identifier.as_str(), *inline), ("valid", true)),
+        other => panic!("Expected only the valid function to be parsed, but found {:?}", other)
+    }
+
+    // the first error is still returned on its own when the errors are not wanted
+    assert!(frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).is_err());
+    assert!(frontend::parser::parse_program_with_errors(&source.replace("(a, 1)+\n", "(a, 1)+;\n").replace("valid(2;", "s;"), DEFAULT_NESTING_LIMIT).unwrap().1.is_empty());
+}
+
+
+#[test]
+fn watch_mode_waits_for_the_file_to_stop_changing() {
+    let time = |seconds:u64| Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds));
+
+    // unchanged, being replaced, changed, still changing, being replaced again, then the same twice in a row
+    let mut polls = vec![time(1), None, time(2), time(3), None, time(3), time(3)].into_iter();
+    let mut intervals = vec![];
+    let settled = crate::wait_for_change(time(1), |interval| {
+        intervals.push(interval);
+        polls.next().expect("polled after the file had settled")
+    });
+
+    assert_eq!(settled, time(3));
+    let (poll, debounce) = (crate::WATCH_POLL_INTERVAL, crate::WATCH_DEBOUNCE);
+    assert_eq!(intervals, vec![poll, poll, poll, debounce, debounce, debounce, debounce]);
+}
+
+
+#[test]
+fn help_lists_every_flag_and_target() {
+    let help = crate::get_help_text();
+    for option in &crate::CLI_OPTIONS {
+        assert!(help.lines().any(|line| line.trim_start().starts_with(option.name)), "{} is missing from the help:\n{}", option.name, help);
+    }
+
+    assert!(help.contains("-mips  MIPS assembly"), "Unexpected help:\n{}", help);
+    assert!(help.contains("-run"));
+
+    // every flag without a value can be read by the same table
+    let flags:Vec<String> = crate::CLI_OPTIONS.iter().filter(|option| option.value.is_none()).map(|option| option.name.to_owned()).collect();
+    let options = crate::parse_options(&flags);
+    assert!(options.prune_branches && options.checked && options.size_budget_hard && options.trace_semantics);
+}
+
+
+#[test]
+fn backends_are_found_by_name() {
+    let source = "fn void main() {\n    print << \"hi\";\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+
+    let options = backend::mips::CodegenOptions::default();
+    let names:Vec<&str> = backend::get_backends(&options).iter().map(|backend| backend.get_name()).collect();
+    assert_eq!(names, vec!["mips"]);
+    assert!(backend::find_backend("x64", &options).is_none());
+
+    // the MIPS backend gives exactly what generating MIPS directly does
+    let mips = backend::find_backend("mips", &options).unwrap();
+    assert_eq!(mips.get_extension(), "asm");
+    let mut output:Vec<u8> = vec![];
+    let counts = mips.generate(instructions.clone(), &symbol_table, &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap());
+    assert!(counts["main"] > 0);
+}
+
+
+#[test]
+fn debugger_stops_at_breakpoints_with_the_stack_and_variables() {
+    let source = "fn int add(int a, int b) {\n    let int total = (a, b)+;\n    return total;\n}\n\nfn void main() {\n    let int x = 4;\n    let int y = add(x, 3);\n    print << y;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let (instructions, variables) = frontend::intermediate_gen::generate_program_intermediate_with_variables(ast, &symbol_table).unwrap();
+
+    let debug = |commands:&str| {
+        let interpreter = backend::interpreter::Interpreter::new(&instructions, &symbol_table);
+        let mut debugger = backend::debugger::Debugger::new(interpreter, &instructions, &variables, MessageFormat::Json);
+        let (mut output, mut debug_output) = (vec![], vec![]);
+        let exit_code = debugger.run(&mut commands.as_bytes(), &mut output, &mut debug_output).unwrap();
+        let events:Vec<serde_json::Value> = String::from_utf8(debug_output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        (exit_code, String::from_utf8(output).unwrap(), events)
+    };
+
+    // both operands of the addition are on the stack once the parameters have been stored and loaded again
+    let (exit_code, output, events) = debug("break add\ncontinue\nprint a\nstep\nstep\nstep\nstep\nstep\nstep\nstack\nprint 0\nprint b\ncontinue\n");
+    assert_eq!(events[0]["event"], "stopped");
+    assert_eq!(events[0]["function"], "main");
+    assert_eq!(events[1]["event"], "breakpoint");
+    assert_eq!((&events[2]["reason"], &events[2]["function"]), (&serde_json::json!("breakpoint"), &serde_json::json!("add")));
+    assert_eq!(events[3]["event"], "error");
+
+    let stack = events.iter().find(|event| event["event"] == "stack").unwrap();
+    assert_eq!(stack["values"], serde_json::json!([{"type": "int", "value": "4"}, {"type": "int", "value": "3"}]));
+
+    let variables:Vec<_> = events.iter().filter(|event| event["event"] == "variable").collect();
+    assert_eq!((&variables[0]["name"], &variables[0]["value"]), (&serde_json::json!("0"), &serde_json::json!("4")));
+    assert_eq!((&variables[1]["name"], &variables[1]["id"], &variables[1]["value"]), (&serde_json::json!("b"), &serde_json::json!(1), &serde_json::json!("3")));
+    assert_eq!(events.last().unwrap(), &serde_json::json!({"event": "exited", "code": 0}));
+    assert_eq!((exit_code, output.as_str()), (0, "7"));
+
+    // the program is left where it stopped when the debugger quits, and bad commands are reported
+    let (_, output, events) = debug("step\nbreak nowhere\njump\nquit\ncontinue\n");
+    assert_eq!(output, "");
+    assert_eq!(events.iter().filter(|event| event["event"] == "error").count(), 2);
+    assert_eq!(events.last().unwrap()["event"], "error");
+}
+
+
+#[test]
+fn stack_usage_counts_each_recursive_cycle_once() {
+    let source = fs::read_to_string("tests/programs/mutual_recursion.iri").unwrap();
+    let ast = frontend::parser::parse_program(&source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let call_graph = frontend::call_graph::CallGraph::new(&ast);
+    assert_eq!(call_graph.get_cycles(), [["is_even", "is_odd"]]);
+
+    // each call to is_even takes its argument, the return address, the frame pointer and two words of frame
+    let report = StackReport::new(&call_graph, &symbol_table, &backend::mips::CodegenOptions::default()).unwrap();
+    let json:serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["functions"][0], serde_json::json!({"name": "is_even", "call_size": 20, "deepest": 40}));
+    assert_eq!(json["deepest_path"], serde_json::json!(["main", "sum_squares", "square"]));
+    assert_eq!(report.get_max_depth(), Some(84));
+    assert_eq!(report.get_cycles()[0].iteration_size, 40);
+    assert!(report.get_cycles()[0].get_warning().starts_with("recursion through is_even, is_odd takes up 40 bytes"));
+    assert!(report.to_string().contains("\ndeepest path: main -> sum_squares -> square (84 bytes)\n"));
+
+    // the canaries of --frame-checks only grow the frames which had no room left in their last doubleword,
+    // which brings the recursive path level with the other, and the first one main calls is given
+    let options = backend::mips::CodegenOptions {frame_checks: true, ..Default::default()};
+    let report = StackReport::new(&call_graph, &symbol_table, &options).unwrap();
+    let json:serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(report.get_cycles()[0].iteration_size, 56);
+    assert_eq!(json["deepest_path"], serde_json::json!(["main", "is_even", "is_odd"]));
+    assert_eq!(json["max_depth"], 84);
+
+    // a function which calls itself is a cycle of its own, and a cycle main never reaches is not reported
+    let source = "fn int count(int n) {\n    return count(n);\n}\nfn void main() {\n    print << \"hi\";\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let call_graph = frontend::call_graph::CallGraph::new(&ast);
+    assert_eq!(call_graph.get_cycles(), [["count"]]);
+    assert!(StackReport::new(&call_graph, &symbol_table, &backend::mips::CodegenOptions::default()).unwrap().get_cycles().is_empty());
+}
+
+
+#[test]
+fn stack_size_moves_the_stack_into_the_data_section() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/mutual_recursion.iri"), false).unwrap();
+    let generate = |stack_size:Option<u64>| {
+        let mut buffer:Vec<u8> = vec![];
+        let options = backend::mips::CodegenOptions {stack_size, ..Default::default()};
+        backend::mips::generate_mips(instructions.clone(), &mut buffer, &symbol_table, &options).map(|_| String::from_utf8(buffer).unwrap())
+    };
+
+    // the stack pointer starts on the last word of the space, before main saves the frame pointer there
+    let assembly = generate(Some(4096)).unwrap();
+    assert!(assembly.contains("\t.align 3\n\t__stack: .space 4096\n"));
+    let startup = assembly.find("\tla $sp, __stack").unwrap();
+    assert!(startup < assembly.find("fn_main:").unwrap());
+    assert!(assembly[startup..].starts_with("\tla $sp, __stack # use the 4096 byte stack in the data section\n\tli $t0, 4092\n\taddu $sp, $sp, $t0\n"));
+    assert!(!generate(None).unwrap().contains("__stack:"));
+
+    let options = backend::mips::CodegenOptions {stack_size: Some(4096), ..Default::default()};
+    assert_eq!(backend::mips::get_data_sizes(&instructions, &options).unwrap().stack, 4096);
+
+    // the stack has to fit in the data section before the heap starts
+    let err = generate(Some(backend::mips::DATA_SEGMENT_SIZE)).unwrap_err();
+    assert!(err.is::<crate::errors::StackSizeTooLarge>());
+    assert_eq!(crate::parse_options(&[String::from("--stack-size"), String::from("4096")]).stack_size, Some(4096));
+}
+
+
+#[test]
+fn dead_stores_are_removed_unless_their_value_can_fail() {
+    let generate = |source:&str| {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+        let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+        frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap()
+    };
+
+    // y is never read, so its store goes along with the arithmetic and loads feeding it
+    let instructions = generate("fn void main() {\n    let int x = 4;\n    let int y = (x, 2)*;\n    print << x;\n}");
+    assert_eq!(format_ir(&instructions), "func_start main\n    push.i32 4\n    store.i32 %0\n    load.i32 %0\n    push.i32 2\n    mult\n    store.i32 %1\n    load.i32 %0\n    out.i32\nfunc_end main\n");
+    assert_eq!(format_ir(&optimisation::liveness::eliminate_dead_stores(instructions.clone(), backend::OverflowMode::Wrap)), "func_start main\n    push.i32 4\n    store.i32 %0\n    load.i32 %0\n    out.i32\nfunc_end main\n");
+
+    // the multiplication can overflow when it traps, so it is kept
+    let trapping = optimisation::liveness::eliminate_dead_stores(instructions.clone(), backend::OverflowMode::Trap);
+    assert_eq!(format_ir(&trapping), format_ir(&instructions));
+
+    // division, pow and indexing a string are kept with the stores of their unused results, so dividing by
+    // zero still fails and --checked still finds the index to guard
+    let source = "fn void main() {\n    let int z = 0;\n    let int y = (5, z)/;\n    let int p = pow(2, 3);\n    let string s = \"ab\";\n    for char c in s {\n        print << \"x\";\n    }\n}";
+    let instructions = generate(source);
+    let optimised = optimisation::liveness::eliminate_dead_stores(instructions.clone(), backend::OverflowMode::Wrap);
+    assert_eq!(format_ir(&optimised), format_ir(&instructions));
+    assert!(format_ir(&optimised).contains("    push.i32 5\n    load.i32 %0\n    div\n    store.i32 %1\n    push.i32 2\n    push.i32 3\n    pow\n    store.i32 %2\n"));
+    assert!(format_ir(&frontend::intermediate_gen::add_bounds_checks(optimised.clone())).contains("check_index"));
+
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast).unwrap();
+    let err = run_interpreter(&optimised, &symbol_table, "").unwrap_err();
+    assert_eq!(err.to_string(), "Division by zero in function main.");
+}
+
+
+#[test]
+fn input_must_read_at_least_one_character() {
+    assert!(validate_source("fn void main() {\n    let string s = input 1;\n}").is_ok());
+
+    let err = validate_source("fn void main() {\n    let string s = input 0;\n}").unwrap_err();
+    assert!(err.is::<crate::errors::EmptyInput>(), "unexpected error: {}", err);
+    assert_eq!(Diagnostic::from_error(err.as_ref(), "input.iri").code, "empty-input");
+}
+
+
+#[test]
+fn duplicate_symbols_are_reported_with_both_definitions() {
+    let generate = |source:&str| {
+        let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+        frontend::semantics::generate_symbol_table(ast)
+    };
+
+    let duplicate = |source:&str| -> crate::errors::DuplicateSymbolError {
+        let err = generate(source).unwrap_err();
+        *err.downcast::<crate::errors::DuplicateSymbolError>().unwrap_or_else(|err| panic!("unexpected error for {}: {}", source, err))
+    };
+
+    // two functions of the same name are always an error
+    let err = duplicate("fn int f() {\n    return 1;\n}\n\nfn int f() {\n    return 2;\n}\nfn void main() {}");
+    assert_eq!(err.to_string(), "Duplicate identifier f: function in global scope on line 5 is already defined as a function in global scope on line 1.");
+    assert_eq!(Diagnostic::from_error(&err, "duplicate.iri").line, Some(5));
+
+    // as are two variables in the same scope, including parameters and loop variables
+    let err = duplicate("fn void main() {\n    let int x = 1;\n    let int x = 2;\n}");
+    assert_eq!(err.to_string(), "Duplicate identifier x: variable in function main on line 3 is already defined as a variable in function main on line 2.");
+
+    let err = duplicate("fn int f(int x,\n         int x) {\n    return x;\n}\nfn void main() {}");
+    assert_eq!((err.existing_line, err.duplicate_line), (Some(1), Some(2)));
+
+    let err = duplicate("fn int f(int x) {\n    let int x = 1;\n    return x;\n}\nfn void main() {}");
+    assert_eq!((err.existing_kind.as_str(), err.existing_line, err.duplicate_line), ("variable", Some(1), Some(2)));
+
+    let err = duplicate("fn void main() {\n    for int i = 0 until 3 {\n        let int i = 1;\n    }\n}");
+    assert_eq!((err.existing_line, err.duplicate_line), (Some(2), Some(3)));
+
+    // a variable may share its name with a function, or with a variable in another function or block
+    for source in ["fn int f() {\n    return 1;\n}\nfn void main() {\n    let int f = 2;\n}",
+                   "fn int f() {\n    let int x = 1;\n    return x;\n}\nfn void main() {\n    let int x = 2;\n}",
+                   "fn void main() {\n    let int x = 1;\n    if true {\n        let int x = 2;\n    }\n}"] {
+        assert!(generate(source).is_ok(), "unexpected error for {}", source);
+    }
+}
+
+
+#[test]
+fn while_conditions_are_validated() {
+    assert!(validate_source("fn void main() {\n    let int y = 0;\n    while (y, 1)< {\n        break;\n    }\n}").is_ok());
+
+    let err = validate_source("fn void main() {\n    while 5 {\n        break;\n    }\n}").unwrap_err();
+    assert!(err.is::<NonBooleanCondition>(), "unexpected error: {}", err);
+
+    let err = validate_source("fn void main() {\n    while (y, 1)< {\n        break;\n    }\n}").unwrap_err();
+    assert!(err.is::<SymbolNotFoundError>(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn ternary_arms_and_conditions_are_validated() {
+    assert!(validate_source("fn void main() {\n    let bool big = (3, 2)>;\n    let int x = big ? 1 : 2;\n}").is_ok());
+
+    // the arms must have the same type as each other, and as the variable given their value
+    let err = validate_source("fn void main() {\n    let int x = true ? 1 : \"no\";\n}").unwrap_err();
+    assert!(err.is::<MismatchedTernaryArms>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "Both arms of a ternary expression must have the same type, found int and string.");
+
+    let err = validate_source("fn void main() {\n    let int x = true ? \"a\" : \"b\";\n}").unwrap_err();
+    assert!(err.is::<IncorrectDatatype>(), "unexpected error: {}", err);
+
+    // and the condition must be a bool
+    let err = validate_source("fn void main() {\n    let int x = 5 ? 1 : 2;\n}").unwrap_err();
+    assert!(err.is::<NonBooleanCondition>(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn discarded_return_values_are_popped() {
+    let source = "fn int compute(int n) {\n    return (n, 2)*;\n}\n\nfn long compute_long() {\n    return 5l;\n}\n\nfn int run() {\n    let int a = 3;\n    compute(a);\n    compute_long();\n    let int b = (a, 4)+;\n    return (b, 1)-;\n}\n\nfn void main() {\n    let int r = run();\n    print << r;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let ast = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+    let instructions = frontend::intermediate_gen::generate_program_intermediate(ast, &symbol_table).unwrap();
+
+    // each call made as a statement has its return value popped straight away
+    let ir = format_ir(&instructions);
+    assert!(ir.contains("    call compute/1 -> i32\n    pop.i32\n"), "unexpected intermediate code:\n{}", ir);
+    assert!(ir.contains("    call compute_long/0 -> i64\n    pop.i64\n"), "unexpected intermediate code:\n{}", ir);
+
+    // so the arithmetic after the calls works on the right values in both backends
+    assert_eq!(run_interpreter(&instructions, &symbol_table, "").unwrap().output, "6");
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+    assert!(assembly.contains("\taddi $sp, $sp, 4 # pop int\n"));
+    assert!(assembly.contains("\taddi $sp, $sp, 8 # pop long\n"));
+}
+
+
+#[test]
+fn functions_named_like_internal_labels_get_their_own_labels() {
+    let (instructions, symbol_table) = compile(Path::new("tests/programs/reserved_names.iri"), false).unwrap();
+    let assembly = backend::mips::generate_mips_to_string(instructions, &symbol_table).unwrap();
+
+    // every label is defined once, so the user's functions cannot clash with the backend's or the library's
+    let mut labels:Vec<&str> = assembly.lines()
+        .filter_map(|line| line.split('#').next().unwrap().trim().strip_suffix(':'))
+        .filter(|label| !label.starts_with('.'))
+        .collect();
+    let label_count = labels.len();
+    labels.sort();
+    labels.dedup();
+    assert_eq!(labels.len(), label_count, "a label is defined more than once");
+    for label in ["fn_end", "fn_push", "fn_strlen", "__end", "__strlen"] {
+        assert!(labels.contains(&label), "label {} is missing", label);
+    }
+
+    let err = validate_source("fn void __end() {}\nfn void main() {}").unwrap_err();
+    assert!(err.is::<ReservedIdentifier>(), "unexpected error: {}", err);
+    let err = validate_source("fn void main() {\n    let int __t_1 = 0;\n}").unwrap_err();
+    assert!(err.is::<ReservedIdentifier>(), "unexpected error: {}", err);
+}
+
+
+#[test]
+fn internal_compiler_errors_say_what_was_being_compiled() {
+    let source = "fn void main() {\n    let mut int total = 1;\n    total = 2;\n}";
+    let ast = frontend::parser::parse_program(source, DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let mut crafted = frontend::semantics::semantic_validation(ast, &symbol_table).unwrap();
+
+    // semantic analysis only lets values be assigned to variables, so the assignment is given a break statement
+    match &mut crafted[0] {
+        frontend::ast::ASTNode::Function {statements, ..} => match &mut statements[1] {
+            frontend::ast::ASTNode::VarAssignStatement {value, ..} => **value = frontend::ast::ASTNode::Break,
+            other => panic!("{:?} is not a variable assignment", other)
+        },
+        other => panic!("{:?} is not a function", other)
+    }
+
+    let err = frontend::intermediate_gen::generate_program_intermediate(crafted, &symbol_table).unwrap_err();
+    assert_eq!(err.to_string(), "Internal compiler error: the variable total cannot be given the value of a break statement. \
+        This is a bug in the compiler.");
+
+    // panics are reported by the hook along with the function and statement being compiled
+    let description = errors::describe_internal_error("function 'main', variable assignment on line 3", "index out of bounds",
+        Some("src/frontend/intermediate_gen.rs:10:5".to_owned()));
+    assert_eq!(description, "internal compiler error while compiling function 'main', variable assignment on line 3: index out of bounds\n  \
+        raised at src/frontend/intermediate_gen.rs:10:5\nThis is a bug in the compiler, please report it along with the program being compiled.");
+    assert!(!errors::describe_internal_error("function 'main'", "unknown error", None).contains("raised at"));
+}
+
+
+#[test]
+fn calls_to_missing_functions_suggest_similar_names() {
+    let err = validate_source("fn int print_line(int n) {\n    return n;\n}\n\nfn void main() {\n    let int a = print_lne(1);\n}").unwrap_err();
+    assert!(err.is::<SymbolNotFoundError>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "Could not find symbol print_lne called in function main. Did you mean `print_line`?");
+
+    // nothing is suggested when no function is close
+    let err = validate_source("fn int print_line(int n) {\n    return n;\n}\n\nfn void main() {\n    let int a = square(1);\n}").unwrap_err();
+    assert_eq!(err.to_string(), "Could not find symbol square called in function main.");
+}
+
+
+#[test]
+fn only_functions_which_are_not_recursive_can_be_inlined() {
+    let ast = validate_source("@inline\nfn int twice(int n) {\n    return (n, 2)*;\n}\n\nfn void main() {\n    let int a = twice(1);\n}").unwrap();
+    match &ast[0] {
+        frontend::ast::ASTNode::Function {identifier, inline, ..} => assert!(inline, "{} is not marked inline", identifier),
+        other => panic!("{:?} is not a function", other)
+    }
+
+    let err = validate_source("@inline\nfn int count(int n) {\n    return count(n);\n}\n\nfn void main() {\n    let int a = count(1);\n}").unwrap_err();
+    assert!(err.is::<RecursiveInlineFunction>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "Function count is marked @inline but is recursive, so cannot be inlined.");
+
+    // recursion through another function counts too
+    let err = validate_source("@inline\nfn int ping(int n) {\n    return pong(n);\n}\n\nfn int pong(int n) {\n    return ping(n);\n}\n\nfn void main() {\n    let int a = ping(1);\n}").unwrap_err();
+    assert!(err.is::<RecursiveInlineFunction>(), "unexpected error: {}", err);
+
+    // calling a recursive function does not make a function recursive itself
+    assert!(validate_source("@inline\nfn int first(int n) {\n    return count(n);\n}\n\nfn int count(int n) {\n    return count(n);\n}\n\nfn void main() {\n    let int a = first(1);\n}").is_ok());
+}
+
+
+#[test]
+fn variables_cannot_be_used_before_they_are_declared() {
+    let err = validate_source("fn void main() {\n    let int y = x;\n    let int x = 5;\n}").unwrap_err();
+    assert!(err.is::<UseBeforeDeclaration>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "Variable x is used on line 2 before it is declared on line 3.");
+
+    // a use in a nested scope before the declaration is rejected too, but one after it is fine
+    let err = validate_source("fn void main() {\n    if true {\n        print << x;\n    }\n    let int x = 5;\n}").unwrap_err();
+    assert!(err.is::<UseBeforeDeclaration>(), "unexpected error: {}", err);
+    assert!(validate_source("fn void main() {\n    let int x = 5;\n    if true {\n        let int y = x;\n        print << y;\n    }\n}").is_ok());
+}
+
+
+#[test]
+fn values_of_void_calls_cannot_be_used() {
+    let log = "fn void log_message() {\n    print << \"logged\";\n}\n\nfn int twice(int n) {\n    return (n, 2)*;\n}\n\n";
+    for statement in ["let int x = log_message();", "let int x = (log_message(), 1)+;", "if log_message() {}", "let int x = twice(log_message());"] {
+        let source = format!("{}fn void main() {{\n    {}\n}}", log, statement);
+        let err = validate_source(&source).unwrap_err();
+        assert!(err.is::<VoidValueUsed>(), "unexpected error for {}: {}", statement, err);
+        assert!(err.to_string().starts_with("The call log_message() returns void"), "unexpected error for {}: {}", statement, err);
+    }
+
+    let err = validate_source(&format!("{}fn int run() {{\n    return log_message();\n}}\n\nfn void main() {{}}", log)).unwrap_err();
+    assert!(err.is::<VoidValueUsed>(), "unexpected error: {}", err);
+
+    // calling it as a statement is still fine
+    assert!(validate_source(&format!("{}fn void main() {{\n    log_message();\n}}", log)).is_ok());
+}
+
+
+#[test]
+fn comparisons_of_mismatched_or_unsupported_types_are_errors() {
+    let err = validate_source("fn void main() {\n    let int x = 1;\n    if (x, \"a\")== {\n    }\n}").unwrap_err();
+    assert!(err.is::<MismatchedOperandTypes>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "The == operator cannot be applied to values of types int and string on line 3, as both must have the same type.");
+
+    // the operands of a comparison are checked before it is
+    let err = validate_source("fn void main() {\n    let int x = 1;\n    if ((x, \"a\")+, 1)== {\n    }\n}").unwrap_err();
+    assert!(err.is::<MismatchedOperandTypes>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "The + operator cannot be applied to values of types int and string on line 3, as both must have the same type.");
+
+    let err = validate_source("fn void main() {\n    let string a = \"x\";\n    if (a, \"b\")> {\n    }\n}").unwrap_err();
+    assert!(err.is::<UnsupportedOperatorType>(), "unexpected error: {}", err);
+    let err = validate_source("fn void main() {\n    let int x = 1;\n    if (x)! {\n    }\n}").unwrap_err();
+    assert!(err.is::<UnsupportedOperatorType>(), "unexpected error: {}", err);
+    let err = validate_source("fn void main() {\n    if (1, 2)&& {\n    }\n}").unwrap_err();
+    assert_eq!(err.to_string(), "The && operator cannot be applied to values of type int on line 2.");
+
+    // the parser never puts anything but boolean terms and expressions in a condition, so one is crafted
+    let ast = frontend::parser::parse_program("fn void main() {\n    if (1, 2)== {\n    }\n}", DEFAULT_NESTING_LIMIT).unwrap();
+    let symbol_table = frontend::semantics::generate_symbol_table(ast.clone()).unwrap();
+    let mut crafted = ast;
+    match &mut crafted[0] {
+        frontend::ast::ASTNode::Function {statements, ..} => match &mut statements[0] {
+            frontend::ast::ASTNode::IfElifElseStatement {statements} => match &mut statements[0] {
+                frontend::ast::ASTNode::IfStatement {condition, ..} => match &mut **condition {
+                    frontend::ast::ASTNode::BooleanExpression {lhs, ..} => **lhs = frontend::ast::ASTNode::Break,
+                    other => panic!("{:?} is not a boolean expression", other)
+                },
+                other => panic!("{:?} is not an if statement", other)
+            },
+            other => panic!("{:?} is not an if statement", other)
+        },
+        other => panic!("{:?} is not a function", other)
+    }
+
+    let err = frontend::semantics::semantic_validation(crafted, &symbol_table).unwrap_err();
+    assert_eq!(err.to_string(), "Internal compiler error: a break statement is not a valid argument to a boolean expression. This is a bug in the compiler.");
+}
+
+
+#[test]
+fn declarations_outside_of_functions_are_internal_errors() {
+    // the grammar only allows parameters inside a function, so one is put at the top level by hand
+    let parameter = frontend::ast::ASTNode::Parameter {param_type: Type::Integer, identifier: "stray".to_owned(), line: 1};
+    let err = frontend::semantics::generate_symbol_table(vec![parameter]).unwrap_err();
+    assert!(err.is::<InternalCompilerError>(), "unexpected error: {}", err);
+    assert_eq!(err.to_string(), "Internal compiler error: the variable stray is not declared inside a function. This is a bug in the compiler.");
+}
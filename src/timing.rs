@@ -0,0 +1,104 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+
+/**
+ * Represents a phase of compilation and how long it took, along with the time taken by each of the passes
+ * run within it, such as the individual optimisation passes.
+ */
+#[derive(Debug)]
+struct Phase {
+    name: &'static str,
+    duration: Duration,
+    passes: Vec<(&'static str, Duration)>
+}
+
+
+/**
+ * Records how long each phase of compiling a program takes, in the order the phases are run. Phases which
+ * were never reached, such as those after a phase which failed, are simply not recorded.
+ */
+#[derive(Debug, Default)]
+pub struct PhaseTimings {
+    phases: Vec<Phase>
+}
+
+impl PhaseTimings {
+    pub fn new() -> PhaseTimings {
+        PhaseTimings {phases: vec![]}
+    }
+
+
+    /**
+     * Runs the given phase of compilation and records how long it took, returning whatever the phase returned.
+     * The time is recorded even if the phase returns an error, so that slow phases can be found in programs
+     * which fail to compile.
+     */
+    pub fn time<T>(&mut self, name:&'static str, phase:impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = phase();
+        self.phases.push(Phase {name, duration: start.elapsed(), passes: vec![]});
+        result
+    }
+
+
+    /**
+     * Runs a single pass within the named phase and records how long it took. The time is added to the phase,
+     * which is started if it is not the phase currently being recorded.
+     */
+    pub fn time_pass<T>(&mut self, phase_name:&'static str, pass_name:&'static str, pass:impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = pass();
+        let duration = start.elapsed();
+
+        if self.phases.last().is_none_or(|phase| phase.name != phase_name) {
+            self.phases.push(Phase {name: phase_name, duration: Duration::ZERO, passes: vec![]});
+        }
+
+        let phase = self.phases.last_mut().unwrap();
+        phase.duration += duration;
+        phase.passes.push((pass_name, duration));
+        result
+    }
+
+
+    /**
+     * Returns the total time taken by every phase recorded.
+     */
+    pub fn get_total(&self) -> Duration {
+        self.phases.iter().map(|phase| phase.duration).sum()
+    }
+}
+
+
+/**
+ * Returns the duration in milliseconds and as a percentage of the total, formatted for a row of the table.
+ */
+fn format_duration(duration:Duration, total:Duration) -> String {
+    let percentage = match total.is_zero() {
+        true => 0.0,
+        false => duration.as_secs_f64() / total.as_secs_f64() * 100.0
+    };
+
+    format!("{:>12.3}ms {:>6.1}%", duration.as_secs_f64() * 1000.0, percentage)
+}
+
+
+impl fmt::Display for PhaseTimings {
+    /**
+     * Formats the timings as a table with a row for each phase followed by the passes within it, and the total
+     * time at the bottom.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.get_total();
+        writeln!(f, "{:<28}{:>14} {:>7}", "Phase", "Time", "Share")?;
+        for phase in &self.phases {
+            writeln!(f, "{:<28}{}", phase.name, format_duration(phase.duration, total))?;
+            for (pass, duration) in &phase.passes {
+                writeln!(f, "  {:<26}{}", pass, format_duration(*duration, total))?;
+            }
+        }
+
+        write!(f, "{:<28}{}", "total", format_duration(total, total))
+    }
+}
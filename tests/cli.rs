@@ -0,0 +1,376 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+
+/**
+ * Runs the compiler with the given arguments, writing the source to its stdin, and returns what it printed
+ * to stdout and stderr along with whether it succeeded.
+ */
+fn run_compiler(args:&[&str], source:&str) -> (String, String, bool) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_iridescent_compiler")).args(args)
+                                                                          .stdin(Stdio::piped())
+                                                                          .stdout(Stdio::piped())
+                                                                          .stderr(Stdio::piped())
+                                                                          .spawn()
+                                                                          .unwrap();
+
+    child.stdin.take().unwrap().write_all(source.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap(), output.status.success())
+}
+
+
+#[test]
+fn program_is_piped_from_stdin_to_stdout() {
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    for output_name in ["-", "--stdout"] {
+        let (stdout, stderr, success) = run_compiler(&["-", output_name, "-mips"], &source);
+        assert!(success, "Compiler failed:\n{}", stderr);
+
+        // nothing but the assembly is written to stdout
+        assert!(stdout.starts_with(".data"), "Unexpected output:\n{}", stdout);
+        assert!(stdout.contains("Hello, world!"));
+        assert!(stdout.ends_with("\tsyscall"));
+        assert!(stderr.contains("Compiling - into"));
+    }
+}
+
+
+#[test]
+fn errors_in_piped_programs_go_to_stderr() {
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-mips"], "fn void main() {\n    let int a = \"a\";\n}\n");
+    assert!(!success);
+    assert!(stdout.is_empty(), "Unexpected output:\n{}", stdout);
+    assert!(stderr.contains("Error"), "Unexpected errors:\n{}", stderr);
+}
+
+
+#[test]
+fn syntax_errors_in_every_function_are_reported_together() {
+    let source = "fn int first() {\n    return 1\n}\n\nfn int second() {\n    return 2;\n}\n\nfn void main() {\n    let int x = ;\n}\n";
+    let (_, stderr, success) = run_compiler(&["-", "-", "-mips", "--message-format", "json"], source);
+    assert!(!success);
+
+    let diagnostics:Vec<&str> = stderr.lines().filter(|line| line.starts_with('{')).collect();
+    assert_eq!(diagnostics.len(), 3, "Unexpected errors:\n{}", stderr);
+    assert!(diagnostics[0].contains("\"line\":2"), "Unexpected error: {}", diagnostics[0]);
+    assert!(diagnostics[1].contains("\"line\":10"), "Unexpected error: {}", diagnostics[1]);
+    assert!(diagnostics[2].contains("\"code\":\"syntax-errors\""), "Unexpected error: {}", diagnostics[2]);
+}
+
+
+#[test]
+fn help_version_and_targets_are_printed_without_a_program() {
+    let (stdout, _, success) = run_compiler(&["--help"], "");
+    assert!(success);
+    assert!(stdout.starts_with("Usage:"), "Unexpected help:\n{}", stdout);
+    assert!(stdout.contains("--max-steps N"));
+
+    let (stdout, _, success) = run_compiler(&["--version"], "");
+    assert!(success);
+    assert_eq!(stdout, format!("iridescent_compiler {}\n", env!("CARGO_PKG_VERSION")));
+
+    let (stdout, _, success) = run_compiler(&["--list-targets"], "");
+    assert!(success);
+    assert!(stdout.starts_with("-mips\t.asm\t"), "Unexpected targets:\n{}", stdout);
+}
+
+
+/**
+ * Creates an empty directory in the temporary directory for a test to write files into.
+ */
+fn create_temp_dir(name:&str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("iridescent_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+
+#[test]
+fn directories_are_built_into_one_assembly_file() {
+    let output = create_temp_dir("build_output").join("program.asm");
+    let (_, stderr, success) = run_compiler(&["build", "tests/projects/split_program", "-o", output.to_str().unwrap()], "");
+    assert!(success, "Compiler failed:\n{}", stderr);
+
+    let assembly = std::fs::read_to_string(output).unwrap();
+    for function in ["fn_main:", "fn_square:", "fn_sum_of_squares:", "fn_label:"] {
+        assert!(assembly.contains(function), "{} is missing from the assembly", function);
+    }
+}
+
+
+#[test]
+fn build_errors_name_the_file_they_are_in() {
+    let directory = create_temp_dir("build_errors");
+    let main_path = directory.join("main.iri");
+    let helpers_path = directory.join("helpers.iri");
+    std::fs::write(&main_path, "fn int twice(int n) {\n    return (n, 2)*;\n}\n\nfn void main() {}\n").unwrap();
+    std::fs::write(&helpers_path, "fn int twice(int n) {\n    return (n, n)+;\n}\n").unwrap();
+
+    let build = || run_compiler(&["build", directory.to_str().unwrap(), "-o", "-"], "");
+    let (_, stderr, success) = build();
+    assert!(!success);
+    assert!(stderr.contains(&format!("Error: {}: Duplicate identifier twice", main_path.display())), "Unexpected errors:\n{}", stderr);
+    assert!(stderr.contains(&format!("already defined as a function in {}", helpers_path.display())), "Unexpected errors:\n{}", stderr);
+
+    std::fs::write(&helpers_path, "fn int triple(int n) {\n    return (n, m)*;\n}\n").unwrap();
+    let (_, stderr, success) = build();
+    assert!(!success);
+    assert!(stderr.contains(&format!("Error: {}: ", helpers_path.display())), "Unexpected errors:\n{}", stderr);
+
+    std::fs::write(&main_path, "fn int twice(int n) {\n    return (n, 2)*;\n}\n").unwrap();
+    std::fs::remove_file(&helpers_path).unwrap();
+    let (_, stderr, success) = build();
+    assert!(!success);
+    assert!(stderr.contains("declare a main function"), "Unexpected errors:\n{}", stderr);
+}
+
+
+#[test]
+fn missing_input_files_are_reported_without_panicking() {
+    let missing = create_temp_dir("missing_input").join("missing.iri");
+    let (stdout, stderr, success) = run_compiler(&[missing.to_str().unwrap(), "-", "-mips"], "");
+    assert!(!success);
+    assert!(stdout.is_empty(), "Unexpected output:\n{}", stdout);
+    assert_eq!(stderr.trim(), format!("Error: No such file: {}.", missing.display()));
+}
+
+
+#[test]
+fn input_files_without_the_iri_extension_are_compiled_with_a_warning() {
+    let directory = create_temp_dir("extensions");
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    for name in ["hello.irid", "hello.txt", "hello"] {
+        std::fs::write(directory.join(name), &source).unwrap();
+    }
+
+    let compile = |name:&str, force:bool| {
+        let path = directory.join(name);
+        let mut args = vec![path.to_str().unwrap(), "-", "-run"];
+        if force {
+            args.push("--force");
+        }
+
+        let (stdout, stderr, success) = run_compiler(&args, "");
+        assert!(success, "Compiler failed:\n{}", stderr);
+        assert_eq!(stdout, "Hello, world!\n");
+        stderr
+    };
+
+    assert!(!compile("hello.irid", false).contains("Warning"));
+    for name in ["hello.txt", "hello"] {
+        let stderr = compile(name, false);
+        assert!(stderr.contains("does not have the .iri or .irid file extension"), "Unexpected errors:\n{}", stderr);
+        assert!(!compile(name, true).contains("Warning"));
+    }
+}
+
+
+#[test]
+fn call_graph_is_written_to_the_given_path() {
+    let graph = create_temp_dir("call_graph").join("calls.dot");
+    let source = "fn int square(int n) {\n    return (n, n)*;\n}\n\nfn int cube(int n) {\n    return (n, 3)*;\n}\n\nfn void main() {\n    let int x = square(4);\n    print << x;\n}\n";
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-run", "-O2", "--emit-callgraph", graph.to_str().unwrap()], source);
+    assert!(success, "Compiler failed:\n{}", stderr);
+    assert_eq!(stdout, "16");
+    assert!(stderr.contains("The function cube is never called"), "Unexpected errors:\n{}", stderr);
+
+    // the graph shows every function, including those -O2 leaves out of the output
+    let dot = std::fs::read_to_string(graph).unwrap();
+    assert_eq!(dot, "digraph calls {\n    \"square\";\n    \"cube\" [style=dashed];\n    \"main\";\n    \"main\" -> \"square\";\n}\n");
+}
+
+
+#[test]
+fn intermediate_code_is_written_to_the_given_paths() {
+    let directory = create_temp_dir("emit_ir");
+    let (ir, debug) = (directory.join("main.ir"), directory.join("main.debug"));
+    let source = "fn void main() {\n    let int x = 5;\n    print << x;\n}\n";
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-run", "--emit-ir", ir.to_str().unwrap(), "--emit-ir-debug", debug.to_str().unwrap()], source);
+    assert!(success, "Compiler failed:\n{}", stderr);
+    assert_eq!(stdout, "5");
+
+    // the code given to the backend, in the textual format and in the raw format of the compiler's types
+    assert_eq!(std::fs::read_to_string(ir).unwrap(), "func_start main\n    push.i32 5\n    store.i32 %0\n    load.i32 %0\n    out.i32\nfunc_end main\n");
+    assert_eq!(std::fs::read_to_string(debug).unwrap(),
+               "FuncStart(\"main\")\nPush(Integer, Integer(5))\nStore(Integer, 0)\nLoad(Integer, 0)\nOut(Integer, Plain)\nFuncEnd(\"main\")\n");
+}
+
+
+#[test]
+fn programs_which_never_end_are_stopped_by_max_steps() {
+    let source = "fn void main() {\n    let mut int n = 0;\n    loop {\n        n = (n, 1)+;\n        if (n, 0)< {\n            break;\n        }\n    }\n}\n";
+    let (_, stderr, success) = run_compiler(&["-", "-", "-run", "--max-steps", "1000"], source);
+    assert!(!success);
+    assert!(stderr.contains("limit of 1000 steps"), "Unexpected errors:\n{}", stderr);
+}
+
+
+#[test]
+fn overflow_flag_chooses_whether_int_arithmetic_traps() {
+    let source = "fn void main() {\n    let int n = (2147483647, 1)+;\n    print << n;\n}\n";
+    let (stdout, _, success) = run_compiler(&["-", "-", "-run", "--overflow", "wrap"], source);
+    assert!(success);
+    assert!(stdout.starts_with("-2147483648"), "Unexpected output:\n{}", stdout);
+
+    let (stdout, _, success) = run_compiler(&["-", "-", "-run", "--overflow", "trap"], source);
+    assert!(!success);
+    assert!(stdout.starts_with("integer overflow in function main\n"), "Unexpected output:\n{}", stdout);
+}
+
+
+#[test]
+fn overflow_traps_whether_or_not_the_result_is_used() {
+    let unused = "fn void main() {\n    let int x = (2147483647, 1)+;\n    print << \"after\";\n}\n";
+    let used = "fn void main() {\n    let int x = (2147483647, 1)+;\n    print << x;\n}\n";
+    for source in [unused, used] {
+        let (stdout, _, success) = run_compiler(&["-", "-", "-run", "--overflow", "trap"], source);
+        assert!(!success);
+        assert_eq!(stdout, "integer overflow in function main\n");
+    }
+
+    // wrapping cannot fail, so the unused addition is removed along with its store
+    let (stdout, _, success) = run_compiler(&["-", "-", "-run", "--overflow", "wrap"], unused);
+    assert!(success);
+    assert_eq!(stdout, "after");
+}
+
+#[test]
+fn overwritten_inputs_are_only_warned_about_with_a_shared_buffer() {
+    let source = "fn void main() {\n    let string first = input 4;\n    let string second = input 4;\n    print << first;\n}\n";
+    let (_, stderr, success) = run_compiler(&["-", "-", "-mips"], source);
+    assert!(success);
+    assert!(stderr.contains("overwrites the input stored in first on line 2"), "Unexpected errors:\n{}", stderr);
+
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-mips", "--distinct-input-buffers"], source);
+    assert!(success);
+    assert!(!stderr.contains("overwrites"), "Unexpected errors:\n{}", stderr);
+    assert!(stdout.contains("__input_buffer_1: .space 6"));
+}
+
+
+#[test]
+fn doctor_checks_the_target_code_without_a_program() {
+    let (stdout, stderr, success) = run_compiler(&["doctor"], "");
+    assert!(success, "Doctor found problems:\n{}", stderr);
+    assert!(stdout.starts_with("mips: "), "Unexpected output:\n{}", stdout);
+    assert!(stdout.contains("\n    cast "));
+    assert!(stdout.ends_with("No problems found\n"));
+}
+
+
+#[test]
+fn stack_report_warns_about_recursion() {
+    let source = std::fs::read_to_string("tests/programs/mutual_recursion.iri").unwrap();
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-run", "--report-stack"], &source);
+    assert!(success, "Compiler failed:\n{}", stderr);
+    assert_eq!(stdout, "even\nodd\n30\n");
+    assert!(stderr.contains("deepest path: main -> sum_squares -> square (84 bytes)"), "Unexpected report:\n{}", stderr);
+    assert!(stderr.contains("recursion through is_even, is_odd takes up 40 bytes"), "Unexpected warnings:\n{}", stderr);
+}
+
+
+#[test]
+fn output_paths_get_the_extension_of_the_target_once() {
+    let directory = create_temp_dir("output_paths");
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    for (output_name, written) in [("bare", "bare.asm"), ("named.asm", "named.asm"), ("dotted.s", "dotted.s.asm")] {
+        let output = directory.join(output_name);
+        let (_, stderr, success) = run_compiler(&["-", output.to_str().unwrap(), "-mips"], &source);
+        assert!(success, "Compiler failed:\n{}", stderr);
+        assert!(directory.join(written).is_file(), "{} was not written for {}", written, output_name);
+    }
+
+    assert!(!directory.join("named.asm.asm").exists());
+}
+
+
+#[test]
+fn missing_output_directories_are_only_created_with_create_dirs() {
+    let directory = create_temp_dir("create_dirs");
+    let output = directory.join("nested").join("deeper").join("program.asm");
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+
+    let (_, stderr, success) = run_compiler(&["-", output.to_str().unwrap(), "-mips"], &source);
+    assert!(!success);
+    assert!(stderr.contains(&output.display().to_string()), "Unexpected errors:\n{}", stderr);
+    assert!(!output.exists());
+
+    let (_, stderr, success) = run_compiler(&["-", output.to_str().unwrap(), "-mips", "--create-dirs"], &source);
+    assert!(success, "Compiler failed:\n{}", stderr);
+    assert!(std::fs::read_to_string(&output).unwrap().contains("Hello, world!"));
+}
+
+
+#[test]
+fn output_which_would_overwrite_the_input_is_refused() {
+    let directory = create_temp_dir("overwrite_input");
+    let input = directory.join("program.asm");
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    std::fs::write(&input, &source).unwrap();
+
+    // the output name gets .asm added, making it the input file, and naming the input file outright is also refused
+    for output_name in ["program", "program.asm"] {
+        let output = directory.join(output_name);
+        let (_, stderr, success) = run_compiler(&[input.to_str().unwrap(), output.to_str().unwrap(), "-mips", "--force"], "");
+        assert!(!success);
+        assert!(stderr.contains(&format!("Refusing to write output to {}.asm as it is the input file.", directory.join("program").display())),
+                "Unexpected errors:\n{}", stderr);
+        assert_eq!(std::fs::read_to_string(&input).unwrap(), source);
+    }
+}
+
+
+#[test]
+fn size_budget_is_refused_for_targets_other_than_mips() {
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-run", "--size-budget", "100"], &source);
+    assert!(!success);
+    assert!(stdout.is_empty(), "Unexpected output:\n{}", stdout);
+    assert!(stderr.contains("Error: --size-budget can only be used with the -mips target"), "Unexpected errors:\n{}", stderr);
+    assert!(!stderr.contains("panicked"), "Unexpected errors:\n{}", stderr);
+}
+
+
+#[test]
+fn operators_on_unsupported_types_are_reported_with_their_line() {
+    for (statement, operator, operand_type) in [("let string x = (\"a\", \"b\")*;", "*", "string"), ("let string x = (\"a\", \"b\")/;", "/", "string"),
+                                                ("let bool x = (true, false)&;", "&", "bool"), ("let bool x = (true, false)|;", "|", "bool"),
+                                                ("let bool x = (true, false)^;", "^", "bool"), ("let bool x = (true)~;", "~", "bool"),
+                                                ("let bool x = (true)-;", "-", "bool"), ("let bool x = (true, false)+;", "+", "bool"),
+                                                ("let int x = (3)!;", "!", "int"), ("let string x = (\"a\")!;", "!", "string"),
+                                                ("let string x = (\"a\", \"b\")<<;", "<<", "string")] {
+        let source = format!("fn void main() {{\n    {}\n}}\n", statement);
+        let (stdout, stderr, success) = run_compiler(&["-", "-", "-mips", "--message-format", "json"], &source);
+        assert!(!success, "{} was accepted", statement);
+        assert!(stdout.is_empty(), "Unexpected output for {}:\n{}", statement, stdout);
+
+        let message = format!("The {} operator cannot be applied to values of type {} on line 2.", operator, operand_type);
+        let diagnostic = stderr.lines().find(|line| line.starts_with('{')).unwrap_or_else(|| panic!("Unexpected errors for {}:\n{}", statement, stderr));
+        assert!(diagnostic.contains("\"code\":\"unsupported-operator-type\"") && diagnostic.contains("\"line\":2") && diagnostic.contains(&message),
+                "Unexpected error for {}: {}", statement, diagnostic);
+    }
+}
+
+
+#[test]
+fn unknown_flags_are_refused() {
+    let source = std::fs::read_to_string("tests/programs/hello_world.iri").unwrap();
+    for flag in ["-O3", "--size-budjet"] {
+        let (stdout, stderr, success) = run_compiler(&["-", "-", "-mips", flag, "100"], &source);
+        assert!(!success, "{} was accepted", flag);
+        assert!(stdout.is_empty(), "Unexpected output:\n{}", stdout);
+        assert!(stderr.contains(&format!("Error: Unknown flag {}, run with --help", flag)), "Unexpected errors:\n{}", stderr);
+    }
+
+    // the value of a flag which takes one is not a flag itself
+    let (_, stderr, success) = run_compiler(&["-", "-", "-run", "--max-steps", "1000", "--message-format", "json", "--fast"], &source);
+    assert!(!success);
+    assert!(stderr.contains("\"code\":\"invalid-option\"") && stderr.contains("Unknown flag --fast") && !stderr.contains("1000"),
+            "Unexpected errors:\n{}", stderr);
+
+    let (stdout, stderr, success) = run_compiler(&["-", "-", "-run", "--max-steps", "1000"], &source);
+    assert!(success, "Compiler failed:\n{}", stderr);
+    assert_eq!(stdout, "Hello, world!\n");
+}